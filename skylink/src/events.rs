@@ -0,0 +1,105 @@
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use crossbeam_channel::Sender;
+use wg_2024::controller::DroneEvent;
+
+//An event together with the moment the drone generated it, since inside a batch
+//it reaches the Sim Contr later than that. The events of a flood sent to many neighbours are
+//all the same, so they share a single copy of the packet.
+#[derive(Debug, Clone)]
+pub struct TimedEvent {
+    pub time: SystemTime,
+    pub event: Arc<DroneEvent>,
+}
+
+//Collects the events of a drone and sends them to the Sim Contr all together, when
+//there are max_len of them or the oldest one waited max_delay, whatever comes first.
+//The batch lives in a RefCell because the drone sends events from methods taking &self.
+pub struct EventBatcher {
+    batch_send: Sender<Vec<TimedEvent>>,
+    max_len: usize,
+    max_delay: Duration,
+    pending: RefCell<Vec<TimedEvent>>,
+    oldest: Cell<Option<Instant>>,
+}
+
+impl EventBatcher {
+    pub fn new(batch_send: Sender<Vec<TimedEvent>>, max_len: usize, max_delay: Duration) -> Self {
+        EventBatcher {
+            batch_send,
+            max_len: max_len.max(1),
+            max_delay,
+            pending: RefCell::new(Vec::new()),
+            oldest: Cell::new(None),
+        }
+    }
+
+    //The pending batch keeps waiting from its oldest event, with the new delay.
+    pub fn set_max_delay(&mut self, max_delay: Duration) {
+        self.max_delay = max_delay;
+    }
+
+    pub fn push(&self, event: Arc<DroneEvent>) {
+        let len = {
+            let mut pending = self.pending.borrow_mut();
+            pending.push(TimedEvent { time: SystemTime::now(), event });
+            pending.len()
+        };
+        if self.oldest.get().is_none() {
+            self.oldest.set(Some(Instant::now()));
+        }
+        if len >= self.max_len {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&self) {
+        let batch = self.pending.take();
+        self.oldest.set(None);
+        if !batch.is_empty() {
+            //At shutdown the Sim Contr may have dropped the receiver already, then the batch has nobody to go to.
+            let _ = self.batch_send.send(batch);
+        }
+    }
+
+    //Flushes the batch if it waited enough, and tells how long until the next one is due
+    //(None when there's nothing waiting).
+    pub fn flush_if_due(&self) -> Option<Duration> {
+        let oldest = self.oldest.get()?;
+        let waited = oldest.elapsed();
+        if waited >= self.max_delay {
+            self.flush();
+            None
+        } else {
+            Some(self.max_delay - waited)
+        }
+    }
+}
+
+//Lets through at most max_per_s events a second, so a runaway drone can't fill the event
+//channel and keep the Sim Contr busy with it instead of everybody else. The second starts
+//with its first event; what's over the cap is thrown away, the caller counts it.
+pub struct EventRateLimit {
+    max_per_s: u64,
+    window_start: Cell<Instant>,
+    in_window: Cell<u64>,
+}
+
+impl EventRateLimit {
+    pub fn new(max_per_s: u64) -> Self {
+        EventRateLimit { max_per_s, window_start: Cell::new(Instant::now()), in_window: Cell::new(0) }
+    }
+
+    pub fn allow(&self) -> bool {
+        if self.window_start.get().elapsed() >= Duration::from_secs(1) {
+            self.window_start.set(Instant::now());
+            self.in_window.set(0);
+        }
+        if self.in_window.get() >= self.max_per_s {
+            return false;
+        }
+        self.in_window.set(self.in_window.get() + 1);
+        true
+    }
+}
@@ -0,0 +1,58 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use crossbeam_channel::Sender;
+use wg_2024::network::NodeId;
+
+//A neighbour I stopped sending to, sent to whoever gave the drone the channel (the Sim Contr).
+#[derive(Debug, Clone)]
+pub struct LinkDown {
+    pub drone: NodeId,
+    pub neighbour: NodeId,
+    pub failures: u32, //The sends in a row that found its channel closed.
+}
+
+//Counts the sends in a row that found the channel of a neighbour disconnected. After `after` of
+//them the neighbour is gone for good (its thread ended), so the drone removes its sender instead
+//of trying it on every packet. The counts live in a RefCell because the nacks go out from &self.
+pub struct NeighbourEviction {
+    after: u32,
+    link_down_send: Sender<LinkDown>,
+    failures: RefCell<HashMap<NodeId, u32>>,
+}
+
+impl NeighbourEviction {
+    pub fn new(after: u32, link_down_send: Sender<LinkDown>) -> Self {
+        NeighbourEviction { after: after.max(1), link_down_send, failures: RefCell::new(HashMap::new()) }
+    }
+
+    pub fn failed(&self, neighbour: NodeId) {
+        *self.failures.borrow_mut().entry(neighbour).or_insert(0) += 1;
+    }
+
+    pub fn succeeded(&self, neighbour: NodeId) {
+        if !self.failures.borrow().is_empty() {
+            self.failures.borrow_mut().remove(&neighbour);
+        }
+    }
+
+    //The neighbours that reached the limit, forgotten here: the drone removes them and I tell the Sim Contr.
+    pub fn evict(&self, drone: NodeId) -> Vec<NodeId> {
+        let mut failures = self.failures.borrow_mut();
+        let evicted = failures
+            .iter()
+            .filter(|(_, count)| **count >= self.after)
+            .map(|(neighbour, _)| *neighbour)
+            .collect::<Vec<NodeId>>();
+        for neighbour in evicted.iter() {
+            if let Some(count) = failures.remove(neighbour) {
+                let _ = self.link_down_send.send(LinkDown { drone, neighbour: *neighbour, failures: count });
+            }
+        }
+        evicted
+    }
+
+    //When the Sim Contr adds or removes the neighbour itself, the count starts over.
+    pub fn forget(&self, neighbour: NodeId) {
+        self.failures.borrow_mut().remove(&neighbour);
+    }
+}
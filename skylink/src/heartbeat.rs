@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+use crossbeam_channel::Sender;
+use wg_2024::network::NodeId;
+
+//What I tell about myself at every beat, to whoever gave me the channel (the Sim Contr).
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    pub drone: NodeId,
+    pub queue_depth: usize, //Packets waiting in my channel.
+    pub handled: u64, //Packets read from my channel since the last beat.
+    pub pdr: f32,
+}
+
+//My side of the heartbeat: the beats go out every interval while I'm alive, a crashing drone is
+//silent like a crashed one.
+pub struct HeartbeatTimer {
+    interval: Duration,
+    beat_send: Sender<Heartbeat>,
+    next: Instant,
+    handled: u64,
+}
+
+impl HeartbeatTimer {
+    pub fn new(interval: Duration, beat_send: Sender<Heartbeat>) -> Self {
+        HeartbeatTimer { interval, beat_send, next: Instant::now() + interval, handled: 0 }
+    }
+
+    pub fn count_packet(&mut self) {
+        self.handled += 1;
+    }
+
+    //Sends the beat if it's due, returns how long until the next one.
+    pub fn beat_if_due(&mut self, drone: NodeId, queue_depth: usize, pdr: f32) -> Duration {
+        let now = Instant::now();
+        if now >= self.next {
+            let _ = self.beat_send.send(Heartbeat { drone, queue_depth, handled: self.handled, pdr });
+            self.handled = 0;
+            //A late beat doesn't make the next ones come in a hurry.
+            self.next = now + self.interval;
+        }
+        self.next.saturating_duration_since(now)
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+use wg_2024::controller::DroneCommand;
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+//Called by the drone at the main points of its work, so an experiment can add its own
+//behaviour to a node (logging, tampering, custom metrics) without a fork of the drone.
+//Every method does nothing by default, an implementation only writes the ones it needs.
+//The hooks run on the thread of the drone, so they must be quick.
+pub trait DroneHooks: Send {
+    //Every packet read from my channel, before any check. The packet can be changed, and
+    //returning false makes it vanish without any nack (like a black hole would).
+    fn on_packet_received(&mut self, _drone_id: NodeId, _packet: &mut Packet) -> bool {
+        true
+    }
+
+    //Right before the packet is sent to next_hop, it can still be changed.
+    fn on_forward(&mut self, _drone_id: NodeId, _next_hop: NodeId, _packet: &mut Packet) {}
+
+    //When I refuse a fragment, with the nack I'm sending back for it.
+    fn on_drop(&mut self, _drone_id: NodeId, _nack: &Packet) {}
+
+    //When I drop a fragment I already forwarded a moment ago (see dedup.rs), no nack is sent for it.
+    fn on_duplicate_suppressed(&mut self, _drone_id: NodeId, _packet: &Packet) {}
+
+    //Every command of the Sim Contr, before I execute it.
+    fn on_command(&mut self, _drone_id: NodeId, _command: &DroneCommand) {}
+
+    //Every tick of the drone, if it has one (see SkyLinkDrone::with_tick), with or without traffic.
+    fn on_tick(&mut self, _drone_id: NodeId) {}
+
+    //The last call: everyone dropped the senders of my channels, so my run() is about to return.
+    fn on_terminated(&mut self, _drone_id: NodeId) {}
+}
+
+//Makes the hooks of a drone, for the code that has to start drones on its own (the Sim Contr
+//with start_drone and reboot_drone): every drone needs its own hooks.
+pub type HooksFactory = Arc<dyn Fn(NodeId) -> Box<dyn DroneHooks> + Send + Sync>;
@@ -1,5 +1,39 @@
 mod drone;
+mod commands;
+mod counters;
+mod events;
+mod links;
+mod gossip;
+mod hooks;
+mod handshake;
+mod heartbeat;
+mod priority;
+mod reorder;
+mod flood_cache;
+mod state;
+mod loss;
+mod dedup;
+mod eviction;
+mod delay;
+mod tap;
 mod error;
 mod checks;
 
-pub use drone::*;
\ No newline at end of file
+pub use drone::*;
+pub use commands::*;
+pub use counters::*;
+pub use events::*;
+pub use links::*;
+pub use gossip::*;
+pub use hooks::*;
+pub use handshake::*;
+pub use heartbeat::*;
+pub use priority::*;
+pub use reorder::*;
+pub use flood_cache::*;
+pub use state::*;
+pub use loss::*;
+pub use dedup::*;
+pub use eviction::*;
+pub use delay::*;
+pub use tap::*;
\ No newline at end of file
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+use wg_2024::packet::{Packet, PacketType};
+
+//How much a link can carry: the tokens (bytes) come back at bytes_per_sec up to burst_bytes,
+//and a packet without enough tokens waits in a queue of at most queue_bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkCapacity {
+    pub bytes_per_sec: u64,
+    pub burst_bytes: u64,
+    pub queue_bytes: u64,
+}
+
+//How many packets a link lets out, whatever their size: packets_per_sec, after a burst of
+//burst packets, and up to queue more wait their turn. With queue = 0 the fragments over the
+//rate are nacked at once, the other packets can't be lost so they always wait.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PacketRate {
+    pub packets_per_sec: u64,
+    pub burst: u64,
+    pub queue: u64,
+}
+
+impl PacketRate {
+    //The same bucket of the capacity, with every packet taking one token.
+    pub fn bucket(&self) -> TokenBucket {
+        TokenBucket::new(LinkCapacity {
+            bytes_per_sec: self.packets_per_sec,
+            burst_bytes: self.burst.max(1),
+            queue_bytes: self.queue,
+        })
+    }
+}
+
+//The token bucket of one of my links. The tokens can go below zero: the missing ones are the
+//bytes still waiting in the queue, so the queue needs no list of its own.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: LinkCapacity,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: LinkCapacity) -> Self {
+        TokenBucket {
+            capacity,
+            tokens: capacity.burst_bytes as f64,
+            refilled: Instant::now(),
+        }
+    }
+
+    //How long a full queue takes to go out.
+    pub fn drain_time(&self) -> Duration {
+        if self.capacity.bytes_per_sec == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.capacity.queue_bytes as f64 / self.capacity.bytes_per_sec as f64)
+    }
+
+    //Puts a packet of size bytes in the queue of the link: returns how long it waits before
+    //going out, or None if the queue is full and the packet is dropped.
+    pub fn admit(&mut self, size: u64, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.capacity.bytes_per_sec as f64).min(self.capacity.burst_bytes as f64);
+        self.refilled = now;
+
+        let tokens = self.tokens - size as f64;
+        if tokens >= 0.0 {
+            self.tokens = tokens;
+            return Some(Duration::ZERO);
+        }
+        //Without a rate the tokens never come back, so a packet that has to wait waits forever.
+        if -tokens > self.capacity.queue_bytes as f64 || self.capacity.bytes_per_sec == 0 {
+            return None;
+        }
+        self.tokens = tokens;
+        Some(Duration::from_secs_f64(-tokens / self.capacity.bytes_per_sec as f64))
+    }
+}
+
+//The bytes a packet takes on a link: the payload of a fragment, plus the route and a few
+//bytes for the rest of the header.
+pub fn packet_size(packet: &Packet) -> u64 {
+    let payload = match &packet.pack_type {
+        PacketType::MsgFragment(fragment) => fragment.length as u64,
+        PacketType::FloodRequest(flood_request) => 2 * flood_request.path_trace.len() as u64,
+        PacketType::FloodResponse(flood_response) => 2 * flood_response.path_trace.len() as u64,
+        _ => 0,
+    };
+    payload + packet.routing_header.hops.len() as u64 + 16
+}
@@ -0,0 +1,47 @@
+use std::collections::VecDeque;
+use crossbeam_channel::Receiver;
+use wg_2024::packet::{Packet, PacketType};
+
+//How many packets I take out of my channel ahead of time to sort them. With a bounded channel
+//the senders still feel my backlog, only the window in front of it is reordered.
+pub const PRIORITY_WINDOW: usize = 64;
+
+//The packets I've read but not handled yet, in two queues: the Acks, Nacks and FloodResponses
+//go before the fragments and the FloodRequests, so a nack never waits behind a long message and
+//the retransmission starts as soon as it can. Each queue keeps its own order.
+#[derive(Debug, Default)]
+pub struct PriorityQueues {
+    control: VecDeque<Packet>,
+    data: VecDeque<Packet>,
+}
+
+impl PriorityQueues {
+    pub fn push(&mut self, packet: Packet) {
+        match packet.pack_type {
+            PacketType::Ack(_) | PacketType::Nack(_) | PacketType::FloodResponse(_) => self.control.push_back(packet),
+            PacketType::MsgFragment(_) | PacketType::FloodRequest(_) => self.data.push_back(packet),
+        }
+    }
+
+    //Fills the window with what's already waiting in the channel, without blocking.
+    pub fn fill(&mut self, packet_recv: &Receiver<Packet>) {
+        while self.len() < PRIORITY_WINDOW {
+            let Ok(packet) = packet_recv.try_recv() else {
+                break;
+            };
+            self.push(packet);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Packet> {
+        self.control.pop_front().or_else(|| self.data.pop_front())
+    }
+
+    pub fn len(&self) -> usize {
+        self.control.len() + self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.control.is_empty() && self.data.is_empty()
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+use crate::counters::CounterValues;
+
+//My state at a moment, as I tell it when asked with SkyLinkCommand::Snapshot: made to be
+//looked at, checked in the tests and saved, not to bring a drone back (that's the reboot).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DroneState {
+    pub id: NodeId,
+    pub pdr: f32,
+    pub neighbours: Vec<NodeId>, //Sorted, so two snapshots of the same drone compare.
+    pub flood_cache: usize, //The floods I remember right now.
+    pub queued: usize, //Packets waiting in my channel and in my priority queues.
+    pub crashing: bool,
+    pub counters: CounterValues,
+}
@@ -0,0 +1,33 @@
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+//What a tapped drone reports, in the order it happened on its thread: what came in (packets
+//and commands, before the hooks and the checks) and what went out (the events). That's enough
+//to give the same inputs to a fresh drone and compare what it does.
+#[derive(Debug, Clone)]
+pub enum TapRecord {
+    Packet(Packet),
+    Command(TappedCommand),
+    Event(DroneEvent),
+}
+
+//A DroneCommand without the channel of AddSender, which can't be kept or written anywhere.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TappedCommand {
+    AddSender(NodeId),
+    RemoveSender(NodeId),
+    SetPacketDropRate(f32),
+    Crash,
+}
+
+impl TappedCommand {
+    pub fn from_command(command: &DroneCommand) -> Self {
+        match command {
+            DroneCommand::AddSender(node_id, _) => TappedCommand::AddSender(*node_id),
+            DroneCommand::RemoveSender(node_id) => TappedCommand::RemoveSender(*node_id),
+            DroneCommand::SetPacketDropRate(pdr) => TappedCommand::SetPacketDropRate(*pdr),
+            DroneCommand::Crash => TappedCommand::Crash,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+use wg_2024::packet::{Ack, Packet, PacketType};
+
+//A client or server that acks every fragment it gets, as written in the input file:
+//    [[ack_sink]]
+//    node = 9
+//    delay_ms = 20
+//    loss = 0.1
+//There are no server threads, so the Sim Contr plays the sink: the ack goes back on the route
+//of the fragment after delay_ms, and loss is the probability (0.0 - 1.0) that it's never sent.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AckSinkConfig {
+    pub node: NodeId,
+    #[serde(default)]
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub loss: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct AckSinks {
+    sinks: HashMap<NodeId, AckSinkConfig>,
+    pending: Vec<(Instant, Packet)>, //The acks waiting for their delay, in no particular order.
+    pub(crate) sent: u64,
+    pub(crate) lost: u64,
+}
+
+impl AckSinks {
+    //A second config for the same node replaces the first one.
+    pub fn add(&mut self, config: AckSinkConfig) {
+        self.sinks.insert(config.node, config);
+    }
+
+    //Called with every PacketSent, only the fragments reaching one of the sinks are acked.
+    pub fn record(&mut self, packet: &Packet) {
+        let PacketType::MsgFragment(fragment) = &packet.pack_type else {
+            return;
+        };
+        let header = &packet.routing_header;
+        if header.hops.len() < 2 || header.hop_index + 1 != header.hops.len() {
+            return;
+        }
+        let Some(sink) = header.hops.last().and_then(|node| self.sinks.get(node)) else {
+            return;
+        };
+        if fastrand::f32() < sink.loss {
+            self.lost += 1;
+            return;
+        }
+        let ack = Packet {
+            pack_type: PacketType::Ack(Ack { fragment_index: fragment.fragment_index }),
+            routing_header: SourceRoutingHeader { hop_index: 1, hops: header.hops.iter().rev().copied().collect() },
+            session_id: packet.session_id,
+        };
+        self.pending.push((Instant::now() + Duration::from_millis(sink.delay_ms), ack));
+    }
+
+    //The acks whose delay is over, to be sent to their first hop.
+    pub fn due(&mut self) -> Vec<Packet> {
+        let now = Instant::now();
+        let (due, pending) = self.pending.drain(..).partition::<Vec<(Instant, Packet)>, _>(|(at, _)| *at <= now);
+        self.pending = pending;
+        self.sent += due.len() as u64;
+        due.into_iter().map(|(_, ack)| ack).collect()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut nodes = self.sinks.keys().copied().collect::<Vec<NodeId>>();
+        nodes.sort();
+        format!("ack sinks {:?}: {} acks sent, {} lost, {} waiting", nodes, self.sent, self.lost, self.pending.len())
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::PacketType;
+use crate::sim_control::NodeStats;
+use crate::groups::GroupDefinition;
+
+//The rules are checked this often, and the windows are measured in these steps.
+pub const ALERT_PERIOD: Duration = Duration::from_secs(1);
+
+//A rule of the input file:
+//    [[alert]]
+//    name = "lossy node"
+//    rule = "drop_rate"
+//    threshold = 0.3
+//    window_s = 10
+//
+//    [[alert]]
+//    rule = "no_delivery"
+//    window_s = 5
+//The other rule is "queue_length" (threshold, in packets). drop_rate and queue_length look at
+//every node, or only at the one in node, or only at the members of the [[group]] in group. The
+//name is only for the log, the rule is used without it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertConfig {
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub rule: AlertRule,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum AlertRule {
+    //Dropped over handled (sent + dropped) packets of a node, in the window. Fewer than
+    //min_packets aren't enough to say anything.
+    DropRate {
+        threshold: f64,
+        #[serde(default = "default_drop_window")]
+        window_s: u64,
+        node: Option<NodeId>,
+        group: Option<String>,
+        #[serde(default = "default_min_packets")]
+        min_packets: u64,
+    },
+    //Fragments were moving in the window, but none of them reached its destination.
+    NoDelivery {
+        #[serde(default = "default_delivery_window")]
+        window_s: u64,
+    },
+    QueueLength { threshold: u64, node: Option<NodeId>, group: Option<String> },
+}
+
+fn default_drop_window() -> u64 {
+    10
+}
+
+fn default_min_packets() -> u64 {
+    10
+}
+
+fn default_delivery_window() -> u64 {
+    5
+}
+
+impl AlertConfig {
+    fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| match self.rule {
+            AlertRule::DropRate { .. } => "drop rate".to_string(),
+            AlertRule::NoDelivery { .. } => "no delivery".to_string(),
+            AlertRule::QueueLength { .. } => "queue length".to_string(),
+        })
+    }
+}
+
+//A rule that started or stopped holding.
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub at: Duration, //From the start of the simulation.
+    pub name: String,
+    pub node: Option<NodeId>,
+    pub message: String,
+    pub resolved: bool,
+}
+
+impl Alert {
+    pub fn describe(&self) -> String {
+        let state = if self.resolved { "resolved" } else { "ALERT" };
+        format!("{} at {:.1}s [{}]: {}", state, self.at.as_secs_f64(), self.name, self.message)
+    }
+}
+
+//What the rules need at every step: the counters of the nodes and the fragments seen so far.
+struct AlertSample {
+    counters: HashMap<NodeId, (u64, u64)>, //(sent, dropped)
+    fragments: u64,
+    delivered: u64,
+}
+
+//Checks the rules at every ALERT_PERIOD. An alert is raised when its rule starts holding and
+//resolved when it stops, so a node that stays lossy is reported once and not every second.
+pub struct AlertMonitor {
+    started: Instant,
+    checked: Instant,
+    rules: Vec<AlertConfig>,
+    samples: VecDeque<AlertSample>, //The newest is the last, one for every ALERT_PERIOD.
+    fragments: u64,
+    delivered: u64,
+    active: HashSet<(usize, Option<NodeId>)>, //(rule, node) that are holding now.
+    pub(crate) history: Vec<Alert>,
+    groups: HashMap<String, Vec<NodeId>>, //The members of the groups, for the rules with a group.
+}
+
+impl Default for AlertMonitor {
+    fn default() -> Self {
+        AlertMonitor {
+            started: Instant::now(),
+            checked: Instant::now(),
+            rules: Vec::new(),
+            samples: VecDeque::new(),
+            fragments: 0,
+            delivered: 0,
+            active: HashSet::new(),
+            history: Vec::new(),
+            groups: HashMap::new(),
+        }
+    }
+}
+
+impl AlertMonitor {
+    pub fn add_rule(&mut self, rule: AlertConfig) {
+        self.rules.push(rule);
+    }
+
+    pub fn set_groups(&mut self, groups: &[GroupDefinition]) {
+        self.groups = groups.iter().map(|group| (group.name.clone(), group.nodes.clone())).collect();
+    }
+
+    //Whether the rule looks at the node: all of them, only node, or only the members of group.
+    fn in_scope(&self, id: NodeId, node: Option<NodeId>, group: &Option<String>) -> bool {
+        node.map_or(true, |node| node == id) && group.as_ref().map_or(true, |group| self.groups.get(group).map_or(false, |members| members.contains(&id)))
+    }
+
+    pub fn record(&mut self, event: &DroneEvent) {
+        let DroneEvent::PacketSent(packet) = event else {
+            return;
+        };
+        if let PacketType::MsgFragment(_) = packet.pack_type {
+            self.fragments += 1;
+            let header = &packet.routing_header;
+            if header.hop_index + 1 == header.hops.len() {
+                self.delivered += 1;
+            }
+        }
+    }
+
+    //Returns the alerts raised or resolved by this check, they're in the history too.
+    pub fn check_if_due(&mut self, stats: &HashMap<NodeId, NodeStats>) -> Vec<Alert> {
+        if self.rules.is_empty() || self.checked.elapsed() < ALERT_PERIOD {
+            return Vec::new();
+        }
+        self.checked = Instant::now();
+
+        self.samples.push_back(AlertSample {
+            counters: stats.iter().map(|(id, stats)| (*id, (stats.packets_sent, stats.packets_dropped))).collect(),
+            fragments: self.fragments,
+            delivered: self.delivered,
+        });
+        let longest = self.rules.iter().map(|rule| window_steps(&rule.rule)).max().unwrap_or(0);
+        while self.samples.len() > longest + 1 {
+            self.samples.pop_front();
+        }
+
+        //What holds now, with the message to show if it's new.
+        let mut holding = HashMap::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            for (node, message) in self.evaluate(&rule.rule, stats) {
+                holding.insert((index, node), message);
+            }
+        }
+
+        let mut alerts = Vec::new();
+        let at = self.started.elapsed();
+        for (key, message) in holding.iter() {
+            if self.active.insert(*key) {
+                alerts.push(Alert { at, name: self.rules[key.0].name(), node: key.1, message: message.clone(), resolved: false });
+            }
+        }
+        let stopped = self.active
+            .iter()
+            .filter(|key| !holding.contains_key(key))
+            .copied()
+            .collect::<Vec<(usize, Option<NodeId>)>>();
+        for key in stopped {
+            self.active.remove(&key);
+            let message = key.1.map_or("back to normal".to_string(), |node| format!("node {} back to normal", node));
+            alerts.push(Alert { at, name: self.rules[key.0].name(), node: key.1, message, resolved: true });
+        }
+        alerts.sort_by_key(|alert| (alert.node, alert.resolved));
+        self.history.extend(alerts.iter().cloned());
+        alerts
+    }
+
+    //The nodes the rule holds for (None for the rules about the whole network), with a message.
+    fn evaluate(&self, rule: &AlertRule, stats: &HashMap<NodeId, NodeStats>) -> Vec<(Option<NodeId>, String)> {
+        let newest = self.samples.back().unwrap();
+        //The window is as long as the samples I have, until the simulation has run for long enough.
+        let window_start = |window_s: u64| {
+            let steps = (window_s as usize).min(self.samples.len() - 1);
+            &self.samples[self.samples.len() - 1 - steps]
+        };
+        match *rule {
+            AlertRule::DropRate { threshold, window_s, node, ref group, min_packets } => {
+                let oldest = window_start(window_s);
+                newest.counters
+                    .iter()
+                    .filter(|(id, _)| self.in_scope(**id, node, group))
+                    .filter_map(|(id, (sent, dropped))| {
+                        let (old_sent, old_dropped) = oldest.counters.get(id).copied().unwrap_or_default();
+                        let dropped = dropped.saturating_sub(old_dropped);
+                        let handled = sent.saturating_sub(old_sent) + dropped;
+                        let rate = dropped as f64 / handled.max(1) as f64;
+                        (handled >= min_packets && rate > threshold).then(|| {
+                            (Some(*id), format!("node {} dropped {:.0}% of {} packets in {}s", id, rate * 100.0, handled, window_s))
+                        })
+                    })
+                    .collect()
+            }
+            AlertRule::NoDelivery { window_s } => {
+                let oldest = window_start(window_s);
+                let fragments = newest.fragments - oldest.fragments;
+                let delivered = newest.delivered - oldest.delivered;
+                if self.samples.len() > 1 && fragments > 0 && delivered == 0 {
+                    vec![(None, format!("{} fragments moved in {}s, none delivered", fragments, window_s))]
+                } else {
+                    Vec::new()
+                }
+            }
+            AlertRule::QueueLength { threshold, node, ref group } => stats
+                .iter()
+                .filter(|(id, stats)| self.in_scope(**id, node, group) && stats.queue_len > threshold)
+                .map(|(id, stats)| (Some(*id), format!("node {} has {} packets waiting", id, stats.queue_len)))
+                .collect(),
+        }
+    }
+
+    //The alerts raised in the last moments, for the toasts of the GUI.
+    pub fn recent(&self, within: Duration) -> impl Iterator<Item = &Alert> {
+        let now = self.started.elapsed();
+        self.history.iter().filter(move |alert| now.saturating_sub(alert.at) < within)
+    }
+}
+
+fn window_steps(rule: &AlertRule) -> usize {
+    match rule {
+        AlertRule::DropRate { window_s, .. } | AlertRule::NoDelivery { window_s } => *window_s as usize,
+        AlertRule::QueueLength { .. } => 0,
+    }
+}
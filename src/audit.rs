@@ -0,0 +1,109 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use wg_2024::controller::DroneCommand;
+use wg_2024::network::NodeId;
+use crate::skylink_drone::commands::SkyLinkCommand;
+use crate::skylink_drone::tap::TappedCommand;
+
+//The newest commands kept for every node: a shutdown alone sends a RemoveSender for every node.
+const MAX_AUDIT_PER_NODE: usize = 1000;
+
+//Who asked the Sim Contr for a command. The frontends and the scenarios set it once, the faults
+//of the timeline and the ipc requests only while they run; what's left is the Sim Contr's own
+//doing (the setup, the reboots, the shutdown).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandOrigin {
+    SimContr,
+    Gui,
+    Tui,
+    Scenario,
+    Chaos,
+    Api,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub at: Duration, //From the start of the simulation.
+    pub node: NodeId,
+    pub command: String,
+    pub origin: CommandOrigin,
+}
+
+impl AuditEntry {
+    pub fn describe(&self) -> String {
+        format!("{:.3}s {:?}: {}", self.at.as_secs_f64(), self.origin, self.command)
+    }
+}
+
+//Every command sent to every node, DroneCommands and SkyLinkCommands, with who asked for it.
+#[derive(Debug)]
+pub struct AuditLog {
+    started: Instant,
+    entries: HashMap<NodeId, VecDeque<AuditEntry>>,
+    origin: CommandOrigin,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        AuditLog { started: Instant::now(), entries: HashMap::new(), origin: CommandOrigin::SimContr }
+    }
+}
+
+impl AuditLog {
+    //Returns the origin it replaces, to be set back by the ones that only borrow it.
+    pub fn set_origin(&mut self, origin: CommandOrigin) -> CommandOrigin {
+        std::mem::replace(&mut self.origin, origin)
+    }
+
+    pub fn record(&mut self, node: NodeId, command: &DroneCommand) {
+        self.push(node, format!("{:?}", TappedCommand::from_command(command)));
+    }
+
+    pub fn record_skylink(&mut self, node: NodeId, command: &SkyLinkCommand) {
+        let command = match command {
+            SkyLinkCommand::SetLinkImpairment(to, impairment) => format!("SetLinkImpairment({}, {:?})", to, impairment),
+            SkyLinkCommand::SetLinkCapacity(to, capacity) => format!("SetLinkCapacity({}, {:?})", to, capacity),
+            SkyLinkCommand::SetLinkRate(to, rate) => format!("SetLinkRate({:?}, {:?})", to, rate),
+            SkyLinkCommand::SetLinkPdr(to, pdr) => format!("SetLinkPdr({}, {:?})", to, pdr),
+            SkyLinkCommand::SetFilters(rules) => format!("SetFilters({:?})", rules),
+            SkyLinkCommand::Ping(_) => "Ping".to_string(),
+            SkyLinkCommand::Snapshot(_) => "Snapshot".to_string(),
+            SkyLinkCommand::SetTap(tap) => format!("SetTap({})", if tap.is_some() { "on" } else { "off" }),
+            SkyLinkCommand::SetProcessingDelay(to, delay) => format!("SetProcessingDelay({:?}, {:?})", to, delay),
+            SkyLinkCommand::SetPacketFaults(faults) => format!("SetPacketFaults({:?})", faults),
+            SkyLinkCommand::SetReorder(reorder) => format!("SetReorder({:?})", reorder),
+            SkyLinkCommand::SetBurstLoss(burst_loss) => format!("SetBurstLoss({:?})", burst_loss),
+            SkyLinkCommand::SetTelemetry(telemetry) => format!("SetTelemetry({:?})", telemetry),
+            SkyLinkCommand::Shutdown(_) => "Shutdown".to_string(),
+        };
+        self.push(node, command);
+    }
+
+    fn push(&mut self, node: NodeId, command: String) {
+        let entries = self.entries.entry(node).or_default();
+        entries.push_back(AuditEntry { at: self.started.elapsed(), node, command, origin: self.origin });
+        if entries.len() > MAX_AUDIT_PER_NODE {
+            entries.pop_front();
+        }
+    }
+
+    //The commands of a node, the oldest first, only the ones from origin if there's one.
+    pub fn query(&self, node: NodeId, origin: Option<CommandOrigin>) -> Vec<&AuditEntry> {
+        self.entries
+            .get(&node)
+            .map(|entries| entries.iter().filter(|entry| origin.map_or(true, |origin| entry.origin == origin)).collect())
+            .unwrap_or_default()
+    }
+
+    //Every node, sorted by time, as JSON.
+    pub fn save(&self, file: &str) -> io::Result<()> {
+        let mut entries = self.entries.values().flatten().collect::<Vec<&AuditEntry>>();
+        entries.sort_by_key(|entry| entry.at);
+        let json = serde_json::to_string_pretty(&entries).map_err(io::Error::other)?;
+        fs::write(file, json)
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, HashMap};
+use wg_2024::network::NodeId;
+use wg_2024::packet::{NodeType, Packet, PacketType};
+use crate::skylink_drone::links::packet_size;
+
+//The drones only carry the traffic of the others, the clients and the servers are where it starts and ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NodeClass {
+    Transit,
+    Endpoint,
+}
+
+impl NodeClass {
+    fn of(node: NodeId, node_types: &HashMap<NodeId, NodeType>) -> Option<Self> {
+        match node_types.get(&node)? {
+            NodeType::Drone => Some(NodeClass::Transit),
+            NodeType::Client | NodeType::Server => Some(NodeClass::Endpoint),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrafficKind {
+    Fragment,
+    Ack,
+    Nack,
+    FloodRequest,
+    FloodResponse,
+}
+
+impl TrafficKind {
+    fn of(packet: &Packet) -> Self {
+        match packet.pack_type {
+            PacketType::MsgFragment(_) => TrafficKind::Fragment,
+            PacketType::Ack(_) => TrafficKind::Ack,
+            PacketType::Nack(_) => TrafficKind::Nack,
+            PacketType::FloodRequest(_) => TrafficKind::FloodRequest,
+            PacketType::FloodResponse(_) => TrafficKind::FloodResponse,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByteCount {
+    pub packets: u64,
+    pub bytes: u64,         //As packet_size counts them, headers included.
+    pub payload_bytes: u64, //Only the data of the fragments.
+}
+
+//The bytes of every packet the Sim Contr sees going out (the PacketSent of the drones, and what it
+//sends itself for the clients and the servers), by the class of the node sending and receiving it
+//and by kind of packet. Only the data of the fragments is payload, everything else is the cost of
+//the protocol. A flood request doesn't say where it's going, so it's only counted as sent.
+#[derive(Debug, Default)]
+pub struct BandwidthUsage {
+    pub(crate) sent: BTreeMap<(NodeClass, TrafficKind), ByteCount>,
+    pub(crate) received: BTreeMap<(NodeClass, TrafficKind), ByteCount>,
+}
+
+impl BandwidthUsage {
+    pub fn record(&mut self, from: Option<NodeId>, to: Option<NodeId>, packet: &Packet, node_types: &HashMap<NodeId, NodeType>) {
+        let kind = TrafficKind::of(packet);
+        let bytes = packet_size(packet);
+        let payload_bytes = match &packet.pack_type {
+            PacketType::MsgFragment(fragment) => fragment.length as u64,
+            _ => 0,
+        };
+        let ends = [(from, &mut self.sent), (to, &mut self.received)];
+        for (node, table) in ends {
+            if let Some(class) = node.and_then(|node| NodeClass::of(node, node_types)) {
+                let count = table.entry((class, kind)).or_default();
+                count.packets += 1;
+                count.bytes += bytes;
+                count.payload_bytes += payload_bytes;
+            }
+        }
+    }
+
+    //Of all the bytes sent, the share that isn't payload.
+    pub fn overhead_ratio(&self) -> f64 {
+        let bytes = self.sent.values().map(|count| count.bytes).sum::<u64>();
+        let payload_bytes = self.sent.values().map(|count| count.payload_bytes).sum::<u64>();
+        if bytes == 0 {
+            return 0.0;
+        }
+        1.0 - payload_bytes as f64 / bytes as f64
+    }
+}
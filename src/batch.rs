@@ -0,0 +1,199 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process::{Child, Command, Stdio};
+use serde::{Deserialize, Serialize};
+use crate::sim_control::SimulationControl;
+
+//What a single run of a scenario measured, written by the run as JSON at the end.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunMetrics {
+    pub seed: u64,
+    pub sessions: u64,
+    pub delivered: u64,
+    pub dropped: u64, //Sessions that got at least a Dropped nack.
+    pub mean_latency_ms: f64, //End to end, over the delivered sessions.
+    pub packets_sent: u64,
+    pub packets_dropped: u64,
+    pub shortcuts: u64,
+}
+
+impl RunMetrics {
+    pub fn from_simulation(sim_contr: &SimulationControl, seed: u64) -> Self {
+        let mut metrics = RunMetrics { seed, ..RunMetrics::default() };
+        let mut total_latency = 0.0;
+        for session in sim_contr.sessions.sessions.values() {
+            //Only the sessions that carried fragments are messages.
+            if session.destination.is_none() {
+                continue;
+            }
+            metrics.sessions += 1;
+            if session.dropped {
+                metrics.dropped += 1;
+            }
+            if session.delivered.is_some() {
+                metrics.delivered += 1;
+                total_latency += session.end_to_end_latency().unwrap_or_default().as_secs_f64() * 1000.0;
+            }
+        }
+        if metrics.delivered > 0 {
+            metrics.mean_latency_ms = total_latency / metrics.delivered as f64;
+        }
+        for stats in sim_contr.stats.values() {
+            metrics.packets_sent += stats.packets_sent;
+            metrics.packets_dropped += stats.packets_dropped;
+            metrics.shortcuts += stats.shortcuts;
+        }
+        metrics
+    }
+
+    pub fn delivery_rate(&self) -> f64 {
+        if self.sessions == 0 { 0.0 } else { self.delivered as f64 / self.sessions as f64 }
+    }
+
+    pub fn loss_rate(&self) -> f64 {
+        if self.sessions == 0 { 0.0 } else { self.dropped as f64 / self.sessions as f64 }
+    }
+
+    pub fn save(&self, file: &str) -> io::Result<()> {
+        let json = serde_json::to_string(self).map_err(io::Error::other)?;
+        fs::write(file, json)
+    }
+
+    fn load(file: &str) -> io::Result<Self> {
+        let json = fs::read_to_string(file)?;
+        serde_json::from_str(&json).map_err(io::Error::other)
+    }
+}
+
+//    batch <scenario.rhai> [--runs N] [--parallel P] [--config file] [--out dir]
+//Every run is a separate process of this same program (so runs can't affect each other),
+//running the scenario headless with its own seed. At the end the metrics of all the runs
+//are summed up with their 95% confidence intervals.
+pub fn run_batch_cli(args: &[String]) {
+    let Some(scenario) = args.first() else {
+        println!("usage: batch <scenario.rhai> [--runs N] [--parallel P] [--config file] [--out dir]");
+        return;
+    };
+    let option = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+    let runs = option("--runs").and_then(|runs| runs.parse().ok()).unwrap_or(10);
+    let parallel = option("--parallel").and_then(|parallel| parallel.parse().ok()).unwrap_or(1);
+    let out_dir = option("--out").cloned().unwrap_or("batch".to_string());
+    let config = option("--config");
+
+    match run_batch(scenario, config.map(|config| config.as_str()), runs, parallel, &out_dir) {
+        Ok(report) => println!("{}", report),
+        Err(e) => println!("batch failed: {}", e),
+    }
+}
+
+pub fn run_batch(scenario: &str, config: Option<&str>, runs: u64, parallel: usize, out_dir: &str) -> io::Result<String> {
+    fs::create_dir_all(out_dir)?;
+    let exe = std::env::current_exe()?;
+
+    let spawn = |seed: u64| -> io::Result<Child> {
+        let mut command = Command::new(&exe);
+        command
+            .arg("--scenario").arg(scenario)
+            .arg("--seed").arg(seed.to_string())
+            .arg("--metrics").arg(format!("{}/metrics_{}.json", out_dir, seed))
+            .arg("--stats-out").arg(format!("{}/stats_{}.csv", out_dir, seed))
+            //The drones print a lot, every run gets its own log.
+            .stdout(Stdio::from(File::create(format!("{}/run_{}.log", out_dir, seed))?));
+        if let Some(config) = config {
+            command.arg("--config").arg(config);
+        }
+        command.spawn()
+    };
+
+    //At most 'parallel' runs at the same time, I wait for the oldest before starting a new one.
+    let mut running: Vec<(u64, Child)> = Vec::new();
+    let mut next_seed = 0;
+    while next_seed < runs || !running.is_empty() {
+        while next_seed < runs && running.len() < parallel.max(1) {
+            println!("starting run {}/{}", next_seed + 1, runs);
+            running.push((next_seed, spawn(next_seed)?));
+            next_seed += 1;
+        }
+        let (seed, mut child) = running.remove(0);
+        let status = child.wait()?;
+        if !status.success() {
+            println!("run with seed {} failed ({})", seed, status);
+        }
+    }
+
+    let mut results = Vec::new();
+    for seed in 0..runs {
+        match RunMetrics::load(&format!("{}/metrics_{}.json", out_dir, seed)) {
+            Ok(metrics) => results.push(metrics),
+            Err(e) => println!("no metrics for seed {}: {}", seed, e),
+        }
+    }
+    write_runs_csv(&results, &format!("{}/runs.csv", out_dir))?;
+
+    let report = summary(scenario, runs, &results);
+    fs::write(format!("{}/report.txt", out_dir), &report)?;
+    Ok(report)
+}
+
+fn write_runs_csv(results: &[RunMetrics], file: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(file)?);
+    writeln!(writer, "seed,sessions,delivered,dropped,delivery_rate,loss_rate,mean_latency_ms,packets_sent,packets_dropped,shortcuts")?;
+    for run in results {
+        writeln!(
+            writer,
+            "{},{},{},{},{:.4},{:.4},{:.3},{},{},{}",
+            run.seed,
+            run.sessions,
+            run.delivered,
+            run.dropped,
+            run.delivery_rate(),
+            run.loss_rate(),
+            run.mean_latency_ms,
+            run.packets_sent,
+            run.packets_dropped,
+            run.shortcuts
+        )?;
+    }
+    writer.flush()
+}
+
+fn summary(scenario: &str, runs: u64, results: &[RunMetrics]) -> String {
+    let mut report = format!("scenario {}: {} of {} runs completed\n", scenario, results.len(), runs);
+    let metrics: [(&str, fn(&RunMetrics) -> f64); 4] = [
+        ("delivery rate", RunMetrics::delivery_rate),
+        ("loss rate", RunMetrics::loss_rate),
+        ("mean latency ms", |run| run.mean_latency_ms),
+        ("packets sent", |run| run.packets_sent as f64),
+    ];
+    for (name, metric) in metrics {
+        let values = results.iter().map(metric).collect::<Vec<f64>>();
+        let (mean, half_width) = confidence_interval(&values);
+        report.push_str(&format!("{:>16}: {:.4} ± {:.4} (95% CI)\n", name, mean, half_width));
+    }
+    report
+}
+
+//Mean and half width of the 95% confidence interval, with the t distribution since
+//batches are usually small.
+fn confidence_interval(values: &[f64]) -> (f64, f64) {
+    let n = values.len();
+    if n == 0 {
+        return (0.0, 0.0);
+    }
+    let mean = values.iter().sum::<f64>() / n as f64;
+    if n == 1 {
+        return (mean, 0.0);
+    }
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+    (mean, t_critical(n - 1) * (variance / n as f64).sqrt())
+}
+
+//Two-sided 95% critical values of the t distribution, from 1 to 30 degrees of freedom.
+fn t_critical(degrees_of_freedom: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+        2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+        2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+    ];
+    TABLE.get(degrees_of_freedom - 1).copied().unwrap_or(1.96)
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+use crossbeam_channel::{Receiver, Sender};
+use serde::Deserialize;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::{NodeType, Packet, PacketType};
+
+//A bridge is declared in the input file next to the drones:
+//    [[bridge]]
+//    id = 50
+//    connected_node_ids = [3, 4]
+//    address = "192.168.1.10:7000"
+//    listen = true
+//The other process declares a bridge with the same id and address (and listen = false),
+//connected to its own nodes. For the rest of the network the two halves are a single node.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BridgeConfig {
+    pub id: NodeId,
+    pub connected_node_ids: Vec<NodeId>,
+    pub address: String,
+    #[serde(default)]
+    pub listen: bool,
+}
+
+pub struct SkyLinkBridge {
+    id: NodeId,
+    controller_send: Sender<DroneEvent>,
+    packet_recv: Receiver<Packet>,
+    packet_send: HashMap<NodeId, Sender<Packet>>,
+    address: String,
+    listen: bool,
+}
+
+impl SkyLinkBridge {
+    pub fn new(config: BridgeConfig,
+               controller_send: Sender<DroneEvent>,
+               packet_recv: Receiver<Packet>,
+               packet_send: HashMap<NodeId, Sender<Packet>>) -> Self {
+        SkyLinkBridge {
+            id: config.id,
+            controller_send,
+            packet_recv,
+            packet_send,
+            address: config.address,
+            listen: config.listen,
+        }
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        if self.listen {
+            let listener = TcpListener::bind(&self.address)?;
+            let (stream, _) = listener.accept()?;
+            Ok(stream)
+        } else {
+            //The other process may not be listening yet, so I retry for a while.
+            let mut attempts = 0;
+            loop {
+                match TcpStream::connect(&self.address) {
+                    Ok(stream) => return Ok(stream),
+                    Err(e) if attempts >= 30 => return Err(e),
+                    Err(_) => {
+                        attempts += 1;
+                        thread::sleep(Duration::from_secs(1));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn run(self) -> io::Result<()> {
+        let stream = self.connect()?;
+        let reader = stream.try_clone()?;
+
+        //Remote side -> local nodes.
+        let id = self.id;
+        let controller_send = self.controller_send.clone();
+        let packet_send = self.packet_send.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else { break };
+                match serde_json::from_str::<Packet>(&line) {
+                    Ok(packet) => deliver_locally(id, packet, &packet_send, &controller_send),
+                    Err(e) => println!("bridge {} received an invalid packet: {}", id, e),
+                }
+            }
+            println!("bridge {} lost the connection with the remote side.", id);
+        });
+
+        //Local nodes -> remote side, the packets travel untouched.
+        let mut writer = stream;
+        for packet in self.packet_recv.iter() {
+            let line = serde_json::to_string(&packet).map_err(io::Error::other)?;
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+//The receiving half does the job of a (lossless) drone: it adds itself to the flood path trace
+//and moves the hop index forward, then hands the packet to its local neighbours.
+fn deliver_locally(id: NodeId, mut packet: Packet, packet_send: &HashMap<NodeId, Sender<Packet>>, controller_send: &Sender<DroneEvent>) {
+    //The path trace is updated in place: matching on a copy of pack_type would copy the
+    //payload of every fragment that goes through the bridge.
+    if let PacketType::FloodRequest(flood_request) = &mut packet.pack_type {
+        let prev = flood_request.path_trace.last().map(|(id, _)| *id).unwrap_or(flood_request.initiator_id);
+        flood_request.path_trace.push((id, NodeType::Drone));
+        for (neighbour, sender) in packet_send.iter() {
+            if *neighbour != prev && sender.send(packet.clone()).is_ok() {
+                let _ = controller_send.send(DroneEvent::PacketSent(packet.clone()));
+            }
+        }
+        return;
+    }
+
+    if packet.routing_header.hops.get(packet.routing_header.hop_index) != Some(&id) {
+        println!("bridge {} received a packet not routed through it.", id);
+        return;
+    }
+    packet.routing_header.hop_index += 1;
+    let Some(next_hop) = packet.routing_header.hops.get(packet.routing_header.hop_index) else {
+        println!("bridge {} is the destination of a packet, discarded.", id);
+        return;
+    };
+    if let Some(sender) = packet_send.get(next_hop) {
+        if sender.send(packet.clone()).is_ok() {
+            let _ = controller_send.send(DroneEvent::PacketSent(packet));
+            return;
+        }
+    }
+    if let PacketType::MsgFragment(_) = packet.pack_type {
+        println!("bridge {} can't reach {}, fragment discarded.", id, next_hop);
+    } else {
+        let _ = controller_send.send(DroneEvent::ControllerShortcut(packet));
+    }
+}
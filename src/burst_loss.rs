@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use wg_2024::network::NodeId;
+use crate::skylink_drone::loss::BurstLoss;
+
+//The drones that drop in bursts instead of with their pdr, as written in the input file:
+//    [[burst_loss]]
+//    drone = 3
+//    p_good_to_bad = 0.05
+//    p_bad_to_good = 0.3
+//    loss_bad = 0.8
+//Before every fragment the link goes bad with p_good_to_bad and good again with p_bad_to_good,
+//then the fragment is dropped with loss_good (0 if not given) or loss_bad (1 if not given).
+//A link with its own pdr (see link_pdr.rs) keeps using it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BurstLossConfig {
+    pub drone: NodeId,
+    pub p_good_to_bad: f32,
+    pub p_bad_to_good: f32,
+    #[serde(default)]
+    pub loss_good: f32,
+    #[serde(default = "default_loss_bad")]
+    pub loss_bad: f32,
+}
+
+fn default_loss_bad() -> f32 {
+    1.0
+}
+
+impl BurstLossConfig {
+    pub fn new(drone: NodeId, p_good_to_bad: f32, p_bad_to_good: f32) -> Self {
+        BurstLossConfig { drone, p_good_to_bad, p_bad_to_good, loss_good: 0.0, loss_bad: default_loss_bad() }
+    }
+
+    pub fn burst_loss(&self) -> BurstLoss {
+        BurstLoss {
+            p_good_to_bad: self.p_good_to_bad.clamp(0.0, 1.0),
+            p_bad_to_good: self.p_bad_to_good.clamp(0.0, 1.0),
+            loss_good: self.loss_good.clamp(0.0, 1.0),
+            loss_bad: self.loss_bad.clamp(0.0, 1.0),
+        }
+    }
+}
+
+//The burst loss by drone, for SkyLinkDrone::with_burst_loss.
+pub fn burst_losses(configs: &[BurstLossConfig]) -> HashMap<NodeId, BurstLoss> {
+    configs.iter().map(|config| (config.drone, config.burst_loss())).collect()
+}
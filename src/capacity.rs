@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use wg_2024::network::NodeId;
+use crate::skylink_drone::links::LinkCapacity;
+
+//The capacity of the links, as written in the input file:
+//    [link_capacity]
+//    bytes_per_sec = 20000
+//    burst_bytes = 1024
+//    queue_bytes = 8192
+//    [[link_capacity.link]]
+//    a = 1
+//    b = 2
+//    bytes_per_sec = 2000
+//Without bytes_per_sec in the table only the listed links have a capacity, the others carry
+//everything at once as before. A listed link has the same capacity in both directions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LinkCapacityConfig {
+    pub bytes_per_sec: Option<u64>,
+    pub burst_bytes: u64,
+    pub queue_bytes: u64,
+    pub link: Vec<LinkCapacityOverride>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinkCapacityOverride {
+    pub a: NodeId,
+    pub b: NodeId,
+    pub bytes_per_sec: u64,
+    pub burst_bytes: Option<u64>,
+    pub queue_bytes: Option<u64>,
+}
+
+impl Default for LinkCapacityConfig {
+    fn default() -> Self {
+        LinkCapacityConfig {
+            bytes_per_sec: None,
+            burst_bytes: 1024,
+            queue_bytes: 8192,
+            link: Vec::new(),
+        }
+    }
+}
+
+impl LinkCapacityConfig {
+    pub fn capacity(&self, from: NodeId, to: NodeId) -> Option<LinkCapacity> {
+        let listed = self.link
+            .iter()
+            .find(|link| (link.a, link.b) == (from, to) || (link.a, link.b) == (to, from));
+        match listed {
+            Some(link) => Some(LinkCapacity {
+                bytes_per_sec: link.bytes_per_sec,
+                burst_bytes: link.burst_bytes.unwrap_or(self.burst_bytes),
+                queue_bytes: link.queue_bytes.unwrap_or(self.queue_bytes),
+            }),
+            None => self.bytes_per_sec.map(|bytes_per_sec| LinkCapacity {
+                bytes_per_sec,
+                burst_bytes: self.burst_bytes,
+                queue_bytes: self.queue_bytes,
+            }),
+        }
+    }
+
+    //The capacities of the links of a node, for SkyLinkDrone::with_link_capacities.
+    pub fn capacities(&self, from: NodeId, neighbours: &[NodeId]) -> HashMap<NodeId, LinkCapacity> {
+        neighbours
+            .iter()
+            .filter_map(|to| self.capacity(from, *to).map(|capacity| (*to, capacity)))
+            .collect()
+    }
+}
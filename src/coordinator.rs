@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+use crate::dead_letters::DeadLetterReason;
+use crate::sim_control::{NodeStats, SimulationControl};
+
+//A part of the network run by its own Sim Contr, declared in the input file:
+//    [[segment]]
+//    name = "north"
+//    drones = [1, 2, 3]
+//The clients and the servers go with the segment of their first drone, and only keep the links
+//to the drones of that segment. The links between drones of two segments are kept: the packets
+//cross them as usual, it's only the commands and the events that stay in every segment.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SegmentConfig {
+    pub name: String,
+    pub drones: Vec<NodeId>,
+}
+
+//The config of every segment, and the links between them as (drone, node of another segment),
+//both ways. The drones left out of every segment are left out of the network too.
+pub fn split_config(config: &Config, segments: &[SegmentConfig]) -> (Vec<Config>, Vec<(NodeId, NodeId)>) {
+    let segment_of = segments
+        .iter()
+        .enumerate()
+        .flat_map(|(i, segment)| segment.drones.iter().map(move |id| (*id, i)))
+        .collect::<HashMap<NodeId, usize>>();
+    let mut configs = vec![Config { drone: Vec::new(), client: Vec::new(), server: Vec::new() }; segments.len()];
+    let mut endpoint_segment = HashMap::new();
+    for client in config.client.iter() {
+        if let Some(&i) = client.connected_drone_ids.first().and_then(|id| segment_of.get(id)) {
+            let mut client = client.clone();
+            client.connected_drone_ids.retain(|id| segment_of.get(id) == Some(&i));
+            endpoint_segment.insert(client.id, i);
+            configs[i].client.push(client);
+        }
+    }
+    for server in config.server.iter() {
+        if let Some(&i) = server.connected_drone_ids.first().and_then(|id| segment_of.get(id)) {
+            let mut server = server.clone();
+            server.connected_drone_ids.retain(|id| segment_of.get(id) == Some(&i));
+            endpoint_segment.insert(server.id, i);
+            configs[i].server.push(server);
+        }
+    }
+
+    let mut cross_links = Vec::new();
+    for drone in config.drone.iter() {
+        let Some(&i) = segment_of.get(&drone.id) else {
+            continue;
+        };
+        let mut drone = drone.clone();
+        drone.connected_node_ids.retain(|id| {
+            match segment_of.get(id).or(endpoint_segment.get(id)) {
+                Some(&j) if j == i => true,
+                //The endpoints of another segment don't link back, see above.
+                Some(_) if endpoint_segment.contains_key(id) => false,
+                Some(_) => {
+                    cross_links.push((drone.id, *id));
+                    false
+                }
+                None => false,
+            }
+        });
+        configs[i].drone.push(drone);
+    }
+    (configs, cross_links)
+}
+
+pub struct Segment {
+    pub name: String,
+    pub sim_contr: SimulationControl,
+}
+
+//The Sim Contrs of the segments, moved together: every one reads the events of its own drones,
+//and the shortcuts for a node of another segment are handed to the segment that has it.
+pub struct Coordinator {
+    pub(crate) segments: Vec<Segment>,
+    pub(crate) relayed: u64, //Shortcuts that crossed from a segment to another.
+}
+
+impl Coordinator {
+    pub fn new(segments: Vec<Segment>, cross_links: &[(NodeId, NodeId)]) -> Self {
+        let mut coordinator = Coordinator { segments, relayed: 0 };
+        for (local, foreign) in cross_links {
+            let (Some(i), Some(j)) = (coordinator.segment_of(*local), coordinator.segment_of(*foreign)) else {
+                continue;
+            };
+            if let Some(foreign_send) = coordinator.segments[j].sim_contr.all_sender_packets.get(foreign).cloned() {
+                coordinator.segments[i].sim_contr.add_foreign_link(*local, *foreign, foreign_send);
+            }
+        }
+        for segment in coordinator.segments.iter_mut() {
+            segment.sim_contr.segmented = true;
+        }
+        coordinator
+    }
+
+    pub fn segment_of(&self, node: NodeId) -> Option<usize> {
+        self.segments.iter().position(|segment| segment.sim_contr.node_types.contains_key(&node))
+    }
+
+    pub fn process_events(&mut self) {
+        for segment in self.segments.iter_mut() {
+            segment.sim_contr.process_events();
+        }
+        for i in 0..self.segments.len() {
+            for packet in std::mem::take(&mut self.segments[i].sim_contr.foreign_shortcuts) {
+                let destination = packet.routing_header.hops.last().copied();
+                match destination.and_then(|destination| self.segment_of(destination)) {
+                    Some(j) if j != i => {
+                        self.relayed += 1;
+                        self.segments[j].sim_contr.relay_shortcut(packet);
+                    }
+                    _ => {
+                        let sim_contr = &mut self.segments[i].sim_contr;
+                        sim_contr.log.push(format!("dead letter: no segment has node {:?} for session {}", destination, packet.session_id));
+                        sim_contr.dead_letters.push(packet, DeadLetterReason::UnknownDestination);
+                    }
+                }
+            }
+        }
+    }
+
+    //The stats of every node of every segment, as if it was a single Sim Contr.
+    pub fn stats(&self) -> HashMap<NodeId, NodeStats> {
+        self.segments
+            .iter()
+            .flat_map(|segment| segment.sim_contr.stats.iter().map(|(id, stats)| (*id, stats.clone())))
+            .collect()
+    }
+
+    //A line for every segment with its totals, and one for the whole network.
+    pub fn describe(&self) -> Vec<String> {
+        let totals = |stats: Vec<&NodeStats>| {
+            let sent = stats.iter().map(|stats| stats.packets_sent).sum::<u64>();
+            let dropped = stats.iter().map(|stats| stats.packets_dropped).sum::<u64>();
+            let shortcuts = stats.iter().map(|stats| stats.shortcuts).sum::<u64>();
+            format!("{} packets sent, {} dropped, {} shortcuts", sent, dropped, shortcuts)
+        };
+        let mut lines = self.segments
+            .iter()
+            .map(|segment| {
+                let crashed = segment.sim_contr.crashed.len();
+                format!("{}: {} nodes ({} crashed), {}", segment.name, segment.sim_contr.node_types.len(), crashed, totals(segment.sim_contr.stats.values().collect()))
+            })
+            .collect::<Vec<String>>();
+        let stats = self.stats();
+        lines.push(format!("all segments: {}, {} shortcuts relayed between segments", totals(stats.values().collect()), self.relayed));
+        lines
+    }
+
+    pub fn shutdown(&mut self) {
+        for segment in self.segments.iter_mut() {
+            segment.sim_contr.shutdown();
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+use wg_2024::packet::{Ack, FloodRequest, FloodResponse, Fragment, Nack, NackType, NodeType, Packet, PacketType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CraftedType {
+    Fragment,
+    Ack,
+    Nack,
+    FloodRequest,
+    FloodResponse,
+}
+
+//The NackType without its node, which is nack_node in the draft.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CraftedNack {
+    ErrorInRouting,
+    DestinationIsDrone,
+    Dropped,
+    UnexpectedRecipient,
+}
+
+//A packet being put together by hand in the crafting panel of the GUI, any field can be wrong on
+//purpose: the drones get it as it is, through inject_packet. The route is clicked on the canvas.
+#[derive(Debug, Clone)]
+pub struct PacketDraft {
+    pub packet_type: CraftedType,
+    pub session_id: u64,
+    pub fragment_index: u64,
+    pub total_n_fragments: u64,
+    pub data: String, //Cut to the 128 bytes of a fragment.
+    pub nack: CraftedNack,
+    pub nack_node: NodeId, //The node of an ErrorInRouting or UnexpectedRecipient.
+    pub flood_id: u64,
+    pub route: Vec<NodeId>,
+    pub hop_index: usize, //The node of the route the packet is delivered to.
+}
+
+impl Default for PacketDraft {
+    fn default() -> Self {
+        PacketDraft {
+            packet_type: CraftedType::Fragment,
+            session_id: 0,
+            fragment_index: 0,
+            total_n_fragments: 1,
+            data: String::new(),
+            nack: CraftedNack::Dropped,
+            nack_node: 0,
+            flood_id: 0,
+            route: Vec::new(),
+            hop_index: 1,
+        }
+    }
+}
+
+impl PacketDraft {
+    //Only what inject_packet can't get past is refused: a hop index outside the route.
+    //The flood packets take their path trace from the route, with the types of the nodes: a
+    //request has the nodes it went through before the hop index, a response the whole route backwards.
+    pub fn build(&self, node_types: &HashMap<NodeId, NodeType>) -> Result<Packet, String> {
+        if self.hop_index >= self.route.len() {
+            return Err(format!("the hop index {} is outside the route of {} nodes", self.hop_index, self.route.len()));
+        }
+        let trace = |nodes: Vec<NodeId>| nodes.into_iter().map(|id| (id, node_types.get(&id).copied().unwrap_or(NodeType::Drone))).collect::<Vec<(NodeId, NodeType)>>();
+        let pack_type = match self.packet_type {
+            CraftedType::Fragment => {
+                let bytes = self.data.as_bytes();
+                let length = bytes.len().min(128);
+                let mut data = [0; 128];
+                data[..length].copy_from_slice(&bytes[..length]);
+                PacketType::MsgFragment(Fragment { fragment_index: self.fragment_index, total_n_fragments: self.total_n_fragments, length: length as u8, data })
+            }
+            CraftedType::Ack => PacketType::Ack(Ack { fragment_index: self.fragment_index }),
+            CraftedType::Nack => {
+                let nack_type = match self.nack {
+                    CraftedNack::ErrorInRouting => NackType::ErrorInRouting(self.nack_node),
+                    CraftedNack::DestinationIsDrone => NackType::DestinationIsDrone,
+                    CraftedNack::Dropped => NackType::Dropped,
+                    CraftedNack::UnexpectedRecipient => NackType::UnexpectedRecipient(self.nack_node),
+                };
+                PacketType::Nack(Nack { fragment_index: self.fragment_index, nack_type })
+            }
+            CraftedType::FloodRequest => PacketType::FloodRequest(FloodRequest {
+                flood_id: self.flood_id,
+                initiator_id: self.route[0],
+                path_trace: trace(self.route[..self.hop_index].to_vec()),
+            }),
+            CraftedType::FloodResponse => PacketType::FloodResponse(FloodResponse {
+                flood_id: self.flood_id,
+                path_trace: trace(self.route.iter().rev().copied().collect()),
+            }),
+        };
+        Ok(Packet {
+            pack_type,
+            routing_header: SourceRoutingHeader { hop_index: self.hop_index, hops: self.route.clone() },
+            session_id: self.session_id,
+        })
+    }
+}
@@ -0,0 +1,57 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const PAGE: &str = include_str!("dashboard.html");
+//A browser always sends its request line right away, a connection that doesn't is let go.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+//Serves the dashboard page and the last state published by the Sim Contr (see
+//SimulationControl::attach_dashboard). The page polls /state, so no websocket is needed.
+pub fn start_dashboard(address: &str) -> io::Result<Arc<Mutex<String>>> {
+    let listener = TcpListener::bind(address)?;
+    let state = Arc::new(Mutex::new("{}".to_string()));
+
+    let shared_state = state.clone();
+    thread::spawn(move || {
+        //A thread per connection, like the ipc: a slow client doesn't hold up the others.
+        for stream in listener.incoming() {
+            if let Ok(stream) = stream {
+                let state = shared_state.clone();
+                thread::spawn(move || {
+                    if let Err(e) = serve(stream, &state) {
+                        println!("dashboard request failed: {}", e);
+                    }
+                });
+            }
+        }
+    });
+
+    Ok(state)
+}
+
+fn serve(stream: TcpStream, state: &Arc<Mutex<String>>) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, content_type, body) = match path {
+        "/" | "/index.html" => ("200 OK", "text/html; charset=utf-8", PAGE.to_string()),
+        "/state" => ("200 OK", "application/json", state.lock().unwrap().clone()),
+        _ => ("404 Not Found", "text/plain", "not found".to_string()),
+    };
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
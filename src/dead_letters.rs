@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+use wg_2024::network::NodeId;
+use wg_2024::packet::{Packet, PacketType};
+
+//Only the newest ones are kept, a crashed server can make a lot of them.
+const MAX_DEAD_LETTERS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeadLetterReason {
+    UnknownDestination,
+    DestinationCrashed,
+    DestinationIsDrone, //Only the clients and the servers are the end of a route.
+    ChannelClosed, //The destination was there, but its channel was closed.
+    ChannelFull, //The destination was there, but too busy to take the packet right away.
+}
+
+//A packet the drones gave to the Sim Contr (a ControllerShortcut) that not even the Sim Contr
+//could deliver.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub packet: Packet,
+    pub destination: Option<NodeId>,
+    pub reason: DeadLetterReason,
+}
+
+impl DeadLetter {
+    pub fn describe(&self) -> String {
+        let kind = match &self.packet.pack_type {
+            PacketType::MsgFragment(fragment) => format!("fragment {}", fragment.fragment_index),
+            PacketType::Ack(ack) => format!("ack {}", ack.fragment_index),
+            PacketType::Nack(nack) => format!("nack {} {:?}", nack.fragment_index, nack.nack_type),
+            PacketType::FloodRequest(_) => "flood request".to_string(),
+            PacketType::FloodResponse(_) => "flood response".to_string(),
+        };
+        format!(
+            "session {} {} to {}: {:?} (route {:?})",
+            self.packet.session_id,
+            kind,
+            self.destination.map_or("?".to_string(), |id| id.to_string()),
+            self.reason,
+            self.packet.routing_header.hops
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DeadLetterQueue {
+    pub(crate) letters: VecDeque<DeadLetter>, //The oldest first.
+    pub(crate) total: u64, //Ever, the ones that didn't fit anymore too.
+}
+
+impl DeadLetterQueue {
+    pub fn push(&mut self, packet: Packet, reason: DeadLetterReason) {
+        let destination = packet.routing_header.hops.last().copied();
+        self.letters.push_back(DeadLetter { packet, destination, reason });
+        if self.letters.len() > MAX_DEAD_LETTERS {
+            self.letters.pop_front();
+        }
+        self.total += 1;
+    }
+
+    //The dead letters of a session, e.g. the one that never completes.
+    pub fn of_session(&self, session_id: u64) -> impl Iterator<Item = &DeadLetter> {
+        self.letters.iter().filter(move |letter| letter.packet.session_id == session_id)
+    }
+}
@@ -0,0 +1,29 @@
+use std::time::Duration;
+use serde::Deserialize;
+use crate::skylink_drone::dedup::DedupWindow;
+
+//The drones drop the copies of the fragments they've just forwarded, as written in the input file:
+//    [dedup]
+//    window = 256
+//    ttl_ms = 200
+//Every drone remembers the last `window` fragments it forwarded (256 if not given) by session and
+//index, each for ttl_ms (200 if not given), and drops a fragment it still remembers without a
+//nack. Without the table every copy is forwarded, as before.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DedupConfig {
+    pub window: usize,
+    pub ttl_ms: u64,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        DedupConfig { window: 256, ttl_ms: 200 }
+    }
+}
+
+impl DedupConfig {
+    pub fn window(&self) -> DedupWindow {
+        DedupWindow { capacity: self.window.max(1), ttl: Duration::from_millis(self.ttl_ms) }
+    }
+}
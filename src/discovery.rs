@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use crossbeam_channel::Receiver;
+use serde::Deserialize;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::{NodeType, PacketType};
+use crate::skylink_drone::gossip::{GossipMessage, TopologyView};
+
+//The gossip, as written in the input file:
+//    [gossip]
+//    period_ms = 500
+//    fanout = 0
+//Without the table the drones don't gossip, and the topology is only found with the floods.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GossipConfig {
+    pub period_ms: u64,
+    pub fanout: usize, //Neighbours told at every round, 0 for all of them but only when something changed.
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        GossipConfig { period_ms: 500, fanout: 0 }
+    }
+}
+
+//How far a client got with one of the two ways of finding the topology. The goal is to know
+//every node it can reach, the cost is what the network sent to get there.
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryProgress {
+    pub known: usize,
+    pub reachable: usize,
+    pub converged: Option<Duration>,
+    pub cost: u64, //Gossip messages (of the whole network) or flood packets.
+}
+
+//A flood started by the Sim Contr on behalf of a client.
+struct FloodRun {
+    client: NodeId,
+    flood_id: u64,
+    started: Instant,
+    target: HashSet<NodeId>, //What the client could reach when the flood started.
+    known: HashSet<NodeId>,
+    packets: u64,
+    converged: Option<Duration>,
+}
+
+//Follows the gossip and the floods reaching the clients, so the two can be compared.
+pub struct DiscoveryTracker {
+    started: Instant,
+    mailboxes: HashMap<NodeId, Receiver<GossipMessage>>, //Of the clients, the drones read their own.
+    views: HashMap<NodeId, TopologyView>,
+    gossip_converged: HashMap<NodeId, Duration>,
+    floods: Vec<FloodRun>,
+    next_flood_id: u64,
+}
+
+impl Default for DiscoveryTracker {
+    fn default() -> Self {
+        DiscoveryTracker {
+            started: Instant::now(),
+            mailboxes: HashMap::new(),
+            views: HashMap::new(),
+            gossip_converged: HashMap::new(),
+            floods: Vec::new(),
+            next_flood_id: 1 << 32, //Far from the flood ids of the tests.
+        }
+    }
+}
+
+impl DiscoveryTracker {
+    pub fn with_mailboxes(mailboxes: HashMap<NodeId, Receiver<GossipMessage>>) -> Self {
+        DiscoveryTracker { mailboxes, ..DiscoveryTracker::default() }
+    }
+
+    pub fn gossip_enabled(&self) -> bool {
+        !self.mailboxes.is_empty()
+    }
+
+    //Reads the gossip reaching the clients, returns the clients that just converged.
+    pub fn receive_gossip(&mut self, targets: &HashMap<NodeId, HashSet<NodeId>>) -> Vec<NodeId> {
+        let mut converged = Vec::new();
+        for (client, mailbox) in self.mailboxes.iter() {
+            let view = self.views.entry(*client).or_default();
+            while let Ok(message) = mailbox.try_recv() {
+                view.merge(&message.view);
+            }
+            if self.gossip_converged.contains_key(client) {
+                continue;
+            }
+            if let Some(target) = targets.get(client) {
+                if target.is_subset(&view.known_nodes()) {
+                    self.gossip_converged.insert(*client, self.started.elapsed());
+                    converged.push(*client);
+                }
+            }
+        }
+        converged
+    }
+
+    pub fn gossip_progress(&self, client: NodeId, target: &HashSet<NodeId>, gossip_sent: u64) -> DiscoveryProgress {
+        let known = self.views.get(&client).map(|view| view.known_nodes()).unwrap_or_default();
+        DiscoveryProgress {
+            known: target.intersection(&known).count(),
+            reachable: target.len(),
+            converged: self.gossip_converged.get(&client).copied(),
+            cost: gossip_sent,
+        }
+    }
+
+    //Without a flood id the next free one is used. With one, it can be the same of a flood of
+    //another client: the drones tell the floods apart by (flood_id, initiator), and so do I.
+    pub fn start_flood(&mut self, client: NodeId, target: HashSet<NodeId>, flood_id: Option<u64>) -> u64 {
+        let flood_id = flood_id.unwrap_or_else(|| {
+            self.next_flood_id += 1;
+            self.next_flood_id - 1
+        });
+        self.floods.push(FloodRun {
+            client,
+            flood_id,
+            started: Instant::now(),
+            target,
+            known: HashSet::from([client]),
+            packets: 0,
+            converged: None,
+        });
+        flood_id
+    }
+
+    //Counts the packets of the floods and reads the responses reaching their client,
+    //returns the client if its flood just converged.
+    pub fn record(&mut self, event: &DroneEvent) -> Option<NodeId> {
+        let DroneEvent::PacketSent(packet) = event else {
+            return None;
+        };
+        //A response goes back to the initiator, so it's the last hop of its route.
+        let header = &packet.routing_header;
+        let (flood_id, initiator, response) = match &packet.pack_type {
+            PacketType::FloodRequest(flood_request) => (flood_request.flood_id, Some(flood_request.initiator_id), None),
+            PacketType::FloodResponse(flood_response) => (flood_response.flood_id, header.hops.last().copied(), Some(&flood_response.path_trace)),
+            _ => return None,
+        };
+        //The newest run, if a client used the same flood id twice.
+        let run = self.floods.iter_mut().rev().find(|run| run.flood_id == flood_id && Some(run.client) == initiator)?;
+        run.packets += 1;
+        let arrived = header.hop_index + 1 == header.hops.len() && header.hops.last() == Some(&run.client);
+        if let (Some(path_trace), true, None) = (response, arrived, run.converged) {
+            run.known.extend(path_trace.iter().map(|(node_id, _)| *node_id));
+            if run.target.is_subset(&run.known) {
+                run.converged = Some(run.started.elapsed());
+                return Some(run.client);
+            }
+        }
+        None
+    }
+
+    //Of the last flood of the client.
+    pub fn flood_progress(&self, client: NodeId) -> Option<DiscoveryProgress> {
+        let run = self.floods.iter().rev().find(|run| run.client == client)?;
+        Some(DiscoveryProgress {
+            known: run.target.intersection(&run.known).count(),
+            reachable: run.target.len(),
+            converged: run.converged,
+            cost: run.packets,
+        })
+    }
+
+    //Whether the gossip still has to converge somewhere, so the targets are computed only when needed.
+    pub fn gossip_waiting(&self) -> bool {
+        self.mailboxes.keys().any(|client| !self.gossip_converged.contains_key(client))
+    }
+}
+
+//The nodes a client can reach going only through drones that are up, itself included.
+pub fn reachable(
+    graph: &HashMap<NodeId, Vec<NodeId>>,
+    node_types: &HashMap<NodeId, NodeType>,
+    crashed: &HashSet<NodeId>,
+    from: NodeId,
+) -> HashSet<NodeId> {
+    let mut seen = HashSet::from([from]);
+    let mut queue = VecDeque::from([from]);
+    while let Some(node) = queue.pop_front() {
+        let is_drone = matches!(node_types.get(&node), Some(NodeType::Drone) | None);
+        if node != from && !is_drone {
+            continue;
+        }
+        for next in graph.get(&node).into_iter().flatten() {
+            if !crashed.contains(next) && seen.insert(*next) {
+                queue.push_back(*next);
+            }
+        }
+    }
+    seen
+}
+
+pub fn describe(progress: &DiscoveryProgress) -> String {
+    let converged = progress.converged.map_or("not converged".to_string(), |time| format!("converged after {} ms", time.as_millis()));
+    format!("{}/{} nodes, {}, cost {}", progress.known, progress.reachable, converged, progress.cost)
+}
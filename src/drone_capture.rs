@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::drone::Drone;
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+use crate::skylink_drone::drone::{DroneStep, SkyLinkDrone};
+use crate::skylink_drone::tap::{TapRecord, TappedCommand};
+
+//What came into a drone, as written in the capture file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "input", rename_all = "snake_case")]
+pub enum CapturedInput {
+    Packet { packet: Packet },
+    AddSender { node: NodeId },
+    RemoveSender { node: NodeId },
+    SetPdr { pdr: f32 },
+    Crash,
+}
+
+//The traffic of a single drone taken with the tap, from when the capture started: the inputs
+//can be given to a fresh SkyLinkDrone, alone in this thread, and what it does compared with
+//what the drone did in the simulation. The filters, the impairments and the floods the drone
+//had already seen aren't in the capture, so it's best started right after the launch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DroneCapture {
+    pub drone_id: NodeId,
+    pub pdr: f32,
+    pub neighbours: Vec<NodeId>, //When the capture started.
+    pub inputs: Vec<CapturedInput>,
+    pub events: Vec<String>, //As Debug strings, like the traces of replay.rs.
+}
+
+impl DroneCapture {
+    pub fn new(drone_id: NodeId, pdr: f32, neighbours: Vec<NodeId>) -> Self {
+        DroneCapture {
+            drone_id,
+            pdr,
+            neighbours,
+            inputs: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, record: TapRecord) {
+        match record {
+            TapRecord::Packet(packet) => self.inputs.push(CapturedInput::Packet { packet }),
+            TapRecord::Command(TappedCommand::AddSender(node)) => self.inputs.push(CapturedInput::AddSender { node }),
+            TapRecord::Command(TappedCommand::RemoveSender(node)) => self.inputs.push(CapturedInput::RemoveSender { node }),
+            TapRecord::Command(TappedCommand::SetPacketDropRate(pdr)) => self.inputs.push(CapturedInput::SetPdr { pdr }),
+            TapRecord::Command(TappedCommand::Crash) => self.inputs.push(CapturedInput::Crash),
+            TapRecord::Event(event) => self.events.push(format!("{:?}", event)),
+        }
+    }
+
+    //Gives the inputs to a fresh drone one at a time, each once the previous one is done, and
+    //returns its events. With a pdr the drops are rolled with the seed, so they only match the
+    //capture by chance, but two replays with the same seed always match.
+    pub fn replay(&self, seed: u64) -> Vec<String> {
+        fastrand::seed(seed);
+        let (event_send, event_recv) = unbounded::<DroneEvent>();
+        let (command_send, command_recv) = unbounded::<DroneCommand>();
+        let (packet_send, packet_recv) = unbounded::<Packet>();
+        //The neighbours are channels nobody reads but me, emptied after every input.
+        let mut neighbours: HashMap<NodeId, (Sender<Packet>, Receiver<Packet>)> = HashMap::new();
+        for id in self.neighbours.iter() {
+            neighbours.insert(*id, unbounded());
+        }
+        let drone_send = neighbours.iter().map(|(id, (send, _))| (*id, send.clone())).collect();
+        let mut drone = SkyLinkDrone::new(self.drone_id, event_send, command_recv, packet_recv, drone_send, self.pdr);
+
+        let mut events = Vec::new();
+        for input in self.inputs.iter() {
+            match input {
+                CapturedInput::Packet { packet } => {
+                    let _ = packet_send.send(packet.clone());
+                }
+                CapturedInput::AddSender { node } => {
+                    let (send, _) = neighbours.entry(*node).or_insert_with(unbounded);
+                    let _ = command_send.send(DroneCommand::AddSender(*node, send.clone()));
+                }
+                CapturedInput::RemoveSender { node } => {
+                    let _ = command_send.send(DroneCommand::RemoveSender(*node));
+                }
+                CapturedInput::SetPdr { pdr } => {
+                    let _ = command_send.send(DroneCommand::SetPacketDropRate(*pdr));
+                }
+                CapturedInput::Crash => {
+                    let _ = command_send.send(DroneCommand::Crash);
+                }
+            }
+            while drone.step() == DroneStep::Worked {}
+            while let Ok(event) = event_recv.try_recv() {
+                events.push(format!("{:?}", event));
+            }
+            for (_, recv) in neighbours.values() {
+                while recv.try_recv().is_ok() {}
+            }
+        }
+        events
+    }
+
+    pub fn save(&self, file: &str) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(file, json)
+    }
+
+    pub fn load(file: &str) -> io::Result<Self> {
+        let json = fs::read_to_string(file)?;
+        serde_json::from_str(&json).map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use wg_2024::network::NodeId;
+use crate::skylink_drone::commands::EnergyBudget;
+
+//The battery of the drones, as written in the input file:
+//    [[energy]]
+//    drone = 3
+//    capacity = 500
+//    forward_cost = 1
+//    flood_cost = 2
+//Every packet the drone forwards costs forward_cost, every copy of a flood it sends flood_cost
+//(1 if not given). With an empty battery the drone crashes on its own, and the Sim Contr removes
+//it from its neighbours like after a crash of its own. A reboot gives back a full battery.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnergyConfig {
+    pub drone: NodeId,
+    pub capacity: u64,
+    #[serde(default = "default_cost")]
+    pub forward_cost: u64,
+    #[serde(default = "default_cost")]
+    pub flood_cost: u64,
+}
+
+fn default_cost() -> u64 {
+    1
+}
+
+impl EnergyConfig {
+    pub fn budget(&self) -> EnergyBudget {
+        EnergyBudget { capacity: self.capacity, forward_cost: self.forward_cost, flood_cost: self.flood_cost }
+    }
+}
+
+//The batteries by drone, for SkyLinkDrone::with_energy.
+pub fn budgets(configs: &[EnergyConfig]) -> HashMap<NodeId, EnergyBudget> {
+    configs.iter().map(|config| (config.drone, config.budget())).collect()
+}
@@ -0,0 +1,108 @@
+use std::thread;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use wg_2024::drone::Drone;
+use crate::skylink_drone::drone::{DroneStep, SkyLinkDrone};
+
+//How many steps a drone can do before the worker moves on to the next one of its shard.
+const BATCH: usize = 32;
+//How long a worker sleeps when no drone of its shard had anything to do.
+const IDLE_SLEEP: Duration = Duration::from_micros(500);
+
+//The default backend, a thread for every drone. Starting thousands of threads one after the
+//other is slow, so a few spawner threads share the work, calling progress(started, total) as they go.
+pub fn spawn_drone_threads(drones: Vec<SkyLinkDrone>, progress: &(dyn Fn(usize, usize) + Sync)) -> Vec<JoinHandle<()>> {
+    let total = drones.len();
+    let n_spawners = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let chunk_size = total.div_ceil(n_spawners).max(1);
+
+    let mut chunks = Vec::new();
+    let mut drones = drones;
+    while !drones.is_empty() {
+        let rest = drones.split_off(chunk_size.min(drones.len()));
+        chunks.push(drones);
+        drones = rest;
+    }
+
+    let started = AtomicUsize::new(0);
+    let started = &started;
+    thread::scope(|scope| {
+        let spawners = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || {
+                chunk
+                    .into_iter()
+                    .map(|mut drone| {
+                        let handle = thread::spawn(move || drone.run());
+                        progress(started.fetch_add(1, Ordering::Relaxed) + 1, total);
+                        handle
+                    })
+                    .collect::<Vec<JoinHandle<()>>>()
+            }))
+            .collect::<Vec<_>>();
+        spawners
+            .into_iter()
+            .flat_map(|spawner| spawner.join().unwrap())
+            .collect()
+    })
+}
+
+//Alternative to a thread for every drone: the drones are split in n_workers shards, and every
+//worker polls the channels of its drones in a loop. With 10k drones this means a handful of
+//threads instead of 10k, at the cost of a bit of latency when the network is idle.
+pub fn run_sharded(drones: Vec<SkyLinkDrone>, n_workers: usize) -> Vec<JoinHandle<()>> {
+    let n_workers = n_workers.max(1);
+    let mut shards = (0..n_workers).map(|_| Vec::new()).collect::<Vec<Vec<SkyLinkDrone>>>();
+    for (i, drone) in drones.into_iter().enumerate() {
+        shards[i % n_workers].push(drone);
+    }
+
+    shards
+        .into_iter()
+        .filter(|shard| !shard.is_empty())
+        .map(|shard| thread::spawn(move || run_shard(shard)))
+        .collect()
+}
+
+fn run_shard(mut shard: Vec<SkyLinkDrone>) {
+    //The worker ends when all its drones would have ended their run().
+    while !shard.is_empty() {
+        let mut worked = false;
+        shard.retain_mut(|drone| {
+            for _ in 0..BATCH {
+                match drone.step() {
+                    DroneStep::Worked => worked = true,
+                    DroneStep::Idle => return true,
+                    DroneStep::Finished => return false,
+                }
+            }
+            true
+        });
+        if !worked {
+            thread::sleep(IDLE_SLEEP);
+        }
+    }
+}
+
+//Waits for the drone threads to end after a shutdown, giving up on the ones that don't
+//(e.g. a bridge still waiting for its remote side), so the process can always exit.
+pub fn join_drones(handles: Vec<JoinHandle<()>>) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut handles = handles;
+    while !handles.is_empty() && Instant::now() < deadline {
+        let (finished, running): (Vec<JoinHandle<()>>, Vec<JoinHandle<()>>) = handles
+            .into_iter()
+            .partition(|handle| handle.is_finished());
+        for handle in finished {
+            if handle.join().is_err() {
+                println!("a drone thread panicked.");
+            }
+        }
+        handles = running;
+        thread::sleep(Duration::from_millis(10));
+    }
+    if !handles.is_empty() {
+        println!("{} threads didn't stop in time.", handles.len());
+    }
+}
@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use wg_2024::network::NodeId;
+
+//A fault of the timeline, declared in the input file:
+//    [[fault]]
+//    at_s = 30
+//    fault = "crash"
+//    drone = 7
+//
+//    [[fault]]
+//    at_s = 60
+//    fault = "flap"
+//    a = 2
+//    b = 5
+//    until_s = 90
+//    period_ms = 2000
+//
+//    [[fault]]
+//    at_s = 40
+//    fault = "crash_group"
+//    group = "backbone"
+//    count = 1
+//The other faults are "set_pdr" (drone, pdr), "link_down" and "link_up" (a, b) and
+//"set_group_pdr" (group, pdr). The times are from the start of the simulation, so the same file
+//always fails the same way, but the members of a group are taken when the fault runs: count of
+//them at random, all of them without count.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FaultConfig {
+    pub at_s: f64,
+    #[serde(flatten)]
+    pub fault: Fault,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "fault", rename_all = "snake_case")]
+pub enum Fault {
+    Crash { drone: NodeId },
+    SetPdr { drone: NodeId, pdr: f32 },
+    LinkDown { a: NodeId, b: NodeId },
+    LinkUp { a: NodeId, b: NodeId },
+    CrashGroup { group: String, count: Option<usize> },
+    SetGroupPdr { group: String, pdr: f32 },
+    //The link goes down at at_s, and then up and down every period_ms, until until_s when it's up again.
+    Flap {
+        a: NodeId,
+        b: NodeId,
+        until_s: f64,
+        #[serde(default = "default_flap_period")]
+        period_ms: u64,
+    },
+}
+
+fn default_flap_period() -> u64 {
+    1000
+}
+
+//What the Sim Contr does at a point of the timeline, a flap is a sequence of these.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FaultAction {
+    Crash(NodeId),
+    SetPdr(NodeId, f32),
+    LinkDown(NodeId, NodeId),
+    LinkUp(NodeId, NodeId),
+    CrashGroup(String, Option<usize>),
+    SetGroupPdr(String, f32),
+}
+
+pub struct FaultSchedule {
+    started: Instant,
+    actions: Vec<(Duration, FaultAction)>, //Sorted by time, the first one is the next to run.
+}
+
+impl Default for FaultSchedule {
+    fn default() -> Self {
+        FaultSchedule { started: Instant::now(), actions: Vec::new() }
+    }
+}
+
+impl FaultSchedule {
+    pub fn add(&mut self, fault: &FaultConfig) {
+        let at = Duration::from_secs_f64(fault.at_s.max(0.0));
+        match fault.fault {
+            Fault::Crash { drone } => self.push(at, FaultAction::Crash(drone)),
+            Fault::SetPdr { drone, pdr } => self.push(at, FaultAction::SetPdr(drone, pdr)),
+            Fault::LinkDown { a, b } => self.push(at, FaultAction::LinkDown(a, b)),
+            Fault::LinkUp { a, b } => self.push(at, FaultAction::LinkUp(a, b)),
+            Fault::CrashGroup { ref group, count } => self.push(at, FaultAction::CrashGroup(group.clone(), count)),
+            Fault::SetGroupPdr { ref group, pdr } => self.push(at, FaultAction::SetGroupPdr(group.clone(), pdr)),
+            Fault::Flap { a, b, until_s, period_ms } => {
+                let until = Duration::from_secs_f64(until_s.max(0.0));
+                let period = Duration::from_millis(period_ms.max(1));
+                let mut time = at;
+                let mut down = true;
+                while time < until {
+                    let action = if down { FaultAction::LinkDown(a, b) } else { FaultAction::LinkUp(a, b) };
+                    self.push(time, action);
+                    time += period;
+                    down = !down;
+                }
+                self.push(until, FaultAction::LinkUp(a, b));
+            }
+        }
+    }
+
+    fn push(&mut self, at: Duration, action: FaultAction) {
+        //After the ones at the same time, so they run in the order they were declared.
+        let index = self.actions.partition_point(|(time, _)| *time <= at);
+        self.actions.insert(index, (at, action));
+    }
+
+    //The actions whose time has come, in order, each with its time.
+    pub fn due(&mut self) -> Vec<(Duration, FaultAction)> {
+        let now = self.started.elapsed();
+        let n_due = self.actions.partition_point(|(time, _)| *time <= now);
+        self.actions.drain(..n_due).collect()
+    }
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+use crate::skylink_drone::commands::FilterRule;
+
+//The filter rules of a drone, as written in the input file:
+//    [[filter]]
+//    drone = 3
+//    deny_from = [1]
+//    only_forward_to = [4, 5]
+//    block_session = [42]
+//Every key can be left out, a drone without [[filter]] lets everything through.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    pub deny_from: Vec<NodeId>,
+    pub only_forward_to: Option<Vec<NodeId>>,
+    pub block_session: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DroneFilterConfig {
+    pub drone: NodeId,
+    #[serde(flatten)]
+    pub filters: FilterConfig,
+}
+
+impl FilterConfig {
+    pub fn rules(&self) -> Vec<FilterRule> {
+        self.deny_from
+            .iter()
+            .map(|node_id| FilterRule::DenyFrom(*node_id))
+            .chain(self.only_forward_to.iter().map(|next_hops| FilterRule::OnlyForwardTo(next_hops.clone())))
+            .chain(self.block_session.iter().map(|session_id| FilterRule::BlockSession(*session_id)))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deny_from.is_empty() && self.only_forward_to.is_none() && self.block_session.is_empty()
+    }
+}
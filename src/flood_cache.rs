@@ -0,0 +1,22 @@
+use std::time::Duration;
+use serde::Deserialize;
+use crate::skylink_drone::flood_cache::FloodCacheLimits;
+
+//How long the drones remember the floods they've seen, as written in the input file:
+//    [flood_cache]
+//    capacity = 1000
+//    ttl_ms = 5000
+//Both are optional. A drone that forgot a flood handles it again as a new one, so a client can
+//run a new discovery with the ids it already used. Without the table the drones never forget.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FloodCacheConfig {
+    pub capacity: Option<usize>,
+    pub ttl_ms: Option<u64>,
+}
+
+impl FloodCacheConfig {
+    pub fn limits(&self) -> FloodCacheLimits {
+        FloodCacheLimits { capacity: self.capacity, ttl: self.ttl_ms.map(Duration::from_millis) }
+    }
+}
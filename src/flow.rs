@@ -0,0 +1,271 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+use wg_2024::packet::{Fragment, NackType, Packet, PacketType};
+use crate::routing::RoutingStrategy;
+
+//A fragment neither delivered nor nacked after this long is taken as lost and sent again.
+pub const RETRANSMIT_TIMEOUT: Duration = Duration::from_secs(1);
+//Far from the sessions of the tests and below the ones of the traceroute probes.
+pub const TRANSFER_SESSION_BASE: u64 = 1 << 40;
+
+//The sliding window of the messages sent by the Sim Contr for a client, as written in the input file:
+//    [flow_control]
+//    window = 8
+//    min_window = 1
+//    max_window = 64
+//    suppress_duplicate_acks = true
+//window is where a message starts, 0 sends every fragment at once like before. Then the window
+//grows by one fragment every window acks and is halved by every nack or timeout.
+//An Ack for a fragment already acked (a retransmission acked twice, or a drone making acks up) is
+//always counted, and with suppress_duplicate_acks it's ignored; without, it grows the window again.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FlowControlConfig {
+    pub window: usize,
+    pub min_window: usize,
+    pub max_window: usize,
+    pub suppress_duplicate_acks: bool,
+}
+
+impl Default for FlowControlConfig {
+    fn default() -> Self {
+        FlowControlConfig { window: 8, min_window: 1, max_window: 64, suppress_duplicate_acks: true }
+    }
+}
+
+//A message of many fragments on its way. There are no client and server threads, so the Sim
+//Contr plays both: a fragment reaching the end of the route counts as acked, a nack reaching
+//the start of the route sends it again. With more routes (see routing.rs) all of them go from the
+//same client to the same server.
+pub struct Transfer {
+    pub session_id: u64,
+    routes: Vec<Vec<NodeId>>,
+    dropped_routes: HashSet<usize>, //Broken, the fragments go on the others.
+    pub strategy: RoutingStrategy,
+    pub initial_routes: usize,
+    pub total: u64,
+    to_send: VecDeque<u64>, //Never sent or to be sent again, the first one goes next.
+    in_flight: HashMap<u64, (Instant, usize)>, //With the route it was sent on.
+    delivered: HashSet<u64>,
+    pub window: f64, //0 for no window at all.
+    min_window: f64,
+    max_window: f64,
+    pub retransmissions: u64,
+    pub corrupted: u64, //Fragments that reached the server with another payload, taken as lost.
+    acked: HashSet<u64>, //By an Ack back at the client, only with an ack sink at the server (see ack_sink.rs).
+    pub duplicate_acks: u64,
+    suppress_duplicate_acks: bool,
+    started: Instant,
+    pub finished: Option<Duration>,
+    pub cancelled: bool, //Given up by the client: nothing is sent anymore, what comes back is ignored.
+}
+
+impl Transfer {
+    pub fn new(session_id: u64, routes: Vec<Vec<NodeId>>, strategy: RoutingStrategy, total: u64, window: usize, config: &FlowControlConfig) -> Self {
+        Transfer {
+            session_id,
+            initial_routes: routes.len(),
+            routes,
+            dropped_routes: HashSet::new(),
+            strategy,
+            total,
+            to_send: (0..total).collect(),
+            in_flight: HashMap::new(),
+            delivered: HashSet::new(),
+            window: window as f64,
+            min_window: config.min_window.max(1) as f64,
+            max_window: config.max_window.max(config.min_window.max(1)) as f64,
+            retransmissions: 0,
+            corrupted: 0,
+            acked: HashSet::new(),
+            duplicate_acks: 0,
+            suppress_duplicate_acks: config.suppress_duplicate_acks,
+            started: Instant::now(),
+            finished: None,
+            cancelled: false,
+        }
+    }
+
+    fn shrink(&mut self) {
+        if self.window > 0.0 {
+            self.window = (self.window / 2.0).max(self.min_window);
+        }
+    }
+
+    //The fragments that fit in the window now, after the lost ones went back in the queue.
+    pub fn next_packets(&mut self) -> Vec<Packet> {
+        let now = Instant::now();
+        let mut lost = self.in_flight
+            .iter()
+            .filter(|(_, (sent, _))| now.duration_since(*sent) > RETRANSMIT_TIMEOUT)
+            .map(|(index, _)| *index)
+            .collect::<Vec<u64>>();
+        lost.sort();
+        if !lost.is_empty() {
+            //A whole burst timing out is one congestion event, not one for every fragment.
+            self.shrink();
+        }
+        for index in lost.into_iter().rev() {
+            self.in_flight.remove(&index);
+            self.to_send.push_front(index);
+            self.retransmissions += 1;
+        }
+
+        let mut packets = Vec::new();
+        while self.window == 0.0 || (self.in_flight.len() as f64) < self.window.floor() {
+            let Some(index) = self.to_send.pop_front() else {
+                break;
+            };
+            if self.delivered.contains(&index) {
+                continue;
+            }
+            let route = self.least_loaded_route();
+            self.in_flight.insert(index, (now, route));
+            packets.push(self.fragment(index, route));
+        }
+        packets
+    }
+
+    //The route still up with the fewest fragments in flight, the shortest one on a tie.
+    fn least_loaded_route(&self) -> usize {
+        (0..self.routes.len())
+            .filter(|route| !self.dropped_routes.contains(route))
+            .min_by_key(|route| self.in_flight.values().filter(|(_, sent_on)| sent_on == route).count())
+            .unwrap_or(0)
+    }
+
+    //The same every time it's sent, so the server can tell a damaged one.
+    fn payload(&self, index: u64) -> [u8; 128] {
+        let text = format!("session {} fragment {}/{} ", self.session_id, index, self.total);
+        let mut data = [0; 128];
+        for (byte, text_byte) in data.iter_mut().zip(text.bytes().cycle()) {
+            *byte = text_byte;
+        }
+        data
+    }
+
+    fn fragment(&self, index: u64, route: usize) -> Packet {
+        Packet {
+            pack_type: PacketType::MsgFragment(Fragment {
+                fragment_index: index,
+                total_n_fragments: self.total,
+                length: 128,
+                data: self.payload(index),
+            }),
+            routing_header: SourceRoutingHeader { hop_index: 1, hops: self.routes[route].clone() },
+            session_id: self.session_id,
+        }
+    }
+
+    //No more fragments or retransmissions, the ones in flight are forgotten.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+        self.to_send.clear();
+        self.in_flight.clear();
+    }
+
+    //The server all the routes go to.
+    pub fn destination(&self) -> Option<NodeId> {
+        self.routes.first().and_then(|route| route.last()).copied()
+    }
+
+    fn grow(&mut self) {
+        if self.window > 0.0 {
+            self.window = (self.window + 1.0 / self.window).min(self.max_window);
+        }
+    }
+
+    //Reads the packets of the transfer reaching its ends, returns true if it just finished.
+    pub fn record(&mut self, packet: &Packet) -> bool {
+        let header = &packet.routing_header;
+        if packet.session_id != self.session_id || header.hop_index + 1 != header.hops.len() {
+            return false;
+        }
+        //The acks are counted even once the message is over, the late duplicates are the interesting ones.
+        if let PacketType::Ack(ack) = &packet.pack_type {
+            if header.hops.last() == self.routes[0].first() {
+                self.record_ack(ack.fragment_index);
+            }
+        }
+        if self.finished.is_some() || self.cancelled {
+            return false;
+        }
+        match &packet.pack_type {
+            //The server throws a damaged fragment away, it's sent again when it times out.
+            PacketType::MsgFragment(fragment) if header.hops.last() == self.routes[0].last() && fragment.data != self.payload(fragment.fragment_index) => {
+                self.corrupted += 1;
+            }
+            PacketType::MsgFragment(fragment) if header.hops.last() == self.routes[0].last() => {
+                self.in_flight.remove(&fragment.fragment_index);
+                if self.delivered.insert(fragment.fragment_index) {
+                    self.grow();
+                }
+            }
+            PacketType::Nack(nack) if header.hops.last() == self.routes[0].first() => {
+                let sent_on = self.in_flight.remove(&nack.fragment_index).map(|(_, route)| route);
+                //A broken route is left for good, as long as there's another one.
+                if let (Some(route), NackType::ErrorInRouting(_)) = (sent_on, nack.nack_type) {
+                    if self.routes.len() - self.dropped_routes.len() > 1 {
+                        self.dropped_routes.insert(route);
+                    }
+                }
+                if sent_on.is_some() && !self.delivered.contains(&nack.fragment_index) {
+                    self.to_send.push_front(nack.fragment_index);
+                    self.retransmissions += 1;
+                    self.shrink();
+                }
+            }
+            _ => {}
+        }
+        if self.delivered.len() as u64 == self.total {
+            self.finished = Some(self.started.elapsed());
+            return true;
+        }
+        false
+    }
+
+    //The first Ack of a fragment confirms it, in case its arrival at the server wasn't seen.
+    fn record_ack(&mut self, index: u64) {
+        if index >= self.total {
+            return;
+        }
+        if !self.acked.insert(index) {
+            self.duplicate_acks += 1;
+            if !self.suppress_duplicate_acks && self.finished.is_none() && !self.cancelled {
+                self.grow();
+            }
+            return;
+        }
+        if self.finished.is_some() || self.cancelled {
+            return;
+        }
+        self.in_flight.remove(&index);
+        if self.delivered.insert(index) {
+            self.grow();
+        }
+    }
+
+    pub fn delivered(&self) -> usize {
+        self.delivered.len()
+    }
+
+    pub fn describe(&self) -> String {
+        let state = match self.finished {
+            Some(time) => format!("delivered in {} ms", time.as_millis()),
+            None if self.cancelled => format!("cancelled with {}/{} delivered", self.delivered.len(), self.total),
+            None => format!("{}/{} delivered", self.delivered.len(), self.total),
+        };
+        let window = if self.window > 0.0 { format!("{:.1}", self.window) } else { "none".to_string() };
+        let routes = self.routes.len() - self.dropped_routes.len();
+        let mut faults = String::new();
+        if self.duplicate_acks > 0 {
+            faults.push_str(&format!(", {} duplicate acks", self.duplicate_acks));
+        }
+        if self.corrupted > 0 {
+            faults.push_str(&format!(", {} corrupted fragments", self.corrupted));
+        }
+        format!("message {}: {}, window {}, {} retransmissions, {} routes{}", self.session_id, state, window, self.retransmissions, routes, faults)
+    }
+}
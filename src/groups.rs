@@ -0,0 +1,36 @@
+use std::fs;
+use std::io;
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+
+//A set of nodes with a name, from the input file, the GUI or a groups file:
+//    [[group]]
+//    name = "backbone"
+//    nodes = [1, 2, 3]
+//    color = [255, 140, 0]
+//The name can be used wherever a group goes: the profiles, the faults "crash_group" and
+//"set_group_pdr", and the group of the drop_rate and queue_length alerts. The color is the one
+//the GUI draws around the members with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupDefinition {
+    pub name: String,
+    pub nodes: Vec<NodeId>,
+    pub color: Option<[u8; 3]>,
+}
+
+//A groups file has only the [[group]] tables, so it can be pasted into an input file as it is.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GroupFile {
+    #[serde(default)]
+    group: Vec<GroupDefinition>,
+}
+
+pub fn save_groups(file: &str, groups: &[GroupDefinition]) -> io::Result<()> {
+    let file_str = toml::to_string(&GroupFile { group: groups.to_vec() }).map_err(io::Error::other)?;
+    fs::write(file, file_str)
+}
+
+pub fn load_groups(file: &str) -> io::Result<Vec<GroupDefinition>> {
+    let file_str = fs::read_to_string(file)?;
+    Ok(toml::from_str::<GroupFile>(&file_str).map_err(io::Error::other)?.group)
+}
@@ -0,0 +1,70 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use wg_2024::network::NodeId;
+use crate::skylink_drone::heartbeat::Heartbeat;
+
+//A drone that misses this many beats in a row is stalled: its thread is stuck (or dead) even if
+//nobody crashed it.
+const MISSED_BEATS: u32 = 3;
+
+//The last heartbeat of a drone, as seen by the Sim Contr.
+#[derive(Debug, Clone)]
+pub struct NodeHealth {
+    pub last_beat: Instant,
+    pub queue_depth: usize,
+    pub packets_per_s: f64, //Handled between the last two beats.
+    pub pdr: f32,
+    pub stalled: bool,
+}
+
+//The heartbeats of every drone, only if they beat (heartbeat_ms in the input file).
+pub struct HealthMonitor {
+    pub interval: Duration,
+    pub nodes: HashMap<NodeId, NodeHealth>,
+}
+
+impl HealthMonitor {
+    pub fn new(interval: Duration) -> Self {
+        HealthMonitor { interval, nodes: HashMap::new() }
+    }
+
+    pub fn stall_time(&self) -> Duration {
+        self.interval * MISSED_BEATS
+    }
+
+    //Returns true if the drone was stalled and it's back.
+    pub fn record(&mut self, beat: &Heartbeat) -> bool {
+        let now = Instant::now();
+        let since = self.nodes.get(&beat.drone).map_or(self.interval, |health| now.duration_since(health.last_beat));
+        let was_stalled = self.nodes.get(&beat.drone).map_or(false, |health| health.stalled);
+        self.nodes.insert(beat.drone, NodeHealth {
+            last_beat: now,
+            queue_depth: beat.queue_depth,
+            packets_per_s: beat.handled as f64 / since.as_secs_f64().max(0.001),
+            pdr: beat.pdr,
+            stalled: false,
+        });
+        was_stalled
+    }
+
+    //The drones that just missed too many beats. The crashed ones are silent on purpose, they're
+    //forgotten until they beat again.
+    pub fn newly_stalled(&mut self, crashed: &HashSet<NodeId>) -> Vec<NodeId> {
+        let limit = self.stall_time();
+        self.nodes.retain(|id, _| !crashed.contains(id));
+        let mut stalled = Vec::new();
+        for (id, health) in self.nodes.iter_mut() {
+            if !health.stalled && health.last_beat.elapsed() > limit {
+                health.stalled = true;
+                stalled.push(*id);
+            }
+        }
+        stalled.sort();
+        stalled
+    }
+
+    //A rebooted drone starts from a clean slate.
+    pub fn forget(&mut self, id: NodeId) {
+        self.nodes.remove(&id);
+    }
+}
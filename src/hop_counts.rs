@@ -0,0 +1,95 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use wg_2024::network::NodeId;
+use wg_2024::packet::{Packet, PacketType};
+
+//The route lengths of the messages that went from a source to a destination.
+#[derive(Debug, Clone, Default)]
+pub struct HopCountStats {
+    pub distribution: BTreeMap<usize, u64>, //Messages by number of hops.
+    pub extra_hops: u64, //Over the shortest route there was when each message arrived.
+    pub compared: u64,   //The messages with a shortest route to compare with.
+}
+
+impl HopCountStats {
+    pub fn messages(&self) -> u64 {
+        self.distribution.values().sum()
+    }
+
+    pub fn mean(&self) -> f64 {
+        let hops = self.distribution.iter().map(|(hops, n)| *hops as u64 * n).sum::<u64>();
+        hops as f64 / self.messages().max(1) as f64
+    }
+
+    //How many hops more than needed a message took on average, 0 if every route was a shortest one.
+    pub fn mean_extra_hops(&self) -> Option<f64> {
+        (self.compared > 0).then(|| self.extra_hops as f64 / self.compared as f64)
+    }
+
+    pub fn describe(&self) -> String {
+        let min = self.distribution.keys().next().copied().unwrap_or(0);
+        let max = self.distribution.keys().last().copied().unwrap_or(0);
+        let mut text = format!("{} messages, {} to {} hops, mean {:.2}", self.messages(), min, max, self.mean());
+        if let Some(extra) = self.mean_extra_hops() {
+            text.push_str(&format!(", {:.2} more than the shortest", extra));
+        }
+        text
+    }
+}
+
+//How long the routes of the delivered messages were, by (source, destination). A message counts
+//once, when its first fragment reaches the destination, so a learned route can be compared to
+//the shortest one without the number of fragments getting in the way.
+#[derive(Debug, Default)]
+pub struct HopCountTable {
+    pub(crate) pairs: BTreeMap<(NodeId, NodeId), HopCountStats>,
+    counted: HashSet<(NodeId, u64)>, //(source, session), the session ids are only unique for a source.
+}
+
+impl HopCountTable {
+    //Called with every PacketSent. shortest(source, destination) gives the hops of the shortest
+    //route at the moment, it's only called once for every message.
+    pub fn record(&mut self, packet: &Packet, shortest: impl FnOnce(NodeId, NodeId) -> Option<usize>) {
+        let header = &packet.routing_header;
+        if !matches!(packet.pack_type, PacketType::MsgFragment(_)) || header.hops.len() < 2 || header.hop_index + 1 != header.hops.len() {
+            return;
+        }
+        let (source, destination) = (header.hops[0], header.hops[header.hops.len() - 1]);
+        if !self.counted.insert((source, packet.session_id)) {
+            return;
+        }
+        let hops = header.hops.len() - 1;
+        let stats = self.pairs.entry((source, destination)).or_default();
+        *stats.distribution.entry(hops).or_insert(0) += 1;
+        if let Some(shortest) = shortest(source, destination) {
+            stats.extra_hops += hops.saturating_sub(shortest) as u64;
+            stats.compared += 1;
+        }
+    }
+
+    //One row per pair, with the whole distribution in the last column (hops:messages).
+    pub fn export_csv(&self, file: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(file)?);
+        writeln!(writer, "source,destination,messages,min_hops,max_hops,mean_hops,mean_extra_hops,distribution")?;
+        for ((source, destination), stats) in self.pairs.iter() {
+            let distribution = stats.distribution
+                .iter()
+                .map(|(hops, n)| format!("{}:{}", hops, n))
+                .collect::<Vec<String>>();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{:.3},{},{}",
+                source,
+                destination,
+                stats.messages(),
+                stats.distribution.keys().next().copied().unwrap_or(0),
+                stats.distribution.keys().last().copied().unwrap_or(0),
+                stats.mean(),
+                stats.mean_extra_hops().map(|extra| format!("{:.3}", extra)).unwrap_or_default(),
+                distribution.join(";")
+            )?;
+        }
+        writer.flush()
+    }
+}
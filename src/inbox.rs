@@ -0,0 +1,121 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+use wg_2024::network::NodeId;
+use wg_2024::packet::{NodeType, Packet, PacketType};
+
+//Only the newest messages of every server are kept, so a long run doesn't keep all its traffic.
+const MAX_MESSAGES_PER_SERVER: usize = 200;
+
+//A message as seen arriving at a server: the Sim Contr puts its fragments back together from
+//the PacketSent events of the last hop, so it can show what the server got without asking it.
+#[derive(Debug, Clone)]
+pub struct ReceivedMessage {
+    pub session_id: u64,
+    pub source: NodeId,
+    pub total_n_fragments: u64,
+    pub fragments: BTreeMap<u64, Vec<u8>>, //By fragment index, a duplicate replaces the first copy.
+    pub first_at: SystemTime,
+    pub last_at: SystemTime,
+}
+
+impl ReceivedMessage {
+    pub fn is_complete(&self) -> bool {
+        self.fragments.len() as u64 == self.total_n_fragments
+    }
+
+    pub fn size(&self) -> usize {
+        self.fragments.values().map(|data| data.len()).sum()
+    }
+
+    //The bytes in order, the missing fragments are left out.
+    pub fn data(&self) -> Vec<u8> {
+        self.fragments.values().flatten().copied().collect()
+    }
+
+    //From the first fragment to the last one received.
+    pub fn duration(&self) -> Duration {
+        self.last_at.duration_since(self.first_at).unwrap_or_default()
+    }
+
+    pub fn describe(&self) -> String {
+        let state = if self.is_complete() {
+            "complete".to_string()
+        } else {
+            format!("{}/{} fragments", self.fragments.len(), self.total_n_fragments)
+        };
+        let data = self.data();
+        let mut preview = String::from_utf8_lossy(&data[..data.len().min(32)]).replace(|c: char| c.is_control(), ".");
+        if data.len() > 32 {
+            preview.push_str("...");
+        }
+        format!(
+            "session {} from {}: {} bytes, {}, in {} ms \"{}\"",
+            self.session_id,
+            self.source,
+            self.size(),
+            state,
+            self.duration().as_millis(),
+            preview
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ServerInboxes {
+    pub(crate) messages: HashMap<NodeId, Vec<ReceivedMessage>>, //By server, the oldest first.
+    cancelled: HashSet<u64>, //Sessions whose fragments are thrown away, the late ones too.
+}
+
+impl ServerInboxes {
+    //The cancel notice of a client: the server throws away the message it was putting together,
+    //unless it's already complete, and whatever fragment of it is still on the way.
+    pub fn cancel(&mut self, server: NodeId, session_id: u64) -> bool {
+        self.cancelled.insert(session_id);
+        let Some(inbox) = self.messages.get_mut(&server) else {
+            return false;
+        };
+        let before = inbox.len();
+        inbox.retain(|message| message.session_id != session_id || message.is_complete());
+        inbox.len() < before
+    }
+
+    //Called with every PacketSent, only the fragments reaching one of the servers count.
+    pub fn record(&mut self, packet: &Packet, node_types: &HashMap<NodeId, NodeType>, time: SystemTime) {
+        let PacketType::MsgFragment(fragment) = &packet.pack_type else {
+            return;
+        };
+        let header = &packet.routing_header;
+        let Some(server) = header.hops.last() else {
+            return;
+        };
+        if header.hop_index + 1 != header.hops.len() || !matches!(node_types.get(server), Some(NodeType::Server)) {
+            return;
+        }
+        if self.cancelled.contains(&packet.session_id) {
+            return;
+        }
+
+        let inbox = self.messages.entry(*server).or_default();
+        let index = match inbox.iter().position(|message| message.session_id == packet.session_id) {
+            Some(index) => index,
+            None => {
+                inbox.push(ReceivedMessage {
+                    session_id: packet.session_id,
+                    source: header.hops[0],
+                    total_n_fragments: fragment.total_n_fragments,
+                    fragments: BTreeMap::new(),
+                    first_at: time,
+                    last_at: time,
+                });
+                if inbox.len() > MAX_MESSAGES_PER_SERVER {
+                    inbox.remove(0);
+                }
+                inbox.len() - 1
+            }
+        };
+        let message = &mut inbox[index];
+        let length = (fragment.length as usize).min(fragment.data.len());
+        message.fragments.insert(fragment.fragment_index, fragment.data[..length].to_vec());
+        message.last_at = time;
+    }
+}
@@ -1,14 +1,227 @@
 use std::{fs, thread};
 use std::thread::JoinHandle;
 use std::collections::HashMap;
-use crossbeam_channel::unbounded;
+use std::sync::Arc;
+use std::time::Duration;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use serde::Deserialize;
 use wg_2024::config::Config;
 use wg_2024::drone::Drone;
-use crate::sim_control::SimulationControl;
+use wg_2024::network::NodeId;
+use crate::sim_control::{DroneGossip, SimulationControl};
+use wg_2024::packet::{NodeType, Packet};
 use crate::skylink_drone::drone::SkyLinkDrone;
+use crate::skylink_drone::counters::DroneCounters;
+use crate::snapshot::SimulationSnapshot;
+use crate::bridge::{BridgeConfig, SkyLinkBridge};
+use crate::executor::{run_sharded, spawn_drone_threads};
+use crate::memory::MemoryLimits;
+use crate::regions::Region;
+use crate::filters::{DroneFilterConfig, FilterConfig};
+use crate::capacity::LinkCapacityConfig;
+use crate::latency::{delays_of, ProcessingDelayConfig};
+use crate::link_rate::{rates_of, LinkRateConfig};
+use crate::link_pdr::{link_pdrs, pdrs_of, LinkPdrConfig};
+use crate::energy::{budgets, EnergyConfig};
+use crate::reorder::{reorders, ReorderConfig};
+use crate::flood_cache::FloodCacheConfig;
+use crate::burst_loss::{burst_losses, BurstLossConfig};
+use crate::dedup::DedupConfig;
+use crate::discovery::GossipConfig;
+use crate::faults::FaultConfig;
+use crate::alerts::AlertConfig;
+use crate::flow::FlowControlConfig;
+use crate::profiles::Profile;
+use crate::ack_sink::AckSinkConfig;
+use crate::sla::SlaProbeConfig;
+use crate::groups::GroupDefinition;
+use crate::tags::TagConfig;
+use crate::telemetry::TelemetryConfig;
+use crate::routing::RoutingConfig;
+use crate::coordinator::{split_config, Coordinator, Segment, SegmentConfig};
+use crate::skylink_drone::gossip::{Gossip, GossipMailboxes, GossipMessage};
+
+//The sections of the input file that aren't part of the wg_2024 Config.
+#[derive(Debug, Default, Deserialize)]
+struct ExtraConfig {
+    //Top level keys, they must come before the first [[drone]]:
+    //    channel_capacity = 64
+    //    send_timeout_ms = 50
+    //Without a capacity the packet channels are unbounded as before.
+    channel_capacity: Option<usize>,
+    send_timeout_ms: Option<u64>,
+    //    executor_threads = 8
+    //With it the drones are run by that many worker threads (see executor.rs) instead of a thread each.
+    executor_threads: Option<usize>,
+    //    event_batch_size = 64
+    //    event_batch_ms = 10
+    //With a batch size the drones send their events to the Sim Contr in batches (see events.rs).
+    event_batch_size: Option<usize>,
+    event_batch_ms: Option<u64>,
+    //    handshake_timeout_ms = 1000
+    //With it every drone sends a hello to the neighbours added while running (see handshake.rs),
+    //and a link that only works one way is reported in the log.
+    handshake_timeout_ms: Option<u64>,
+    //    heartbeat_ms = 500
+    //With it every drone tells the Sim Contr it's alive this often (see health.rs), so a stuck
+    //thread shows up as stalled.
+    heartbeat_ms: Option<u64>,
+    //    max_events_per_s = 5000
+    //With it no drone sends more events than that to the Sim Contr in a second, the others are
+    //counted as suppressed (see events.rs): a runaway drone can't starve the event loop anymore.
+    max_events_per_s: Option<u64>,
+    //    max_hops = 16
+    //With it the drones nack the fragments on a route longer than that, or on a route that goes
+    //through them twice (see ttl_check), instead of following it.
+    max_hops: Option<usize>,
+    //    evict_after_failures = 3
+    //With it a drone removes a neighbour after that many sends in a row found its channel closed
+    //(see eviction.rs), and the Sim Contr hears that the link is down.
+    evict_after_failures: Option<u32>,
+    //    tick_ms = 100
+    //With it every drone wakes up this often even without traffic, to expire its flood cache and
+    //run the DroneHooks::on_tick of the experiments (see SkyLinkDrone::on_tick).
+    tick_ms: Option<u64>,
+    //With a [dedup] table the drones drop the copies of the fragments they've just forwarded (see dedup.rs).
+    dedup: Option<DedupConfig>,
+    //    seed = 42
+    //With it every drone rolls its drops with its own generator seeded from it (see drone_seed),
+    //so a run with the same seed and the same traffic drops the same packets.
+    seed: Option<u64>,
+    #[serde(default)]
+    memory_limits: MemoryLimits,
+    #[serde(default)]
+    bridge: Vec<BridgeConfig>,
+    #[serde(default)]
+    region: Vec<Region>,
+    #[serde(default)]
+    filter: Vec<DroneFilterConfig>,
+    #[serde(default)]
+    link_capacity: LinkCapacityConfig,
+    #[serde(default)]
+    processing_delay: Vec<ProcessingDelayConfig>,
+    #[serde(default)]
+    link_rate: Vec<LinkRateConfig>,
+    #[serde(default)]
+    link: Vec<LinkPdrConfig>,
+    #[serde(default)]
+    energy: Vec<EnergyConfig>,
+    #[serde(default)]
+    reorder: Vec<ReorderConfig>,
+    #[serde(default)]
+    burst_loss: Vec<BurstLossConfig>,
+    #[serde(default)]
+    flood_cache: FloodCacheConfig,
+    gossip: Option<GossipConfig>,
+    #[serde(default)]
+    fault: Vec<FaultConfig>,
+    #[serde(default)]
+    alert: Vec<AlertConfig>,
+    #[serde(default)]
+    flow_control: FlowControlConfig,
+    #[serde(default)]
+    routing: RoutingConfig,
+    #[serde(default)]
+    profile: Vec<Profile>,
+    #[serde(default)]
+    ack_sink: Vec<AckSinkConfig>,
+    #[serde(default)]
+    sla_probe: Vec<SlaProbeConfig>,
+    #[serde(default)]
+    group: Vec<GroupDefinition>,
+    #[serde(default)]
+    tag: Vec<TagConfig>,
+    #[serde(default)]
+    telemetry: Vec<TelemetryConfig>,
+    #[serde(default)]
+    segment: Vec<SegmentConfig>,
+}
+
+//Every drone gets a seed of its own, the same one every time (a rebooted drone too).
+pub fn drone_seed(seed: u64, id: NodeId) -> u64 {
+    seed.wrapping_add(id as u64)
+}
+
+pub fn packet_channel(capacity: Option<usize>) -> (Sender<Packet>, Receiver<Packet>) {
+    match capacity {
+        Some(capacity) => bounded(capacity),
+        None => unbounded(),
+    }
+}
 
 pub fn initialize(file: &str) -> (SimulationControl, Vec<JoinHandle<()>>) {
+    initialize_with_progress(file, &|_, _| {})
+}
+
+//Same as initialize, but progress(started, total) is called every time a drone starts,
+//so a frontend can show how far along a big network is.
+pub fn initialize_with_progress(file: &str, progress: &(dyn Fn(usize, usize) + Sync)) -> (SimulationControl, Vec<JoinHandle<()>>) {
+    let config = parse_config(file);
+    let extra = parse_extra_config(file);
+    initialize_config(config, extra, progress)
+}
+
+//Starts the network drawn in the topology editor. If it came from a file, the sections of the
+//file that the editor doesn't show (regions, filters, ...) are still applied.
+pub fn initialize_edited(config: Config, file: Option<&str>) -> (SimulationControl, Vec<JoinHandle<()>>) {
+    let extra = file.map(parse_extra_config).unwrap_or_default();
+    initialize_config(config, extra, &print_progress)
+}
+
+//Every [[segment]] of the file gets its own Sim Contr, under a Coordinator. The other sections
+//of the file aren't applied to the segments (yet).
+pub fn initialize_segmented(file: &str) -> (Coordinator, Vec<JoinHandle<()>>) {
     let config = parse_config(file);
+    let extra = parse_extra_config(file);
+    let (configs, cross_links) = split_config(&config, &extra.segment);
+    let mut segments = Vec::new();
+    let mut handles = Vec::new();
+    for (segment, config) in extra.segment.iter().zip(configs) {
+        let (sim_contr, segment_handles) = initialize_config(config, ExtraConfig::default(), &print_progress);
+        segments.push(Segment { name: segment.name.clone(), sim_contr });
+        handles.extend(segment_handles);
+    }
+    (Coordinator::new(segments, &cross_links), handles)
+}
+
+//A line every 10%, only for networks big enough to take a while.
+pub fn print_progress(started: usize, total: usize) {
+    if total >= 1000 && started % (total / 10) == 0 {
+        println!("{}/{} drones started", started, total);
+    }
+}
+
+//Builds the network described in the snapshot, then puts back crashes and stats.
+pub fn initialize_from_snapshot(file: &str) -> (SimulationControl, Vec<JoinHandle<()>>) {
+    let snapshot = SimulationSnapshot::load(file).unwrap();
+    initialize_from_state(&snapshot)
+}
+
+//Same, with a snapshot taken from a live simulation instead of a file.
+pub fn initialize_from_state(snapshot: &SimulationSnapshot) -> (SimulationControl, Vec<JoinHandle<()>>) {
+    let (mut sim_contr, handles) = initialize_config(snapshot.to_config(), ExtraConfig::default(), &print_progress);
+    sim_contr.restore_snapshot(snapshot);
+    (sim_contr, handles)
+}
+
+fn initialize_config(config: Config, extra: ExtraConfig, progress: &(dyn Fn(usize, usize) + Sync)) -> (SimulationControl, Vec<JoinHandle<()>>) {
+    let bridges = extra.bridge;
+    let capacity = extra.channel_capacity;
+    let send_timeout = Duration::from_millis(extra.send_timeout_ms.unwrap_or(50));
+    let executor_threads = extra.executor_threads;
+    let event_batch_size = extra.event_batch_size;
+    let event_batch_delay = Duration::from_millis(extra.event_batch_ms.unwrap_or(10));
+    let (event_batch_send, event_batch_recv) = unbounded();
+    let handshake_timeout = extra.handshake_timeout_ms.map(Duration::from_millis);
+    let max_events_per_s = extra.max_events_per_s;
+    let seed = extra.seed;
+    let (handshake_send, handshake_recv) = unbounded();
+    let heartbeat_interval = extra.heartbeat_ms.map(Duration::from_millis);
+    let (heartbeat_send, heartbeat_recv) = unbounded();
+    let evict_after_failures = extra.evict_after_failures;
+    let tick_interval = extra.tick_ms.map(Duration::from_millis);
+    let (link_down_send, link_down_recv) = unbounded();
+
     let mut handles = Vec::new();
     //I'll return the handles of the threads, and join them to the main thread.
 
@@ -18,24 +231,13 @@ pub fn initialize(file: &str) -> (SimulationControl, Vec<JoinHandle<()>>) {
     //I create the channel, the 'send' will be given to every drone,
     //while the 'recv' will go to the Sim contr.
 
-    let mut packet_senders = HashMap::new();
-    let mut packet_receivers = HashMap::new();
-    //I create receivers and senders for every drone.
-    for drone in config.drone.iter() {
-        let (send, recv) = unbounded();
-        packet_senders.insert(drone.id, send);
-        packet_receivers.insert(drone.id, recv);
-    }
-    for client in config.client.iter() {
-        let (send, recv) = unbounded();
-        packet_senders.insert(client.id, send);
-        packet_receivers.insert(client.id, recv);
-    }
-    for server in config.server.iter() {
-        let (send, recv) = unbounded();
-        packet_senders.insert(server.id, send);
-        packet_receivers.insert(server.id, recv);
-    }
+    //I create receivers and senders for every node.
+    let node_ids = config.drone.iter().map(|drone| drone.id)
+        .chain(config.client.iter().map(|client| client.id))
+        .chain(config.server.iter().map(|server| server.id))
+        .chain(bridges.iter().map(|bridge| bridge.id))
+        .collect::<Vec<NodeId>>();
+    let (packet_senders, mut packet_receivers) = build_packet_channels(&node_ids, capacity);
 
     //I crate a hashmap that will be used as graph by the Simulation Controller.
     let mut network_graph = HashMap::new();
@@ -48,6 +250,59 @@ pub fn initialize(file: &str) -> (SimulationControl, Vec<JoinHandle<()>>) {
     for client in config.client.iter() {
         network_graph.insert(client.id, client.connected_drone_ids.clone());
     }
+    for bridge in bridges.iter() {
+        network_graph.insert(bridge.id, bridge.connected_node_ids.clone());
+    }
+
+    //I save the type of every node, since the graph alone doesn't tell them apart.
+    let mut node_types = HashMap::new();
+    for drone in config.drone.iter() {
+        node_types.insert(drone.id, NodeType::Drone);
+    }
+    for client in config.client.iter() {
+        node_types.insert(client.id, NodeType::Client);
+    }
+    for server in config.server.iter() {
+        node_types.insert(server.id, NodeType::Server);
+    }
+
+    //I save the pdr of every drone, so the Sim Contr knows the starting state of the network.
+    let node_pdr = config.drone.iter().map(|drone| (drone.id, drone.pdr)).collect();
+
+    //Every drone writes its stats in its counters, and the Sim Contr reads them from there.
+    let mut counters = HashMap::new();
+    //And the Sim Contr reaches it with the commands outside of wg_2024 through one of these.
+    let mut skylink_send = HashMap::new();
+
+    //The filter rules of the drones, the Sim Contr keeps a copy to show them and change them.
+    let filters = extra.filter
+        .into_iter()
+        .map(|filter| (filter.drone, filter.filters))
+        .collect::<HashMap<NodeId, FilterConfig>>();
+
+    //With the gossip every drone and client gets a mailbox, the drones read their own and the
+    //Sim Contr reads the ones of the clients.
+    let mut gossip_recvs = HashMap::new();
+    let mut gossip_mailboxes = HashMap::new();
+    if extra.gossip.is_some() {
+        for id in config.drone.iter().map(|drone| drone.id).chain(config.client.iter().map(|client| client.id)) {
+            let (gossip_send, gossip_recv) = unbounded();
+            gossip_mailboxes.insert(id, gossip_send);
+            gossip_recvs.insert(id, gossip_recv);
+        }
+    }
+    let gossip_mailboxes: GossipMailboxes = Arc::new(gossip_mailboxes);
+    //The drones' ones are moved into the drones, the Sim Contr keeps a copy for the drones started later.
+    let drone_gossip_recvs = config.drone.iter()
+        .filter_map(|drone| gossip_recvs.get(&drone.id).map(|recv| (drone.id, recv.clone())))
+        .collect::<HashMap<NodeId, Receiver<GossipMessage>>>();
+
+    //The drones are built here, and started all together by the chosen executor.
+    let mut drones = Vec::new();
+    let pdrs = link_pdrs(&extra.link);
+    let energy = budgets(&extra.energy);
+    let reorder = reorders(&extra.reorder);
+    let burst_loss = burst_losses(&extra.burst_loss);
 
     for drone in config.drone.into_iter() {
         //Adding the sender to this drone to the senders of the Sim Contr.
@@ -57,6 +312,8 @@ pub fn initialize(file: &str) -> (SimulationControl, Vec<JoinHandle<()>>) {
         //Give the drone a copy of the sender of events to the Sim Contr.
         let node_event_send = event_send.clone();
 
+        let link_capacities = extra.link_capacity.capacities(drone.id, &drone.connected_node_ids);
+
         //Take the channels necessary to this drone.
         let drone_recv = packet_receivers.remove(&drone.id).unwrap();
         let drone_send = drone
@@ -65,23 +322,192 @@ pub fn initialize(file: &str) -> (SimulationControl, Vec<JoinHandle<()>>) {
             .map(|id| (id, packet_senders[&id].clone()))
             .collect();
 
-        //create the thread of the drone, and add it to a Vec to be pushed afterward
-        handles.push(thread::spawn(move || {
-            let mut drone = SkyLinkDrone::new(drone.id, node_event_send, contr_recv, drone_recv, drone_send, drone.pdr);
+        let drone_counters = Arc::new(DroneCounters::default());
+        counters.insert(drone.id, drone_counters.clone());
+        let (drone_skylink_send, drone_skylink_recv) = unbounded();
+        skylink_send.insert(drone.id, drone_skylink_send);
 
-            drone.run();
-        }));
+        let mut drone = SkyLinkDrone::new(drone.id, node_event_send, contr_recv, drone_recv, drone_send, drone.pdr)
+            .with_send_timeout(send_timeout)
+            .with_counters(drone_counters)
+            .with_skylink_commands(drone_skylink_recv)
+            .with_filters(filters.get(&drone.id).map(|filter| filter.rules()).unwrap_or_default())
+            .with_link_capacities(link_capacities)
+            .with_processing_delays(delays_of(&extra.processing_delay, drone.id))
+            .with_link_rates(rates_of(&extra.link_rate, drone.id))
+            .with_link_pdrs(pdrs_of(&pdrs, drone.id))
+            .with_reorder(reorder.get(&drone.id).copied())
+            .with_burst_loss(burst_loss.get(&drone.id).copied())
+            .with_max_hops(extra.max_hops)
+            .with_dedup(extra.dedup.as_ref().map(DedupConfig::window))
+            .with_tick(tick_interval)
+            .with_flood_cache(extra.flood_cache.limits());
+        if let Some(batch_size) = event_batch_size {
+            drone = drone.with_event_batching(event_batch_send.clone(), batch_size, event_batch_delay);
+        }
+        if let Some(budget) = energy.get(&drone.get_id()) {
+            drone = drone.with_energy(*budget);
+        }
+        if let Some(config) = extra.telemetry.iter().find(|config| config.node == drone.get_id()) {
+            drone = drone.with_telemetry(config.telemetry());
+        }
+        if let Some(timeout) = handshake_timeout {
+            drone = drone.with_handshake(timeout, handshake_send.clone());
+        }
+        if let Some(interval) = heartbeat_interval {
+            drone = drone.with_heartbeat(interval, heartbeat_send.clone());
+        }
+        if let Some(after) = evict_after_failures {
+            drone = drone.with_eviction(after, link_down_send.clone());
+        }
+        if let Some(max_per_s) = max_events_per_s {
+            drone = drone.with_event_rate_limit(max_per_s);
+        }
+        if let Some(seed) = seed {
+            let id = drone.get_id();
+            drone = drone.with_seed(drone_seed(seed, id));
+        }
+        if let (Some(gossip), Some(gossip_recv)) = (&extra.gossip, gossip_recvs.remove(&drone.get_id())) {
+            let period = Duration::from_millis(gossip.period_ms);
+            drone = drone.with_gossip(Gossip::new(gossip_mailboxes.clone(), period, gossip.fanout), gossip_recv);
+        }
+
+        drones.push(drone);
         //This will probably need to be changed based on the
         //implementation of other groups drones in our network.
     }
+    match executor_threads {
+        Some(n_workers) => handles.extend(run_sharded(drones, n_workers)),
+        None => handles.extend(spawn_drone_threads(drones, progress)),
+    }
 
+    for bridge in bridges.into_iter() {
+        let node_event_send = event_send.clone();
+        let bridge_recv = packet_receivers.remove(&bridge.id).unwrap();
+        let bridge_send = bridge
+            .connected_node_ids
+            .iter()
+            .map(|id| (*id, packet_senders[id].clone()))
+            .collect();
 
-    let sim_contr = SimulationControl::new(command_send, event_recv, event_send, packet_senders, network_graph);
+        handles.push(thread::spawn(move || {
+            let id = bridge.id;
+            if let Err(e) = SkyLinkBridge::new(bridge, node_event_send, bridge_recv, bridge_send).run() {
+                println!("bridge {} stopped: {}", id, e);
+            }
+        }));
+    }
+
+
+    let mut sim_contr = SimulationControl::new(command_send, event_recv, event_send, packet_senders, network_graph, node_types, node_pdr);
+    sim_contr.set_channel_capacity(capacity, send_timeout);
+    sim_contr.set_event_rate_limit(max_events_per_s);
+    sim_contr.set_max_hops(extra.max_hops);
+    sim_contr.set_flood_cache(extra.flood_cache.limits());
+    sim_contr.set_dedup(extra.dedup.as_ref().map(DedupConfig::window));
+    sim_contr.set_tick(tick_interval);
+    sim_contr.set_drone_seed(seed);
+    if let Some(batch_size) = event_batch_size {
+        sim_contr.attach_event_batches(event_batch_send, batch_size, event_batch_delay, event_batch_recv);
+    }
+    sim_contr.set_counters(counters);
+    if let Some(timeout) = handshake_timeout {
+        sim_contr.attach_handshakes(timeout, handshake_send, handshake_recv);
+    }
+    if let Some(interval) = heartbeat_interval {
+        sim_contr.attach_heartbeats(interval, heartbeat_send, heartbeat_recv);
+    }
+    if let Some(after) = evict_after_failures {
+        sim_contr.attach_links_down(after, link_down_send, link_down_recv);
+    }
+    sim_contr.set_memory_limits(extra.memory_limits);
+    sim_contr.set_skylink_commands(skylink_send);
+    sim_contr.set_regions(extra.region);
+    sim_contr.filters = filters;
+    sim_contr.set_link_capacity(extra.link_capacity);
+    sim_contr.processing_delays = extra.processing_delay;
+    sim_contr.link_rates = extra.link_rate;
+    sim_contr.link_pdrs = pdrs;
+    sim_contr.energy = energy;
+    sim_contr.reorder = reorder;
+    sim_contr.burst_loss = burst_loss;
+    //Before the profiles, faults and alerts that use them.
+    for group in extra.group {
+        sim_contr.define_group(group);
+    }
+    sim_contr.set_annotations(&extra.tag);
+    //The drones already have theirs, the Sim Contr keeps them for the reboots and the verbosity.
+    for config in extra.telemetry {
+        if !config.is_default() {
+            sim_contr.telemetry.insert(config.node, config);
+        }
+    }
+    for fault in extra.fault.iter() {
+        sim_contr.schedule_fault(fault);
+    }
+    sim_contr.set_flow_control(extra.flow_control);
+    sim_contr.set_routing(extra.routing);
+    for alert in extra.alert {
+        sim_contr.add_alert(alert);
+    }
+    for profile in extra.profile {
+        sim_contr.add_profile(profile);
+    }
+    for ack_sink in extra.ack_sink {
+        sim_contr.add_ack_sink(ack_sink);
+    }
+    for sla_probe in extra.sla_probe {
+        sim_contr.add_sla_probe(sla_probe);
+    }
+    if let Some(gossip) = &extra.gossip {
+        //Only the mailboxes of the clients are left.
+        sim_contr.attach_gossip(gossip_recvs);
+        sim_contr.set_drone_gossip(DroneGossip {
+            mailboxes: gossip_mailboxes,
+            drone_mailboxes: drone_gossip_recvs,
+            period: Duration::from_millis(gossip.period_ms),
+            fanout: gossip.fanout,
+        });
+    }
 
     (sim_contr, handles)
 }
 
-fn parse_config(file: &str) -> Config {
+//With thousands of nodes the channels are built by a few threads, each taking a slice of the ids.
+fn build_packet_channels(node_ids: &[NodeId], capacity: Option<usize>) -> (HashMap<NodeId, Sender<Packet>>, HashMap<NodeId, Receiver<Packet>>) {
+    let n_threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let chunk_size = node_ids.len().div_ceil(n_threads).max(1);
+
+    let channels = thread::scope(|scope| {
+        let builders = node_ids
+            .chunks(chunk_size)
+            .map(|ids| scope.spawn(move || {
+                ids.iter()
+                    .map(|id| (*id, packet_channel(capacity)))
+                    .collect::<Vec<(NodeId, (Sender<Packet>, Receiver<Packet>))>>()
+            }))
+            .collect::<Vec<_>>();
+        builders
+            .into_iter()
+            .flat_map(|builder| builder.join().unwrap())
+            .collect::<Vec<(NodeId, (Sender<Packet>, Receiver<Packet>))>>()
+    });
+
+    let mut packet_senders = HashMap::new();
+    let mut packet_receivers = HashMap::new();
+    for (id, (send, recv)) in channels {
+        packet_senders.insert(id, send);
+        packet_receivers.insert(id, recv);
+    }
+    (packet_senders, packet_receivers)
+}
+
+pub fn parse_config(file: &str) -> Config {
+    let file_str = fs::read_to_string(file).unwrap();
+    toml::from_str(&file_str).unwrap()
+}
+
+fn parse_extra_config(file: &str) -> ExtraConfig {
     let file_str = fs::read_to_string(file).unwrap();
     toml::from_str(&file_str).unwrap()
 }
\ No newline at end of file
@@ -0,0 +1,401 @@
+use std::io;
+use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+use crate::sim_control::{NodeStats, SimulationControl};
+use crate::filters::FilterConfig;
+use crate::profiles::NodeGroup;
+use crate::audit::CommandOrigin;
+use crate::telemetry::{EventCategory, LogVerbosity, TelemetryConfig};
+use crate::link_rate::LinkRateConfig;
+use crate::reorder::{DelayKind, ReorderConfig};
+use crate::burst_loss::BurstLossConfig;
+
+//One request per line, for example:
+//    {"cmd": "crash", "id": 3}
+//    {"cmd": "reboot", "id": 3}
+//    {"cmd": "set_pdr", "id": 3, "pdr": 0.5}
+//    {"cmd": "set_link_pdr", "a": 3, "b": 4, "pdr": 0.3}
+//    {"cmd": "stats", "id": 3}
+//    {"cmd": "drone_state", "id": 3}
+//    {"cmd": "drone_state", "file": "drones.json"}
+//    {"cmd": "memory"}
+//    {"cmd": "export_report", "file": "report.html"}
+//    {"cmd": "diff_snapshot", "file": "snapshot.toml"}
+//    {"cmd": "set_region", "index": 0, "extra_drop": 0.3, "latency_ms": 20}
+//    {"cmd": "set_filters", "id": 3, "deny_from": [1], "block_session": [42]}
+//    {"cmd": "set_processing_delay", "id": 3, "delay_ms": 20, "jitter_ms": 5, "to": 4}
+//    {"cmd": "set_link_rate", "id": 3, "to": 4, "packets_per_sec": 10, "burst": 5, "queue": 0}
+//    {"cmd": "set_packet_faults", "id": 3, "duplicate": 0.1, "corrupt": 0.05}
+//    {"cmd": "set_reorder", "id": 3, "buffer": 8, "distribution": "exponential", "max_ms": 50}
+//    {"cmd": "set_reorder", "id": 3}
+//    {"cmd": "set_burst_loss", "id": 3, "p_good_to_bad": 0.05, "p_bad_to_good": 0.3, "loss_bad": 0.8}
+//    {"cmd": "set_burst_loss", "id": 3}
+//    {"cmd": "set_telemetry", "id": 3, "verbosity": "verbose", "interval_ms": 5, "mute": ["floods"]}
+//    {"cmd": "apply_profile", "name": "lossy", "group": "core"}
+//    {"cmd": "tag", "id": 3, "tag": "backbone"}
+//    {"cmd": "tag", "id": 3, "link_to": 4, "tag": "fiber", "remove": true}
+//    {"cmd": "tags"}
+//    {"cmd": "dead_letters", "session": 42}
+//    {"cmd": "traffic_matrix"}
+//    {"cmd": "commands", "id": 3, "origin": "chaos"}
+//    {"cmd": "cancel", "session": 1099511627776}
+//Every request gets back exactly one line with an IpcResponse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum IpcRequest {
+    Nodes,
+    Crash { id: NodeId },
+    Reboot { id: NodeId },
+    SetPdr { id: NodeId, pdr: f32 },
+    SetLinkPdr { a: NodeId, b: NodeId, pdr: Option<f32> }, //Without pdr the link goes back to the one of the drones.
+    Stats { id: NodeId },
+    DroneState { id: Option<NodeId>, file: Option<String> }, //Without an id, of every SkyLink drone.
+    Pause,
+    Snapshot { file: String },
+    DiffSnapshot { file: String },
+    ExportStats { file: String },
+    ExportReport { file: String },
+    Memory,
+    SetRegion { index: usize, extra_drop: f32, latency_ms: u64 },
+    SetFilters {
+        id: NodeId,
+        #[serde(default)]
+        deny_from: Vec<NodeId>,
+        #[serde(default)]
+        only_forward_to: Option<Vec<NodeId>>,
+        #[serde(default)]
+        block_session: Vec<u64>,
+    },
+    SetProcessingDelay {
+        id: NodeId,
+        to: Option<NodeId>,
+        delay_ms: u64,
+        #[serde(default)]
+        jitter_ms: u64,
+    },
+    SetLinkRate {
+        id: NodeId,
+        to: Option<NodeId>,
+        packets_per_sec: u64,
+        burst: Option<u64>,
+        queue: Option<u64>,
+    },
+    SetPacketFaults {
+        id: NodeId,
+        #[serde(default)]
+        duplicate: f32,
+        #[serde(default)]
+        corrupt: f32,
+    },
+    SetReorder {
+        id: NodeId,
+        buffer: Option<usize>,
+        #[serde(default)]
+        distribution: DelayKind,
+        #[serde(default)]
+        min_ms: u64,
+        max_ms: Option<u64>, //Without it the jitter mode is turned off.
+        mean_ms: Option<u64>,
+    },
+    SetBurstLoss {
+        id: NodeId,
+        p_good_to_bad: Option<f32>, //Without it the drone goes back to its pdr.
+        #[serde(default)]
+        p_bad_to_good: f32,
+        #[serde(default)]
+        loss_good: f32,
+        loss_bad: Option<f32>,
+    },
+    SetTelemetry {
+        id: NodeId,
+        #[serde(default)]
+        verbosity: LogVerbosity,
+        interval_ms: Option<u64>,
+        #[serde(default)]
+        mute: Vec<EventCategory>,
+    },
+    Profiles,
+    ApplyProfile { name: String, group: Option<NodeGroup> },
+    Tag {
+        id: NodeId,
+        link_to: Option<NodeId>,
+        tag: String,
+        #[serde(default)]
+        remove: bool,
+    },
+    Tags,
+    DeadLetters { session: Option<u64> },
+    TrafficMatrix,
+    Commands { id: NodeId, origin: Option<CommandOrigin> },
+    Cancel { session: u64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcResponse {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl IpcResponse {
+    fn ok(data: Option<serde_json::Value>) -> Self {
+        IpcResponse { ok: true, error: None, data }
+    }
+
+    fn error(error: String) -> Self {
+        IpcResponse { ok: false, error: Some(error), data: None }
+    }
+}
+
+//A request waiting to be executed by the Sim Contr, with the channel to send back the result.
+pub struct IpcCall {
+    pub request: IpcRequest,
+    pub reply: Sender<IpcResponse>,
+}
+
+//Executed by the Sim Contr in its own thread, so the socket threads never touch it.
+pub(crate) fn handle_request(sim_contr: &mut SimulationControl, request: IpcRequest) -> IpcResponse {
+    match request {
+        IpcRequest::Nodes => {
+            let mut ids = sim_contr.network_graph.keys().copied().collect::<Vec<NodeId>>();
+            ids.sort();
+            IpcResponse::ok(serde_json::to_value(ids).ok())
+        },
+        IpcRequest::Crash { id } => {
+            if !sim_contr.drone_ids().contains(&id) {
+                return IpcResponse::error(format!("drone {} not found in the network", id));
+            }
+            sim_contr.crash_drone(id);
+            IpcResponse::ok(None)
+        },
+        IpcRequest::Reboot { id } => {
+            if !sim_contr.reboot_drone(id) {
+                return IpcResponse::error(format!("drone {} can't be rebooted", id));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::SetPdr { id, pdr } => {
+            if !sim_contr.drone_ids().contains(&id) {
+                return IpcResponse::error(format!("drone {} not found in the network", id));
+            }
+            sim_contr.set_pdr(id, pdr);
+            IpcResponse::ok(None)
+        },
+        IpcRequest::SetLinkPdr { a, b, pdr } => {
+            if !sim_contr.set_link_pdr(a, b, pdr) {
+                return IpcResponse::error(format!("no SkyLink drone at either end of {}-{}", a, b));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::Stats { id } => {
+            let stats = sim_contr.stats.get(&id).cloned().unwrap_or(NodeStats::default());
+            IpcResponse::ok(serde_json::to_value(stats).ok())
+        },
+        IpcRequest::DroneState { id: Some(id), file: _ } => match sim_contr.drone_state(id) {
+            Some(state) => IpcResponse::ok(serde_json::to_value(state).ok()),
+            None => IpcResponse::error(format!("drone {} didn't tell its state", id)),
+        },
+        IpcRequest::DroneState { id: None, file } => {
+            let states = sim_contr.drone_states(file.as_deref());
+            IpcResponse::ok(serde_json::to_value(states).ok())
+        },
+        IpcRequest::Pause => {
+            sim_contr.toggle_pause();
+            IpcResponse::ok(serde_json::to_value(sim_contr.paused).ok())
+        },
+        IpcRequest::Snapshot { file } => {
+            sim_contr.save_snapshot(&file);
+            IpcResponse::ok(None)
+        },
+        IpcRequest::DiffSnapshot { file } => {
+            match sim_contr.diff_with_snapshot(&file) {
+                Some(diff) => IpcResponse::ok(serde_json::to_value(diff).ok()),
+                None => IpcResponse::error(format!("snapshot {} not readable", file)),
+            }
+        },
+        IpcRequest::ExportStats { file } => {
+            sim_contr.export_stats(&file);
+            IpcResponse::ok(None)
+        },
+        IpcRequest::ExportReport { file } => {
+            sim_contr.export_report(&file);
+            IpcResponse::ok(None)
+        },
+        IpcRequest::Memory => {
+            IpcResponse::ok(serde_json::to_value(&sim_contr.memory).ok())
+        },
+        IpcRequest::SetRegion { index, extra_drop, latency_ms } => {
+            if !sim_contr.set_region(index, extra_drop, latency_ms) {
+                return IpcResponse::error(format!("region {} not found", index));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::SetFilters { id, deny_from, only_forward_to, block_session } => {
+            if !sim_contr.set_filters(id, FilterConfig { deny_from, only_forward_to, block_session }) {
+                return IpcResponse::error(format!("drone {} doesn't take filter rules", id));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::SetProcessingDelay { id, to, delay_ms, jitter_ms } => {
+            if !sim_contr.set_processing_delay(id, to, delay_ms, jitter_ms) {
+                return IpcResponse::error(format!("drone {} doesn't take a processing delay", id));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::SetLinkRate { id, to, packets_per_sec, burst, queue } => {
+            let mut config = LinkRateConfig::new(id, to, packets_per_sec);
+            config.burst = burst.unwrap_or(config.burst);
+            config.queue = queue.unwrap_or(config.queue);
+            if !sim_contr.set_link_rate(config) {
+                return IpcResponse::error(format!("drone {} doesn't take a link rate", id));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::SetPacketFaults { id, duplicate, corrupt } => {
+            if !sim_contr.set_packet_faults(id, duplicate, corrupt) {
+                return IpcResponse::error(format!("drone {} doesn't take packet faults", id));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::SetReorder { id, buffer, distribution, min_ms, max_ms, mean_ms } => {
+            let reorder = max_ms.map(|max_ms| {
+                let mut config = ReorderConfig::new(id, distribution, max_ms);
+                config.buffer = buffer.unwrap_or(config.buffer);
+                config.min_ms = min_ms;
+                config.mean_ms = mean_ms;
+                config.reorder()
+            });
+            if !sim_contr.set_reorder(id, reorder) {
+                return IpcResponse::error(format!("drone {} doesn't take a jitter mode", id));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::SetBurstLoss { id, p_good_to_bad, p_bad_to_good, loss_good, loss_bad } => {
+            let burst_loss = p_good_to_bad.map(|p_good_to_bad| {
+                let mut config = BurstLossConfig::new(id, p_good_to_bad, p_bad_to_good);
+                config.loss_good = loss_good;
+                config.loss_bad = loss_bad.unwrap_or(config.loss_bad);
+                config.burst_loss()
+            });
+            if !sim_contr.set_burst_loss(id, burst_loss) {
+                return IpcResponse::error(format!("drone {} doesn't take a burst loss", id));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::SetTelemetry { id, verbosity, interval_ms, mute } => {
+            if !sim_contr.set_telemetry(TelemetryConfig { node: id, verbosity, interval_ms, mute }) {
+                return IpcResponse::error(format!("node {} doesn't take this telemetry", id));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::Profiles => {
+            IpcResponse::ok(serde_json::to_value(&sim_contr.profiles).ok())
+        },
+        IpcRequest::DeadLetters { session } => {
+            let letters = sim_contr.dead_letters.letters
+                .iter()
+                .filter(|letter| session.map_or(true, |session| letter.packet.session_id == session))
+                .map(|letter| letter.describe())
+                .collect::<Vec<String>>();
+            IpcResponse::ok(serde_json::to_value(letters).ok())
+        },
+        IpcRequest::ApplyProfile { name, group } => {
+            if !sim_contr.apply_profile(&name, group) {
+                return IpcResponse::error(format!("profile {} not found", name));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::Tag { id, link_to, tag, remove } => {
+            let changed = match (link_to, remove) {
+                (None, false) => sim_contr.tag_node(id, &tag),
+                (None, true) => sim_contr.untag_node(id, &tag),
+                (Some(to), false) => sim_contr.tag_link(id, to, &tag),
+                (Some(to), true) => sim_contr.untag_link(id, to, &tag),
+            };
+            if !changed {
+                return IpcResponse::error(format!("tag {} not changed on {}{}", tag, id, link_to.map(|to| format!(" - {}", to)).unwrap_or_default()));
+            }
+            IpcResponse::ok(None)
+        },
+        IpcRequest::Tags => {
+            IpcResponse::ok(serde_json::to_value(sim_contr.annotations.to_configs()).ok())
+        },
+        IpcRequest::TrafficMatrix => {
+            let matrix = sim_contr.traffic_matrix();
+            IpcResponse::ok(serde_json::to_value(matrix.pairs.values().collect::<Vec<_>>()).ok())
+        },
+        IpcRequest::Commands { id, origin } => {
+            IpcResponse::ok(serde_json::to_value(sim_contr.audit.query(id, origin)).ok())
+        },
+        IpcRequest::Cancel { session } => {
+            if !sim_contr.cancel_transfer(session) {
+                return IpcResponse::error(format!("message {} not on its way", session));
+            }
+            IpcResponse::ok(None)
+        },
+    }
+}
+
+#[cfg(unix)]
+pub fn start_ipc_server(path: &str) -> io::Result<Receiver<IpcCall>> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::thread;
+    use crossbeam_channel::unbounded;
+
+    //A socket left behind by a previous run would make the bind fail.
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path)?;
+    let (call_send, call_recv) = unbounded();
+
+    fn serve(stream: UnixStream, call_send: Sender<IpcCall>) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<IpcRequest>(&line) {
+                Ok(request) => {
+                    let (reply_send, reply_recv) = unbounded();
+                    if call_send.send(IpcCall { request, reply: reply_send }).is_err() {
+                        //The Sim Contr is gone, nothing else to do for this client.
+                        return Ok(());
+                    }
+                    reply_recv.recv().unwrap_or(IpcResponse::error("simulation controller stopped".to_string()))
+                },
+                Err(e) => IpcResponse::error(format!("invalid request: {}", e)),
+            };
+            let response = serde_json::to_string(&response).map_err(io::Error::other)?;
+            writeln!(writer, "{}", response)?;
+        }
+        Ok(())
+    }
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let call_send = call_send.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = serve(stream, call_send) {
+                            println!("ipc connection closed: {}", e);
+                        }
+                    });
+                },
+                Err(e) => println!("ipc connection failed: {}", e),
+            }
+        }
+    });
+
+    Ok(call_recv)
+}
+
+#[cfg(not(unix))]
+pub fn start_ipc_server(_path: &str) -> io::Result<Receiver<IpcCall>> {
+    //Only unix sockets: there's no named pipe transport, on Windows '--ipc' fails with this error.
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the ipc socket is only available on unix, there's no named pipe transport"))
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+use crate::sim_control::packet_source;
+use crate::timeline::{Timeline, TimelineEntry};
+
+//A line of the journal, written as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum JournalRecord {
+    Start { unix_ms: u64 },
+    Sent { unix_ms: u64, packet: Packet },
+    Dropped { unix_ms: u64, packet: Packet },
+    Shortcut { unix_ms: u64, packet: Packet },
+    Timeline { entry: TimelineEntry },
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+//Every event the Sim Contr reads and every entry of the timeline, written as soon as they arrive
+//and synced to the disk every sync_every: a crash of the process (or of the machine) loses at most
+//the last sync_every of the run, and the line being written when it happened (see recover).
+pub struct EventJournal {
+    file: String,
+    writer: BufWriter<File>,
+    sync_every: Duration,
+    last_sync: Instant,
+    timeline_seen: usize, //Entries of the timeline already written, see Timeline::total.
+    pub(crate) records: u64,
+    failed: bool, //After a write error I stop writing, what's in the file up to there can still be recovered.
+}
+
+impl EventJournal {
+    pub fn create(file: &str, sync_every: Duration) -> io::Result<Self> {
+        let mut journal = EventJournal {
+            file: file.to_string(),
+            writer: BufWriter::new(File::create(file)?),
+            sync_every,
+            last_sync: Instant::now(),
+            timeline_seen: 0,
+            records: 0,
+            failed: false,
+        };
+        journal.append(&JournalRecord::Start { unix_ms: unix_ms(SystemTime::now()) });
+        Ok(journal)
+    }
+
+    pub fn append(&mut self, record: &JournalRecord) {
+        if self.failed {
+            return;
+        }
+        //The whole line in a single write, so a crash can only cut the last one.
+        let result = serde_json::to_string(record)
+            .map_err(io::Error::other)
+            .and_then(|line| self.writer.write_all(format!("{}\n", line).as_bytes()));
+        match result {
+            Ok(_) => self.records += 1,
+            Err(e) => self.fail(e),
+        }
+    }
+
+    pub fn append_event(&mut self, event: &DroneEvent, time: SystemTime) {
+        let unix_ms = unix_ms(time);
+        let packet = match event {
+            DroneEvent::PacketSent(packet) | DroneEvent::PacketDropped(packet) | DroneEvent::ControllerShortcut(packet) => packet.clone(),
+        };
+        self.append(&match event {
+            DroneEvent::PacketSent(_) => JournalRecord::Sent { unix_ms, packet },
+            DroneEvent::PacketDropped(_) => JournalRecord::Dropped { unix_ms, packet },
+            DroneEvent::ControllerShortcut(_) => JournalRecord::Shortcut { unix_ms, packet },
+        });
+    }
+
+    //The entries added to the timeline since the last call.
+    pub fn append_timeline(&mut self, timeline: &Timeline) {
+        let new = timeline.total.saturating_sub(self.timeline_seen).min(timeline.entries.len());
+        for entry in timeline.entries[timeline.entries.len() - new..].iter() {
+            self.append(&JournalRecord::Timeline { entry: entry.clone() });
+        }
+        self.timeline_seen = timeline.total;
+    }
+
+    //For a new Sim Contr (after a restart), whose timeline starts again from nothing.
+    pub fn reset_timeline(&mut self) {
+        self.timeline_seen = 0;
+    }
+
+    pub fn sync_if_due(&mut self) {
+        if self.last_sync.elapsed() >= self.sync_every {
+            self.sync();
+        }
+    }
+
+    pub fn sync(&mut self) {
+        self.last_sync = Instant::now();
+        if self.failed {
+            return;
+        }
+        if let Err(e) = self.writer.flush().and_then(|_| self.writer.get_ref().sync_data()) {
+            self.fail(e);
+        }
+    }
+
+    fn fail(&mut self, e: io::Error) {
+        println!("error in writing the journal {}, it stops here: {}", self.file, e);
+        self.failed = true;
+    }
+}
+
+//A panic unwinding through the Sim Contr still gets the last events to the disk.
+impl Drop for EventJournal {
+    fn drop(&mut self) {
+        self.sync();
+    }
+}
+
+//What could be read back from a journal.
+pub struct RecoveredJournal {
+    pub records: Vec<JournalRecord>,
+    pub valid_bytes: u64,
+    pub torn_bytes: u64, //After the last good line: the line being written at the crash, or garbage left by the disk.
+}
+
+impl RecoveredJournal {
+    pub fn timeline(&self) -> Vec<TimelineEntry> {
+        self.records
+            .iter()
+            .filter_map(|record| match record {
+                JournalRecord::Timeline { entry } => Some(entry.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    //A line for the whole journal, and one for every node with its events (sent, dropped, shortcuts).
+    pub fn describe(&self) -> Vec<String> {
+        let mut per_node: BTreeMap<NodeId, (u64, u64, u64)> = BTreeMap::new();
+        let (mut first, mut last) = (None, None);
+        for record in self.records.iter() {
+            let (unix_ms, packet) = match record {
+                JournalRecord::Sent { unix_ms, packet } | JournalRecord::Dropped { unix_ms, packet } | JournalRecord::Shortcut { unix_ms, packet } => (*unix_ms, packet),
+                JournalRecord::Start { unix_ms } => {
+                    first = first.or(Some(*unix_ms));
+                    continue;
+                }
+                JournalRecord::Timeline { .. } => continue,
+            };
+            last = Some(unix_ms);
+            let Some(node) = packet_source(packet) else {
+                continue;
+            };
+            let counts = per_node.entry(node).or_default();
+            match record {
+                JournalRecord::Sent { .. } => counts.0 += 1,
+                JournalRecord::Dropped { .. } => counts.1 += 1,
+                _ => counts.2 += 1,
+            }
+        }
+        let span_s = match (first, last) {
+            (Some(first), Some(last)) => last.saturating_sub(first) as f64 / 1000.0,
+            _ => 0.0,
+        };
+        let mut lines = vec![format!(
+            "{} records over {:.1}s ({} timeline entries), {} bytes cut off at the end",
+            self.records.len(), span_s, self.timeline().len(), self.torn_bytes
+        )];
+        for (node, (sent, dropped, shortcuts)) in per_node {
+            lines.push(format!("node {}: {} sent, {} dropped, {} shortcuts", node, sent, dropped, shortcuts));
+        }
+        lines
+    }
+}
+
+//Reads the journal up to the first line that isn't a whole record: everything before it was
+//written (and, up to the last sync, on the disk) before the crash.
+pub fn recover(file: &str) -> io::Result<RecoveredJournal> {
+    let bytes = fs::read(file)?;
+    let mut records = Vec::new();
+    let mut valid_bytes = 0;
+    for line in bytes.split_inclusive(|byte| *byte == b'\n') {
+        if !line.ends_with(b"\n") {
+            break;
+        }
+        match std::str::from_utf8(line).ok().and_then(|line| serde_json::from_str::<JournalRecord>(line).ok()) {
+            Some(record) => records.push(record),
+            None => break,
+        }
+        valid_bytes += line.len();
+    }
+    Ok(RecoveredJournal {
+        records,
+        valid_bytes: valid_bytes as u64,
+        torn_bytes: (bytes.len() - valid_bytes) as u64,
+    })
+}
+
+//Cuts the torn end off the file, so the journal can be read (or appended to) by anything else.
+pub fn repair(file: &str, recovered: &RecoveredJournal) -> io::Result<()> {
+    if recovered.torn_bytes == 0 {
+        return Ok(());
+    }
+    let journal = OpenOptions::new().write(true).open(file)?;
+    journal.set_len(recovered.valid_bytes)?;
+    journal.sync_all()
+}
+
+//'journal <file>': recovers a journal left by a crashed run, cuts its torn end off and writes
+//its timeline next to it as <file>_timeline.json.
+pub fn run_journal_cli(args: &[String]) {
+    let Some(file) = args.first() else {
+        println!("usage: journal <file>");
+        return;
+    };
+    let recovered = match recover(file) {
+        Ok(recovered) => recovered,
+        Err(e) => {
+            println!("journal {} not readable: {}", file, e);
+            return;
+        }
+    };
+    for line in recovered.describe() {
+        println!("{}", line);
+    }
+    if let Err(e) = repair(file, &recovered) {
+        println!("the torn end of {} couldn't be cut off: {}", file, e);
+    }
+    let timeline_file = format!("{}_timeline.json", file.trim_end_matches(".jsonl"));
+    let written = serde_json::to_string_pretty(&recovered.timeline())
+        .map_err(io::Error::other)
+        .and_then(|json| fs::write(&timeline_file, json));
+    match written {
+        Ok(_) => println!("timeline written to {}", timeline_file),
+        Err(e) => println!("timeline not written to {}: {}", timeline_file, e),
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+use serde::Deserialize;
+use wg_2024::network::NodeId;
+use crate::skylink_drone::commands::ProcessingDelay;
+
+//The time a drone takes on every packet before forwarding it, as written in the input file:
+//    [[processing_delay]]
+//    drone = 3
+//    delay_ms = 5
+//    jitter_ms = 2
+//    [[processing_delay]]
+//    drone = 3
+//    to = 4
+//    delay_ms = 40
+//Without `to` it's for every packet the drone forwards, with it only for the ones sent to that
+//neighbour (a slow link), and that one wins. Up to jitter_ms more is added at random to every packet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProcessingDelayConfig {
+    pub drone: NodeId,
+    pub to: Option<NodeId>,
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub jitter_ms: u64,
+}
+
+impl ProcessingDelayConfig {
+    pub fn delay(&self) -> ProcessingDelay {
+        ProcessingDelay {
+            delay: Duration::from_millis(self.delay_ms),
+            jitter: Duration::from_millis(self.jitter_ms),
+        }
+    }
+}
+
+//The delays of a drone, for SkyLinkDrone::with_processing_delays.
+pub fn delays_of(delays: &[ProcessingDelayConfig], drone: NodeId) -> Vec<(Option<NodeId>, ProcessingDelay)> {
+    delays
+        .iter()
+        .filter(|delay| delay.drone == drone)
+        .map(|delay| (delay.to, delay.delay()))
+        .collect()
+}
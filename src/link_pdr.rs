@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+
+//The drop rate of single links, as written in the input file:
+//    [[link]]
+//    a = 1
+//    b = 2
+//    pdr = 0.3
+//A link has the same pdr both ways, but each end rolls its own drops: the fragments 1 sends to 2
+//and the ones 2 sends to 1 are lost independently. On a link with a pdr it's used instead of the
+//pdr of the drone, the other links of the drone keep that one. Only SkyLink drones take it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkPdrConfig {
+    pub a: NodeId,
+    pub b: NodeId,
+    pub pdr: f32,
+}
+
+//The same link whichever way round it's given.
+pub fn link_key(a: NodeId, b: NodeId) -> (NodeId, NodeId) {
+    (a.min(b), a.max(b))
+}
+
+pub fn link_pdrs(configs: &[LinkPdrConfig]) -> HashMap<(NodeId, NodeId), f32> {
+    configs
+        .iter()
+        .map(|config| (link_key(config.a, config.b), config.pdr.clamp(0.0, 1.0)))
+        .collect()
+}
+
+//The pdrs of the links of a drone, for SkyLinkDrone::with_link_pdrs.
+pub fn pdrs_of(link_pdrs: &HashMap<(NodeId, NodeId), f32>, drone: NodeId) -> HashMap<NodeId, f32> {
+    link_pdrs
+        .iter()
+        .filter_map(|((a, b), pdr)| match (*a == drone, *b == drone) {
+            (true, _) => Some((*b, *pdr)),
+            (_, true) => Some((*a, *pdr)),
+            _ => None,
+        })
+        .collect()
+}
+
+//Back to the tables of the input file, for the snapshots.
+pub fn to_configs(link_pdrs: &HashMap<(NodeId, NodeId), f32>) -> Vec<LinkPdrConfig> {
+    let mut configs = link_pdrs
+        .iter()
+        .map(|((a, b), pdr)| LinkPdrConfig { a: *a, b: *b, pdr: *pdr })
+        .collect::<Vec<LinkPdrConfig>>();
+    configs.sort_by_key(|config| (config.a, config.b));
+    configs
+}
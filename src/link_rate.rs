@@ -0,0 +1,54 @@
+use serde::Deserialize;
+use wg_2024::network::NodeId;
+use crate::skylink_drone::links::PacketRate;
+
+//The packets per second a drone lets out on its links, as written in the input file:
+//    [[link_rate]]
+//    drone = 3
+//    packets_per_sec = 100
+//    [[link_rate]]
+//    drone = 3
+//    to = 4
+//    packets_per_sec = 10
+//    burst = 5
+//    queue = 0
+//Without `to` it's for every neighbour of the drone (each one with its own bucket), with it only
+//for that one, and that one wins. The fragments over the rate wait in a queue of `queue` packets
+//(20 if not given), beyond it they're nacked as Dropped: queue = 0 nacks them at once.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LinkRateConfig {
+    pub drone: NodeId,
+    pub to: Option<NodeId>,
+    pub packets_per_sec: u64,
+    #[serde(default = "default_burst")]
+    pub burst: u64,
+    #[serde(default = "default_queue")]
+    pub queue: u64,
+}
+
+fn default_burst() -> u64 {
+    1
+}
+
+fn default_queue() -> u64 {
+    20
+}
+
+impl LinkRateConfig {
+    pub fn new(drone: NodeId, to: Option<NodeId>, packets_per_sec: u64) -> Self {
+        LinkRateConfig { drone, to, packets_per_sec, burst: default_burst(), queue: default_queue() }
+    }
+
+    pub fn rate(&self) -> PacketRate {
+        PacketRate { packets_per_sec: self.packets_per_sec, burst: self.burst, queue: self.queue }
+    }
+}
+
+//The rates of a drone, for SkyLinkDrone::with_link_rates.
+pub fn rates_of(rates: &[LinkRateConfig], drone: NodeId) -> Vec<(Option<NodeId>, PacketRate)> {
+    rates
+        .iter()
+        .filter(|rate| rate.drone == drone)
+        .map(|rate| (rate.to, rate.rate()))
+        .collect()
+}
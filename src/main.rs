@@ -1,12 +1,78 @@
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crossbeam_channel::unbounded;
 use crate::test::test_bench::*;
-use crate::initializer::initialize;
+use crate::initializer::{initialize, initialize_edited, initialize_from_snapshot, initialize_segmented, initialize_with_progress, parse_config, print_progress};
+use crate::snapshot::SimulationSnapshot;
+use crate::node_logs::NodeLogs;
+use crate::journal::EventJournal;
+use crate::executor::join_drones;
 
+mod ack_sink;
+mod alerts;
+mod audit;
+mod bandwidth;
+mod batch;
+mod capacity;
+mod latency;
+mod link_rate;
+mod link_pdr;
+mod health;
+mod energy;
+mod reorder;
+mod flood_cache;
+mod burst_loss;
+mod dedup;
+mod coordinator;
+mod crafting;
+mod bridge;
+#[cfg(feature = "http")]
+mod dashboard;
+mod dead_letters;
 mod sim_app;
+mod sim_tui;
 mod sim_control;
+mod scenario;
+mod selftest;
 mod initializer;
+mod discovery;
+mod drone_capture;
+mod executor;
+mod faults;
+mod filters;
+mod groups;
+mod flow;
+mod inbox;
+mod hop_counts;
+mod memory;
+mod node_logs;
+mod postmortem;
+mod journal;
+mod regions;
+mod replay;
+mod repro;
+mod report;
+mod review;
+mod route_cache;
+mod routing;
+mod ipc;
 mod skylink_drone;
+mod snapshot;
+mod sessions;
+mod sla;
+mod otlp;
+mod profiles;
+mod stats_series;
+mod summary;
+mod tags;
+mod telemetry;
+mod timeline;
+mod topology_editor;
+mod traceroute;
+mod traffic_matrix;
+mod walkthrough;
 mod test;
 
 fn main() {
@@ -27,21 +93,295 @@ fn main() {
         // test_star_flood();
         // test_butterfly_flood();
         // test_tree_flood();
+        // test_concurrent_floods();
+        // test_flood_id_collision();
+        // test_flood_empty_path_trace();
+        // test_shortcut_fallback();
+        // test_pdr_values();
+        // test_drone_seed();
+        // test_forwarding_counters();
+        // test_congestion_nack();
+        // test_link_rate();
+        // test_link_pdr();
+        // test_duplicate_acks();
+        // test_packet_faults();
+        // test_tags();
+        // test_drone_shutdown();
+        // test_drone_terminates();
+        // test_heartbeat();
+        // test_energy();
+        // test_priority();
+        // test_reorder();
+        // test_ttl();
+        // test_flood_cache_expiry();
+        // test_rediscovery();
+        // test_drone_state();
+        // test_burst_loss();
+        // test_dedup();
+        // test_neighbour_eviction();
+        // test_tick();
+        // test_route_cache_invalidation();
+        // test_telemetry();
+        // test_processing_delay();
+        // test_journal_recovery();
+        // test_postmortem();
+        // test_segments();
+        // test_replay_determinism();
+        // test_drone_capture();
+        // test_ack_sink();
+        // test_sla_probe();
+        // test_node_groups();
+        // test_command_audit();
          test_drone_commands();
+        // test_drone_hooks();
         // test_busy_network();
+        // bench_fragment_forward();
+        // bench_star_flood();
+        // bench_butterfly_flood();
+        // bench_flood_fanout();
+        // bench_scaling();
 
         
 
     } else {
-        let (sim_contr, handles) = initialize("inputs/input_generic_fragment_forward.toml");
+        let args = std::env::args().collect::<Vec<String>>();
+        //Launch with 'batch <scenario> --runs N --parallel P' to repeat a scenario with many seeds.
+        if args.get(1).map(|arg| arg.as_str()) == Some("batch") {
+            batch::run_batch_cli(&args[2..]);
+            return;
+        }
+        //Launch with '--selftest' to check on a small built-in network that the floods, the messages
+        //through a lossy drone and the crashes still work, e.g. after putting in the drones of
+        //another group. It prints PASS or FAIL for every check, and exits with 1 if one failed.
+        if args.iter().any(|arg| arg == "--selftest") {
+            let (passed, handles) = selftest::run_selftest();
+            join_drones(handles);
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+        //Launch with 'segments <file> [seconds]' to run the [[segment]]s of the file each with its own
+        //Sim Contr under a coordinator, headless, printing the stats of every segment once a second.
+        if args.get(1).map(|arg| arg.as_str()) == Some("segments") {
+            let Some(file) = args.get(2) else {
+                println!("usage: segments <file> [seconds]");
+                return;
+            };
+            let seconds = args.get(3).and_then(|seconds| seconds.parse::<u64>().ok()).unwrap_or(10);
+            let (mut coordinator, handles) = initialize_segmented(file);
+            let end = Instant::now() + Duration::from_secs(seconds);
+            let mut next_print = Instant::now();
+            while Instant::now() < end {
+                coordinator.process_events();
+                if Instant::now() >= next_print {
+                    for line in coordinator.describe() {
+                        println!("{}", line);
+                    }
+                    next_print += Duration::from_secs(1);
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            coordinator.shutdown();
+            join_drones(handles);
+            return;
+        }
+        //Launch with 'replay record|verify ...' to record a run in deterministic mode, or to check
+        //that running it again gives the same events.
+        if args.get(1).map(|arg| arg.as_str()) == Some("replay") {
+            replay::run_replay_cli(&args[2..]);
+            return;
+        }
+        //Launch with 'journal <file>' to read back the journal of a run that crashed (see '--journal').
+        if args.get(1).map(|arg| arg.as_str()) == Some("journal") {
+            journal::run_journal_cli(&args[2..]);
+            return;
+        }
+        //Launch with 'postmortem <journal> [session]' to get the story of every failed session of a recorded run.
+        if args.get(1).map(|arg| arg.as_str()) == Some("postmortem") {
+            postmortem::run_postmortem_cli(&args[2..]);
+            return;
+        }
+        //Launch with 'diff <old> <new>' to compare two snapshots without starting the network.
+        if args.get(1).map(|arg| arg.as_str()) == Some("diff") {
+            match (args.get(2), args.get(3)) {
+                (Some(old), Some(new)) => match (SimulationSnapshot::load(old), SimulationSnapshot::load(new)) {
+                    (Ok(old), Ok(new)) => {
+                        let diff = old.diff(&new);
+                        if diff.is_empty() {
+                            println!("no changes.");
+                        }
+                        for line in diff.describe() {
+                            println!("{}", line);
+                        }
+                    }
+                    (Err(e), _) | (_, Err(e)) => println!("snapshot not readable: {}", e),
+                },
+                _ => println!("usage: diff <old snapshot> <new snapshot>"),
+            }
+            return;
+        }
+        //Launch with '--edit [file]' to draw the network (empty, or the one in the file) in the
+        //topology editor first, it starts when the editor is closed with Launch.
+        let edit = args.iter().position(|arg| arg == "--edit").map(|i| args.get(i + 1).filter(|arg| !arg.starts_with("--")).cloned());
+        let (sim_contr, mut handles) = if let Some(file) = edit {
+            let config = file.as_deref().map(parse_config);
+            match topology_editor::run_topology_editor(config, file.as_deref().unwrap_or("inputs/input_edited.toml")) {
+                Some(config) => initialize_edited(config, file.as_deref()),
+                None => return,
+            }
+        } else {
+            //Launch with '--snapshot <file>' to restore a network saved previously.
+            match args.iter().position(|arg| arg == "--snapshot") {
+                Some(i) if i + 1 < args.len() => initialize_from_snapshot(&args[i + 1]),
+                //Launch with '--config <file>' to use another input file (e.g. the inputs/input_bridge_*.toml pair).
+                _ => match args.iter().position(|arg| arg == "--config") {
+                    Some(i) if i + 1 < args.len() => initialize_with_progress(&args[i + 1], &print_progress),
+                    _ => initialize("inputs/input_generic_fragment_forward.toml"),
+                },
+            }
+        };
         let mut pass = Rc::new(RefCell::new(sim_contr));
-        pass.borrow_mut().crash_drone(2);
-        sim_app::run_simulation_gui(pass.clone());
 
+        //Ctrl-C doesn't kill the process anymore: it asks the frontend to close, and then
+        //the usual shutdown path below stops the drones and writes the exports.
+        let (shutdown_send, shutdown_recv) = unbounded();
+        ctrlc::set_handler(move || {
+            let _ = shutdown_send.send(());
+        }).expect("Failed to install the Ctrl-C handler");
+        pass.borrow_mut().attach_shutdown_signal(shutdown_recv);
 
-
-        for handle in handles.into_iter() {
-            handle.join().unwrap();
+        //Launch with '--ipc <path>' to control the simulation through a local socket (unix only).
+        if let Some(i) = args.iter().position(|arg| arg == "--ipc") {
+            if let Some(path) = args.get(i + 1) {
+                match ipc::start_ipc_server(path) {
+                    Ok(ipc_recv) => pass.borrow_mut().attach_ipc(ipc_recv),
+                    Err(e) => println!("ipc socket {} not available: {}", path, e),
+                }
+            }
+        }
+        //Launch with '--stats-out <file>' to export the stats somewhere else than stats.csv.
+        if let Some(i) = args.iter().position(|arg| arg == "--stats-out") {
+            if let Some(file) = args.get(i + 1) {
+                pass.borrow_mut().set_stats_file(file.clone());
+            }
         }
+        //Launch with '--node-logs <dir> [ids]' to write the events of every node (or only of the
+        //nodes in ids, e.g. 7,12) to its own file too, like <dir>/drone_7.log.
+        if let Some(i) = args.iter().position(|arg| arg == "--node-logs") {
+            if let Some(dir) = args.get(i + 1) {
+                let ids = args.get(i + 2).filter(|arg| !arg.starts_with("--"));
+                let only = ids.and_then(|ids| NodeLogs::parse_ids(ids));
+                if ids.is_some() && only.is_none() {
+                    println!("node ids {} not valid, logging every node.", ids.unwrap());
+                }
+                match NodeLogs::new(dir, only) {
+                    Ok(node_logs) => pass.borrow_mut().set_node_logs(node_logs),
+                    Err(e) => println!("node logs not available in {}: {}", dir, e),
+                }
+            }
+        }
+        //Launch with '--journal <file> [sync_ms]' to write every event to the file as it arrives, synced
+        //to the disk every sync_ms (1000 by default): if the process dies, 'journal <file>' reads it back.
+        if let Some(i) = args.iter().position(|arg| arg == "--journal") {
+            if let Some(file) = args.get(i + 1) {
+                let sync_ms = args.get(i + 2).and_then(|ms| ms.parse::<u64>().ok()).unwrap_or(1000);
+                match EventJournal::create(file, Duration::from_millis(sync_ms)) {
+                    Ok(journal) => pass.borrow_mut().set_journal(journal),
+                    Err(e) => println!("journal {} not available: {}", file, e),
+                }
+            }
+        }
+        //Launch with '--capture <drone> <file>' to capture everything the drone receives and sends
+        //until the shutdown, then 'replay drone <file>' runs it again on the drone alone.
+        if let Some(i) = args.iter().position(|arg| arg == "--capture") {
+            match (args.get(i + 1).and_then(|id| id.parse::<u8>().ok()), args.get(i + 2)) {
+                (Some(id), Some(file)) => {
+                    pass.borrow_mut().start_capture(id, file);
+                }
+                _ => println!("usage: --capture <drone> <file>"),
+            }
+        }
+        //Launch with '--scenario <file>' to run a rhai script headless instead of a frontend,
+        //'--seed <n>' and '--metrics <file>' are used by the batch runs.
+        if let Some(i) = args.iter().position(|arg| arg == "--scenario") {
+            if let Some(file) = args.get(i + 1) {
+                let seed = args.iter()
+                    .position(|arg| arg == "--seed")
+                    .and_then(|i| args.get(i + 1))
+                    .and_then(|seed| seed.parse::<u64>().ok());
+                if let Err(e) = scenario::run_scenario(file, pass.clone(), seed) {
+                    println!("scenario {} failed: {}", file, e);
+                    //The failed run is cut down to what touched the nodes involved, to be run again
+                    //with '--snapshot <repro>.toml --scenario <repro>.rhai'.
+                    let failed_at = e.position().line().and_then(|line| {
+                        let code = std::fs::read_to_string(file).ok()?.lines().nth(line - 1)?.to_string();
+                        Some((line, code))
+                    });
+                    let repro = repro::extract_repro(&pass.borrow(), &e.to_string(), failed_at);
+                    match repro.save(file, seed) {
+                        Ok((snapshot_file, script_file)) => println!(
+                            "repro on {} nodes written, run it with --snapshot {} --scenario {}{}",
+                            repro.involved.len(), snapshot_file, script_file,
+                            seed.map_or(String::new(), |seed| format!(" --seed {}", seed))
+                        ),
+                        Err(e) => println!("repro not written: {}", e),
+                    }
+                }
+                pass.borrow_mut().shutdown_all(handles);
+                if let Some(i) = args.iter().position(|arg| arg == "--metrics") {
+                    if let Some(metrics_file) = args.get(i + 1) {
+                        let metrics = batch::RunMetrics::from_simulation(&pass.borrow(), seed.unwrap_or(0));
+                        if let Err(e) = metrics.save(metrics_file) {
+                            println!("metrics not saved to {}: {}", metrics_file, e);
+                        }
+                    }
+                }
+                return;
+            }
+        }
+        //Launch with '--traceroute <from> <to>' to probe the route between two nodes and exit.
+        if let Some(i) = args.iter().position(|arg| arg == "--traceroute") {
+            let from = args.get(i + 1).and_then(|id| id.parse::<u8>().ok());
+            let to = args.get(i + 2).and_then(|id| id.parse::<u8>().ok());
+            if let (Some(from), Some(to)) = (from, to) {
+                if pass.borrow_mut().start_traceroute(from, to) {
+                    for line in pass.borrow_mut().wait_traceroute() {
+                        println!("{}", line);
+                    }
+                } else if let Some(line) = pass.borrow().log.last() {
+                    println!("{}", line);
+                }
+            } else {
+                println!("usage: --traceroute <from> <to>");
+            }
+            pass.borrow_mut().shutdown_all(handles);
+            return;
+        }
+        //Launch with '--otlp <host:port>' to send the message sessions as traces (e.g. to Jaeger)
+        //when the simulation shuts down.
+        if let Some(i) = args.iter().position(|arg| arg == "--otlp") {
+            if let Some(endpoint) = args.get(i + 1) {
+                pass.borrow_mut().set_otlp_endpoint(endpoint.clone());
+            }
+        }
+        //Launch with '--dashboard <address>' (built with '--features http') to watch the
+        //simulation from a browser.
+        #[cfg(feature = "http")]
+        if let Some(i) = args.iter().position(|arg| arg == "--dashboard") {
+            if let Some(address) = args.get(i + 1) {
+                match dashboard::start_dashboard(address) {
+                    Ok(state) => pass.borrow_mut().attach_dashboard(state),
+                    Err(e) => println!("dashboard on {} not available: {}", address, e),
+                }
+            }
+        }
+        pass.borrow_mut().crash_drone(2);
+        //Launch with '--tui' to get the terminal interface instead of the egui one (useful over ssh).
+        if args.iter().any(|arg| arg == "--tui") {
+            sim_tui::run_simulation_tui(pass.clone()).expect("Failed to start TUI");
+        } else {
+            let tab_handles = sim_app::run_simulation_gui(pass.clone());
+            handles.extend(tab_handles);
+        }
+
+        pass.borrow_mut().shutdown_all(handles);
     }
 }
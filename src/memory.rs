@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+use crate::sessions::{HopRecord, SessionRecord, SessionTable};
+use crate::stats_series::StatsSeries;
+
+//Estimate in bytes of the structures that keep growing during a run. They're not exact
+//(allocator overhead isn't counted), but they grow like the real thing, which is what
+//matters to spot a leak in a long run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryReport {
+    pub log_bytes: u64,
+    pub sessions_bytes: u64,
+    pub stats_series_bytes: u64,
+    pub flood_cache_bytes: u64, //All the drones together.
+    pub largest_flood_cache: Option<(NodeId, u64)>,
+}
+
+//Read from the input file:
+//    [memory_limits]
+//    log_bytes = 50000000
+//    flood_cache_bytes = 1000000
+//Every limit is optional, the flood cache one is for a single drone.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MemoryLimits {
+    pub log_bytes: Option<u64>,
+    pub sessions_bytes: Option<u64>,
+    pub stats_series_bytes: Option<u64>,
+    pub flood_cache_bytes: Option<u64>,
+}
+
+//An entry of the flood cache of a drone: the (flood_id, initiator) tuple with when it was seen,
+//plus the hash of the map and the tuple again in the expiry order (only with limits, but it's counted anyway).
+pub const FLOOD_CACHE_ENTRY_BYTES: u64 = (2 * size_of::<(u64, NodeId)>() + size_of::<Instant>() + size_of::<u64>()) as u64;
+
+impl MemoryReport {
+    pub fn new(log: &[String], sessions: &SessionTable, stats_series: &StatsSeries, flood_caches: &HashMap<NodeId, u64>) -> Self {
+        let log_bytes = log
+            .iter()
+            .map(|entry| (size_of::<String>() + entry.capacity()) as u64)
+            .sum();
+        let sessions_bytes = sessions.sessions
+            .values()
+            .map(|session| {
+                let hops = session.hops
+                    .iter()
+                    .map(|hop| (size_of::<HopRecord>() + hop.packet_type.capacity()) as u64)
+                    .sum::<u64>();
+                (size_of::<(u64, SessionRecord)>() as u64) + hops
+            })
+            .sum();
+        let stats_series_bytes = stats_series.bytes();
+        let flood_cache_bytes = flood_caches.values().map(|entries| entries * FLOOD_CACHE_ENTRY_BYTES).sum();
+        let largest_flood_cache = flood_caches
+            .iter()
+            .max_by_key(|(_, entries)| **entries)
+            .map(|(id, entries)| (*id, entries * FLOOD_CACHE_ENTRY_BYTES));
+
+        MemoryReport {
+            log_bytes,
+            sessions_bytes,
+            stats_series_bytes,
+            flood_cache_bytes,
+            largest_flood_cache,
+        }
+    }
+
+    //The structures over their limit, with a message ready for the log.
+    pub fn exceeded(&self, limits: &MemoryLimits) -> Vec<(String, String)> {
+        let mut exceeded = Vec::new();
+        let checks = [
+            ("log", self.log_bytes, limits.log_bytes),
+            ("sessions", self.sessions_bytes, limits.sessions_bytes),
+            ("stats series", self.stats_series_bytes, limits.stats_series_bytes),
+        ];
+        for (name, bytes, limit) in checks {
+            if let Some(limit) = limit {
+                if bytes > limit {
+                    exceeded.push((name.to_string(), format!("{} uses {} bytes, over the limit of {}", name, bytes, limit)));
+                }
+            }
+        }
+        if let (Some((id, bytes)), Some(limit)) = (self.largest_flood_cache, limits.flood_cache_bytes) {
+            if bytes > limit {
+                exceeded.push((format!("flood cache {}", id), format!("flood cache of drone {} uses {} bytes, over the limit of {}", id, bytes, limit)));
+            }
+        }
+        exceeded
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::NodeType;
+
+//A log file for every node (logs/drone_7.log, logs/client_1.log...), on top of the log of the
+//Sim Contr. Every event goes in, even the ones the Sim Contr log leaves out under a storm.
+pub struct NodeLogs {
+    dir: PathBuf,
+    only: Option<HashSet<NodeId>>, //None for every node.
+    files: HashMap<NodeId, BufWriter<File>>,
+    failed: HashSet<NodeId>, //The files that couldn't be created, I don't try again at every event.
+    start: SystemTime,
+}
+
+impl NodeLogs {
+    pub fn new(dir: &str, only: Option<HashSet<NodeId>>) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(NodeLogs {
+            dir: PathBuf::from(dir),
+            only,
+            files: HashMap::new(),
+            failed: HashSet::new(),
+            start: SystemTime::now(),
+        })
+    }
+
+    //"7,12,20" -> the nodes to log, to keep the files of a 100 nodes run to the ones that matter.
+    pub fn parse_ids(text: &str) -> Option<HashSet<NodeId>> {
+        text.split(',').map(|id| id.trim().parse::<NodeId>().ok()).collect()
+    }
+
+    pub fn write(&mut self, id: NodeId, node_type: Option<&NodeType>, time: SystemTime, line: &str) {
+        if self.only.as_ref().is_some_and(|only| !only.contains(&id)) || self.failed.contains(&id) {
+            return;
+        }
+        if !self.files.contains_key(&id) {
+            let kind = match node_type {
+                Some(NodeType::Client) => "client",
+                Some(NodeType::Server) => "server",
+                _ => "drone",
+            };
+            let path = self.dir.join(format!("{}_{}.log", kind, id));
+            match File::create(&path) {
+                Ok(file) => {
+                    self.files.insert(id, BufWriter::new(file));
+                }
+                Err(e) => {
+                    println!("error in creating the log of node {} in {}: {}", id, path.display(), e);
+                    self.failed.insert(id);
+                    return;
+                }
+            }
+        }
+        let Some(writer) = self.files.get_mut(&id) else { return };
+        let elapsed = time.duration_since(self.start).unwrap_or_default();
+        let _ = writeln!(writer, "{:>10.3}s {}", elapsed.as_secs_f64(), line);
+    }
+
+    pub fn write_event(&mut self, id: NodeId, node_type: Option<&NodeType>, time: SystemTime, event: &DroneEvent) {
+        let (action, packet) = match event {
+            DroneEvent::PacketSent(packet) => ("sent", packet),
+            DroneEvent::PacketDropped(packet) => ("dropped", packet),
+            DroneEvent::ControllerShortcut(packet) => ("shortcut", packet),
+        };
+        let line = format!(
+            "{} session {} {:?} route {:?} hop {}",
+            action,
+            packet.session_id,
+            packet.pack_type,
+            packet.routing_header.hops,
+            packet.routing_header.hop_index
+        );
+        self.write(id, node_type, time, &line);
+    }
+
+    //Called once per frame, so the files can be followed with tail -f.
+    pub fn flush(&mut self) {
+        for writer in self.files.values_mut() {
+            let _ = writer.flush();
+        }
+    }
+}
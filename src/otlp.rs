@@ -0,0 +1,104 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use crate::sessions::{SessionRecord, SessionTable};
+
+//Every session becomes a trace with a root span, and every hop a child span that starts when
+//the previous hop was seen and ends when this one is seen. The traces are sent with the
+//OTLP/HTTP JSON encoding, which Jaeger accepts directly on port 4318.
+pub fn export_traces(sessions: &SessionTable, endpoint: &str) -> io::Result<usize> {
+    //The trace ids get a random prefix, so sessions with the same id in different runs don't merge.
+    let run_id = fastrand::u64(..);
+    let spans = sessions.sessions
+        .values()
+        .flat_map(|session| session_spans(session, run_id))
+        .collect::<Vec<Value>>();
+    if spans.is_empty() {
+        return Ok(0);
+    }
+    let n_spans = spans.len();
+
+    let body = json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": "skylink" } }]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "skylink.sim_control" },
+                "spans": spans
+            }]
+        }]
+    });
+    post(endpoint, "/v1/traces", &body.to_string())?;
+    Ok(n_spans)
+}
+
+fn session_spans(session: &SessionRecord, run_id: u64) -> Vec<Value> {
+    let (Some(first), Some(last)) = (session.hops.first(), session.hops.last()) else {
+        return Vec::new();
+    };
+    let trace_id = format!("{:016x}{:016x}", run_id, session.session_id);
+    let root_id = span_id();
+
+    let mut spans = vec![json!({
+        "traceId": trace_id,
+        "spanId": root_id,
+        "name": format!("session {}", session.session_id),
+        "kind": 1,
+        "startTimeUnixNano": nanos(first.time),
+        "endTimeUnixNano": nanos(last.time),
+        "attributes": [
+            { "key": "skylink.session_id", "value": { "intValue": session.session_id.to_string() } },
+            { "key": "skylink.hops", "value": { "intValue": session.hops.len().to_string() } }
+        ]
+    })];
+
+    let mut previous = first.time;
+    for hop in session.hops.iter() {
+        spans.push(json!({
+            "traceId": trace_id,
+            "spanId": span_id(),
+            "parentSpanId": root_id,
+            "name": format!("{} -> {}", hop.from, hop.to),
+            "kind": 1,
+            "startTimeUnixNano": nanos(previous),
+            "endTimeUnixNano": nanos(hop.time),
+            "attributes": [
+                { "key": "skylink.from", "value": { "intValue": hop.from.to_string() } },
+                { "key": "skylink.to", "value": { "intValue": hop.to.to_string() } },
+                { "key": "skylink.packet", "value": { "stringValue": hop.packet_type } }
+            ]
+        }));
+        previous = hop.time;
+    }
+    spans
+}
+
+fn span_id() -> String {
+    format!("{:016x}", fastrand::u64(1..))
+}
+
+fn nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string()
+}
+
+fn post(endpoint: &str, path: &str, body: &str) -> io::Result<()> {
+    let mut stream = TcpStream::connect(endpoint)?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        endpoint,
+        body.len(),
+        body
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status = response.lines().next().unwrap_or("");
+    if status.split_whitespace().nth(1) == Some("200") {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("collector answered '{}'", status)))
+    }
+}
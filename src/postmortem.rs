@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, BTreeSet};
+use wg_2024::network::NodeId;
+use wg_2024::packet::{NackType, Packet, PacketType};
+use crate::journal::{self, JournalRecord, RecoveredJournal};
+use crate::sim_control::packet_source;
+use crate::sla::SLA_SESSION_BASE;
+use crate::timeline::TimelineKind;
+
+//What happened to a fragment, in the order it happened.
+#[derive(Debug, Clone, PartialEq)]
+enum FragmentStep {
+    Hop { from: NodeId, to: NodeId },
+    Lost { at: NodeId }, //A PacketDropped event: the pdr, a full queue or a closed channel.
+    Nacked { at: NodeId, nack_type: NackType },
+    Shortcut { at: NodeId },
+    Delivered,
+}
+
+//The story of a session, rebuilt from the events of the journal.
+#[derive(Debug, Default)]
+pub struct SessionStory {
+    pub session_id: u64,
+    pub source: Option<NodeId>,
+    pub destination: Option<NodeId>,
+    pub total_fragments: u64,
+    pub routes: Vec<Vec<NodeId>>, //Every route its fragments went on, in the order they were first seen.
+    fragments: BTreeMap<u64, Vec<(u64, FragmentStep)>>, //With the unix ms of every step.
+    pub acks: BTreeSet<u64>,
+}
+
+impl SessionStory {
+    fn delivered(&self) -> BTreeSet<u64> {
+        self.fragments
+            .iter()
+            .filter(|(_, steps)| steps.iter().any(|(_, step)| *step == FragmentStep::Delivered))
+            .map(|(index, _)| *index)
+            .collect()
+    }
+
+    //Some fragment of the message never got to the server.
+    pub fn failed(&self) -> bool {
+        self.delivered().len() < self.total_fragments as usize
+    }
+
+    fn nacks(&self) -> Vec<(u64, NodeId, NackType)> {
+        let mut nacks = Vec::new();
+        for (index, steps) in self.fragments.iter() {
+            for (_, step) in steps.iter() {
+                if let FragmentStep::Nacked { at, nack_type } = step {
+                    nacks.push((*index, *at, *nack_type));
+                }
+            }
+        }
+        nacks
+    }
+
+    //Whether the fragment was seen again after its first nack, i.e. the client sent it again.
+    fn retransmitted(&self, index: u64) -> Option<bool> {
+        let steps = self.fragments.get(&index)?;
+        let nacked_at = steps.iter().find(|(_, step)| matches!(step, FragmentStep::Nacked { .. })).map(|(ms, _)| *ms)?;
+        Some(steps.iter().any(|(ms, step)| *ms > nacked_at && matches!(step, FragmentStep::Hop { .. } | FragmentStep::Lost { .. })))
+    }
+
+    //Why the fragment didn't arrive, from the last thing that happened to it.
+    fn cause(&self, index: u64, crashes: &Crashes) -> String {
+        let Some((ms, last)) = self.fragments.get(&index).and_then(|steps| steps.last()) else {
+            return "never seen on any drone (lost before the first one, or never sent)".to_string();
+        };
+        let not_again = match self.retransmitted(index) {
+            Some(false) => ", not sent again",
+            _ => "",
+        };
+        match last {
+            FragmentStep::Nacked { at, nack_type: NackType::ErrorInRouting(next_hop) } => match crashes.down_at(*next_hop, *ms) {
+                true => format!("drone {} couldn't reach {}, which had crashed{}", at, next_hop, not_again),
+                false => format!("drone {} couldn't reach {}{}", at, next_hop, not_again),
+            },
+            FragmentStep::Nacked { at, nack_type: NackType::Dropped } => format!("dropped by drone {}{}", at, not_again),
+            FragmentStep::Nacked { at, nack_type } => format!("{:?} at {}{}", nack_type, at, not_again),
+            FragmentStep::Lost { at } => format!("lost at drone {} without a nack (full queue or closed channel)", at),
+            FragmentStep::Shortcut { at } => format!("handed by drone {} to the Sim Contr, never delivered", at),
+            FragmentStep::Hop { from, to } => match crashes.down_at(*to, *ms) {
+                true => format!("last seen from {} to {}, which had crashed", from, to),
+                false => format!("last seen from {} to {}, still in flight at the end of the journal", from, to),
+            },
+            FragmentStep::Delivered => "delivered".to_string(),
+        }
+    }
+
+    //The diagnosis, a line for the session and one for every thing worth knowing about it.
+    pub fn describe(&self, crashes: &Crashes) -> Vec<String> {
+        let delivered = self.delivered();
+        let mut lines = vec![format!(
+            "session {}: {} -> {}, {} of {} fragments delivered, {} acked",
+            self.session_id,
+            self.source.map_or("?".to_string(), |id| id.to_string()),
+            self.destination.map_or("?".to_string(), |id| id.to_string()),
+            delivered.len(),
+            self.total_fragments,
+            self.acks.len()
+        )];
+        for route in self.routes.iter() {
+            lines.push(format!("  route {:?}", route));
+        }
+        let nacks = self.nacks();
+        if !nacks.is_empty() {
+            let mut seen: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+            for (index, at, nack_type) in nacks {
+                seen.entry(format!("{:?} from {}", nack_type, at)).or_default().push(index);
+            }
+            for (nack, indexes) in seen {
+                lines.push(format!("  nack {} for fragments {:?}", nack, indexes));
+            }
+        }
+        let retransmitted = self.fragments.keys().filter(|index| self.retransmitted(**index) == Some(true)).count();
+        if retransmitted > 0 {
+            lines.push(format!("  {} fragments sent again after a nack", retransmitted));
+        }
+        //The same cause for many fragments is said once.
+        let mut causes: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+        for index in (0..self.total_fragments).filter(|index| !delivered.contains(index)) {
+            causes.entry(self.cause(index, crashes)).or_default().push(index);
+        }
+        for (cause, indexes) in causes {
+            lines.push(format!("  cause: {} (fragments {:?})", cause, indexes));
+        }
+        lines
+    }
+}
+
+//When the nodes crashed and came back, from the timeline in the journal.
+#[derive(Debug, Default)]
+pub struct Crashes {
+    changes: BTreeMap<NodeId, Vec<(u64, bool)>>, //unix ms, down.
+}
+
+impl Crashes {
+    fn down_at(&self, node: NodeId, ms: u64) -> bool {
+        self.changes
+            .get(&node)
+            .and_then(|changes| changes.iter().filter(|(at, _)| *at <= ms).last())
+            .map_or(false, |(_, down)| *down)
+    }
+}
+
+fn fragment_index(packet: &Packet) -> Option<u64> {
+    match &packet.pack_type {
+        PacketType::MsgFragment(fragment) => Some(fragment.fragment_index),
+        PacketType::Nack(nack) => Some(nack.fragment_index),
+        _ => None,
+    }
+}
+
+//Every session with fragments in the journal, with the crashes of the run. The probes, the
+//hellos and the floods aren't messages and are left out.
+pub fn analyze(recovered: &RecoveredJournal) -> (Vec<SessionStory>, Crashes) {
+    let mut start_ms = 0;
+    let mut crashes = Crashes::default();
+    let mut events = Vec::new();
+    for record in recovered.records.iter() {
+        match record {
+            JournalRecord::Start { unix_ms } => start_ms = *unix_ms,
+            JournalRecord::Timeline { entry } => {
+                let down = match entry.kind {
+                    TimelineKind::Crash => true,
+                    TimelineKind::Reboot | TimelineKind::Spawn => false,
+                    _ => continue,
+                };
+                if let Some(node) = entry.node {
+                    crashes.changes.entry(node).or_default().push((start_ms + entry.at.as_millis() as u64, down));
+                }
+            }
+            JournalRecord::Sent { unix_ms, packet } => events.push((*unix_ms, 's', packet)),
+            JournalRecord::Dropped { unix_ms, packet } => events.push((*unix_ms, 'd', packet)),
+            JournalRecord::Shortcut { unix_ms, packet } => events.push((*unix_ms, 'c', packet)),
+        }
+    }
+    //The batched events arrive late, their time is the one they happened at.
+    events.sort_by_key(|(unix_ms, _, _)| *unix_ms);
+
+    let mut stories: BTreeMap<u64, SessionStory> = BTreeMap::new();
+    for (unix_ms, kind, packet) in events {
+        if packet.session_id >= SLA_SESSION_BASE {
+            continue;
+        }
+        let (Some(index), Some(at)) = (fragment_index(packet), packet_source(packet)) else {
+            if let (PacketType::Ack(ack), 's') = (&packet.pack_type, kind) {
+                let header = &packet.routing_header;
+                if header.hop_index + 1 == header.hops.len() {
+                    stories.entry(packet.session_id).or_default().acks.insert(ack.fragment_index);
+                }
+            }
+            continue;
+        };
+        let story = stories.entry(packet.session_id).or_default();
+        story.session_id = packet.session_id;
+        let header = &packet.routing_header;
+        let step = match (&packet.pack_type, kind) {
+            (PacketType::MsgFragment(fragment), _) => {
+                story.total_fragments = story.total_fragments.max(fragment.total_n_fragments);
+                story.source = header.hops.first().copied();
+                story.destination = header.hops.last().copied();
+                if !story.routes.contains(&header.hops) {
+                    story.routes.push(header.hops.clone());
+                }
+                match kind {
+                    'd' => FragmentStep::Lost { at },
+                    'c' => FragmentStep::Shortcut { at },
+                    _ if header.hop_index + 1 == header.hops.len() => FragmentStep::Delivered,
+                    _ => FragmentStep::Hop { from: at, to: header.hops[header.hop_index] },
+                }
+            }
+            //A nack is told once, where it starts: its first hop, or the shortcut when it couldn't even do that.
+            (PacketType::Nack(nack), 's' | 'c') if header.hop_index <= 1 => FragmentStep::Nacked {
+                at: header.hops.first().copied().unwrap_or(at),
+                nack_type: nack.nack_type,
+            },
+            _ => continue,
+        };
+        story.fragments.entry(index).or_default().push((unix_ms, step));
+    }
+    //A session only seen through its nacks and acks isn't a message I can say anything about.
+    let stories = stories.into_values().filter(|story| story.total_fragments > 0).collect();
+    (stories, crashes)
+}
+
+//'postmortem <journal> [session]': the diagnosis of every failed session of a recorded run
+//(see '--journal'), or of the given one even if it didn't fail.
+pub fn run_postmortem_cli(args: &[String]) {
+    let Some(file) = args.first() else {
+        println!("usage: postmortem <journal> [session]");
+        return;
+    };
+    let only = args.get(1).and_then(|session| session.parse::<u64>().ok());
+    let recovered = match journal::recover(file) {
+        Ok(recovered) => recovered,
+        Err(e) => {
+            println!("journal {} not readable: {}", file, e);
+            return;
+        }
+    };
+    let (stories, crashes) = analyze(&recovered);
+    let failed = stories.iter().filter(|story| story.failed()).count();
+    println!("{} sessions in {}, {} failed.", stories.len(), file, failed);
+    for story in stories.iter().filter(|story| only.map_or(story.failed(), |session| story.session_id == session)) {
+        for line in story.describe(&crashes) {
+            println!("{}", line);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+use wg_2024::packet::NodeType;
+use crate::groups::GroupDefinition;
+
+//A loss regime for a group of drones, as written in the input file:
+//    [[profile]]
+//    name = "congested"
+//    pdr = 0.1
+//    latency_ms = 30
+//    group = "core"
+//The group is "all" (the default), "core" (the drones with no client or server around),
+//"edge" (the ones with a client or server as neighbour), the name of a [[group]] or a list of ids. What's missing is
+//left as it is: a profile with only the latency doesn't touch the pdr. The built-in profiles
+//are "perfect", "lossy", "flaky-core" and "slow-edge", one in the file with the same name replaces them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub pdr: Option<f32>,
+    pub latency_ms: Option<u64>,
+    #[serde(default)]
+    pub group: NodeGroup,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NodeGroup {
+    Named(GroupName),
+    Defined(String), //A [[group]], see groups.rs.
+    Nodes(Vec<NodeId>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupName {
+    All,
+    Core,
+    Edge,
+}
+
+impl Default for NodeGroup {
+    fn default() -> Self {
+        NodeGroup::Named(GroupName::All)
+    }
+}
+
+impl NodeGroup {
+    //"all", "core", "edge", ids separated by commas ("1,2,3") or the name of a group.
+    pub fn parse(text: &str) -> Option<NodeGroup> {
+        match text.trim() {
+            "all" => Some(NodeGroup::Named(GroupName::All)),
+            "core" => Some(NodeGroup::Named(GroupName::Core)),
+            "edge" => Some(NodeGroup::Named(GroupName::Edge)),
+            name if name.starts_with(|c: char| c.is_alphabetic()) => Some(NodeGroup::Defined(name.to_string())),
+            ids => ids.split(',').map(|id| id.trim().parse::<NodeId>().ok()).collect::<Option<Vec<NodeId>>>().map(NodeGroup::Nodes),
+        }
+    }
+
+    //The drones of the group that are still up, sorted. A group that isn't defined has none.
+    pub fn members(&self, graph: &HashMap<NodeId, Vec<NodeId>>, node_types: &HashMap<NodeId, NodeType>, crashed: &HashSet<NodeId>, defined: &[GroupDefinition]) -> Vec<NodeId> {
+        let defined = match self {
+            NodeGroup::Defined(name) => defined.iter().find(|group| group.name == *name).map(|group| group.nodes.clone()).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        let is_endpoint = |id: &NodeId| matches!(node_types.get(id), Some(NodeType::Client) | Some(NodeType::Server));
+        let mut members = graph
+            .iter()
+            .filter(|(id, _)| matches!(node_types.get(id), Some(NodeType::Drone)) && !crashed.contains(id))
+            .filter(|(id, neighbours)| match self {
+                NodeGroup::Named(GroupName::All) => true,
+                NodeGroup::Named(GroupName::Core) => !neighbours.iter().any(is_endpoint),
+                NodeGroup::Named(GroupName::Edge) => neighbours.iter().any(is_endpoint),
+                NodeGroup::Defined(_) => defined.contains(id),
+                NodeGroup::Nodes(ids) => ids.contains(id),
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<NodeId>>();
+        members.sort();
+        members
+    }
+}
+
+impl Profile {
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency_ms.map(Duration::from_millis)
+    }
+}
+
+pub fn builtin_profiles() -> Vec<Profile> {
+    let profile = |name: &str, pdr: Option<f32>, latency_ms: Option<u64>, group: GroupName| Profile {
+        name: name.to_string(),
+        pdr,
+        latency_ms,
+        group: NodeGroup::Named(group),
+    };
+    vec![
+        profile("perfect", Some(0.0), Some(0), GroupName::All),
+        profile("lossy", Some(0.2), None, GroupName::All),
+        profile("flaky-core", Some(0.4), None, GroupName::Core),
+        profile("slow-edge", None, Some(80), GroupName::Edge),
+    ]
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::skylink_drone::commands::LinkImpairment;
+
+//An area of the canvas with interference, declared in the input file:
+//    [[region]]
+//    shape = "circle"
+//    x = 300.0
+//    y = 200.0
+//    radius = 120.0
+//    extra_drop = 0.3
+//    latency_ms = 20
+//or with shape = "rect" and x, y, width, height. Every link crossing it gets the extra drop
+//probability and latency. The coordinates are the ones of the GUI canvas.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Region {
+    #[serde(flatten)]
+    pub shape: RegionShape,
+    #[serde(default)]
+    pub extra_drop: f32,
+    #[serde(default)]
+    pub latency_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum RegionShape {
+    Circle { x: f32, y: f32, radius: f32 },
+    Rect { x: f32, y: f32, width: f32, height: f32 },
+}
+
+impl Region {
+    //True if the segment between the two nodes goes through the region.
+    pub fn crosses(&self, from: (f32, f32), to: (f32, f32)) -> bool {
+        match self.shape {
+            RegionShape::Circle { x, y, radius } => segment_distance((x, y), from, to) <= radius,
+            RegionShape::Rect { x, y, width, height } => segment_crosses_rect(from, to, (x, y), (x + width, y + height)),
+        }
+    }
+}
+
+//The impairment of a link is the sum of the regions it crosses: the drop probabilities
+//combine as independent events, the latencies add up.
+pub fn link_impairment(regions: &[Region], from: (f32, f32), to: (f32, f32)) -> LinkImpairment {
+    let mut delivered = 1.0;
+    let mut latency = Duration::ZERO;
+    for region in regions.iter().filter(|region| region.crosses(from, to)) {
+        delivered *= 1.0 - region.extra_drop.clamp(0.0, 1.0);
+        latency += Duration::from_millis(region.latency_ms);
+    }
+    LinkImpairment {
+        extra_drop: 1.0 - delivered,
+        latency,
+    }
+}
+
+fn segment_distance(point: (f32, f32), from: (f32, f32), to: (f32, f32)) -> f32 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((point.0 - from.0) * dx + (point.1 - from.1) * dy) / length).clamp(0.0, 1.0)
+    };
+    let (closest_x, closest_y) = (from.0 + t * dx, from.1 + t * dy);
+    ((point.0 - closest_x).powi(2) + (point.1 - closest_y).powi(2)).sqrt()
+}
+
+//Liang-Barsky: I clip the segment to the rectangle, if something is left the segment crosses it.
+fn segment_crosses_rect(from: (f32, f32), to: (f32, f32), min: (f32, f32), max: (f32, f32)) -> bool {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let mut t0: f32 = 0.0;
+    let mut t1: f32 = 1.0;
+    for (p, q) in [
+        (-dx, from.0 - min.0),
+        (dx, max.0 - from.0),
+        (-dy, from.1 - min.1),
+        (dy, max.1 - from.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return false;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                t0 = t0.max(r);
+            } else {
+                t1 = t1.min(r);
+            }
+        }
+    }
+    t0 <= t1
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use serde::Deserialize;
+use wg_2024::network::NodeId;
+use crate::skylink_drone::reorder::{DelayDistribution, Reorder};
+
+//The jitter mode of the drones, to get fragments out of order at the servers, as written in the input file:
+//    [[reorder]]
+//    drone = 3
+//    buffer = 8
+//    max_ms = 50
+//    [[reorder]]
+//    drone = 5
+//    distribution = "exponential"
+//    mean_ms = 10
+//    max_ms = 100
+//Every fragment the drone forwards waits in a buffer of `buffer` fragments (8 if not given) for
+//a delay of at most max_ms: uniform from min_ms (0 if not given), or exponential with mean_ms (a
+//quarter of max_ms if not given). A full buffer lets out the fragment due first.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ReorderConfig {
+    pub drone: NodeId,
+    #[serde(default = "default_buffer")]
+    pub buffer: usize,
+    #[serde(default)]
+    pub distribution: DelayKind,
+    #[serde(default)]
+    pub min_ms: u64,
+    pub max_ms: u64,
+    pub mean_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DelayKind {
+    #[default]
+    Uniform,
+    Exponential,
+}
+
+fn default_buffer() -> usize {
+    8
+}
+
+impl ReorderConfig {
+    pub fn new(drone: NodeId, distribution: DelayKind, max_ms: u64) -> Self {
+        ReorderConfig { drone, buffer: default_buffer(), distribution, min_ms: 0, max_ms, mean_ms: None }
+    }
+
+    pub fn reorder(&self) -> Reorder {
+        let max = Duration::from_millis(self.max_ms.max(self.min_ms));
+        let delay = match self.distribution {
+            DelayKind::Uniform => DelayDistribution::Uniform { min: Duration::from_millis(self.min_ms), max },
+            DelayKind::Exponential => DelayDistribution::Exponential { mean: Duration::from_millis(self.mean_ms.unwrap_or(self.max_ms / 4)), max },
+        };
+        Reorder { buffer: self.buffer.max(1), delay }
+    }
+}
+
+//The jitter mode by drone, for SkyLinkDrone::with_reorder.
+pub fn reorders(configs: &[ReorderConfig]) -> HashMap<NodeId, Reorder> {
+    configs.iter().map(|config| (config.drone, config.reorder())).collect()
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use wg_2024::config::Config;
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::drone::Drone;
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+use wg_2024::packet::{FloodRequest, Fragment, NodeType, Packet, PacketType};
+use crate::skylink_drone::drone::{DroneStep, SkyLinkDrone};
+use crate::drone_capture::DroneCapture;
+
+//What is done to the network during a recorded run, in order, written in a file like:
+//    [[input]]
+//    action = "message"
+//    route = [0, 1, 2, 3]
+//    fragments = 10
+//
+//    [[input]]
+//    action = "set_pdr"
+//    drone = 1
+//    pdr = 0.3
+//The other actions are "flood" (client) and "crash" (drone).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ReplayInput {
+    Message { route: Vec<NodeId>, fragments: u64 },
+    Flood { client: NodeId },
+    SetPdr { drone: NodeId, pdr: f32 },
+    Crash { drone: NodeId },
+}
+
+#[derive(Debug, Deserialize)]
+struct InputFile {
+    #[serde(default)]
+    input: Vec<ReplayInput>,
+}
+
+//A run in deterministic mode: enough to run it again, and the events it gave. The events have
+//no timestamp, so two runs can be compared line by line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayTrace {
+    pub config_toml: String, //The whole input file, the trace doesn't depend on the file staying the same.
+    pub seed: u64,
+    pub inputs: Vec<ReplayInput>,
+    pub events: Vec<String>,
+}
+
+//Where two traces stop being the same.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub index: usize,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+}
+
+impl Divergence {
+    pub fn describe(&self) -> String {
+        let line = |event: &Option<String>| event.clone().unwrap_or("(end of the trace)".to_string());
+        format!("first divergence at event {}:\n  recorded:   {}\n  replayed:   {}", self.index, line(&self.expected), line(&self.found))
+    }
+}
+
+//The network of the config, with every drone in this thread: the drones are stepped one at a
+//time in the order of their ids, and an input is given only when nothing moves anymore. With a
+//single thread, and the generator of every drone taken from the seeded one of this thread, the
+//pdr and the impairments roll the same numbers every time, so the same inputs always give the
+//same events.
+struct LockstepNetwork {
+    drones: Vec<SkyLinkDrone>,
+    command_send: HashMap<NodeId, Sender<DroneCommand>>,
+    packet_send: HashMap<NodeId, Sender<Packet>>,
+    endpoint_recv: Vec<Receiver<Packet>>, //The clients and servers, emptied and nothing more.
+    graph: HashMap<NodeId, Vec<NodeId>>,
+    event_recv: Receiver<DroneEvent>,
+    events: Vec<String>,
+    next_session: u64,
+}
+
+impl LockstepNetwork {
+    fn new(config: Config) -> Self {
+        let (event_send, event_recv) = unbounded();
+        let mut packet_send = HashMap::new();
+        let mut packet_recv = HashMap::new();
+        let mut graph = HashMap::new();
+        for drone in config.drone.iter() {
+            graph.insert(drone.id, drone.connected_node_ids.clone());
+        }
+        for (id, neighbours) in config.client.iter().map(|client| (client.id, &client.connected_drone_ids))
+            .chain(config.server.iter().map(|server| (server.id, &server.connected_drone_ids))) {
+            graph.insert(id, neighbours.clone());
+        }
+        for id in graph.keys() {
+            let (send, recv) = unbounded();
+            packet_send.insert(*id, send);
+            packet_recv.insert(*id, recv);
+        }
+
+        let mut command_send = HashMap::new();
+        let mut drones = Vec::new();
+        let mut config_drones = config.drone;
+        config_drones.sort_by_key(|drone| drone.id);
+        for drone in config_drones.into_iter() {
+            let (contr_send, contr_recv) = unbounded();
+            command_send.insert(drone.id, contr_send);
+            let drone_send = drone
+                .connected_node_ids
+                .iter()
+                .filter_map(|id| packet_send.get(id).map(|send: &Sender<Packet>| (*id, send.clone())))
+                .collect();
+            let drone_recv = packet_recv.remove(&drone.id).unwrap();
+            drones.push(SkyLinkDrone::new(drone.id, event_send.clone(), contr_recv, drone_recv, drone_send, drone.pdr));
+        }
+        let mut endpoints = packet_recv.into_iter().collect::<Vec<(NodeId, Receiver<Packet>)>>();
+        endpoints.sort_by_key(|(id, _)| *id);
+
+        LockstepNetwork {
+            drones,
+            command_send,
+            packet_send,
+            endpoint_recv: endpoints.into_iter().map(|(_, recv)| recv).collect(),
+            graph,
+            event_recv,
+            events: Vec::new(),
+            next_session: 1,
+        }
+    }
+
+    //Steps the drones round after round until a whole round does nothing.
+    fn settle(&mut self) {
+        loop {
+            let mut worked = false;
+            for drone in self.drones.iter_mut() {
+                if drone.step() == DroneStep::Worked {
+                    worked = true;
+                }
+            }
+            while let Ok(event) = self.event_recv.try_recv() {
+                self.events.push(format!("{:?}", event));
+            }
+            for recv in self.endpoint_recv.iter() {
+                while recv.try_recv().is_ok() {}
+            }
+            if !worked {
+                break;
+            }
+        }
+    }
+
+    fn apply(&mut self, input: &ReplayInput) {
+        match input {
+            ReplayInput::Message { route, fragments } => {
+                let session_id = self.next_session;
+                self.next_session += 1;
+                let Some(first_hop) = route.get(1).and_then(|id| self.packet_send.get(id)) else {
+                    return;
+                };
+                for index in 0..*fragments {
+                    let packet = Packet {
+                        pack_type: PacketType::MsgFragment(Fragment {
+                            fragment_index: index,
+                            total_n_fragments: *fragments,
+                            length: 128,
+                            data: [index as u8; 128],
+                        }),
+                        routing_header: SourceRoutingHeader { hop_index: 1, hops: route.clone() },
+                        session_id,
+                    };
+                    let _ = first_hop.send(packet);
+                }
+            }
+            ReplayInput::Flood { client } => {
+                let flood_id = self.next_session;
+                self.next_session += 1;
+                let mut neighbours = self.graph.get(client).cloned().unwrap_or_default();
+                neighbours.sort();
+                for neighbour in neighbours {
+                    let packet = Packet {
+                        pack_type: PacketType::FloodRequest(FloodRequest {
+                            flood_id,
+                            initiator_id: *client,
+                            path_trace: vec![(*client, NodeType::Client)],
+                        }),
+                        routing_header: SourceRoutingHeader { hop_index: 0, hops: Vec::new() },
+                        session_id: flood_id,
+                    };
+                    if let Some(sender) = self.packet_send.get(&neighbour) {
+                        let _ = sender.send(packet);
+                    }
+                }
+            }
+            ReplayInput::SetPdr { drone, pdr } => {
+                if let Some(sender) = self.command_send.get(drone) {
+                    let _ = sender.send(DroneCommand::SetPacketDropRate(*pdr));
+                }
+            }
+            ReplayInput::Crash { drone } => {
+                //Like the Sim Contr: the neighbours forget the drone first, then it crashes.
+                let mut neighbours = self.graph.get(drone).cloned().unwrap_or_default();
+                neighbours.sort();
+                for neighbour in neighbours {
+                    if let Some(sender) = self.command_send.get(&neighbour) {
+                        let _ = sender.send(DroneCommand::RemoveSender(*drone));
+                    }
+                }
+                if let Some(sender) = self.command_send.get(drone) {
+                    let _ = sender.send(DroneCommand::Crash);
+                }
+            }
+        }
+        self.settle();
+    }
+}
+
+//Runs the inputs on the network of the config in deterministic mode, returns the events.
+pub fn run_deterministic(config_toml: &str, seed: u64, inputs: &[ReplayInput]) -> io::Result<Vec<String>> {
+    let config: Config = toml::from_str(config_toml).map_err(io::Error::other)?;
+    fastrand::seed(seed);
+    let mut network = LockstepNetwork::new(config);
+    network.settle();
+    for input in inputs.iter() {
+        network.apply(input);
+    }
+    Ok(network.events)
+}
+
+pub fn record(config_file: &str, inputs_file: &str, seed: u64) -> io::Result<ReplayTrace> {
+    let config_toml = fs::read_to_string(config_file)?;
+    let inputs = toml::from_str::<InputFile>(&fs::read_to_string(inputs_file)?).map_err(io::Error::other)?.input;
+    let events = run_deterministic(&config_toml, seed, &inputs)?;
+    Ok(ReplayTrace { config_toml, seed, inputs, events })
+}
+
+//Runs the trace again, None if the events are the same ones.
+pub fn verify(trace: &ReplayTrace) -> io::Result<Option<Divergence>> {
+    let events = run_deterministic(&trace.config_toml, trace.seed, &trace.inputs)?;
+    Ok(first_divergence(&trace.events, &events))
+}
+
+pub fn first_divergence(expected: &[String], found: &[String]) -> Option<Divergence> {
+    let index = expected
+        .iter()
+        .zip(found.iter())
+        .position(|(expected, found)| expected != found)
+        .unwrap_or(expected.len().min(found.len()));
+    if index == expected.len() && index == found.len() {
+        return None;
+    }
+    Some(Divergence { index, expected: expected.get(index).cloned(), found: found.get(index).cloned() })
+}
+
+impl ReplayTrace {
+    pub fn save(&self, file: &str) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(file, json)
+    }
+
+    pub fn load(file: &str) -> io::Result<Self> {
+        let json = fs::read_to_string(file)?;
+        serde_json::from_str(&json).map_err(io::Error::other)
+    }
+}
+
+//    replay record <config.toml> <inputs.toml> <trace.json> [--seed N]
+//    replay verify <trace.json>
+//    replay drone <capture.json> [--seed N]
+pub fn run_replay_cli(args: &[String]) {
+    let seed = args.iter()
+        .position(|arg| arg == "--seed")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|seed| seed.parse::<u64>().ok())
+        .unwrap_or(0);
+    match args.iter().map(|arg| arg.as_str()).collect::<Vec<&str>>().as_slice() {
+        ["record", config, inputs, trace, ..] => match record(config, inputs, seed).and_then(|recorded| recorded.save(trace).map(|_| recorded)) {
+            Ok(recorded) => println!("{} events recorded to {}.", recorded.events.len(), trace),
+            Err(e) => println!("recording failed: {}", e),
+        },
+        ["verify", trace, ..] => match ReplayTrace::load(trace).and_then(|recorded| verify(&recorded)) {
+            Ok(None) => println!("the replay matches the recording."),
+            Ok(Some(divergence)) => {
+                println!("{}", divergence.describe());
+                std::process::exit(1);
+            }
+            Err(e) => println!("replay failed: {}", e),
+        },
+        ["drone", capture, ..] => match DroneCapture::load(capture) {
+            Ok(capture) => match first_divergence(&capture.events, &capture.replay(seed)) {
+                None => println!("the drone did the same {} things again.", capture.events.len()),
+                Some(divergence) => {
+                    println!("{}", divergence.describe());
+                    std::process::exit(1);
+                }
+            },
+            Err(e) => println!("replay failed: {}", e),
+        },
+        _ => println!("usage: replay record <config.toml> <inputs.toml> <trace.json> [--seed N] | replay verify <trace.json> | replay drone <capture.json> [--seed N]"),
+    }
+}
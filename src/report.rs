@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::time::SystemTime;
+use wg_2024::network::NodeId;
+use wg_2024::packet::NodeType;
+use crate::alerts::Alert;
+use crate::bandwidth::BandwidthUsage;
+use crate::hop_counts::HopCountTable;
+use crate::sessions::{SessionRecord, SessionTable};
+use crate::sim_control::NodeStats;
+use crate::stats_series::StatsSeries;
+use crate::tags::Annotations;
+
+const WIDTH: f32 = 640.0;
+const HEIGHT: f32 = 360.0;
+const LOG_TAIL: usize = 200;
+
+//Everything the report shows, borrowed from the Sim Contr when it's written.
+pub struct ReportData<'a> {
+    pub network_graph: &'a HashMap<NodeId, Vec<NodeId>>,
+    pub node_types: &'a HashMap<NodeId, NodeType>,
+    pub crashed: &'a HashSet<NodeId>,
+    pub positions: &'a HashMap<NodeId, (f32, f32)>,
+    pub stats: &'a HashMap<NodeId, NodeStats>,
+    pub stats_series: &'a StatsSeries,
+    pub sessions: &'a SessionTable,
+    pub hop_counts: &'a HopCountTable,
+    pub alerts: &'a [Alert],
+    pub bandwidth: &'a BandwidthUsage,
+    pub annotations: &'a Annotations,
+    pub log: &'a [String],
+}
+
+//A single HTML file, with the pictures drawn as inline SVG and no script or external file,
+//so it can be mailed or attached to an issue as it is.
+pub fn write_report(data: &ReportData, file: &str) -> io::Result<()> {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>SkyLink report</title>\n");
+    html.push_str("<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:2px 8px;text-align:right}pre{background:#f4f4f4;padding:1em;max-height:30em;overflow:auto}svg{border:1px solid #ccc}</style>\n");
+    html.push_str("</head><body>\n<h1>SkyLink report</h1>\n");
+
+    let delivered = data.sessions.sessions.values().filter(|session| session.delivered.is_some()).count();
+    let dropped = data.sessions.sessions.values().filter(|session| session.dropped).count();
+    let _ = writeln!(
+        html,
+        "<p>{} nodes ({} crashed), {} sessions: {} delivered, {} with a drop. {} alerts.</p>",
+        data.network_graph.len(),
+        data.crashed.len(),
+        data.sessions.sessions.len(),
+        delivered,
+        dropped,
+        data.alerts.iter().filter(|alert| !alert.resolved).count()
+    );
+
+    html.push_str("<h2>Topology</h2>\n");
+    html.push_str(&topology_svg(data));
+
+    html.push_str("<h2>Nodes</h2>\n<table><tr><th>node</th><th>type</th><th>sent</th><th>dropped</th><th>drop %</th><th>shortcuts</th><th>queue peak</th><th>mean hop ms</th><th>link utilization</th><th>events suppressed</th><th>fragments forwarded</th><th>fragments dropped</th><th>nacks</th><th>floods</th><th>congestion drops</th><th>rate limited</th><th>tags</th></tr>\n");
+    let mut ids = data.network_graph.keys().copied().collect::<Vec<NodeId>>();
+    ids.sort();
+    for id in ids.iter() {
+        let stats = data.stats.get(id).cloned().unwrap_or_default();
+        let handled = (stats.packets_sent + stats.packets_dropped).max(1);
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td><td>{}</td><td>{}</td><td>{:.3}</td><td>{:.2}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            id,
+            node_label(data, *id),
+            stats.packets_sent,
+            stats.packets_dropped,
+            stats.packets_dropped as f64 * 100.0 / handled as f64,
+            stats.shortcuts,
+            stats.queue_peak,
+            stats.mean_hop_latency_ms,
+            stats.link_utilization,
+            stats.events_suppressed,
+            stats.fragments_forwarded,
+            stats.fragments_dropped,
+            stats.nacks_generated,
+            stats.floods_handled,
+            stats.congestion_drops,
+            stats.rate_limited,
+            escape(&data.annotations.node(*id).map(|annotation| annotation.describe()).unwrap_or_default())
+        );
+    }
+    html.push_str("</table>\n");
+
+    if !data.annotations.links.is_empty() {
+        html.push_str("<h2>Links</h2>\n<table><tr><th>link</th><th>tags</th></tr>\n");
+        for ((a, b), annotation) in data.annotations.links.iter() {
+            let _ = writeln!(html, "<tr><td>{} - {}</td><td>{}</td></tr>", a, b, escape(&annotation.describe()));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str(&bandwidth_table(data.bandwidth));
+
+    html.push_str("<h2>End to end latency of the sessions (ms)</h2>\n");
+    let latencies = session_latencies(data.sessions);
+    html.push_str(&chart_svg(&latencies, "#1f77b4"));
+
+    html.push_str("<h2>Packets sent per second (whole network)</h2>\n");
+    let mut throughput = BTreeMap::new();
+    for id in ids.iter() {
+        for rate in data.stats_series.rates(*id) {
+            *throughput.entry(rate.time_ms).or_insert(0.0) += rate.throughput;
+        }
+    }
+    let throughput = throughput
+        .into_iter()
+        .map(|(time_ms, value)| (time_ms as f64 / 1000.0, value))
+        .collect::<Vec<(f64, f64)>>();
+    html.push_str(&chart_svg(&throughput, "#2ca02c"));
+
+    html.push_str("<h2>Hop counts of the delivered messages</h2>\n");
+    if data.hop_counts.pairs.is_empty() {
+        html.push_str("<p>No data.</p>\n");
+    } else {
+        html.push_str("<table><tr><th>source</th><th>destination</th><th>messages</th><th>mean hops</th><th>mean extra hops</th><th>distribution (hops: messages)</th></tr>\n");
+        for ((source, destination), stats) in data.hop_counts.pairs.iter() {
+            let distribution = stats.distribution
+                .iter()
+                .map(|(hops, n)| format!("{}: {}", hops, n))
+                .collect::<Vec<String>>();
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td><td>{}</td></tr>",
+                source,
+                destination,
+                stats.messages(),
+                stats.mean(),
+                stats.mean_extra_hops().map(|extra| format!("{:.2}", extra)).unwrap_or_default(),
+                distribution.join(", ")
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Alerts</h2>\n");
+    if data.alerts.is_empty() {
+        html.push_str("<p>None.</p>\n");
+    } else {
+        html.push_str("<ul>\n");
+        for alert in data.alerts {
+            let _ = writeln!(html, "<li>{}</li>", escape(&alert.describe()));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    let tail = &data.log[data.log.len().saturating_sub(LOG_TAIL)..];
+    let _ = writeln!(html, "<h2>Log (last {} lines)</h2>\n<pre>", tail.len());
+    for line in tail {
+        html.push_str(&escape(line));
+        html.push('\n');
+    }
+    html.push_str("</pre>\n</body></html>\n");
+
+    fs::write(file, html)
+}
+
+fn node_label(data: &ReportData, id: NodeId) -> &'static str {
+    match (data.node_types.get(&id), data.crashed.contains(&id)) {
+        (_, true) => "crashed",
+        (Some(NodeType::Client), _) => "client",
+        (Some(NodeType::Server), _) => "server",
+        _ => "drone",
+    }
+}
+
+//Where the nodes were on the GUI canvas, or on a circle when there was no GUI.
+fn topology_svg(data: &ReportData) -> String {
+    let mut ids = data.network_graph.keys().copied().collect::<Vec<NodeId>>();
+    ids.sort();
+    let placed = ids.iter().all(|id| data.positions.contains_key(id));
+    let mut points = HashMap::new();
+    if placed && !ids.is_empty() {
+        let xs = ids.iter().map(|id| data.positions[id].0);
+        let ys = ids.iter().map(|id| data.positions[id].1);
+        let (min_x, max_x) = (xs.clone().fold(f32::MAX, f32::min), xs.fold(f32::MIN, f32::max));
+        let (min_y, max_y) = (ys.clone().fold(f32::MAX, f32::min), ys.fold(f32::MIN, f32::max));
+        for id in ids.iter() {
+            let (x, y) = data.positions[id];
+            let x = 30.0 + (x - min_x) / (max_x - min_x).max(1.0) * (WIDTH - 60.0);
+            let y = 30.0 + (y - min_y) / (max_y - min_y).max(1.0) * (HEIGHT - 60.0);
+            points.insert(*id, (x, y));
+        }
+    } else {
+        for (index, id) in ids.iter().enumerate() {
+            let angle = index as f32 / ids.len() as f32 * std::f32::consts::TAU;
+            points.insert(*id, (WIDTH / 2.0 + angle.cos() * (HEIGHT / 2.0 - 30.0), HEIGHT / 2.0 + angle.sin() * (HEIGHT / 2.0 - 30.0)));
+        }
+    }
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n", WIDTH, HEIGHT);
+    for (id, neighbours) in data.network_graph.iter() {
+        for neighbour in neighbours.iter().filter(|neighbour| *neighbour > id) {
+            if let (Some(a), Some(b)) = (points.get(id), points.get(neighbour)) {
+                let _ = writeln!(svg, "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#888\"/>", a.0, a.1, b.0, b.1);
+            }
+        }
+    }
+    for id in ids.iter() {
+        let (x, y) = points[id];
+        let color = match node_label(data, *id) {
+            "crashed" => "#d62728",
+            "client" => "#1f77b4",
+            "server" => "#9467bd",
+            _ => "#2ca02c",
+        };
+        let _ = writeln!(svg, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"12\" fill=\"{}\"/>", x, y, color);
+        let _ = writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"11\" text-anchor=\"middle\" fill=\"white\">{}</text>", x, y + 4.0, id);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+//(seconds from the first session, latency in ms) of the delivered sessions.
+fn session_latencies(sessions: &SessionTable) -> Vec<(f64, f64)> {
+    let start = |session: &SessionRecord| session.originated.or(session.hops.first().map(|hop| hop.time));
+    let first = sessions.sessions.values().filter_map(start).min().unwrap_or_else(SystemTime::now);
+    let mut latencies = sessions.sessions
+        .values()
+        .filter_map(|session| {
+            let latency = session.end_to_end_latency()?;
+            let time = start(session)?.duration_since(first).unwrap_or_default();
+            Some((time.as_secs_f64(), latency.as_secs_f64() * 1000.0))
+        })
+        .collect::<Vec<(f64, f64)>>();
+    latencies.sort_by(|a, b| a.0.total_cmp(&b.0));
+    latencies
+}
+
+//A line through the (x, y) points, with the ranges of the axes written in the corners.
+fn chart_svg(points: &[(f64, f64)], color: &str) -> String {
+    if points.is_empty() {
+        return "<p>No data.</p>\n".to_string();
+    }
+    let (min_x, max_x) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), (x, _)| (lo.min(*x), hi.max(*x)));
+    let max_y = points.iter().fold(f64::EPSILON, |hi, (_, y)| hi.max(*y));
+    let (width, height) = (WIDTH as f64, HEIGHT as f64 / 2.0);
+    let coordinates = points
+        .iter()
+        .map(|(x, y)| {
+            let px = 40.0 + (x - min_x) / (max_x - min_x).max(f64::EPSILON) * (width - 60.0);
+            let py = height - 20.0 - y / max_y * (height - 40.0);
+            format!("{:.1},{:.1}", px, py)
+        })
+        .collect::<Vec<String>>();
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n", width, height);
+    let _ = writeln!(svg, "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>", color, coordinates.join(" "));
+    let _ = writeln!(svg, "<text x=\"4\" y=\"14\" font-size=\"11\">{:.1}</text>", max_y);
+    let _ = writeln!(svg, "<text x=\"4\" y=\"{:.1}\" font-size=\"11\">{:.1}s</text>", height - 4.0, min_x);
+    let _ = writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"11\" text-anchor=\"end\">{:.1}s</text>", width - 4.0, height - 4.0, max_x);
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+//Sent and received bytes side by side, for every class of node and kind of packet.
+fn bandwidth_table(bandwidth: &BandwidthUsage) -> String {
+    let mut html = String::new();
+    let _ = writeln!(html, "<h2>Bandwidth</h2>\n<p>{:.1}% of the bytes sent are protocol overhead, the rest is fragment payload.</p>", bandwidth.overhead_ratio() * 100.0);
+    html.push_str("<table><tr><th>nodes</th><th>packets</th><th>sent packets</th><th>sent bytes</th><th>payload bytes</th><th>received packets</th><th>received bytes</th></tr>\n");
+    let mut keys = bandwidth.sent.keys().chain(bandwidth.received.keys()).copied().collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let sent = bandwidth.sent.get(&key).copied().unwrap_or_default();
+        let received = bandwidth.received.get(&key).copied().unwrap_or_default();
+        let _ = writeln!(
+            html,
+            "<tr><td>{:?}</td><td>{:?}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            key.0, key.1, sent.packets, sent.bytes, sent.payload_bytes, received.packets, received.bytes
+        );
+    }
+    html.push_str("</table>\n");
+    html
+}
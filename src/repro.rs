@@ -0,0 +1,253 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use wg_2024::network::NodeId;
+use crate::sim_control::{NodeStats, SimulationControl};
+use crate::snapshot::SimulationSnapshot;
+
+//What was done to the network during the run, the things a repro has to do again.
+#[derive(Debug, Clone)]
+pub enum ReproAction {
+    Crash(NodeId),
+    Reboot(NodeId),
+    SetPdr { drone: NodeId, pdr: f32, was: f32 }, //was is the pdr before, for the starting topology.
+    LinkDown(NodeId, NodeId),
+    LinkUp(NodeId, NodeId),
+    Message { session_id: u64, routes: Vec<Vec<NodeId>>, fragments: u64, window: usize },
+}
+
+impl ReproAction {
+    fn nodes(&self) -> Vec<NodeId> {
+        match self {
+            ReproAction::Crash(id) | ReproAction::Reboot(id) => vec![*id],
+            ReproAction::SetPdr { drone, .. } => vec![*drone],
+            ReproAction::LinkDown(a, b) | ReproAction::LinkUp(a, b) => vec![*a, *b],
+            ReproAction::Message { routes, .. } => routes.iter().flatten().copied().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReproStep {
+    pub at: Duration, //From the start of the simulation.
+    pub action: ReproAction,
+}
+
+//Kept by the Sim Contr from the start, whoever asked for the actions (scenario, faults, GUI...).
+#[derive(Debug)]
+pub struct ReproRecorder {
+    started: Instant,
+    pub(crate) steps: Vec<ReproStep>,
+}
+
+impl Default for ReproRecorder {
+    fn default() -> Self {
+        ReproRecorder { started: Instant::now(), steps: Vec::new() }
+    }
+}
+
+impl ReproRecorder {
+    pub fn record(&mut self, action: ReproAction) {
+        self.steps.push(ReproStep { at: self.started.elapsed(), action });
+    }
+}
+
+//The smaller run that should fail the same way: the starting topology as a snapshot, and the
+//script doing again only what touched the involved nodes.
+pub struct Repro {
+    pub snapshot: SimulationSnapshot,
+    pub script: String,
+    pub involved: BTreeSet<NodeId>,
+}
+
+impl Repro {
+    //Next to the scenario, as <scenario>_repro.toml and <scenario>_repro.rhai (with the seed in the
+    //name when there's one, so the runs of a batch don't overwrite each other). Returns the two files.
+    pub fn save(&self, scenario_file: &str, seed: Option<u64>) -> io::Result<(String, String)> {
+        let path = Path::new(scenario_file);
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("scenario");
+        let name = match seed {
+            Some(seed) => format!("{}_repro_seed{}", stem, seed),
+            None => format!("{}_repro", stem),
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let snapshot_file = dir.join(format!("{}.toml", name)).to_string_lossy().to_string();
+        let script_file = dir.join(format!("{}.rhai", name)).to_string_lossy().to_string();
+        self.snapshot.save(&snapshot_file)?;
+        fs::write(&script_file, &self.script)?;
+        Ok((snapshot_file, script_file))
+    }
+}
+
+//The involved nodes are the ones on the routes of the messages that never got through, or of
+//every message when they all did (the failure is about something else then). The steps kept are
+//the messages through them and the faults touching them; the topology is the involved nodes with
+//their neighbours, so the detours are still there, as it was before the first step kept.
+//failed_at is the line of the scenario that failed, if rhai knows it.
+pub fn extract_repro(sim_contr: &SimulationControl, error: &str, failed_at: Option<(usize, String)>) -> Repro {
+    let steps = &sim_contr.repro.steps;
+    let failed_sessions = sim_contr.transfers
+        .iter()
+        .filter(|transfer| transfer.finished.is_none() && !transfer.cancelled)
+        .map(|transfer| transfer.session_id)
+        .collect::<HashSet<u64>>();
+    let is_kept_message = |session_id: &u64| failed_sessions.is_empty() || failed_sessions.contains(session_id);
+
+    let mut involved = BTreeSet::new();
+    for step in steps {
+        if let ReproAction::Message { session_id, .. } = &step.action {
+            if is_kept_message(session_id) {
+                involved.extend(step.action.nodes());
+            }
+        }
+    }
+    //With no messages at all the faults are all there is to go on.
+    if involved.is_empty() {
+        involved.extend(steps.iter().flat_map(|step| step.action.nodes()));
+    }
+    let kept = steps
+        .iter()
+        .filter(|step| match &step.action {
+            ReproAction::Message { session_id, .. } => is_kept_message(session_id),
+            action => action.nodes().iter().any(|node| involved.contains(node)),
+        })
+        .collect::<Vec<&ReproStep>>();
+
+    let snapshot = starting_snapshot(sim_contr, &involved, &kept);
+    let script = write_script(sim_contr, error, failed_at, &involved, &kept);
+    Repro { snapshot, script, involved }
+}
+
+//The network now, cut to the involved nodes and their neighbours, with the kept steps undone
+//from the last one back.
+fn starting_snapshot(sim_contr: &SimulationControl, involved: &BTreeSet<NodeId>, kept: &[&ReproStep]) -> SimulationSnapshot {
+    let mut snapshot = sim_contr.snapshot();
+    let mut nodes = involved.clone();
+    for node in involved {
+        nodes.extend(sim_contr.network_graph.get(node).cloned().unwrap_or_default());
+    }
+    snapshot.drone.retain(|drone| nodes.contains(&drone.id));
+    snapshot.client.retain(|client| nodes.contains(&client.id));
+    snapshot.server.retain(|server| nodes.contains(&server.id));
+
+    let link = |snapshot: &mut SimulationSnapshot, a: NodeId, b: NodeId, up: bool| {
+        for (from, to) in [(a, b), (b, a)] {
+            let neighbours = snapshot.drone
+                .iter_mut()
+                .find(|drone| drone.id == from)
+                .map(|drone| &mut drone.connected_node_ids)
+                .or_else(|| snapshot.client.iter_mut().chain(snapshot.server.iter_mut()).find(|endpoint| endpoint.id == from).map(|endpoint| &mut endpoint.connected_drone_ids));
+            if let Some(neighbours) = neighbours {
+                neighbours.retain(|id| *id != to);
+                if up {
+                    neighbours.push(to);
+                }
+            }
+        }
+    };
+    for step in kept.iter().rev() {
+        match &step.action {
+            ReproAction::Crash(id) | ReproAction::Reboot(id) => {
+                let crashed = matches!(step.action, ReproAction::Reboot(_));
+                if let Some(drone) = snapshot.drone.iter_mut().find(|drone| drone.id == *id) {
+                    drone.crashed = crashed;
+                }
+            }
+            ReproAction::SetPdr { drone: id, was, .. } => {
+                if let Some(drone) = snapshot.drone.iter_mut().find(|drone| drone.id == *id) {
+                    drone.pdr = *was;
+                }
+            }
+            ReproAction::LinkDown(a, b) => link(&mut snapshot, *a, *b, true),
+            ReproAction::LinkUp(a, b) => link(&mut snapshot, *a, *b, false),
+            ReproAction::Message { .. } => {}
+        }
+    }
+
+    //The links to the nodes left out go too, and the repro starts with no stats.
+    let ids = nodes;
+    for drone in snapshot.drone.iter_mut() {
+        drone.connected_node_ids.retain(|id| ids.contains(id));
+        drone.stats = NodeStats::default();
+    }
+    for endpoint in snapshot.client.iter_mut().chain(snapshot.server.iter_mut()) {
+        endpoint.connected_drone_ids.retain(|id| ids.contains(id));
+        endpoint.stats = NodeStats::default();
+    }
+    snapshot
+}
+
+fn write_script(sim_contr: &SimulationControl, error: &str, failed_at: Option<(usize, String)>, involved: &BTreeSet<NodeId>, kept: &[&ReproStep]) -> String {
+    let ids = |nodes: &[NodeId]| nodes.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(", ");
+    let mut lines = vec![
+        "//Minimal repro extracted from a failed run.".to_string(),
+        format!("//The run failed with: {}", error.replace('\n', " ")),
+    ];
+    if let Some((line, code)) = failed_at {
+        lines.push(format!("//at line {}: {}", line, code.trim()));
+    }
+    lines.push(format!("//Involved nodes: {}", ids(&involved.iter().copied().collect::<Vec<NodeId>>())));
+    lines.push(String::new());
+
+    let start = kept.first().map_or(Duration::ZERO, |step| step.at);
+    let mut now = start;
+    let mut messages = Vec::new();
+    for step in kept {
+        let wait = step.at.saturating_sub(now);
+        if wait >= Duration::from_millis(1) {
+            lines.push(format!("sleep({});", wait.as_millis()));
+        }
+        now = step.at;
+        lines.push(match &step.action {
+            ReproAction::Crash(id) => format!("crash({});", id),
+            ReproAction::Reboot(id) => format!("reboot({});", id),
+            ReproAction::SetPdr { drone, pdr, .. } => format!("set_pdr({}, {:?});", drone, pdr),
+            ReproAction::LinkDown(a, b) => format!("schedule_link_down(0.0, {}, {});", a, b),
+            ReproAction::LinkUp(a, b) => format!("schedule_link_up(0.0, {}, {});", a, b),
+            ReproAction::Message { session_id, routes, fragments, window } => {
+                let name = format!("m{}", messages.len());
+                messages.push((name.clone(), *session_id, routes.clone()));
+                //The multipath ones can only be asked again, the routes are found when it's sent.
+                match routes.as_slice() {
+                    [route] => format!("let {} = send_message([{}], {}, {});", name, ids(route), fragments, window),
+                    routes => {
+                        let (from, to) = (routes[0][0], routes[0][routes[0].len() - 1]);
+                        format!("let {} = send_multipath({}, {}, {});", name, from, to, fragments)
+                    }
+                }
+            }
+        });
+    }
+
+    //Then as long as the original run went on after the last step, and the same check on the messages.
+    let end = sim_contr.timeline.elapsed();
+    let wait = end.saturating_sub(now).max(Duration::from_secs(1));
+    lines.push(format!("sleep({});", wait.as_millis()));
+    let failed_messages = messages
+        .iter()
+        .filter(|(_, session_id, _)| sim_contr.transfers.iter().any(|transfer| transfer.session_id == *session_id && transfer.finished.is_none()))
+        .collect::<Vec<_>>();
+    if failed_messages.is_empty() {
+        lines.push("//Every message got through in the failed run, put the check that failed here.".to_string());
+    }
+    for (name, _, routes) in failed_messages {
+        let route = &routes[0];
+        lines.push(format!(
+            "if !message({}).done {{ throw \"the message from {} to {} still isn't delivered\"; }}",
+            name, route[0], route[route.len() - 1]
+        ));
+    }
+
+    //What the original run saw from the first step kept on, on the involved nodes.
+    lines.push(String::new());
+    lines.push("//The timeline of the failed run, from the first step on:".to_string());
+    for entry in sim_contr.timeline.entries.iter().filter(|entry| entry.at >= start) {
+        if entry.node.map_or(true, |node| involved.contains(&node)) {
+            lines.push(format!("//    {}", entry.describe()));
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
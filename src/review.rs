@@ -0,0 +1,25 @@
+use wg_2024::network::NodeId;
+
+//A change of the topology asked from the GUI. In review mode it's only proposed: it waits in a
+//list until it's confirmed, and then the whole list is applied in one go (see
+//SimulationControl::apply_mutations), so a wrong click in the middle of a demo breaks nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mutation {
+    AddLink(NodeId, NodeId),
+    RemoveLink(NodeId, NodeId),
+    Crash(NodeId),
+    Reboot(NodeId),
+    Spawn { pdr: f32, connections: Vec<NodeId> },
+}
+
+impl Mutation {
+    pub fn describe(&self) -> String {
+        match self {
+            Mutation::AddLink(a, b) => format!("add the link {} - {}", a, b),
+            Mutation::RemoveLink(a, b) => format!("remove the link {} - {}", a, b),
+            Mutation::Crash(id) => format!("crash drone {}", id),
+            Mutation::Reboot(id) => format!("reboot drone {}", id),
+            Mutation::Spawn { pdr, connections } => format!("spawn a drone with pdr {} linked to {:?}", pdr, connections),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use wg_2024::network::NodeId;
+use wg_2024::packet::{NackType, Packet, PacketType};
+
+//The routes the clients (played by the Sim Contr) already computed, by (client, destination),
+//so a message to the same destination doesn't compute its route again. A route is thrown away
+//when an ErrorInRouting nack says one of its links is gone, or when one of its drones crashes.
+#[derive(Debug, Default)]
+pub struct RouteCache {
+    routes: HashMap<(NodeId, NodeId), Vec<NodeId>>,
+    pub(crate) hits: u64,
+    pub(crate) misses: u64,
+    pub(crate) invalidations: u64, //Routes thrown away, not the events that did it.
+}
+
+impl RouteCache {
+    //The cached route, or the one compute gives (cached in turn if there's one).
+    pub fn route(&mut self, client: NodeId, destination: NodeId, compute: impl FnOnce() -> Option<Vec<NodeId>>) -> Option<Vec<NodeId>> {
+        if let Some(route) = self.routes.get(&(client, destination)) {
+            self.hits += 1;
+            return Some(route.clone());
+        }
+        self.misses += 1;
+        let route = compute()?;
+        self.routes.insert((client, destination), route.clone());
+        Some(route)
+    }
+
+    pub fn cached(&self, client: NodeId, destination: NodeId) -> Option<&Vec<NodeId>> {
+        self.routes.get(&(client, destination))
+    }
+
+    //Called with every PacketSent: an ErrorInRouting nack reaching a client means the node that
+    //sent it has no link to the next hop anymore. The SkyLink drones put their own id in the nack
+    //instead of the next hop (is_next_hop_check, a crashing drone, ttl_check), then the next hop is
+    //the one after them on the cached routes that went through the same nodes as the nack.
+    pub fn record(&mut self, packet: &Packet) -> usize {
+        let PacketType::Nack(nack) = &packet.pack_type else {
+            return 0;
+        };
+        let NackType::ErrorInRouting(missing) = nack.nack_type else {
+            return 0;
+        };
+        let header = &packet.routing_header;
+        if header.hop_index + 1 != header.hops.len() {
+            return 0;
+        }
+        let Some(reporter) = header.hops.first().copied() else {
+            return 0;
+        };
+        if missing != reporter {
+            return self.invalidate_link(reporter, missing);
+        }
+        //The nack went back the way the fragment came, so its route reversed is the start of the fragment's.
+        let walked = header.hops.iter().rev().copied().collect::<Vec<NodeId>>();
+        let next_hops = self.routes
+            .values()
+            .filter(|route| route.len() > walked.len() && route.starts_with(&walked))
+            .map(|route| route[walked.len()])
+            .collect::<Vec<NodeId>>();
+        next_hops.into_iter().map(|next_hop| self.invalidate_link(reporter, next_hop)).sum()
+    }
+
+    pub fn invalidate_node(&mut self, node: NodeId) -> usize {
+        self.invalidate(|route| route.len() > 2 && route[1..route.len() - 1].contains(&node))
+    }
+
+    pub fn invalidate_link(&mut self, a: NodeId, b: NodeId) -> usize {
+        self.invalidate(|route| route.windows(2).any(|link| (link[0] == a && link[1] == b) || (link[0] == b && link[1] == a)))
+    }
+
+    fn invalidate(&mut self, broken: impl Fn(&[NodeId]) -> bool) -> usize {
+        let before = self.routes.len();
+        self.routes.retain(|_, route| !broken(route));
+        let invalidated = before - self.routes.len();
+        self.invalidations += invalidated as u64;
+        invalidated
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        self.hits as f64 / (self.hits + self.misses).max(1) as f64
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} routes cached, {} hits, {} misses ({:.1}% hit rate), {} invalidated",
+            self.routes.len(),
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.invalidations
+        )
+    }
+}
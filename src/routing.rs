@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+use wg_2024::packet::NodeType;
+use crate::flow::Transfer;
+use crate::traceroute;
+
+//How the clients (played by the Sim Contr) route the messages of send_to, as written in the input file:
+//    [routing]
+//    strategy = "multipath"
+//    max_routes = 3
+//"single_path" (the default) sends every fragment on the shortest route. "multipath" spreads the
+//fragments of a message on up to max_routes disjoint routes: every fragment goes on the route
+//with the fewest fragments in flight, so the faster routes get more of them, and a route that
+//gets an ErrorInRouting nack is dropped as long as the message has another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingStrategy {
+    SinglePath,
+    Multipath,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub strategy: RoutingStrategy,
+    pub max_routes: usize,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        RoutingConfig { strategy: RoutingStrategy::SinglePath, max_routes: 3 }
+    }
+}
+
+//Shortest routes that share no link, apart from the first and the last one: a client or a
+//server with a single drone would have only one route otherwise. The shortest one comes first.
+pub fn disjoint_routes(
+    graph: &HashMap<NodeId, Vec<NodeId>>,
+    node_types: &HashMap<NodeId, NodeType>,
+    crashed: &HashSet<NodeId>,
+    from: NodeId,
+    to: NodeId,
+    max_routes: usize,
+) -> Vec<Vec<NodeId>> {
+    let mut graph = graph.clone();
+    let mut routes = Vec::new();
+    while routes.len() < max_routes.max(1) {
+        let Some(route) = traceroute::shortest_route(&graph, node_types, crashed, from, to) else {
+            break;
+        };
+        //A route with only the access links can't be avoided by the next one.
+        if route.len() < 4 {
+            routes.push(route);
+            break;
+        }
+        for link in route[1..route.len() - 1].windows(2) {
+            for (a, b) in [(link[0], link[1]), (link[1], link[0])] {
+                if let Some(neighbours) = graph.get_mut(&a) {
+                    neighbours.retain(|neighbour| *neighbour != b);
+                }
+            }
+        }
+        routes.push(route);
+    }
+    routes
+}
+
+//The messages sent with a strategy, to compare it with the other one.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StrategyStats {
+    pub messages: u64,
+    pub finished: u64,
+    pub fragments: u64,
+    pub delivered_fragments: u64,
+    pub retransmissions: u64,
+    pub mean_completion_ms: Option<f64>, //Of the finished messages.
+    pub mean_routes: f64, //The routes a message started with.
+}
+
+impl StrategyStats {
+    pub fn describe(&self) -> String {
+        let completion = self.mean_completion_ms.map_or("-".to_string(), |ms| format!("{:.1} ms", ms));
+        format!(
+            "{} messages ({} finished), {}/{} fragments delivered, {} retransmissions, mean completion {}, {:.1} routes per message",
+            self.messages, self.finished, self.delivered_fragments, self.fragments, self.retransmissions, completion, self.mean_routes
+        )
+    }
+}
+
+//Built from the transfers whenever it's asked for, like the traffic matrix.
+pub fn strategy_stats(transfers: &[Transfer]) -> BTreeMap<RoutingStrategy, StrategyStats> {
+    let mut stats: BTreeMap<RoutingStrategy, StrategyStats> = BTreeMap::new();
+    let mut completion_sums: BTreeMap<RoutingStrategy, f64> = BTreeMap::new();
+    for transfer in transfers {
+        let entry = stats.entry(transfer.strategy).or_default();
+        entry.messages += 1;
+        entry.fragments += transfer.total;
+        entry.delivered_fragments += transfer.delivered() as u64;
+        entry.retransmissions += transfer.retransmissions;
+        entry.mean_routes += (transfer.initial_routes as f64 - entry.mean_routes) / entry.messages as f64;
+        if let Some(time) = transfer.finished {
+            entry.finished += 1;
+            *completion_sums.entry(transfer.strategy).or_insert(0.0) += time.as_secs_f64() * 1000.0;
+        }
+    }
+    for (strategy, sum) in completion_sums {
+        if let Some(entry) = stats.get_mut(&strategy) {
+            entry.mean_completion_ms = Some(sum / entry.finished.max(1) as f64);
+        }
+    }
+    stats
+}
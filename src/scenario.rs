@@ -0,0 +1,674 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+use wg_2024::packet::{Fragment, Packet, PacketType};
+use crate::sim_control::SimulationControl;
+use crate::filters::FilterConfig;
+use crate::faults::{Fault, FaultConfig};
+use crate::alerts::{AlertConfig, AlertRule};
+use crate::profiles::NodeGroup;
+use crate::ack_sink::AckSinkConfig;
+use crate::timeline::TimelineKind;
+use crate::sla::SlaProbeConfig;
+use crate::groups::GroupDefinition;
+use crate::audit::CommandOrigin;
+use crate::routing::RoutingStrategy;
+use crate::telemetry::{EventCategory, LogVerbosity, TelemetryConfig};
+use crate::link_rate::LinkRateConfig;
+use crate::reorder::{DelayKind, ReorderConfig};
+use crate::burst_loss::BurstLossConfig;
+
+//Every function registered here is a thin wrapper around the Sim Contr, so a script can do
+//everything the GUI can do, plus conditions and loops (see inputs/scenario_crash_busiest.rhai).
+fn build_engine(sim_contr: Rc<RefCell<SimulationControl>>) -> Engine {
+    let mut engine = Engine::new();
+
+    let contr = sim_contr.clone();
+    engine.register_fn("crash", move |id: i64| {
+        contr.borrow_mut().crash_drone(id as NodeId);
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("reboot", move |id: i64| -> bool {
+        contr.borrow_mut().reboot_drone(id as NodeId)
+    });
+
+    //The reboot ends while the script sleeps, this says if it's over.
+    let contr = sim_contr.clone();
+    engine.register_fn("rebooting", move |id: i64| -> bool {
+        contr.borrow().rebooting(id as NodeId)
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("set_pdr", move |id: i64, pdr: f64| {
+        contr.borrow_mut().set_pdr(id as NodeId, pdr as f32);
+    });
+    //set_link_pdr(3, 4, 0.3) for the link between 3 and 4 only, both ways. clear_link_pdr(3, 4) goes
+    //back to the pdr of the two drones.
+    let contr = sim_contr.clone();
+    engine.register_fn("set_link_pdr", move |a: i64, b: i64, pdr: f64| -> bool {
+        contr.borrow_mut().set_link_pdr(a as NodeId, b as NodeId, Some(pdr as f32))
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("clear_link_pdr", move |a: i64, b: i64| -> bool {
+        contr.borrow_mut().set_link_pdr(a as NodeId, b as NodeId, None)
+    });
+
+    //set_filters(3, #{deny_from: [1], only_forward_to: [4, 5], block_session: [42]}), #{} removes them.
+    let contr = sim_contr.clone();
+    engine.register_fn("set_filters", move |id: i64, rules: Map| {
+        let ids = |key: &str| -> Option<Vec<i64>> {
+            rules.get(key)?.clone().into_typed_array::<i64>().ok()
+        };
+        let filters = FilterConfig {
+            deny_from: ids("deny_from").unwrap_or_default().into_iter().map(|id| id as NodeId).collect(),
+            only_forward_to: ids("only_forward_to").map(|ids| ids.into_iter().map(|id| id as NodeId).collect()),
+            block_session: ids("block_session").unwrap_or_default().into_iter().map(|id| id as u64).collect(),
+        };
+        contr.borrow_mut().set_filters(id as NodeId, filters);
+    });
+
+    //set_packet_faults(3, 0.1, 0.05): drone 3 sends 10% of the fragments twice and damages 5%, (3, 0.0, 0.0) stops it.
+    let contr = sim_contr.clone();
+    engine.register_fn("set_packet_faults", move |id: i64, duplicate: f64, corrupt: f64| -> bool {
+        contr.borrow_mut().set_packet_faults(id as NodeId, duplicate as f32, corrupt as f32)
+    });
+
+    //set_reorder(3, 8, 0, 50): drone 3 holds up to 8 fragments for 0 - 50 ms each, set_reorder_exp(3, 8, 10, 100)
+    //for 10 ms on average and never more than 100, clear_reorder(3) sends them in order again.
+    let contr = sim_contr.clone();
+    engine.register_fn("set_reorder", move |id: i64, buffer: i64, min_ms: i64, max_ms: i64| -> bool {
+        let mut config = ReorderConfig::new(id as NodeId, DelayKind::Uniform, max_ms.max(0) as u64);
+        config.buffer = buffer.max(1) as usize;
+        config.min_ms = min_ms.max(0) as u64;
+        contr.borrow_mut().set_reorder(id as NodeId, Some(config.reorder()))
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("set_reorder_exp", move |id: i64, buffer: i64, mean_ms: i64, max_ms: i64| -> bool {
+        let mut config = ReorderConfig::new(id as NodeId, DelayKind::Exponential, max_ms.max(0) as u64);
+        config.buffer = buffer.max(1) as usize;
+        config.mean_ms = Some(mean_ms.max(0) as u64);
+        contr.borrow_mut().set_reorder(id as NodeId, Some(config.reorder()))
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("clear_reorder", move |id: i64| -> bool {
+        contr.borrow_mut().set_reorder(id as NodeId, None)
+    });
+
+    //set_burst_loss(3, 0.05, 0.3, 0.8): drone 3 goes bad with 0.05, good again with 0.3, and loses
+    //80% of the fragments while bad (none while good); clear_burst_loss(3) goes back to its pdr.
+    let contr = sim_contr.clone();
+    engine.register_fn("set_burst_loss", move |id: i64, p_good_to_bad: f64, p_bad_to_good: f64, loss_bad: f64| -> bool {
+        let mut config = BurstLossConfig::new(id as NodeId, p_good_to_bad as f32, p_bad_to_good as f32);
+        config.loss_bad = loss_bad as f32;
+        contr.borrow_mut().set_burst_loss(id as NodeId, Some(config.burst_loss()))
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("clear_burst_loss", move |id: i64| -> bool {
+        contr.borrow_mut().set_burst_loss(id as NodeId, None)
+    });
+
+    //set_telemetry(3, "verbose", 5, []) to watch drone 3 closely, set_telemetry(7, "quiet", 0, ["floods", "acks"])
+    //to hear nothing from 7 (an interval of 0 keeps the one it has), set_telemetry(3, "normal", 0, []) goes back.
+    let contr = sim_contr.clone();
+    engine.register_fn("set_telemetry", move |id: i64, verbosity: &str, interval_ms: i64, mute: Array| -> Result<bool, Box<EvalAltResult>> {
+        let verbosity = LogVerbosity::parse(verbosity).ok_or(format!("{} is not a verbosity (quiet, normal, verbose)", verbosity))?;
+        let mut categories = Vec::new();
+        for category in mute {
+            let category = category.into_string()?;
+            categories.push(EventCategory::parse(&category).ok_or(format!("{} is not an event category", category))?);
+        }
+        let interval_ms = (interval_ms > 0).then_some(interval_ms as u64);
+        Ok(contr.borrow_mut().set_telemetry(TelemetryConfig { node: id as NodeId, verbosity, interval_ms, mute: categories }))
+    });
+
+    //set_processing_delay(3, 20, 5) for everything drone 3 forwards, set_link_delay(3, 4, 40, 0) only
+    //for what it sends to 4. A delay of 0 removes it.
+    let contr = sim_contr.clone();
+    engine.register_fn("set_processing_delay", move |id: i64, delay_ms: i64, jitter_ms: i64| -> bool {
+        contr.borrow_mut().set_processing_delay(id as NodeId, None, delay_ms.max(0) as u64, jitter_ms.max(0) as u64)
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("set_link_delay", move |id: i64, to: i64, delay_ms: i64, jitter_ms: i64| -> bool {
+        contr.borrow_mut().set_processing_delay(id as NodeId, Some(to as NodeId), delay_ms.max(0) as u64, jitter_ms.max(0) as u64)
+    });
+
+    //set_drone_rate(3, 100) for each link of drone 3, set_link_rate(3, 4, 10) only for the one to 4,
+    //with the burst and the queue of a [[link_rate]] table without them. A rate of 0 removes it.
+    let contr = sim_contr.clone();
+    engine.register_fn("set_drone_rate", move |id: i64, packets_per_sec: i64| -> bool {
+        contr.borrow_mut().set_link_rate(LinkRateConfig::new(id as NodeId, None, packets_per_sec.max(0) as u64))
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("set_link_rate", move |id: i64, to: i64, packets_per_sec: i64| -> bool {
+        contr.borrow_mut().set_link_rate(LinkRateConfig::new(id as NodeId, Some(to as NodeId), packets_per_sec.max(0) as u64))
+    });
+
+    //The same faults of the [[fault]] timeline, with the times from the start of the simulation.
+    let contr = sim_contr.clone();
+    engine.register_fn("schedule_crash", move |at_s: f64, drone: i64| {
+        contr.borrow_mut().schedule_fault(&FaultConfig { at_s, fault: Fault::Crash { drone: drone as NodeId } });
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("schedule_pdr", move |at_s: f64, drone: i64, pdr: f64| {
+        contr.borrow_mut().schedule_fault(&FaultConfig { at_s, fault: Fault::SetPdr { drone: drone as NodeId, pdr: pdr as f32 } });
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("schedule_link_down", move |at_s: f64, a: i64, b: i64| {
+        contr.borrow_mut().schedule_fault(&FaultConfig { at_s, fault: Fault::LinkDown { a: a as NodeId, b: b as NodeId } });
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("schedule_link_up", move |at_s: f64, a: i64, b: i64| {
+        contr.borrow_mut().schedule_fault(&FaultConfig { at_s, fault: Fault::LinkUp { a: a as NodeId, b: b as NodeId } });
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("schedule_flap", move |a: i64, b: i64, from_s: f64, until_s: f64, period_ms: i64| {
+        let fault = Fault::Flap { a: a as NodeId, b: b as NodeId, until_s, period_ms: period_ms.max(1) as u64 };
+        contr.borrow_mut().schedule_fault(&FaultConfig { at_s: from_s, fault });
+    });
+
+    //count random members of the group crash at at_s, 0 for all of them.
+    let contr = sim_contr.clone();
+    engine.register_fn("schedule_group_crash", move |at_s: f64, group: &str, count: i64| {
+        let fault = Fault::CrashGroup { group: group.to_string(), count: (count > 0).then_some(count as usize) };
+        contr.borrow_mut().schedule_fault(&FaultConfig { at_s, fault });
+    });
+
+    //The rules of the [[alert]] tables, on every node.
+    let contr = sim_contr.clone();
+    engine.register_fn("alert_drop_rate", move |threshold: f64, window_s: i64| {
+        let rule = AlertRule::DropRate { threshold, window_s: window_s.max(1) as u64, node: None, group: None, min_packets: 10 };
+        contr.borrow_mut().add_alert(AlertConfig { name: None, rule });
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("alert_no_delivery", move |window_s: i64| {
+        let rule = AlertRule::NoDelivery { window_s: window_s.max(1) as u64 };
+        contr.borrow_mut().add_alert(AlertConfig { name: None, rule });
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("alert_queue_length", move |threshold: i64| {
+        let rule = AlertRule::QueueLength { threshold: threshold.max(0) as u64, node: None, group: None };
+        contr.borrow_mut().add_alert(AlertConfig { name: None, rule });
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("alerts", move || -> Array {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        contr.alerts.history.iter().map(|alert| Dynamic::from(alert.describe())).collect()
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("inject_fragment", move |route: Array, session_id: i64| {
+        let hops = route
+            .into_iter()
+            .filter_map(|id| id.as_int().ok())
+            .map(|id| id as NodeId)
+            .collect::<Vec<NodeId>>();
+        //The fragment is delivered to the second node of the route, as if the first one sent it.
+        contr.borrow_mut().inject_packet(Packet {
+            pack_type: PacketType::MsgFragment(Fragment {
+                fragment_index: 0,
+                total_n_fragments: 1,
+                length: 128,
+                data: [0; 128],
+            }),
+            routing_header: SourceRoutingHeader {
+                hop_index: 1,
+                hops,
+            },
+            session_id: session_id as u64,
+        });
+    });
+
+    //A message of many fragments sent with a sliding window (0 for none), returns its session.
+    let contr = sim_contr.clone();
+    engine.register_fn("send_message", move |route: Array, n_fragments: i64, window: i64| -> i64 {
+        let hops = route
+            .into_iter()
+            .filter_map(|id| id.as_int().ok())
+            .map(|id| id as NodeId)
+            .collect::<Vec<NodeId>>();
+        contr.borrow_mut().send_message(hops, n_fragments.max(0) as u64, Some(window.max(0) as usize)) as i64
+    });
+
+    //Same, but the client finds the route (or takes the one it cached), -1 if there's none.
+    let contr = sim_contr.clone();
+    engine.register_fn("send_to", move |client: i64, destination: i64, n_fragments: i64| -> i64 {
+        contr.borrow_mut()
+            .send_to(client as NodeId, destination as NodeId, n_fragments.max(0) as u64, None)
+            .map_or(-1, |session_id| session_id as i64)
+    });
+    //send_multipath(0, 9, 30): same, on the disjoint routes between the two nodes, whatever the [routing] says.
+    let contr = sim_contr.clone();
+    engine.register_fn("send_multipath", move |client: i64, destination: i64, n_fragments: i64| -> i64 {
+        contr.borrow_mut()
+            .send_to_with(client as NodeId, destination as NodeId, n_fragments.max(0) as u64, None, RoutingStrategy::Multipath)
+            .map_or(-1, |session_id| session_id as i64)
+    });
+    //routing_stats() -> #{single_path: #{messages: 5, finished: 5, retransmissions: 3, completion_ms: 40.2, routes: 1.0}, multipath: #{...}}.
+    let contr = sim_contr.clone();
+    engine.register_fn("routing_stats", move || -> Map {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        let mut map = Map::new();
+        for (strategy, stats) in contr.strategy_stats() {
+            let mut entry = Map::new();
+            entry.insert("messages".into(), Dynamic::from(stats.messages as i64));
+            entry.insert("finished".into(), Dynamic::from(stats.finished as i64));
+            entry.insert("fragments".into(), Dynamic::from(stats.fragments as i64));
+            entry.insert("delivered".into(), Dynamic::from(stats.delivered_fragments as i64));
+            entry.insert("retransmissions".into(), Dynamic::from(stats.retransmissions as i64));
+            entry.insert("completion_ms".into(), Dynamic::from(stats.mean_completion_ms.unwrap_or(0.0)));
+            entry.insert("routes".into(), Dynamic::from(stats.mean_routes));
+            let name = match strategy {
+                RoutingStrategy::SinglePath => "single_path",
+                RoutingStrategy::Multipath => "multipath",
+            };
+            map.insert(name.into(), Dynamic::from(entry));
+        }
+        map
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("route_cache", move || -> Map {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        let mut map = Map::new();
+        map.insert("hits".into(), Dynamic::from(contr.route_cache.hits as i64));
+        map.insert("misses".into(), Dynamic::from(contr.route_cache.misses as i64));
+        map.insert("invalidations".into(), Dynamic::from(contr.route_cache.invalidations as i64));
+        map.insert("hit_rate".into(), Dynamic::from(contr.route_cache.hit_rate()));
+        map
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("message", move |session_id: i64| -> Map {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        let mut map = Map::new();
+        if let Some(transfer) = contr.transfers.iter().find(|transfer| transfer.session_id == session_id as u64) {
+            map.insert("delivered".into(), Dynamic::from(transfer.delivered() as i64));
+            map.insert("window".into(), Dynamic::from(transfer.window));
+            map.insert("retransmissions".into(), Dynamic::from(transfer.retransmissions as i64));
+            map.insert("duplicate_acks".into(), Dynamic::from(transfer.duplicate_acks as i64));
+            map.insert("corrupted".into(), Dynamic::from(transfer.corrupted as i64));
+            map.insert("done".into(), Dynamic::from(transfer.finished.is_some()));
+            map.insert("cancelled".into(), Dynamic::from(transfer.cancelled));
+            map.insert("ms".into(), Dynamic::from(transfer.finished.map_or(-1, |time| time.as_millis() as i64)));
+        }
+        map
+    });
+
+    //cancel(session): the client gives up on the message, false if it's not on its way.
+    let contr = sim_contr.clone();
+    engine.register_fn("cancel", move |session_id: i64| -> bool {
+        contr.borrow_mut().cancel_transfer(session_id as u64)
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("drones", move || -> Array {
+        contr.borrow()
+            .drone_ids()
+            .into_iter()
+            .map(|id| Dynamic::from(id as i64))
+            .collect()
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("clients", move || -> Array {
+        contr.borrow()
+            .client_ids()
+            .into_iter()
+            .map(|id| Dynamic::from(id as i64))
+            .collect()
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("stats", move |id: i64| -> Map {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        let stats = contr.stats.get(&(id as NodeId)).cloned().unwrap_or_default();
+        let mut map = Map::new();
+        map.insert("sent".into(), Dynamic::from(stats.packets_sent as i64));
+        map.insert("dropped".into(), Dynamic::from(stats.packets_dropped as i64));
+        map.insert("shortcuts".into(), Dynamic::from(stats.shortcuts as i64));
+        map.insert("fragments_forwarded".into(), Dynamic::from(stats.fragments_forwarded as i64));
+        map.insert("fragments_dropped".into(), Dynamic::from(stats.fragments_dropped as i64));
+        map.insert("nacks_generated".into(), Dynamic::from(stats.nacks_generated as i64));
+        map.insert("floods_handled".into(), Dynamic::from(stats.floods_handled as i64));
+        map.insert("congestion_drops".into(), Dynamic::from(stats.congestion_drops as i64));
+        map.insert("rate_limited".into(), Dynamic::from(stats.rate_limited as i64));
+        map.insert("fragments_duplicated".into(), Dynamic::from(stats.fragments_duplicated as i64));
+        map.insert("fragments_reordered".into(), Dynamic::from(stats.fragments_reordered as i64));
+        map.insert("duplicates_suppressed".into(), Dynamic::from(stats.duplicates_suppressed as i64));
+        map.insert("fragments_corrupted".into(), Dynamic::from(stats.fragments_corrupted as i64));
+        map.insert("mean_hop_latency_ms".into(), Dynamic::from(stats.mean_hop_latency_ms));
+        map.insert("crashed".into(), Dynamic::from(contr.crashed.contains(&(id as NodeId))));
+        let stalled = contr.health.as_ref().and_then(|health| health.nodes.get(&(id as NodeId))).map_or(false, |health| health.stalled);
+        map.insert("stalled".into(), Dynamic::from(stalled));
+        //-1 for the drones without a battery.
+        map.insert("energy_left".into(), Dynamic::from(stats.energy_left.map_or(-1, |left| left as i64)));
+        map
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("export_stats", move |file: &str| {
+        contr.borrow_mut().export_stats(file);
+    });
+
+    //The commands sent to a node, the oldest first: commands(3) -> ["1.204s Chaos: Crash", ...].
+    let contr = sim_contr.clone();
+    engine.register_fn("commands", move |id: i64| -> Array {
+        let contr = contr.borrow();
+        contr.audit.query(id as NodeId, None).into_iter().map(|entry| Dynamic::from(entry.describe())).collect()
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("export_audit", move |file: &str| {
+        contr.borrow_mut().export_audit(file);
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("export_sessions", move |file: &str| {
+        contr.borrow_mut().export_sessions(file);
+    });
+
+    //mark("crashing the busiest") puts a step of the scenario on the timeline.
+    let contr = sim_contr.clone();
+    engine.register_fn("mark", move |label: &str| {
+        let mut contr = contr.borrow_mut();
+        contr.log.push(format!("scenario: {}", label));
+        contr.timeline.add(TimelineKind::Step, None, label.to_string());
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("export_timeline", move |file: &str| {
+        contr.borrow_mut().export_timeline(file);
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("traceroute", move |from: i64, to: i64| -> Array {
+        let mut contr = contr.borrow_mut();
+        if !contr.start_traceroute(from as NodeId, to as NodeId) {
+            return Array::new();
+        }
+        contr.wait_traceroute().into_iter().map(Dynamic::from).collect()
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("ping", move |id: i64| -> Map {
+        let report = contr.borrow_mut().ping(id as NodeId);
+        let ms = |rtt: Option<Duration>| rtt.map_or(Dynamic::UNIT, |rtt| Dynamic::from(rtt.as_secs_f64() * 1000.0));
+        let mut map = Map::new();
+        map.insert("command_ms".into(), ms(report.command_rtt));
+        map.insert("packet_ms".into(), ms(report.packet_rtt));
+        map.insert("dead_end".into(), report.dead_end.map_or(Dynamic::UNIT, |node| Dynamic::from(node as i64)));
+        map
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("start_flood", move |client: i64| {
+        contr.borrow_mut().start_flood(client as NodeId, None);
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("start_flood", move |client: i64, flood_id: i64| {
+        contr.borrow_mut().start_flood(client as NodeId, Some(flood_id as u64));
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("flood_converged", move |client: i64| -> bool {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        contr.flood_converged(client as NodeId)
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("discovery", move || -> Array {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        contr.discovery_report().into_iter().map(Dynamic::from).collect()
+    });
+
+    //The changes since a snapshot saved earlier (by the script or not), one line for every kind.
+    let contr = sim_contr.clone();
+    engine.register_fn("diff_snapshot", move |file: &str| -> Array {
+        let diff = contr.borrow_mut().diff_with_snapshot(file).unwrap_or_default();
+        diff.describe().into_iter().map(Dynamic::from).collect()
+    });
+
+    //The messages that reached a server, one line each.
+    let contr = sim_contr.clone();
+    engine.register_fn("received", move |server: i64| -> Array {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        let messages = contr.inbox.messages.get(&(server as NodeId)).cloned().unwrap_or_default();
+        messages.iter().map(|message| Dynamic::from(message.describe())).collect()
+    });
+
+    //A profile on its own group, or on "all", "core", "edge", a defined group or a list of ids.
+    let contr = sim_contr.clone();
+    engine.register_fn("apply_profile", move |name: &str| -> bool {
+        contr.borrow_mut().apply_profile(name, None)
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("apply_profile", move |name: &str, group: &str| -> Result<bool, Box<EvalAltResult>> {
+        let group = NodeGroup::parse(group).ok_or(format!("{} is not a group", group))?;
+        Ok(contr.borrow_mut().apply_profile(name, Some(group)))
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("apply_profile", move |name: &str, ids: Array| -> bool {
+        let ids = ids.into_iter().filter_map(|id| id.as_int().ok()).map(|id| id as NodeId).collect();
+        contr.borrow_mut().apply_profile(name, Some(NodeGroup::Nodes(ids)))
+    });
+
+    //define_group("backbone", [1, 2, 3]), then "backbone" works wherever a group goes.
+    let contr = sim_contr.clone();
+    engine.register_fn("define_group", move |name: &str, ids: Array| {
+        let nodes = ids.into_iter().filter_map(|id| id.as_int().ok()).map(|id| id as NodeId).collect();
+        contr.borrow_mut().define_group(GroupDefinition { name: name.to_string(), nodes, color: None });
+    });
+    //tag(3, "backbone"), then "tag:backbone" works wherever a group goes.
+    let contr = sim_contr.clone();
+    engine.register_fn("tag", move |id: i64, tag: &str| -> bool {
+        contr.borrow_mut().tag_node(id as NodeId, tag)
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("untag", move |id: i64, tag: &str| -> bool {
+        contr.borrow_mut().untag_node(id as NodeId, tag)
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("tag_link", move |a: i64, b: i64, tag: &str| -> bool {
+        contr.borrow_mut().tag_link(a as NodeId, b as NodeId, tag)
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("untag_link", move |a: i64, b: i64, tag: &str| -> bool {
+        contr.borrow_mut().untag_link(a as NodeId, b as NodeId, tag)
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("note", move |id: i64, note: &str| {
+        contr.borrow_mut().set_note(id as NodeId, None, note);
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("note_link", move |a: i64, b: i64, note: &str| {
+        contr.borrow_mut().set_note(a as NodeId, Some(b as NodeId), note);
+    });
+    //The drones of the group that are still up.
+    let contr = sim_contr.clone();
+    engine.register_fn("group", move |name: &str| -> Array {
+        let members = contr.borrow().group_members(&NodeGroup::Defined(name.to_string()));
+        members.into_iter().map(|id| Dynamic::from(id as i64)).collect()
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("crash_group", move |name: &str| {
+        contr.borrow_mut().crash_group(name);
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("reboot_group", move |name: &str| {
+        contr.borrow_mut().reboot_group(name);
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("set_group_pdr", move |name: &str, pdr: f64| {
+        contr.borrow_mut().set_group_pdr(name, pdr as f32);
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("save_groups", move |file: &str| {
+        contr.borrow_mut().save_groups(file);
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("load_groups", move |file: &str| -> bool {
+        contr.borrow_mut().load_groups(file)
+    });
+
+    //The packets that couldn't be delivered, all of them or only the ones of a session.
+    let contr = sim_contr.clone();
+    engine.register_fn("dead_letters", move || -> Array {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        contr.dead_letters.letters.iter().map(|letter| Dynamic::from(letter.describe())).collect()
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("dead_letters", move |session_id: i64| -> Array {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        contr.dead_letters.of_session(session_id as u64).map(|letter| Dynamic::from(letter.describe())).collect()
+    });
+
+    //traffic(0, 9) -> #{attempted: 10, delivered: 9, failed: 1, latency_ms: 12.5}, empty if nothing was sent.
+    let contr = sim_contr.clone();
+    engine.register_fn("traffic", move |source: i64, destination: i64| -> Map {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        let mut map = Map::new();
+        if let Some(pair) = contr.traffic_matrix().get(source as NodeId, destination as NodeId) {
+            map.insert("attempted".into(), Dynamic::from(pair.attempted as i64));
+            map.insert("delivered".into(), Dynamic::from(pair.delivered as i64));
+            map.insert("failed".into(), Dynamic::from(pair.failed as i64));
+            map.insert("latency_ms".into(), Dynamic::from(pair.mean_latency_ms.unwrap_or(0.0)));
+        }
+        map
+    });
+
+    //sla_probe(0, 9, 500, 40.0): a probe from client 0 to server 9 every 500 ms, late over 40 ms
+    //(0.0 for no target). sla(0, 9) -> #{sent: 20, acked: 18, failed: 2, late: 1, ratio: 0.85, rtt_ms: 12.5}.
+    let contr = sim_contr.clone();
+    engine.register_fn("sla_probe", move |client: i64, server: i64, period_ms: i64, target_rtt_ms: f64| -> bool {
+        let config = SlaProbeConfig {
+            client: client as NodeId,
+            server: server as NodeId,
+            period_ms: period_ms.max(1) as u64,
+            target_rtt_ms: Some(target_rtt_ms).filter(|target| *target > 0.0),
+        };
+        contr.borrow_mut().add_sla_probe(config)
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("sla", move |client: i64, server: i64| -> Map {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        let mut map = Map::new();
+        let pair = contr.sla.pairs.iter().find(|pair| pair.config.client == client as NodeId && pair.config.server == server as NodeId);
+        if let Some(pair) = pair {
+            map.insert("sent".into(), Dynamic::from(pair.sent as i64));
+            map.insert("acked".into(), Dynamic::from(pair.acked as i64));
+            map.insert("failed".into(), Dynamic::from(pair.failed as i64));
+            map.insert("late".into(), Dynamic::from(pair.late as i64));
+            map.insert("ratio".into(), Dynamic::from(pair.success_ratio()));
+            map.insert("rtt_ms".into(), Dynamic::from(pair.mean_rtt().map_or(0.0, |rtt| rtt.as_secs_f64() * 1000.0)));
+        }
+        map
+    });
+
+    //The route lengths of the messages delivered from a node to another one.
+    let contr = sim_contr.clone();
+    engine.register_fn("hop_counts", move |source: i64, destination: i64| -> Map {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        let mut map = Map::new();
+        if let Some(stats) = contr.hop_counts.pairs.get(&(source as NodeId, destination as NodeId)) {
+            map.insert("messages".into(), Dynamic::from(stats.messages() as i64));
+            map.insert("mean".into(), Dynamic::from(stats.mean()));
+            map.insert("extra".into(), Dynamic::from(stats.mean_extra_hops().unwrap_or(0.0)));
+            let mut distribution = Map::new();
+            for (hops, n) in stats.distribution.iter() {
+                distribution.insert(hops.to_string().into(), Dynamic::from(*n as i64));
+            }
+            map.insert("distribution".into(), Dynamic::from(distribution));
+        }
+        map
+    });
+
+    //ack_sink(9, 20, 0.1): server 9 acks every fragment after 20 ms, losing one ack in ten.
+    let contr = sim_contr.clone();
+    engine.register_fn("ack_sink", move |node: i64, delay_ms: i64, loss: f64| -> bool {
+        contr.borrow_mut().add_ack_sink(AckSinkConfig { node: node as NodeId, delay_ms: delay_ms.max(0) as u64, loss: loss as f32 })
+    });
+
+    //capture(3, "drone_3.json") ... end_capture(3): the traffic of drone 3 in between, to replay it alone.
+    let contr = sim_contr.clone();
+    engine.register_fn("capture", move |id: i64, file: &str| -> bool {
+        contr.borrow_mut().start_capture(id as NodeId, file)
+    });
+    let contr = sim_contr.clone();
+    engine.register_fn("end_capture", move |id: i64| -> bool {
+        let mut contr = contr.borrow_mut();
+        contr.process_events();
+        contr.stop_capture(id as NodeId)
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("export_report", move |file: &str| {
+        contr.borrow_mut().export_report(file);
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("export_traces", move |endpoint: &str| {
+        contr.borrow_mut().export_traces(endpoint);
+    });
+
+    let contr = sim_contr.clone();
+    engine.register_fn("sleep", move |ms: i64| -> Result<(), Box<EvalAltResult>> {
+        //While the script waits, the Sim Contr keeps collecting events, otherwise the stats
+        //read after the sleep would be stale.
+        let deadline = Instant::now() + Duration::from_millis(ms.max(0) as u64);
+        while Instant::now() < deadline {
+            contr.borrow_mut().process_events();
+            if contr.borrow().shutdown_requested {
+                return Err("simulation shut down".into());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    });
+
+    //Random numbers from the seeded generator of this thread, so a script can take random
+    //decisions and still be repeated with the same seed.
+    engine.register_fn("random", || fastrand::f64());
+    engine.register_fn("random_int", |min: i64, max: i64| fastrand::i64(min..=max.max(min)));
+
+    engine
+}
+
+//With a seed (e.g. from a batch run) the script finds it in the SEED constant.
+pub fn run_scenario(file: &str, sim_contr: Rc<RefCell<SimulationControl>>, seed: Option<u64>) -> Result<(), Box<EvalAltResult>> {
+    if let Some(seed) = seed {
+        fastrand::seed(seed);
+    }
+    let engine = build_engine(sim_contr.clone());
+    sim_contr.borrow_mut().set_command_origin(CommandOrigin::Scenario);
+    let mut scope = Scope::new();
+    scope.push_constant("SEED", seed.unwrap_or(0) as i64);
+    let result = engine.run_file_with_scope(&mut scope, PathBuf::from(file));
+    sim_contr.borrow_mut().process_events();
+    result
+}
+
+//A few lines of script run on the spot, e.g. by a step of the walkthrough of the GUI: the
+//commands count as the scenario's, then the origin goes back to whoever had it.
+pub fn run_snippet(code: &str, sim_contr: Rc<RefCell<SimulationControl>>) -> Result<(), Box<EvalAltResult>> {
+    let engine = build_engine(sim_contr.clone());
+    let origin = sim_contr.borrow_mut().audit.set_origin(CommandOrigin::Scenario);
+    let result = engine.run(code);
+    sim_contr.borrow_mut().audit.set_origin(origin);
+    result
+}
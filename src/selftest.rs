@@ -0,0 +1,95 @@
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::network::NodeId;
+use crate::initializer::initialize_edited;
+use crate::sim_control::SimulationControl;
+
+//Client 0 and server 9 with two disjoint routes between them: 1-2-3, the shortest, and the
+//detour 1-4-5-3 for when drone 2 is down.
+fn selftest_config() -> Config {
+    let drone = |id: NodeId, connected_node_ids: Vec<NodeId>| Drone { id, connected_node_ids, pdr: 0.0 };
+    Config {
+        drone: vec![
+            drone(1, vec![0, 2, 4]),
+            drone(2, vec![1, 3]),
+            drone(3, vec![2, 5, 9]),
+            drone(4, vec![1, 5]),
+            drone(5, vec![4, 3]),
+        ],
+        client: vec![Client { id: 0, connected_drone_ids: vec![1] }],
+        server: vec![Server { id: 9, connected_drone_ids: vec![3] }],
+    }
+}
+
+//Runs the events until done says so, false if it didn't within timeout.
+fn wait_until(sim_contr: &mut SimulationControl, timeout: Duration, done: impl Fn(&SimulationControl) -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        sim_contr.process_events();
+        if done(sim_contr) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+//Whether the message got to the server in full, within timeout.
+fn delivered(sim_contr: &mut SimulationControl, session_id: Option<u64>, timeout: Duration) -> bool {
+    let Some(session_id) = session_id else {
+        return false;
+    };
+    wait_until(sim_contr, timeout, |sim_contr| {
+        sim_contr.transfers.iter().any(|transfer| transfer.session_id == session_id && transfer.finished.is_some())
+    })
+}
+
+fn check(name: &str, passed: bool, detail: String) -> bool {
+    println!("{} {}: {}", if passed { "PASS" } else { "FAIL" }, name, detail);
+    passed
+}
+
+//The quick health check of '--selftest': a flood, a message through a lossy drone, and a crash
+//with its reboot, on a built-in network. Returns whether everything passed, with the threads of
+//the drones (already shut down) to be joined.
+pub fn run_selftest() -> (bool, Vec<JoinHandle<()>>) {
+    let (mut sim_contr, handles) = initialize_edited(selftest_config(), None);
+    let mut results = Vec::new();
+
+    //Every drone has to answer the flood of the client.
+    sim_contr.start_flood(0, None);
+    let converged = wait_until(&mut sim_contr, Duration::from_secs(3), |sim_contr| {
+        sim_contr.discovery.flood_progress(0).map_or(false, |progress| progress.converged.is_some())
+    });
+    let progress = sim_contr.discovery.flood_progress(0).unwrap_or_default();
+    results.push(check("flood", converged, format!("{}/{} drones found", progress.known, progress.reachable)));
+
+    //Drone 2 drops a fragment out of three: they're all delivered in the end, after some retransmissions.
+    sim_contr.set_pdr(2, 0.3);
+    let session_id = sim_contr.send_to(0, 9, 20, None);
+    let passed = delivered(&mut sim_contr, session_id, Duration::from_secs(15));
+    let retransmissions = sim_contr.transfers.last().map_or(0, |transfer| transfer.retransmissions);
+    results.push(check(
+        "transfer with drops",
+        passed && retransmissions > 0,
+        format!("{} fragments retransmitted", retransmissions),
+    ));
+    sim_contr.set_pdr(2, 0.0);
+
+    //With drone 2 down the message takes the detour, once it's back the shortest route works again.
+    sim_contr.crash_drone(2);
+    let session_id = sim_contr.send_to(0, 9, 10, None);
+    let passed = delivered(&mut sim_contr, session_id, Duration::from_secs(10));
+    results.push(check("crash", passed, "message delivered around drone 2".to_string()));
+    let rebooted = sim_contr.reboot_drone(2) && wait_until(&mut sim_contr, Duration::from_secs(3), |sim_contr| !sim_contr.rebooting(2));
+    let session_id = Some(sim_contr.send_message(vec![0, 1, 2, 3, 9], 10, None));
+    let passed = rebooted && delivered(&mut sim_contr, session_id, Duration::from_secs(10));
+    results.push(check("recovery", passed, "message delivered through drone 2 again".to_string()));
+
+    sim_contr.shutdown();
+    let failed = results.iter().filter(|passed| !**passed).count();
+    println!("{}: {} checks, {} failed", if failed == 0 { "PASS" } else { "FAIL" }, results.len(), failed);
+    (failed == 0, handles)
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, SystemTime};
+use wg_2024::network::NodeId;
+use wg_2024::packet::{NackType, Packet, PacketType};
+
+//A single hop of a packet, as seen by the Sim Contr when the PacketSent event arrived.
+#[derive(Debug, Clone)]
+pub struct HopRecord {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub packet_type: String,
+    pub time: SystemTime,
+    pub latency: Option<Duration>, //Since the previous hop of the session (or its origination).
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionRecord {
+    pub session_id: u64,
+    pub hops: Vec<HopRecord>,
+    pub originated: Option<SystemTime>, //When the Sim Contr injected the first fragment, if it did.
+    pub source: Option<NodeId>, //The first node in the route of the fragments.
+    pub destination: Option<NodeId>, //The last node in the route of the fragments.
+    pub delivered: Option<SystemTime>, //When a fragment first reached the destination.
+    pub dropped: bool, //If some fragment was answered with a Dropped nack.
+    pub window: Option<f64>, //For the messages sent with a sliding window, the last size of the window.
+    pub retransmissions: u64,
+    pub duplicate_acks: u64, //Acks the client got more than once for the same fragment.
+}
+
+impl SessionRecord {
+    fn new(session_id: u64) -> Self {
+        SessionRecord {
+            session_id,
+            hops: Vec::new(),
+            originated: None,
+            source: None,
+            destination: None,
+            delivered: None,
+            dropped: false,
+            window: None,
+            retransmissions: 0,
+            duplicate_acks: 0,
+        }
+    }
+
+    //From the origination (or the first hop seen, for packets not sent by the Sim Contr)
+    //to the arrival at the destination.
+    pub fn end_to_end_latency(&self) -> Option<Duration> {
+        let start = self.originated.or(self.hops.first().map(|hop| hop.time))?;
+        self.delivered?.duration_since(start).ok()
+    }
+}
+
+//Every routed packet (fragments, acks and nacks) seen by the Sim Contr, grouped by session.
+//Floods are left out: their session id doesn't identify a message.
+#[derive(Debug, Default)]
+pub struct SessionTable {
+    pub(crate) sessions: HashMap<u64, SessionRecord>,
+}
+
+impl SessionTable {
+    //The packets can't carry a timestamp, so the moment a session starts is kept here.
+    pub fn originate(&mut self, session_id: u64, time: SystemTime) {
+        self.sessions
+            .entry(session_id)
+            .or_insert(SessionRecord::new(session_id))
+            .originated
+            .get_or_insert(time);
+    }
+
+    //The state of the sliding window of a message, see flow.rs.
+    pub fn set_flow(&mut self, session_id: u64, window: f64, retransmissions: u64, duplicate_acks: u64) {
+        let session = self.sessions
+            .entry(session_id)
+            .or_insert(SessionRecord::new(session_id));
+        session.window = (window > 0.0).then_some(window);
+        session.retransmissions = retransmissions;
+        session.duplicate_acks = duplicate_acks;
+    }
+
+    pub fn record(&mut self, packet: &Packet) -> Option<(NodeId, Duration)> {
+        self.record_at(packet, SystemTime::now())
+    }
+
+    //For events that come in a batch, which carry the time the drone sent the packet.
+    //Returns the node that sent the packet with the latency of the hop, when it's known.
+    pub fn record_at(&mut self, packet: &Packet, time: SystemTime) -> Option<(NodeId, Duration)> {
+        let packet_type = match &packet.pack_type {
+            PacketType::MsgFragment(fragment) => format!("fragment {}", fragment.fragment_index),
+            PacketType::Ack(ack) => format!("ack {}", ack.fragment_index),
+            PacketType::Nack(nack) => format!("nack {} {:?}", nack.fragment_index, nack.nack_type),
+            PacketType::FloodRequest(_) | PacketType::FloodResponse(_) => return None,
+        };
+        let hop_index = packet.routing_header.hop_index;
+        let hops = &packet.routing_header.hops;
+        if hop_index == 0 || hop_index >= hops.len() {
+            return None;
+        }
+
+        let session = self.sessions
+            .entry(packet.session_id)
+            .or_insert(SessionRecord::new(packet.session_id));
+        let previous = session.hops.last().map(|hop| hop.time).or(session.originated);
+        let latency = previous.and_then(|previous| time.duration_since(previous).ok());
+        let from = hops[hop_index - 1];
+        session.hops.push(HopRecord {
+            from,
+            to: hops[hop_index],
+            packet_type,
+            time,
+            latency,
+        });
+        match &packet.pack_type {
+            PacketType::MsgFragment(_) => {
+                session.source = hops.first().copied();
+                session.destination = hops.last().copied();
+                if session.delivered.is_none() && hop_index == hops.len() - 1 {
+                    session.delivered = Some(time);
+                }
+            },
+            PacketType::Nack(nack) => {
+                if let NackType::Dropped = nack.nack_type {
+                    session.dropped = true;
+                }
+            },
+            _ => {},
+        }
+        latency.map(|latency| (from, latency))
+    }
+
+    //One row per session, with the latency of every hop in the last column (from>to:ms).
+    pub fn export_csv(&self, file: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(file)?);
+        writeln!(writer, "session_id,destination,hops,delivered,dropped,end_to_end_ms,window,retransmissions,duplicate_acks,hop_latencies_ms")?;
+        let mut sessions = self.sessions.values().collect::<Vec<&SessionRecord>>();
+        sessions.sort_by_key(|session| session.session_id);
+        for session in sessions {
+            let hop_latencies = session.hops
+                .iter()
+                .map(|hop| match hop.latency {
+                    Some(latency) => format!("{}>{}:{:.3}", hop.from, hop.to, latency.as_secs_f64() * 1000.0),
+                    None => format!("{}>{}:", hop.from, hop.to),
+                })
+                .collect::<Vec<String>>();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{}",
+                session.session_id,
+                session.destination.map(|id| id.to_string()).unwrap_or_default(),
+                session.hops.len(),
+                session.delivered.is_some(),
+                session.dropped,
+                session.end_to_end_latency().map(|latency| format!("{:.3}", latency.as_secs_f64() * 1000.0)).unwrap_or_default(),
+                session.window.map(|window| format!("{:.1}", window)).unwrap_or_default(),
+                session.retransmissions,
+                session.duplicate_acks,
+                hop_latencies.join(";")
+            )?;
+        }
+        writer.flush()
+    }
+}
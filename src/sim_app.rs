@@ -1,12 +1,44 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
 use std::rc::Rc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use eframe::egui::{self, Color32, Context, TextureHandle, Vec2};
 use eframe::{App, Frame, NativeOptions};
+use wg_2024::config::Config;
+use wg_2024::network::NodeId;
+use wg_2024::packet::NodeType;
 use crate::sim_control::SimulationControl;
+use crate::initializer::initialize_edited;
+use crate::regions::RegionShape;
+use crate::snapshot::SnapshotDiff;
+use crate::profiles::NodeGroup;
+use crate::review::Mutation;
+use crate::timeline::TimelineKind;
+use crate::sla::SlaProbeConfig;
+use crate::groups::GroupDefinition;
+use crate::audit::CommandOrigin;
+use crate::routing::RoutingStrategy;
+use crate::crafting::{CraftedNack, CraftedType, PacketDraft};
+use crate::walkthrough::{walkthrough_config, Highlight, Walkthrough, WALKTHROUGH};
+use crate::scenario;
+use crate::telemetry::{LogVerbosity, TelemetryConfig};
+
+//Only this many links are highlighted in a frame, so a flood storm doesn't turn into a paint storm.
+const MAX_HIGHLIGHTED_LINKS: usize = 50;
+//How long an alert stays on the screen.
+const TOAST_TIME: Duration = Duration::from_secs(5);
+//An SLA pair is drawn red below this share of probes acked in time.
+const SLA_WARNING_RATIO: f64 = 0.95;
+//A frame of events slower than this, or no frame at all for this long, and the Sim Contr is
+//considered stalled: the window stops waiting for it, so it doesn't freeze with it.
+const STALL_THRESHOLD: Duration = Duration::from_secs(2);
 
 struct Drone {
     id: String,
+    node_id: Option<NodeId>, //None for the drones added from the GUI.
     position: Vec2,
     is_crashed: bool,
     pdr: f32,
@@ -26,10 +58,37 @@ pub struct SimulationApp {
     connection_selections: Vec<bool>,
     log_panel_width: f32,        // Width of the log panel
     control_panel_width: f32,   // Width of the control panel
+    radio_range_mode: bool,     // Links follow the distance between the drones
+    radio_range: f32,           // Max distance (in pixels) of a link in radio range mode
+    trace_from: NodeId,         // Ends of the route probed by the traceroute button
+    trace_to: NodeId,
+    diff_overlay: Option<SnapshotDiff>, // Changes since snapshot.toml, drawn over the network
+    profile: String,            // The profile chosen in the combo box, and the group it goes to
+    profile_group: String,
+    review_mode: bool,          // The changes of the topology wait in proposed until they're confirmed
+    proposed: Vec<(Mutation, Option<usize>)>, // With the index of the GUI drone a Spawn is for
+    timeline_span_s: f32,       // Seconds that fit in the timeline, the zoom
+    timeline_back_s: f32,       // How far in the past the timeline ends, 0 follows the run
+    timeline_selected: Option<usize>, // The entry clicked on the timeline
+    group_name: String,         // The group being defined, and its ids ("1,2,3")
+    group_nodes: String,
+    group_pdr: f32,             // Given to every member by the Set pdr buttons
+    show_groups: bool,          // The members of every group are circled with its color
+    audit_origin: Option<CommandOrigin>, // Only the commands from here in the inspector, None for all
+    tag_input: String,          // The tag (or note) written in the inspector for the selected node
+    send_multipath: bool, // "Send message" on the disjoint routes instead of the cached one
+    last_refresh: Instant,      // When the Sim Contr last ran its events for the window
+    stalled: Option<String>,    // Why the Sim Contr is considered stalled, the window draws what it had meanwhile
+    restart_requested: bool,    // Done by the Workspace, which keeps the threads of the new drones
+    craft: PacketDraft,         // The packet of the crafting panel
+    crafting_route: bool,       // The clicked drones are added to the route of the crafted packet
+    walkthrough: Option<Walkthrough>, // Only in the tab opened with the Walkthrough button
+    panel_rects: HashMap<&'static str, egui::Rect>, // Where the panels were drawn last frame, for the highlight
 }
 
 impl SimulationApp {
     fn new(sim_contr: Rc<RefCell<SimulationControl>>) -> Self {
+        sim_contr.borrow_mut().set_command_origin(CommandOrigin::Gui);
         let network_graph = sim_contr.borrow().network_graph.clone();
 
         let mut drones = Vec::new();
@@ -39,6 +98,7 @@ impl SimulationApp {
             let index = drones.len();
             drones.push(Drone {
                 id: format!("drone{}", node_id),
+                node_id: Some(*node_id),
                 position: Vec2::new(100.0 + (index as f32) * 100.0, 100.0),
                 is_crashed: false,
                 pdr: 0.0,
@@ -71,6 +131,32 @@ impl SimulationApp {
             sim_contr,
             log_panel_width: 200.0,    // Default guess for the left panel width
             control_panel_width: 200.0, // Default guess for the right panel width
+            radio_range_mode: false,
+            radio_range: 150.0,
+            trace_from: 0,
+            trace_to: 0,
+            diff_overlay: None,
+            profile: "perfect".to_string(),
+            profile_group: String::new(),
+            review_mode: false,
+            proposed: Vec::new(),
+            timeline_span_s: 60.0,
+            timeline_back_s: 0.0,
+            timeline_selected: None,
+            tag_input: String::new(),
+            group_name: String::new(),
+            group_nodes: String::new(),
+            group_pdr: 0.0,
+            show_groups: true,
+            audit_origin: None,
+            send_multipath: false,
+            last_refresh: Instant::now(),
+            stalled: None,
+            restart_requested: false,
+            craft: PacketDraft::default(),
+            crafting_route: false,
+            walkthrough: None,
+            panel_rects: HashMap::new(),
         }
     }
 
@@ -117,6 +203,9 @@ impl SimulationApp {
             if response.clicked() {
                 self.selected_drone = Some(i);
                 self.log.push(format!("{} selected", drone.id));
+                if let (true, Some(node_id)) = (self.crafting_route, drone.node_id) {
+                    self.craft.route.push(node_id);
+                }
             }
 
             if response.dragged() {
@@ -163,24 +252,171 @@ impl SimulationApp {
         }
     }
 
+    //The interference regions, drawn under the links.
+    fn render_regions(&self, ui: &mut egui::Ui) {
+        let fill = Color32::from_rgba_unmultiplied(200, 80, 80, 40);
+        let stroke = (1.0, Color32::from_rgb(200, 80, 80));
+        for region in self.sim_contr.borrow().regions.iter() {
+            match region.shape {
+                RegionShape::Circle { x, y, radius } => {
+                    ui.painter().circle(egui::Pos2::new(x, y), radius, fill, stroke);
+                },
+                RegionShape::Rect { x, y, width, height } => {
+                    let rect = egui::Rect::from_min_size(egui::Pos2::new(x, y), Vec2::new(width, height));
+                    ui.painter().rect(rect, 0.0, fill, stroke);
+                },
+            }
+        }
+    }
+
+    fn define_group(&mut self, nodes: Vec<NodeId>) {
+        let name = self.group_name.trim().to_string();
+        if name.is_empty() || NodeGroup::parse(&name) != Some(NodeGroup::Defined(name.clone())) {
+            self.log.push(format!("{} can't be the name of a group", name));
+            return;
+        }
+        //Redefining a group keeps its color.
+        let color = self.sim_contr.borrow().groups.iter().find(|group| group.name == name).and_then(|group| group.color);
+        self.sim_contr.borrow_mut().define_group(GroupDefinition { name, nodes, color });
+    }
+
+    //Every member of a group gets a ring of the color of the group, a node in two groups gets two.
+    fn render_groups(&self, ui: &mut egui::Ui) {
+        if !self.show_groups {
+            return;
+        }
+        for (index, group) in self.sim_contr.borrow().groups.iter().enumerate() {
+            let color = group_color(group, index);
+            let radius = 32.0 + 4.0 * index as f32;
+            let centers = self.drones
+                .iter()
+                .filter(|drone| drone.node_id.map_or(false, |id| group.nodes.contains(&id)))
+                .map(|drone| egui::Pos2::new(drone.position.x + 25.0, drone.position.y + 25.0))
+                .collect::<Vec<egui::Pos2>>();
+            for center in centers.iter() {
+                ui.painter().circle_stroke(*center, radius, (2.0, color));
+            }
+            //The name goes over the topmost member.
+            if let Some(top) = centers.iter().min_by(|a, b| a.y.total_cmp(&b.y)) {
+                let position = egui::Pos2::new(top.x, top.y - radius - 2.0);
+                ui.painter().text(position, egui::Align2::CENTER_BOTTOM, &group.name, egui::FontId::proportional(12.0), color);
+            }
+        }
+    }
+
+    //The regions work on the positions of the nodes, so the Sim Contr gets them at every frame.
+    fn publish_positions(&self) {
+        let positions = self.drones
+            .iter()
+            .filter_map(|drone| drone.node_id.map(|id| (id, (drone.position.x + 25.0, drone.position.y + 25.0))))
+            .collect::<HashMap<NodeId, (f32, f32)>>();
+        self.sim_contr.borrow_mut().set_positions(positions);
+    }
+
+    //The route of the crafted packet, over the links, with the node it's delivered to circled.
+    fn render_crafted_route(&self, ui: &mut egui::Ui) {
+        let center = |id: NodeId| {
+            self.drones
+                .iter()
+                .find(|drone| drone.node_id == Some(id))
+                .map(|drone| egui::Pos2::new(drone.position.x + 25.0, drone.position.y + 25.0))
+        };
+        let points = self.craft.route.iter().filter_map(|id| center(*id)).collect::<Vec<egui::Pos2>>();
+        if points.len() > 1 {
+            ui.painter().extend(egui::Shape::dashed_line(&points, (3.0, Color32::from_rgb(0, 200, 255)), 8.0, 4.0));
+        }
+        if let Some(target) = self.craft.route.get(self.craft.hop_index).and_then(|id| center(*id)) {
+            ui.painter().circle_stroke(target, 32.0, (2.0, Color32::from_rgb(0, 200, 255)));
+        }
+    }
+
     fn render_connections(&self, ui: &mut egui::Ui) {
+        //The busiest links of the last frame, in both directions since a connection is drawn once.
+        let mut links = self.sim_contr.borrow().link_activity.iter()
+            .map(|(link, count)| (*link, *count))
+            .collect::<Vec<((NodeId, NodeId), u64)>>();
+        links.sort_by(|a, b| b.1.cmp(&a.1));
+        links.truncate(MAX_HIGHLIGHTED_LINKS);
+        let active = links
+            .into_iter()
+            .flat_map(|((from, to), _)| [(from, to), (to, from)])
+            .collect::<HashSet<(NodeId, NodeId)>>();
+
+        //The links with a capacity are drawn as a heatmap of their utilization instead.
+        let link_utilization = &self.sim_contr.borrow().link_utilization;
+        let utilization = |a: NodeId, b: NodeId| -> Option<f32> {
+            match (link_utilization.get(&(a, b)), link_utilization.get(&(b, a))) {
+                (None, None) => None,
+                (x, y) => Some(x.copied().unwrap_or(0.0).max(y.copied().unwrap_or(0.0))),
+            }
+        };
+
+        //The links that failed the handshake only work one way, they're drawn before anything else.
+        //So do the ones a drone gave up on (see eviction.rs).
+        let asymmetric_links = &self.sim_contr.borrow().asymmetric_links;
+        let evicted_links = &self.sim_contr.borrow().evicted_links;
+        let one_way = |a: NodeId, b: NodeId| -> bool {
+            asymmetric_links.contains(&(a, b)) || asymmetric_links.contains(&(b, a)) || evicted_links.contains(&(a, b)) || evicted_links.contains(&(b, a))
+        };
+
         for &(i, j) in &self.connections {
             let pos1 = self.drones[i].position + Vec2::new(25.0, 25.0);
             let pos2 = self.drones[j].position + Vec2::new(25.0, 25.0);
 
+            let (is_active, heat, asymmetric) = match (self.drones[i].node_id, self.drones[j].node_id) {
+                (Some(a), Some(b)) => (active.contains(&(a, b)), utilization(a, b), one_way(a, b)),
+                _ => (false, None, false),
+            };
+            let stroke = if asymmetric {
+                (3.0, Color32::from_rgb(255, 0, 255))
+            } else if let Some(heat) = heat {
+                let heat = heat.clamp(0.0, 1.0);
+                (2.0 + 4.0 * heat, Color32::from_rgb((255.0 * heat) as u8, (255.0 * (1.0 - heat)) as u8, 0))
+            } else if is_active {
+                (4.0, Color32::YELLOW)
+            } else {
+                (2.0, Color32::GREEN)
+            };
+
             ui.painter().line_segment(
                 [egui::Pos2::new(pos1.x, pos1.y), egui::Pos2::new(pos2.x, pos2.y)],
-                (2.0, Color32::GREEN),
+                stroke,
             );
         }
     }
 
-    fn render_log(&self, ui: &mut egui::Ui) {
-        egui::ScrollArea::vertical().show(ui, |ui| {
-            for entry in &self.log {
-                ui.label(entry);
+    //Links added in blue and removed ones in red, the nodes that changed get a ring of the same
+    //colours (orange for a new pdr), so what happened since the snapshot can be seen at a glance.
+    fn render_diff_overlay(&self, ui: &mut egui::Ui) {
+        let Some(diff) = &self.diff_overlay else {
+            return;
+        };
+        let centre = |id: NodeId| {
+            self.drones
+                .iter()
+                .find(|drone| drone.node_id == Some(id))
+                .map(|drone| egui::Pos2::new(drone.position.x + 25.0, drone.position.y + 25.0))
+        };
+        for (links, color) in [(&diff.links_added, Color32::LIGHT_BLUE), (&diff.links_removed, Color32::RED)] {
+            for (a, b) in links.iter() {
+                if let (Some(pos1), Some(pos2)) = (centre(*a), centre(*b)) {
+                    let dashes = egui::Shape::dashed_line(&[pos1, pos2], (3.0, color), 8.0, 6.0);
+                    ui.painter().extend(dashes);
+                }
             }
-        });
+        }
+        let pdr_changed = diff.pdr_changed.iter().map(|(id, _, _)| *id).collect::<Vec<NodeId>>();
+        for (ids, color) in [(&diff.added, Color32::LIGHT_BLUE), (&diff.crashed, Color32::RED), (&pdr_changed, Color32::from_rgb(255, 165, 0))] {
+            for id in ids.iter() {
+                if let Some(pos) = centre(*id) {
+                    ui.painter().circle_stroke(pos, 34.0, (3.0, color));
+                }
+            }
+        }
+    }
+
+    fn render_log(&self, ui: &mut egui::Ui) {
+        render_log_rows(ui, &self.log);
     }
 
     fn handle_ui_controls(&mut self, ui: &mut egui::Ui) {
@@ -196,6 +432,7 @@ impl SimulationApp {
 
             let new_drone = Drone {
                 id: new_id.clone(),
+                node_id: None,
                 position: Vec2::new(random_x, random_y),
                 is_crashed: false,
                 pdr: 0.0, // Temporary default value
@@ -211,19 +448,706 @@ impl SimulationApp {
             self.show_connection_dialog = true;
             self.log.push(format!("{} added", new_id));
         }
+
+        ui.checkbox(&mut self.radio_range_mode, "Radio range links");
+        if self.radio_range_mode {
+            ui.add(egui::Slider::new(&mut self.radio_range, 50.0..=600.0).text("range"));
+        }
+
+        //With review mode the links, crashes, reboots and new drones only show up here, and
+        //nothing reaches the Sim Contr until they're applied all together.
+        ui.checkbox(&mut self.review_mode, "Review mode");
+        if !self.proposed.is_empty() {
+            let mut removed = None;
+            egui::CollapsingHeader::new(format!("Proposed changes ({})", self.proposed.len()))
+                .id_source("proposed_changes")
+                .default_open(true)
+                .show(ui, |ui| {
+                    for (index, (mutation, _)) in self.proposed.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(mutation.describe());
+                            if ui.small_button("x").clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                    }
+                });
+            if let Some(index) = removed {
+                self.proposed.remove(index);
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Apply all").clicked() {
+                    let proposed = std::mem::take(&mut self.proposed);
+                    self.apply_mutations(proposed);
+                }
+                if ui.button("Discard all").clicked() {
+                    self.proposed.clear();
+                    self.log.push("proposed changes discarded".to_string());
+                    self.sync_with_network();
+                }
+            });
+        }
+
+        //A whole loss regime at once, on the group of the profile or on the one written here.
+        let profiles = self.sim_contr.borrow().profiles.iter().map(|profile| profile.name.clone()).collect::<Vec<String>>();
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("profile")
+                .selected_text(self.profile.clone())
+                .show_ui(ui, |ui| {
+                    for name in profiles {
+                        ui.selectable_value(&mut self.profile, name.clone(), name);
+                    }
+                });
+            ui.add(egui::TextEdit::singleline(&mut self.profile_group).hint_text("group").desired_width(60.0));
+            if ui.button("Apply").clicked() {
+                match NodeGroup::parse(&self.profile_group) {
+                    _ if self.profile_group.trim().is_empty() => {
+                        self.sim_contr.borrow_mut().apply_profile(&self.profile, None);
+                    }
+                    Some(group) => {
+                        self.sim_contr.borrow_mut().apply_profile(&self.profile, Some(group));
+                    }
+                    None => self.log.push(format!("{} is not a group", self.profile_group)),
+                }
+            }
+        });
+
+        //The named groups: defined here or loaded from groups.toml, and then crashed, rebooted or
+        //made lossy all together. Their names work in the group box of the profiles too.
+        egui::CollapsingHeader::new("Groups").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.group_name).hint_text("name").desired_width(70.0));
+                ui.add(egui::TextEdit::singleline(&mut self.group_nodes).hint_text("1,2,3").desired_width(70.0));
+                if ui.button("Define").clicked() {
+                    let nodes = self.group_nodes.split(',').filter_map(|id| id.trim().parse::<NodeId>().ok()).collect();
+                    self.define_group(nodes);
+                }
+                //The drone selected on the canvas joins the group written in name.
+                let selected = self.selected_drone.and_then(|index| self.drones[index].node_id);
+                if let (Some(id), true) = (selected, ui.button("Add selected").clicked()) {
+                    let mut nodes = self.sim_contr.borrow().groups.iter().find(|group| group.name == self.group_name).map(|group| group.nodes.clone()).unwrap_or_default();
+                    if !nodes.contains(&id) {
+                        nodes.push(id);
+                    }
+                    self.define_group(nodes);
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.sim_contr.borrow_mut().save_groups("groups.toml");
+                }
+                if ui.button("Load").clicked() {
+                    self.sim_contr.borrow_mut().load_groups("groups.toml");
+                }
+                ui.checkbox(&mut self.show_groups, "Draw them");
+                ui.add(egui::DragValue::new(&mut self.group_pdr).clamp_range(0.0..=1.0).speed(0.01).prefix("pdr "));
+            });
+            let groups = self.sim_contr.borrow().groups.clone();
+            for (index, group) in groups.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.colored_label(group_color(group, index), format!("{} {:?}", group.name, group.nodes));
+                    if ui.small_button("Crash").clicked() {
+                        self.sim_contr.borrow_mut().crash_group(&group.name);
+                    }
+                    if ui.small_button("Reboot").clicked() {
+                        self.sim_contr.borrow_mut().reboot_group(&group.name);
+                    }
+                    if ui.small_button("Set pdr").clicked() {
+                        self.sim_contr.borrow_mut().set_group_pdr(&group.name, self.group_pdr);
+                    }
+                    if ui.small_button("x").clicked() {
+                        self.sim_contr.borrow_mut().remove_group(&group.name);
+                    }
+                });
+            }
+        });
+
+        //Every region can be made stronger or weaker while the simulation runs.
+        let regions = self.sim_contr.borrow().regions.clone();
+        for (index, region) in regions.iter().enumerate() {
+            let mut extra_drop = region.extra_drop;
+            let mut latency_ms = region.latency_ms;
+            ui.label(format!("Region {}", index));
+            let drop_changed = ui.add(egui::Slider::new(&mut extra_drop, 0.0..=1.0).text("drop")).changed();
+            let latency_changed = ui.add(egui::Slider::new(&mut latency_ms, 0..=500).text("ms")).changed();
+            if drop_changed || latency_changed {
+                self.sim_contr.borrow_mut().set_region(index, extra_drop, latency_ms);
+            }
+        }
+
+        //The probes go out at once, the answers show up here (and in the log) as they come back.
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.trace_from).prefix("from "));
+            ui.add(egui::DragValue::new(&mut self.trace_to).prefix("to "));
+            if ui.button("Traceroute").clicked() {
+                self.sim_contr.borrow_mut().start_traceroute(self.trace_from, self.trace_to);
+            }
+        });
+        //A message of 10 fragments between the same two nodes, on the route cached by the client
+        //or spread on the disjoint ones.
+        ui.horizontal(|ui| {
+            if ui.button("Send message").clicked() {
+                let strategy = if self.send_multipath { RoutingStrategy::Multipath } else { RoutingStrategy::SinglePath };
+                self.sim_contr.borrow_mut().send_to_with(self.trace_from, self.trace_to, 10, None, strategy);
+            }
+            ui.checkbox(&mut self.send_multipath, "multipath");
+        });
+        //The messages still on their way, a stuck one can be given up.
+        let mut cancel = None;
+        for transfer in self.sim_contr.borrow().transfers.iter().filter(|transfer| transfer.finished.is_none() && !transfer.cancelled) {
+            ui.horizontal(|ui| {
+                ui.label(transfer.describe());
+                if ui.small_button("Cancel").clicked() {
+                    cancel = Some(transfer.session_id);
+                }
+            });
+        }
+        if let Some(session_id) = cancel {
+            self.sim_contr.borrow_mut().cancel_transfer(session_id);
+        }
+        ui.label(format!("Route cache: {}", self.sim_contr.borrow().route_cache.describe()));
+        if let Some(traceroute) = &self.sim_contr.borrow().traceroute {
+            for line in traceroute.report() {
+                ui.monospace(line);
+            }
+        }
+
+        //What the servers got so far, the newest message first.
+        egui::CollapsingHeader::new("Received messages").show(ui, |ui| {
+            let sim_contr = self.sim_contr.borrow();
+            let mut servers = sim_contr.inbox.messages.keys().copied().collect::<Vec<NodeId>>();
+            servers.sort();
+            if servers.is_empty() {
+                ui.label("Nothing yet");
+            }
+            for server in servers {
+                ui.label(format!("Server {}", server));
+                for message in sim_contr.inbox.messages[&server].iter().rev() {
+                    let color = if message.is_complete() { Color32::LIGHT_GREEN } else { Color32::YELLOW };
+                    ui.colored_label(color, message.describe());
+                }
+            }
+        });
+
+        //The route lengths of the delivered messages, one histogram for every source and destination.
+        egui::CollapsingHeader::new("Hop counts").show(ui, |ui| {
+            let sim_contr = self.sim_contr.borrow();
+            if sim_contr.hop_counts.pairs.is_empty() {
+                ui.label("Nothing delivered yet");
+            }
+            for ((source, destination), stats) in sim_contr.hop_counts.pairs.iter() {
+                ui.label(format!("{} -> {}: {}", source, destination, stats.describe()));
+                render_histogram(ui, &stats.distribution, Color32::LIGHT_BLUE);
+            }
+        });
+
+        //The messages of the two strategies side by side.
+        //A packet put together field by field, even a wrong one, to reproduce the edge cases of the protocol.
+        egui::CollapsingHeader::new("Packet crafting").show(ui, |ui| {
+            self.render_packet_crafting(ui);
+        });
+
+        egui::CollapsingHeader::new("Routing strategies").show(ui, |ui| {
+            let stats = self.sim_contr.borrow().strategy_stats();
+            if stats.is_empty() {
+                ui.label("No messages yet");
+            }
+            for (strategy, stats) in stats.iter() {
+                ui.label(format!("{:?}: {}", strategy, stats.describe()));
+            }
+        });
+
+        //The flows watched by the SLA probes, with the rtts of their last probes.
+        egui::CollapsingHeader::new("SLA probes").show(ui, |ui| {
+            if ui.button("Probe from -> to").clicked() {
+                let config = SlaProbeConfig { client: self.trace_from, server: self.trace_to, period_ms: 1000, target_rtt_ms: None };
+                self.sim_contr.borrow_mut().add_sla_probe(config);
+            }
+            let sim_contr = self.sim_contr.borrow();
+            for pair in sim_contr.sla.pairs.iter() {
+                let color = if pair.success_ratio() < SLA_WARNING_RATIO { Color32::RED } else { Color32::GREEN };
+                ui.colored_label(color, pair.describe());
+                let rtts = pair.rtts.iter().map(|rtt| rtt.as_secs_f64() * 1000.0).collect::<Vec<f64>>();
+                render_chart(ui, "rtt ms", &rtts, color);
+            }
+        });
+
+        //Sources in the rows, destinations in the columns: delivered/attempted, red when some failed.
+        egui::CollapsingHeader::new("Traffic matrix").show(ui, |ui| {
+            let matrix = self.sim_contr.borrow().traffic_matrix();
+            if matrix.pairs.is_empty() {
+                ui.label("No messages yet");
+                return;
+            }
+            let destinations = matrix.destinations();
+            egui::Grid::new("traffic_matrix").striped(true).show(ui, |ui| {
+                ui.label("from \\ to");
+                for destination in destinations.iter() {
+                    ui.label(destination.to_string());
+                }
+                ui.end_row();
+                for source in matrix.sources() {
+                    ui.label(source.to_string());
+                    for destination in destinations.iter() {
+                        match matrix.get(source, *destination) {
+                            Some(pair) => {
+                                let color = if pair.failed > 0 {
+                                    Color32::LIGHT_RED
+                                } else if pair.delivered == pair.attempted {
+                                    Color32::LIGHT_GREEN
+                                } else {
+                                    Color32::YELLOW
+                                };
+                                ui.colored_label(color, format!("{}/{}", pair.delivered, pair.attempted)).on_hover_text(pair.describe());
+                            }
+                            None => {
+                                ui.label("-");
+                            }
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+
+        //The shortcuts nobody could deliver, the newest first.
+        let dead_letters_title = format!("Dead letters ({})", self.sim_contr.borrow().dead_letters.total);
+        egui::CollapsingHeader::new(dead_letters_title).id_source("dead_letters").show(ui, |ui| {
+            let sim_contr = self.sim_contr.borrow();
+            if sim_contr.dead_letters.letters.is_empty() {
+                ui.label("None");
+            }
+            for letter in sim_contr.dead_letters.letters.iter().rev() {
+                ui.colored_label(Color32::LIGHT_RED, letter.describe());
+            }
+        });
+
+        if ui.button("Save Snapshot").clicked() {
+            self.sim_contr.borrow_mut().save_snapshot("snapshot.toml");
+            self.log.push("Snapshot saved to snapshot.toml".to_string());
+        }
+
+        let diff_label = if self.diff_overlay.is_some() { "Hide Diff" } else { "Diff with Snapshot" };
+        if ui.button(diff_label).clicked() {
+            self.diff_overlay = match self.diff_overlay {
+                Some(_) => None,
+                None => self.sim_contr.borrow_mut().diff_with_snapshot("snapshot.toml"),
+            };
+        }
+
+        if ui.button("Export Stats").clicked() {
+            self.sim_contr.borrow_mut().export_stats("stats.csv");
+            self.log.push("Stats exported to stats.csv".to_string());
+        }
+
+        if ui.button("Export Report").clicked() {
+            self.sim_contr.borrow_mut().export_report("report.html");
+            self.log.push("Report written to report.html".to_string());
+        }
+    }
+
+
+    //In radio range mode two nodes are linked when they're closer than radio_range, so dragging
+    //a drone away tears its links down (in both directions) and dragging it back restores them.
+    fn apply_radio_range(&mut self) {
+        if !self.radio_range_mode {
+            return;
+        }
+        let crashed = self.sim_contr.borrow().crashed.clone();
+        let is_up = |drone: &Drone| drone.node_id.map_or(false, |id| !crashed.contains(&id));
+
+        let mut in_range = HashSet::new();
+        for i in 0..self.drones.len() {
+            for j in (i + 1)..self.drones.len() {
+                if !is_up(&self.drones[i]) || !is_up(&self.drones[j]) {
+                    continue;
+                }
+                if (self.drones[i].position - self.drones[j].position).length() <= self.radio_range {
+                    in_range.insert((i, j));
+                }
+            }
+        }
+        //The connections are drawn from both ends, so I compare them without the direction.
+        let connected = self.connections
+            .iter()
+            .map(|&(i, j)| (i.min(j), i.max(j)))
+            .filter(|&(i, j)| is_up(&self.drones[i]) && is_up(&self.drones[j]))
+            .collect::<HashSet<(usize, usize)>>();
+
+        let to_add = in_range.difference(&connected).copied().collect::<Vec<(usize, usize)>>();
+        let to_remove = connected.difference(&in_range).copied().collect::<Vec<(usize, usize)>>();
+
+        //In review mode the same change is proposed at every frame, mutate keeps only the first one.
+        for (i, j) in to_add {
+            let (a, b) = (self.drones[i].node_id.unwrap(), self.drones[j].node_id.unwrap());
+            if !self.review_mode {
+                self.log.push(format!("{} and {} in range", self.drones[i].id, self.drones[j].id));
+            }
+            self.mutate(Mutation::AddLink(a, b), None);
+        }
+        for (i, j) in to_remove {
+            let (a, b) = (self.drones[i].node_id.unwrap(), self.drones[j].node_id.unwrap());
+            if !self.review_mode {
+                self.log.push(format!("{} and {} out of range", self.drones[i].id, self.drones[j].id));
+            }
+            self.mutate(Mutation::RemoveLink(a, b), None);
+        }
+    }
+
+    //In review mode the change waits in the proposed list, otherwise it's applied right away.
+    fn mutate(&mut self, mutation: Mutation, gui_drone: Option<usize>) {
+        if !self.review_mode {
+            self.apply_mutations(vec![(mutation, gui_drone)]);
+        } else if !self.proposed.iter().any(|(proposed, _)| *proposed == mutation) {
+            self.log.push(format!("proposed: {}", mutation.describe()));
+            self.proposed.push((mutation, gui_drone));
+        }
+    }
+
+    fn apply_mutations(&mut self, mutations: Vec<(Mutation, Option<usize>)>) {
+        let changes = mutations.iter().map(|(mutation, _)| mutation.clone()).collect::<Vec<Mutation>>();
+        let spawned = self.sim_contr.borrow_mut().apply_mutations(&changes);
+        //The drones added on the canvas get their id once they're really spawned.
+        for ((_, gui_drone), new_id) in mutations.iter().zip(spawned) {
+            if let (Some(index), Some(id)) = (gui_drone, new_id) {
+                self.drones[*index].node_id = Some(id);
+                self.drones[*index].id = format!("drone{}", id);
+            }
+        }
+        self.sync_with_network();
     }
 
+    fn render_packet_crafting(&mut self, ui: &mut egui::Ui) {
+        let craft = &mut self.craft;
+        egui::ComboBox::from_id_source("craft_type")
+            .selected_text(format!("{:?}", craft.packet_type))
+            .show_ui(ui, |ui| {
+                for packet_type in [CraftedType::Fragment, CraftedType::Ack, CraftedType::Nack, CraftedType::FloodRequest, CraftedType::FloodResponse] {
+                    ui.selectable_value(&mut craft.packet_type, packet_type, format!("{:?}", packet_type));
+                }
+            });
+        ui.add(egui::DragValue::new(&mut craft.session_id).prefix("session "));
+        match craft.packet_type {
+            CraftedType::Fragment => {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut craft.fragment_index).prefix("fragment "));
+                    ui.add(egui::DragValue::new(&mut craft.total_n_fragments).prefix("of "));
+                });
+                ui.add(egui::TextEdit::singleline(&mut craft.data).hint_text("data"));
+            }
+            CraftedType::Ack => {
+                ui.add(egui::DragValue::new(&mut craft.fragment_index).prefix("fragment "));
+            }
+            CraftedType::Nack => {
+                ui.add(egui::DragValue::new(&mut craft.fragment_index).prefix("fragment "));
+                egui::ComboBox::from_id_source("craft_nack")
+                    .selected_text(format!("{:?}", craft.nack))
+                    .show_ui(ui, |ui| {
+                        for nack in [CraftedNack::ErrorInRouting, CraftedNack::DestinationIsDrone, CraftedNack::Dropped, CraftedNack::UnexpectedRecipient] {
+                            ui.selectable_value(&mut craft.nack, nack, format!("{:?}", nack));
+                        }
+                    });
+                if matches!(craft.nack, CraftedNack::ErrorInRouting | CraftedNack::UnexpectedRecipient) {
+                    ui.add(egui::DragValue::new(&mut craft.nack_node).prefix("node "));
+                }
+            }
+            CraftedType::FloodRequest | CraftedType::FloodResponse => {
+                ui.add(egui::DragValue::new(&mut craft.flood_id).prefix("flood "));
+            }
+        }
+
+        let route = craft.route.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(" -> ");
+        ui.label(format!("Route: {}", if route.is_empty() { "-" } else { &route }));
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.crafting_route, "Click the route");
+            if ui.button("Clear").clicked() {
+                craft.route.clear();
+            }
+        });
+        ui.add(egui::DragValue::new(&mut craft.hop_index).prefix("hop index "));
+
+        if ui.button("Inject").clicked() {
+            let built = craft.build(&self.sim_contr.borrow().node_types);
+            match built {
+                Ok(packet) => self.sim_contr.borrow_mut().inject_packet(packet),
+                Err(e) => self.log.push(format!("packet not crafted: {}", e)),
+            }
+        }
+    }
+
+    //Runs the events of the frame, unless the Sim Contr stalled: then nothing is asked to it
+    //until it's reconnected or restarted, and the window keeps drawing the state it had.
+    fn refresh(&mut self) {
+        if self.stalled.is_some() {
+            return;
+        }
+        let started = Instant::now();
+        match self.sim_contr.try_borrow_mut() {
+            Ok(mut sim_contr) => sim_contr.process_events(),
+            Err(_) => {
+                //Still held by someone else, the next frame tries again.
+                if self.last_refresh.elapsed() >= STALL_THRESHOLD {
+                    self.stalled = Some(format!("busy for {:.1}s", self.last_refresh.elapsed().as_secs_f64()));
+                }
+                return;
+            }
+        }
+        let took = started.elapsed();
+        if took >= STALL_THRESHOLD {
+            self.stalled = Some(format!("its events took {:.1}s", took.as_secs_f64()));
+        }
+        self.last_refresh = Instant::now();
+    }
+
+    //The red bar on top while stalled, with the ways out.
+    fn render_stall_banner(&mut self, ui: &mut egui::Ui) {
+        let Some(reason) = &self.stalled else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.colored_label(
+                Color32::RED,
+                format!("The simulation controller stalled ({}): this is the state of {:.0}s ago.", reason, self.last_refresh.elapsed().as_secs_f64()),
+            );
+            if ui.button("Reconnect").clicked() {
+                self.stalled = None;
+                self.last_refresh = Instant::now();
+            }
+            if ui.button("Restart").clicked() {
+                self.restart_requested = true;
+            }
+        });
+    }
+
+    //After the Workspace restarted the Sim Contr: same drones, same places, new threads.
+    fn restarted(&mut self) {
+        self.restart_requested = false;
+        self.stalled = None;
+        self.last_refresh = Instant::now();
+        self.sync_with_network();
+    }
+
+    //The links and the crashes as the Sim Contr has them, after some changes or a discarded
+    //proposal. The links of the drones not spawned yet are only on the canvas, they stay.
+    fn sync_with_network(&mut self) {
+        let sim_contr = self.sim_contr.borrow();
+        let index_of = self.drones
+            .iter()
+            .enumerate()
+            .filter_map(|(index, drone)| drone.node_id.map(|id| (id, index)))
+            .collect::<HashMap<NodeId, usize>>();
+        let mut connections = self.connections
+            .iter()
+            .filter(|(i, j)| self.drones[*i].node_id.is_none() || self.drones[*j].node_id.is_none())
+            .copied()
+            .collect::<Vec<(usize, usize)>>();
+        for (id, neighbours) in sim_contr.network_graph.iter() {
+            let Some(&i) = index_of.get(id) else {
+                continue;
+            };
+            connections.extend(neighbours.iter().filter_map(|neighbour| index_of.get(neighbour).map(|&j| (i, j))));
+        }
+        for drone in self.drones.iter_mut() {
+            drone.is_crashed = drone.node_id.map_or(false, |id| sim_contr.crashed.contains(&id));
+        }
+        self.connections = connections;
+    }
+
+    //The crashes, spawns, floods, faults, alerts and scenario steps on a bar, the newest on the
+    //right. Clicking one selects its drone on the canvas (there's no replay in the GUI to jump in).
+    fn render_timeline(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Timeline");
+            ui.add(egui::Slider::new(&mut self.timeline_span_s, 5.0..=3600.0).logarithmic(true).text("s shown"));
+            ui.add(egui::DragValue::new(&mut self.timeline_back_s).clamp_range(0.0..=f32::MAX).prefix("back ").suffix(" s"));
+            for kind in [TimelineKind::Crash, TimelineKind::Spawn, TimelineKind::Reboot, TimelineKind::Flood, TimelineKind::Fault, TimelineKind::Alert, TimelineKind::Step, TimelineKind::Cancel] {
+                ui.colored_label(timeline_color(kind), format!("{:?}", kind));
+            }
+        });
+        let (rect, mut response) = ui.allocate_exact_size(Vec2::new(ui.available_width(), 24.0), egui::Sense::click());
+        ui.painter().rect_stroke(rect, 0.0, (1.0, Color32::GRAY));
+
+        let sim_contr = self.sim_contr.borrow();
+        let entries = &sim_contr.timeline.entries;
+        let end = (sim_contr.timeline.elapsed().as_secs_f32() - self.timeline_back_s).max(self.timeline_span_s);
+        let start = end - self.timeline_span_s;
+        let mut markers = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let x = rect.left() + rect.width() * (entry.at.as_secs_f32() - start) / self.timeline_span_s;
+            if x < rect.left() || x > rect.right() {
+                continue;
+            }
+            let pos = egui::Pos2::new(x, rect.center().y);
+            let radius = if self.timeline_selected == Some(index) { 7.0 } else { 4.0 };
+            ui.painter().circle_filled(pos, radius, timeline_color(entry.kind));
+            markers.push((index, pos));
+        }
+        let nearest = |pointer: egui::Pos2| {
+            markers
+                .iter()
+                .filter(|(_, pos)| pos.distance(pointer) < 8.0)
+                .min_by(|a, b| a.1.distance(pointer).total_cmp(&b.1.distance(pointer)))
+                .map(|(index, _)| *index)
+        };
+        if let Some(index) = response.hover_pos().and_then(nearest) {
+            response = response.on_hover_text(entries[index].describe());
+        }
+        if response.clicked() {
+            self.timeline_selected = response.interact_pointer_pos().and_then(nearest);
+            if let Some(node) = self.timeline_selected.and_then(|index| entries[index].node) {
+                self.selected_drone = self.drones.iter().position(|drone| drone.node_id == Some(node));
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{:.0}s - {:.0}s", start, end));
+            if let Some(entry) = self.timeline_selected.and_then(|index| entries.get(index)) {
+                ui.label(entry.describe());
+            }
+        });
+    }
 
     fn handle_selection(&mut self, ui: &mut egui::Ui) {
         if let Some(idx) = self.selected_drone {
             let drone = &self.drones[idx];
             ui.label(format!("Selected: {}", drone.id));
+            if let Some(node_id) = drone.node_id {
+                ui.horizontal(|ui| {
+                    if ui.button("Ping").clicked() {
+                        let report = self.sim_contr.borrow_mut().ping(node_id);
+                        self.log.push(report.summary());
+                    }
+                    //A crashed drone can only come back: the reboot starts it again with the same id, pdr and links.
+                    if self.sim_contr.borrow().crashed.contains(&node_id) {
+                        if ui.button("Restart").clicked() {
+                            self.mutate(Mutation::Reboot(node_id), None);
+                        }
+                    } else {
+                        if ui.button("Crash").clicked() {
+                            self.mutate(Mutation::Crash(node_id), None);
+                        }
+                        if ui.button("Reboot").clicked() {
+                            self.mutate(Mutation::Reboot(node_id), None);
+                        }
+                    }
+                });
+
+                //The whole history of the node, the older part is coarser but it's still there.
+                let rates = self.sim_contr.borrow().stats_series.rates(node_id);
+                let throughput = rates.iter().map(|rate| rate.throughput).collect::<Vec<f64>>();
+                let drops = rates.iter().map(|rate| rate.drops).collect::<Vec<f64>>();
+                let queue = rates.iter().map(|rate| rate.queue_len as f64).collect::<Vec<f64>>();
+                render_chart(ui, "sent/s", &throughput, Color32::GREEN);
+                render_chart(ui, "dropped/s", &drops, Color32::RED);
+                render_chart(ui, "queue", &queue, Color32::YELLOW);
+                let stats = self.sim_contr.borrow().stats.get(&node_id).cloned().unwrap_or_default();
+                if matches!(self.sim_contr.borrow().node_types.get(&node_id), Some(NodeType::Drone)) {
+                    ui.label(format!(
+                        "{} fragments forwarded, {} dropped, {} nacks, {} floods",
+                        stats.fragments_forwarded, stats.fragments_dropped, stats.nacks_generated, stats.floods_handled
+                    ));
+                }
+                //The last heartbeat, only if the drones beat (heartbeat_ms).
+                let health = self.sim_contr.borrow().health.as_ref().and_then(|health| health.nodes.get(&node_id).cloned());
+                if let Some(health) = health {
+                    let text = format!(
+                        "heartbeat {} ms ago: queue {}, {:.0} packets/s, pdr {}",
+                        health.last_beat.elapsed().as_millis(), health.queue_depth, health.packets_per_s, health.pdr
+                    );
+                    match health.stalled {
+                        true => ui.colored_label(Color32::RED, format!("stalled, {}", text)),
+                        false => ui.label(text),
+                    };
+                }
+                if let (Some(left), Some(budget)) = (stats.energy_left, self.sim_contr.borrow().energy.get(&node_id)) {
+                    match left {
+                        0 => ui.colored_label(Color32::RED, format!("battery empty (of {})", budget.capacity)),
+                        _ => ui.label(format!("battery {} of {}", left, budget.capacity)),
+                    };
+                }
+                if stats.fragments_duplicated + stats.fragments_corrupted > 0 {
+                    ui.colored_label(Color32::YELLOW, format!("{} fragments duplicated, {} corrupted on purpose", stats.fragments_duplicated, stats.fragments_corrupted));
+                }
+                if let Some(reorder) = self.sim_contr.borrow().reorder.get(&node_id) {
+                    ui.colored_label(Color32::YELLOW, format!("jitter mode, buffer of {}: {} fragments reordered", reorder.buffer, stats.fragments_reordered));
+                }
+                if stats.duplicates_suppressed > 0 {
+                    ui.colored_label(Color32::YELLOW, format!("{} copies of forwarded fragments dropped", stats.duplicates_suppressed));
+                }
+                if let Some(burst_loss) = self.sim_contr.borrow().burst_loss.get(&node_id) {
+                    ui.colored_label(Color32::YELLOW, format!("drops in bursts, {:.1}% on average", burst_loss.mean_loss() * 100.0));
+                }
+                if stats.congestion_drops > 0 {
+                    ui.colored_label(Color32::YELLOW, format!("{} fragments dropped on a full queue", stats.congestion_drops));
+                }
+                if stats.rate_limited > 0 {
+                    ui.colored_label(Color32::YELLOW, format!("{} fragments nacked over the packet rate", stats.rate_limited));
+                }
+                let events_suppressed = stats.events_suppressed;
+                if events_suppressed > 0 {
+                    ui.colored_label(Color32::RED, format!("{} events suppressed by the rate limit", events_suppressed));
+                }
+
+                //How much of the node goes in the log, the rest of its telemetry comes from the scenarios and the ipc.
+                let telemetry = self.sim_contr.borrow().telemetry.get(&node_id).cloned().unwrap_or(TelemetryConfig { node: node_id, ..TelemetryConfig::default() });
+                ui.horizontal(|ui| {
+                    ui.label("Log:");
+                    for verbosity in [LogVerbosity::Quiet, LogVerbosity::Normal, LogVerbosity::Verbose] {
+                        if ui.selectable_label(telemetry.verbosity == verbosity, format!("{:?}", verbosity)).clicked() && telemetry.verbosity != verbosity {
+                            self.sim_contr.borrow_mut().set_telemetry(TelemetryConfig { verbosity, ..telemetry.clone() });
+                        }
+                    }
+                });
+
+                //The tags of the node, a click on one takes it off. Every tag is a group too (tag:<tag>).
+                let annotation = self.sim_contr.borrow().annotations.node(node_id).cloned().unwrap_or_default();
+                ui.horizontal_wrapped(|ui| {
+                    for tag in annotation.tags.iter() {
+                        if ui.small_button(format!("{} x", tag)).clicked() {
+                            self.sim_contr.borrow_mut().untag_node(node_id, tag);
+                        }
+                    }
+                });
+                if !annotation.note.is_empty() {
+                    ui.label(format!("Note: {}", annotation.note));
+                }
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.tag_input).hint_text("tag or note").desired_width(100.0));
+                    if ui.button("Tag").clicked() && self.sim_contr.borrow_mut().tag_node(node_id, &self.tag_input) {
+                        self.tag_input.clear();
+                    }
+                    if ui.button("Note").clicked() {
+                        self.sim_contr.borrow_mut().set_note(node_id, None, &self.tag_input);
+                        self.tag_input.clear();
+                    }
+                });
+
+                //Everything the node was told, the newest first, with who asked for it.
+                egui::CollapsingHeader::new("Commands").id_source("commands").show(ui, |ui| {
+                    egui::ComboBox::from_id_source("audit_origin")
+                        .selected_text(self.audit_origin.map_or("all".to_string(), |origin| format!("{:?}", origin)))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.audit_origin, None, "all");
+                            for origin in [CommandOrigin::SimContr, CommandOrigin::Gui, CommandOrigin::Tui, CommandOrigin::Scenario, CommandOrigin::Chaos, CommandOrigin::Api] {
+                                ui.selectable_value(&mut self.audit_origin, Some(origin), format!("{:?}", origin));
+                            }
+                        });
+                    let sim_contr = self.sim_contr.borrow();
+                    let entries = sim_contr.audit.query(node_id, self.audit_origin);
+                    egui::ScrollArea::vertical().id_source("commands_scroll").max_height(150.0).show(ui, |ui| {
+                        for entry in entries.iter().rev() {
+                            ui.monospace(entry.describe());
+                        }
+                    });
+                });
+            }
         } else {
             ui.label("No Drone Selected");
         }
     }
 
     fn render_connection_dialog(&mut self, ui: &mut egui::Ui) {
+        let mut spawn = None;
         if self.show_connection_dialog && self.new_drone_index.is_some() {
             egui::Window::new("Connect New Drone")
                 .collapsible(false)
@@ -252,9 +1176,11 @@ impl SimulationApp {
                     }
 
                     if ui.button("Confirm Connections").clicked() {
+                        let mut neighbours = Vec::new();
                         for (idx, &is_selected) in self.connection_selections.iter().enumerate() {
                             if is_selected && idx != new_drone_index {
                                 self.connections.push((new_drone_index, idx));
+                                neighbours.extend(self.drones[idx].node_id);
                                 self.log.push(format!(
                                     "Connected {} to {}",
                                     self.drones[new_drone_index].id,
@@ -262,6 +1188,8 @@ impl SimulationApp {
                                 ));
                             }
                         }
+                        let pdr = self.drones[new_drone_index].pdr;
+                        spawn = Some((Mutation::Spawn { pdr, connections: neighbours }, new_drone_index));
 
                         // Reset selections
                         self.connection_selections = vec![false; self.drones.len()];
@@ -270,63 +1198,425 @@ impl SimulationApp {
                     }
                 });
         }
+        if let Some((mutation, new_drone_index)) = spawn {
+            self.mutate(mutation, Some(new_drone_index));
+        }
     }
 
 }
 
-impl App for SimulationApp {
-    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+impl SimulationApp {
+    //Draws the simulation, the Workspace has already run its events for this frame.
+    fn show(&mut self, ctx: &Context) {
         self.load_drone_image(ctx);
 
-        egui::CentralPanel::default().show(ctx, |ui| {
+        let canvas = egui::CentralPanel::default().show(ctx, |ui| {
             if let Some(texture) = self.drone_texture.clone() {
+                self.render_regions(ui);
+                self.render_groups(ui);
                 self.render_connections(ui);
                 self.render_drones(ui, &texture);
+                self.render_diff_overlay(ui);
+                self.render_crafted_route(ui);
+                if self.stalled.is_none() {
+                    self.apply_radio_range();
+                }
+                self.publish_positions();
 
                 self.render_connection_dialog(ui);
             }
         });
+        self.panel_rects.insert("canvas", canvas.response.rect);
 
         egui::TopBottomPanel::top("header").show(ctx, |ui| {
             ui.heading("SkyLink Simulation");
         });
 
+        if self.stalled.is_some() {
+            egui::TopBottomPanel::top("stalled").show(ctx, |ui| {
+                self.render_stall_banner(ui);
+            });
+        }
+
         egui::SidePanel::left("log").show(ctx, |ui| {
             ui.heading("Log");
             self.render_log(ui);
         });
 
-        egui::SidePanel::right("controls").show(ctx, |ui| {
+        let controls = egui::SidePanel::right("controls").show(ctx, |ui| {
             ui.heading("Controls");
-            self.handle_ui_controls(ui);
-            self.handle_selection(ui);
+            //Nothing is sent to a stalled Sim Contr, it'd only pile up behind what it's stuck on.
+            ui.add_enabled_ui(self.stalled.is_none(), |ui| {
+                self.handle_ui_controls(ui);
+                self.handle_selection(ui);
+            });
+        });
+        self.panel_rects.insert("controls", controls.response.rect);
+
+        //The alerts of the last seconds float over the canvas, the log keeps them all.
+        egui::Area::new("alerts")
+            .anchor(egui::Align2::RIGHT_TOP, Vec2::new(-10.0, 40.0))
+            .show(ctx, |ui| {
+                for alert in self.sim_contr.borrow().alerts.recent(TOAST_TIME) {
+                    let color = if alert.resolved { Color32::DARK_GREEN } else { Color32::DARK_RED };
+                    egui::Frame::popup(ui.style()).fill(color).show(ui, |ui| {
+                        ui.colored_label(Color32::WHITE, alert.describe());
+                    });
+                }
+            });
+
+        let timeline = egui::TopBottomPanel::bottom("timeline").show(ctx, |ui| {
+            self.render_timeline(ui);
         });
+        self.panel_rects.insert("timeline", timeline.response.rect);
 
         let sim_control_log_vec = &self.sim_contr.borrow().log;
 
-        egui::TopBottomPanel::bottom("bottom_panel")
+        let log = egui::TopBottomPanel::bottom("bottom_panel")
             .min_height(100.0) // Minimum height
             .max_height(400.0) // Maximum height
             .resizable(true)
             .show_separator_line(true)
             .show(ctx, |ui| {
                 ui.label("Simulation controller log:");
-                egui::ScrollArea::vertical().show(ui, |ui| {
-                    for message in sim_control_log_vec {
-                        ui.label(message); // Display each message
+                render_log_rows(ui, sim_control_log_vec);
+            });
+        self.panel_rects.insert("log", log.response.rect);
+
+        if self.walkthrough.is_some() {
+            self.render_walkthrough(ctx);
+        }
+    }
+
+    //The text of the step in a window, and a yellow frame around what it talks about. The code
+    //of a step runs once, when it's reached; Next is enabled when the step has happened.
+    fn render_walkthrough(&mut self, ctx: &Context) {
+        let Some(step) = self.walkthrough.as_ref().and_then(|walkthrough| walkthrough.current()) else {
+            return;
+        };
+        let Some(walkthrough) = self.walkthrough.as_mut() else {
+            return;
+        };
+        if !walkthrough.started {
+            walkthrough.started = true;
+            if let Some(code) = step.code {
+                if let Err(e) = scenario::run_snippet(code, self.sim_contr.clone()) {
+                    walkthrough.error = Some(e.to_string());
+                }
+            }
+        }
+        let done = (step.done)(&self.sim_contr.borrow());
+
+        let highlighted = match step.highlight {
+            Highlight::Canvas => self.panel_rects.get("canvas").copied(),
+            Highlight::Controls => self.panel_rects.get("controls").copied(),
+            Highlight::Log => self.panel_rects.get("log").copied(),
+            Highlight::Timeline => self.panel_rects.get("timeline").copied(),
+            Highlight::Drone(id) => self.drones
+                .iter()
+                .find(|drone| drone.node_id == Some(id))
+                .map(|drone| egui::Rect::from_min_size(egui::Pos2::new(drone.position.x, drone.position.y), Vec2::new(50.0, 50.0))),
+        };
+        if let Some(rect) = highlighted {
+            //A slow blink, so it's seen without hiding what's under it.
+            let alpha = (ctx.input(|input| input.time) * 3.0).sin().abs() * 200.0 + 55.0;
+            let color = Color32::from_rgba_unmultiplied(255, 220, 0, alpha as u8);
+            ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("walkthrough_highlight")))
+                .rect_stroke(rect.expand(4.0), 6.0, (3.0, color));
+        }
+
+        let mut next = false;
+        let mut close = false;
+        let step_number = walkthrough.step + 1;
+        egui::Window::new("Walkthrough")
+            .anchor(egui::Align2::CENTER_TOP, Vec2::new(0.0, 40.0))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.heading(format!("{}/{}: {}", step_number, WALKTHROUGH.len(), step.title));
+                ui.label(step.text);
+                if let Some(error) = &walkthrough.error {
+                    ui.colored_label(Color32::RED, format!("the step failed: {}", error));
+                } else if !done {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(format!("waiting for {}...", step.waiting_for));
+                    });
+                }
+                ui.horizontal(|ui| {
+                    let last = step_number == WALKTHROUGH.len();
+                    if ui.add_enabled(done || walkthrough.error.is_some(), egui::Button::new(if last { "Finish" } else { "Next" })).clicked() {
+                        if last {
+                            close = true;
+                        } else {
+                            next = true;
+                        }
+                    }
+                    if !last && ui.button("Skip the walkthrough").clicked() {
+                        close = true;
                     }
                 });
             });
+        if next {
+            walkthrough.next();
+        }
+        if close {
+            self.walkthrough = None;
+        }
+    }
+}
+
+//A simulation open in the window.
+struct Tab {
+    name: String,
+    app: SimulationApp,
+}
+
+//The simulations opened from the tab bar, with the threads of their drones: they're shut down
+//when their tab is closed, or when the window is.
+type OpenedSimulations = Rc<RefCell<Vec<(Rc<RefCell<SimulationControl>>, Vec<JoinHandle<()>>)>>>;
+
+//Every simulation open in the same window, one tab each, to compare two topologies side by
+//side. They all keep running, only the chosen one is drawn. The first tab is the simulation
+//started by main, the others are opened with an input file.
+pub struct Workspace {
+    tabs: Vec<Tab>,
+    current: usize,
+    open_file: String, //The input file written next to the Open button.
+    opened: OpenedSimulations,
+    status: Option<String>, //Why the last file couldn't be opened.
+}
+
+impl Workspace {
+    fn new(sim_contr: Rc<RefCell<SimulationControl>>, opened: OpenedSimulations) -> Self {
+        Workspace {
+            tabs: vec![Tab { name: "main".to_string(), app: SimulationApp::new(sim_contr) }],
+            current: 0,
+            open_file: "inputs/input_star.toml".to_string(),
+            opened,
+            status: None,
+        }
+    }
+
+    fn open_tab(&mut self) {
+        let file = self.open_file.trim().to_string();
+        //parse_config would panic on a bad file, and take the whole window with it.
+        let config = match fs::read_to_string(&file).map_err(|e| e.to_string()).and_then(|text| toml::from_str::<Config>(&text).map_err(|e| e.to_string())) {
+            Ok(config) => config,
+            Err(e) => {
+                self.status = Some(format!("{} not opened: {}", file, e));
+                return;
+            }
+        };
+        let (mut sim_contr, handles) = initialize_edited(config, Some(&file));
+        //Every tab exports its own stats when it's shut down, the main one keeps stats.csv.
+        let name = Path::new(&file).file_stem().map_or(file.clone(), |stem| stem.to_string_lossy().to_string());
+        sim_contr.set_stats_file(format!("stats_{}_{}.csv", self.tabs.len(), name));
+        let sim_contr = Rc::new(RefCell::new(sim_contr));
+        self.opened.borrow_mut().push((sim_contr.clone(), handles));
+        self.tabs.push(Tab { name, app: SimulationApp::new(sim_contr) });
+        self.current = self.tabs.len() - 1;
+        self.status = None;
+    }
+
+    fn open_walkthrough(&mut self) {
+        let (mut sim_contr, handles) = initialize_edited(walkthrough_config(), None);
+        sim_contr.set_stats_file(format!("stats_{}_walkthrough.csv", self.tabs.len()));
+        let sim_contr = Rc::new(RefCell::new(sim_contr));
+        self.opened.borrow_mut().push((sim_contr.clone(), handles));
+        let mut app = SimulationApp::new(sim_contr);
+        app.walkthrough = Some(Walkthrough::default());
+        self.tabs.push(Tab { name: "walkthrough".to_string(), app });
+        self.current = self.tabs.len() - 1;
+        self.status = None;
+    }
 
+    fn close_tab(&mut self, index: usize) {
+        let tab = self.tabs.remove(index);
+        let sim_contr = tab.app.sim_contr;
+        let position = self.opened.borrow().iter().position(|(opened, _)| Rc::ptr_eq(opened, &sim_contr));
+        match position {
+            Some(position) => {
+                let (_, handles) = self.opened.borrow_mut().remove(position);
+                sim_contr.borrow_mut().shutdown_all(handles);
+            }
+            //The main simulation, its threads are joined by the caller.
+            None => sim_contr.borrow_mut().shutdown(),
+        }
+        if self.current >= index && self.current > 0 {
+            self.current -= 1;
+        }
+    }
+
+    //The tab keeps its Sim Contr, with a new network inside: the threads of the new drones are
+    //kept with the ones of the opened simulations.
+    fn restart_tab(&mut self, index: usize) {
+        let app = &mut self.tabs[index].app;
+        let handles = app.sim_contr.borrow_mut().restart();
+        app.restarted();
+        let mut opened = self.opened.borrow_mut();
+        match opened.iter_mut().find(|(opened, _)| Rc::ptr_eq(opened, &app.sim_contr)) {
+            Some((_, tab_handles)) => tab_handles.extend(handles),
+            None => opened.push((app.sim_contr.clone(), handles)),
+        }
+    }
+
+    fn render_tab_bar(&mut self, ctx: &Context) {
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let mut close = None;
+                for (index, tab) in self.tabs.iter().enumerate() {
+                    if ui.selectable_label(index == self.current, &tab.name).clicked() {
+                        self.current = index;
+                    }
+                    //The main simulation is closed with the window.
+                    if index > 0 && ui.small_button("x").clicked() {
+                        close = Some(index);
+                    }
+                }
+                if let Some(index) = close {
+                    self.close_tab(index);
+                }
+                ui.separator();
+                ui.add(egui::TextEdit::singleline(&mut self.open_file).desired_width(200.0));
+                if ui.button("Open").clicked() {
+                    self.open_tab();
+                }
+                //A small network of its own in a new tab, with the steps of the walkthrough over it.
+                if ui.button("Walkthrough").clicked() {
+                    self.open_walkthrough();
+                }
+                if let Some(status) = &self.status {
+                    ui.colored_label(Color32::RED, status);
+                }
+            });
+        });
+    }
+}
+
+impl App for Workspace {
+    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        for tab in self.tabs.iter_mut() {
+            tab.app.refresh();
+        }
+        if self.tabs[0].app.sim_contr.borrow().shutdown_requested {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+        //Events arrive even when nobody touches the window, so I keep repainting.
+        ctx.request_repaint_after(Duration::from_millis(100));
+
+        self.render_tab_bar(ctx);
+        self.tabs[self.current].app.show(ctx);
+        if self.tabs[self.current].app.restart_requested {
+            self.restart_tab(self.current);
+        }
+    }
+}
+
+//Only the rows inside the visible part of the scroll area get a label, so the cost of a frame
+//doesn't depend on how long the log is. The rows don't wrap, to keep them all the same height.
+fn render_log_rows(ui: &mut egui::Ui, entries: &[String]) {
+    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+    egui::ScrollArea::both()
+        .auto_shrink([false, false])
+        .stick_to_bottom(true)
+        .show_rows(ui, row_height, entries.len(), |ui, row_range| {
+            for entry in &entries[row_range] {
+                ui.add(egui::Label::new(entry).wrap(false));
+            }
+        });
+}
+
+//A line of the values scaled to the highest one, with the last value in the label.
+fn render_chart(ui: &mut egui::Ui, label: &str, values: &[f64], color: Color32) {
+    ui.label(format!("{}: {:.1}", label, values.last().copied().unwrap_or(0.0)));
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(ui.available_width(), 40.0), egui::Sense::hover());
+    ui.painter().rect_stroke(rect, 0.0, (1.0, Color32::GRAY));
+    if values.len() < 2 {
+        return;
+    }
+    let max = values.iter().copied().fold(f64::EPSILON, f64::max);
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let x = rect.left() + rect.width() * index as f32 / (values.len() - 1) as f32;
+            let y = rect.bottom() - rect.height() * (value / max) as f32;
+            egui::Pos2::new(x, y)
+        })
+        .collect::<Vec<egui::Pos2>>();
+    ui.painter().add(egui::Shape::line(points, (1.5, color)));
+}
+
+//The color of the file, or one of the palette in the order the groups were defined.
+fn group_color(group: &GroupDefinition, index: usize) -> Color32 {
+    const PALETTE: [Color32; 6] = [Color32::GOLD, Color32::LIGHT_BLUE, Color32::LIGHT_GREEN, Color32::from_rgb(255, 120, 200), Color32::from_rgb(255, 140, 0), Color32::from_rgb(170, 120, 255)];
+    match group.color {
+        Some([r, g, b]) => Color32::from_rgb(r, g, b),
+        None => PALETTE[index % PALETTE.len()],
     }
 }
 
+fn timeline_color(kind: TimelineKind) -> Color32 {
+    match kind {
+        TimelineKind::Crash => Color32::RED,
+        TimelineKind::Spawn => Color32::GREEN,
+        TimelineKind::Reboot => Color32::LIGHT_BLUE,
+        TimelineKind::Flood => Color32::YELLOW,
+        TimelineKind::Fault => Color32::from_rgb(255, 140, 0),
+        TimelineKind::Alert => Color32::from_rgb(200, 0, 200),
+        TimelineKind::Step => Color32::WHITE,
+        TimelineKind::Cancel => Color32::GRAY,
+    }
+}
+
+//A bar for every number of hops, from the shortest to the longest route seen.
+fn render_histogram(ui: &mut egui::Ui, distribution: &BTreeMap<usize, u64>, color: Color32) {
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(ui.available_width(), 40.0), egui::Sense::hover());
+    ui.painter().rect_stroke(rect, 0.0, (1.0, Color32::GRAY));
+    let (Some(min), Some(max)) = (distribution.keys().next().copied(), distribution.keys().last().copied()) else {
+        return;
+    };
+    let highest = distribution.values().copied().max().unwrap_or(1).max(1);
+    let width = rect.width() / (max - min + 1) as f32;
+    for (hops, n) in distribution.iter() {
+        let left = rect.left() + width * (hops - min) as f32;
+        let height = (rect.height() - 12.0) * *n as f32 / highest as f32;
+        let bar = egui::Rect::from_min_max(
+            egui::Pos2::new(left + 1.0, rect.bottom() - 12.0 - height),
+            egui::Pos2::new(left + width - 1.0, rect.bottom() - 12.0),
+        );
+        ui.painter().rect_filled(bar, 0.0, color);
+        ui.painter().text(
+            egui::Pos2::new(left + width / 2.0, rect.bottom() - 1.0),
+            egui::Align2::CENTER_BOTTOM,
+            hops.to_string(),
+            egui::FontId::proportional(10.0),
+            Color32::GRAY,
+        );
+    }
+}
 
-pub fn run_simulation_gui(sim_contr: Rc<RefCell<SimulationControl>>) {
+//Returns the threads of the simulations still open in the other tabs, already shut down, and of
+//the restarts of sim_contr: sim_contr itself is left to the caller, like before.
+pub fn run_simulation_gui(sim_contr: Rc<RefCell<SimulationControl>>) -> Vec<JoinHandle<()>> {
     let options = NativeOptions::default();
+    let opened = OpenedSimulations::default();
+    let workspace = Workspace::new(sim_contr.clone(), opened.clone());
     eframe::run_native(
         "SkyLink Simulation",
         options,
-        Box::new(|_cc| Box::new(SimulationApp::new(sim_contr))),
+        Box::new(|_cc| Box::new(workspace)),
     ).expect("Failed to start GUI");
+
+    let mut handles = Vec::new();
+    for (opened, tab_handles) in opened.take() {
+        //The main simulation is in here only if it was restarted, the caller shuts it down.
+        if Rc::ptr_eq(&opened, &sim_contr) {
+            handles.extend(tab_handles);
+        } else {
+            opened.borrow_mut().shutdown_all(tab_handles);
+        }
+    }
+    handles
 }
@@ -1,83 +1,1700 @@
-use crossbeam_channel::{select, unbounded, Receiver, Sender};
+use crossbeam_channel::{never, select, unbounded, Receiver, SendError, Sender, TrySendError};
 use std::thread::JoinHandle;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime};
 use wg_2024::controller::{DroneCommand, DroneEvent};
 use wg_2024::controller::DroneCommand::{AddSender, RemoveSender};
 use wg_2024::drone::*;
 use wg_2024::network::NodeId;
-use wg_2024::packet::Packet;
+use wg_2024::network::SourceRoutingHeader;
+use wg_2024::packet::{FloodRequest, NodeType, Packet, PacketType};
+use serde::{Deserialize, Serialize};
 use crate::skylink_drone::drone::SkyLinkDrone;
+use crate::skylink_drone::commands::{EnergyBudget, DroneExit, LinkImpairment, PacketFaults, SkyLinkCommand};
+use crate::skylink_drone::counters::DroneCounters;
+use crate::skylink_drone::reorder::Reorder;
+use crate::skylink_drone::loss::BurstLoss;
+use crate::skylink_drone::dedup::DedupWindow;
+use crate::skylink_drone::eviction::LinkDown;
+use crate::skylink_drone::flood_cache::FloodCacheLimits;
+use crate::skylink_drone::state::DroneState;
+use crate::skylink_drone::events::TimedEvent;
+use crate::skylink_drone::gossip::{Gossip, GossipMailboxes, GossipMessage};
+use crate::skylink_drone::hooks::HooksFactory;
+use crate::skylink_drone::handshake::{self, HandshakeReport};
+use crate::skylink_drone::heartbeat::Heartbeat;
+use crate::health::HealthMonitor;
+use crate::initializer::{drone_seed, initialize_from_state, packet_channel};
+use crate::snapshot::{DroneSnapshot, EndpointSnapshot, SimulationSnapshot, SnapshotDiff};
+use crate::stats_series::StatsSeries;
+use crate::ipc::{self, IpcCall};
+use crate::sessions::SessionTable;
+use crate::otlp;
+use crate::memory::{MemoryLimits, MemoryReport};
+use crate::regions::{self, Region};
+use crate::filters::FilterConfig;
+use crate::capacity::LinkCapacityConfig;
+use crate::latency::{delays_of, ProcessingDelayConfig};
+use crate::link_rate::{rates_of, LinkRateConfig};
+use crate::link_pdr::{self, link_key, pdrs_of};
+use crate::discovery::{self, DiscoveryTracker};
+use crate::faults::{FaultAction, FaultConfig, FaultSchedule};
+use crate::alerts::{AlertConfig, AlertMonitor};
+use crate::report::{self, ReportData};
+use crate::summary::RunSummary;
+use crate::node_logs::NodeLogs;
+use crate::journal::EventJournal;
+use crate::executor::join_drones;
+use crate::drone_capture::DroneCapture;
+use crate::skylink_drone::tap::TapRecord;
+use crate::ack_sink::{AckSinkConfig, AckSinks};
+use crate::traffic_matrix::TrafficMatrix;
+use crate::route_cache::RouteCache;
+use crate::review::Mutation;
+use crate::timeline::{Timeline, TimelineKind};
+use crate::sla::{SlaMonitor, SlaProbeConfig};
+use crate::groups::{self, GroupDefinition};
+use crate::tags::{Annotations, TagConfig};
+use crate::telemetry::{LogVerbosity, TelemetryConfig};
+use crate::audit::{AuditLog, CommandOrigin};
+use crate::bandwidth::BandwidthUsage;
+use crate::routing::{self, RoutingConfig, RoutingStrategy, StrategyStats};
+use crate::repro::{ReproAction, ReproRecorder};
+use crate::inbox::ServerInboxes;
+use crate::hop_counts::HopCountTable;
+use crate::dead_letters::{DeadLetterQueue, DeadLetterReason};
+use crate::profiles::{self, NodeGroup, Profile};
+use crate::flow::{FlowControlConfig, Transfer, TRANSFER_SESSION_BASE};
+use crate::skylink_drone::links::packet_size;
+use crate::traceroute::{self, PingReport, Traceroute, PROBE_SESSION_BASE, PROBE_TIMEOUT};
+
+//The log keeps only the most recent entries, the older ones are in the exports anyway.
+const MAX_LOG_ENTRIES: usize = 100_000;
+//Under a flood storm, a single call of process_events writes at most this many log lines,
+//the other events only go to stats and sessions and are summed up in a single line.
+const MAX_LOG_LINES_PER_CALL: usize = 500;
+//And it stops reading events after this long, the rest is read at the next call (next frame).
+const EVENT_BUDGET: Duration = Duration::from_millis(20);
+//The utilization of the links is measured over this long.
+const UTILIZATION_PERIOD: Duration = Duration::from_secs(1);
+//A reboot waits at most this long for the old drone to stop, then starts the new one anyway.
+const REBOOT_TIMEOUT: Duration = Duration::from_secs(2);
+//shutdown_all waits at most this long for the drones to say they stopped.
+const SHUTDOWN_WAIT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeStats {
+    pub packets_sent: u64,
+    pub packets_dropped: u64,
+    pub shortcuts: u64,
+    pub queue_len: u64, //Packets waiting in the channel of the node the last time I looked.
+    pub queue_peak: u64,
+    pub flood_cache: u64, //Floods remembered by the drone, to spot caches that never stop growing.
+    pub timed_hops: u64,
+    pub mean_hop_latency_ms: f64, //Over the hops sent by the node whose previous hop was seen too.
+    pub link_utilization: f32, //Of the busiest link with a capacity the node sends on, 1.0 is a full link.
+    pub events_suppressed: u64, //Events the drone didn't send me, being over the rate limit.
+    //Counted by the SkyLink drones themselves (packets_dropped only counts the PacketDropped events).
+    pub fragments_forwarded: u64,
+    pub fragments_dropped: u64,
+    pub nacks_generated: u64,
+    pub floods_handled: u64,
+    pub congestion_drops: u64, //Fragments dropped on a full queue of the next hop (bounded channels only).
+    pub rate_limited: u64, //Fragments nacked for going over the packet rate of a link, see set_link_rate.
+    pub fragments_duplicated: u64, //By the injected faults, see set_packet_faults.
+    pub fragments_corrupted: u64,
+    pub fragments_reordered: u64, //By the jitter mode, see set_reorder.
+    pub duplicates_suppressed: u64, //Copies of fragments dropped by the dedup window, see dedup.rs.
+    pub energy_left: Option<u64>, //Only for the drones with a battery, see energy.rs.
+    pub terminated: bool, //The thread of the drone is over, see SkyLinkDrone::terminated.
+}
+
+//The gossip of the drones as the initializer set it up: every node's mailbox, and a copy of the
+//receiving end of every drone's one, for when a drone with that id is started again.
+pub struct DroneGossip {
+    pub mailboxes: GossipMailboxes,
+    pub drone_mailboxes: HashMap<NodeId, Receiver<GossipMessage>>,
+    pub period: Duration,
+    pub fanout: usize,
+}
 
 pub struct SimulationControl{
-    node_send: HashMap<NodeId, Sender<DroneCommand>>,
+    pub(crate) node_send: HashMap<NodeId, Sender<DroneCommand>>,
+    crashed_send: HashMap<NodeId, Sender<DroneCommand>>,
     node_recv: Receiver<DroneEvent>,
+    event_batch_recv: Receiver<Vec<TimedEvent>>, //Events of the drones that send them in batches.
     channel_for_drone: Sender<DroneEvent>, // questo serve così ogni volta che creo un nuovo drone, quando gli devo dare il channel per comunicare con il drone, mi limito a clonare questo
-    all_sender_packets: HashMap<NodeId, Sender<Packet>>, //hashmap con tutti i sender packet così puoi clonarli nel spawn
+    pub(crate) all_sender_packets: HashMap<NodeId, Sender<Packet>>, //hashmap con tutti i sender packet così puoi clonarli nel spawn
     pub(crate) network_graph: HashMap<NodeId, Vec<NodeId>>,
+    pub(crate) node_types: HashMap<NodeId, NodeType>,
     pub(crate) log: Vec<String>,
+    pub(crate) node_pdr: HashMap<NodeId, f32>,
+    pub(crate) link_pdrs: HashMap<(NodeId, NodeId), f32>, //See link_pdr.rs, the key is link_key(a, b).
+    pub(crate) crashed: HashSet<NodeId>,
+    pub(crate) stats: HashMap<NodeId, NodeStats>,
+    pub(crate) paused: bool,
+    pub(crate) stats_series: StatsSeries,
+    ipc_recv: Option<Receiver<IpcCall>>,
+    shutdown_recv: Option<Receiver<()>>,
+    pub(crate) shutdown_requested: bool,
+    dashboard: Option<Arc<Mutex<String>>>,
+    dashboard_updated: Option<Instant>,
+    pub(crate) sessions: SessionTable,
+    otlp_endpoint: Option<String>,
+    channel_capacity: Option<usize>,
+    send_timeout: Duration,
+    counters: HashMap<NodeId, Arc<DroneCounters>>, //Written by the drones themselves, see read_counters.
+    memory_limits: MemoryLimits,
+    pub(crate) memory: MemoryReport,
+    memory_checked: Option<Instant>,
+    memory_warned: HashSet<String>, //Structures already reported, so the log isn't filled with the same warning.
+    pub(crate) link_activity: HashMap<(NodeId, NodeId), u64>, //Packets sent on every link during the last call of process_events.
+    logged_events: usize,
+    coalesced_events: u64,
+    skylink_send: HashMap<NodeId, Sender<SkyLinkCommand>>, //For the commands outside of wg_2024.
+    pub(crate) regions: Vec<Region>,
+    positions: HashMap<NodeId, (f32, f32)>, //Where the nodes are on the GUI canvas, needed by the regions.
+    link_impairments: HashMap<(NodeId, NodeId), LinkImpairment>, //The last ones sent to the drones.
+    stats_file: String, //Where the stats are exported when the simulation shuts down.
+    link_capacity: LinkCapacityConfig,
+    link_bytes: HashMap<(NodeId, NodeId), u64>, //Bytes sent on every link since the last utilization update.
+    pub(crate) bandwidth: BandwidthUsage, //Bytes by class of node and kind of packet, for the report.
+    pub(crate) link_utilization: HashMap<(NodeId, NodeId), f32>, //Only the links with a capacity.
+    utilization_updated: Instant,
+    pub(crate) discovery: DiscoveryTracker, //Gossip and floods reaching the clients, to compare the two.
+    faults: FaultSchedule, //The timeline of the faults, from the input file or from a scenario.
+    pub(crate) filters: HashMap<NodeId, FilterConfig>, //The rules of the drones that have some.
+    pub(crate) processing_delays: Vec<ProcessingDelayConfig>, //As in the input file, with the changes made while running.
+    pub(crate) link_rates: Vec<LinkRateConfig>, //The same.
+    pub(crate) packet_faults: HashMap<NodeId, PacketFaults>, //Of the drones that have some.
+    pub(crate) reorder: HashMap<NodeId, Reorder>, //The drones in jitter mode.
+    pub(crate) burst_loss: HashMap<NodeId, BurstLoss>, //The drones that drop in bursts.
+    pub(crate) energy: HashMap<NodeId, EnergyBudget>, //The batteries, a drone started again gets a full one.
+    pub(crate) telemetry: HashMap<NodeId, TelemetryConfig>, //Of the nodes that aren't at the defaults.
+    pub(crate) traceroute: Option<Traceroute>, //The last one started, with its answers so far.
+    pub(crate) alerts: AlertMonitor, //The rules of the input file, with the alerts they raised.
+    pub(crate) inbox: ServerInboxes, //The messages reaching the servers, put back together.
+    pub(crate) transfers: Vec<Transfer>, //The messages sent with a sliding window, finished ones too.
+    pub(crate) hop_counts: HopCountTable, //How long the routes of the delivered messages were.
+    flow_control: FlowControlConfig,
+    next_transfer_session: u64,
+    next_probe_session: u64,
+    handshake: Option<(Duration, Sender<HandshakeReport>)>, //Given to the drones started later, if the others have it.
+    max_events_per_s: Option<u64>, //The event rate limit of the drones, the ones started later get it too.
+    max_hops: Option<usize>, //Same for the hop limit.
+    flood_cache: FloodCacheLimits, //And for how long the drones remember the floods.
+    dedup: Option<DedupWindow>, //And the fragments they forwarded.
+    tick: Option<Duration>, //And how often they wake up on their own.
+    event_batching: Option<(Sender<Vec<TimedEvent>>, usize, Duration)>, //Given to the drones started later, if the others have it.
+    drone_gossip: Option<DroneGossip>, //Same for the gossip.
+    drone_hooks: Option<HooksFactory>, //Same for the hooks.
+    drone_seed: Option<u64>, //The seed of the drops of the drones (see initializer::drone_seed), the ones started later get it too.
+    handshake_recv: Receiver<HandshakeReport>,
+    heartbeat: Option<Sender<Heartbeat>>, //Given to the drones started later, if the others have it.
+    heartbeat_recv: Receiver<Heartbeat>,
+    eviction: Option<(u32, Sender<LinkDown>)>, //Given to the drones started later, if the others have it.
+    link_down_recv: Receiver<LinkDown>,
+    pub(crate) evicted_links: HashSet<(NodeId, NodeId)>, //(drone, neighbour): the drone removed the neighbour on its own.
+    pub(crate) health: Option<HealthMonitor>, //The last heartbeat of every drone, if they beat.
+    pub(crate) asymmetric_links: HashSet<(NodeId, NodeId)>, //(drone, neighbour): the neighbour has no channel back.
+    pub(crate) profiles: Vec<Profile>, //The built-in ones and the ones of the input file.
+    node_latency: HashMap<NodeId, Duration>, //Set by the profiles, added to every link the drone sends on.
+    pub(crate) dead_letters: DeadLetterQueue, //The shortcuts that couldn't be delivered either.
+    pub(crate) crash_history: Vec<NodeId>, //Every crash of the run, in order, the ones of the shutdown aside.
+    pending_reboots: HashMap<NodeId, (Option<Arc<DroneCounters>>, Instant)>, //The old counters and the deadline, see finish_reboots.
+    pub(crate) faults_injected: u64,
+    node_logs: Option<NodeLogs>, //A log file per node, when asked for.
+    journal: Option<EventJournal>, //Every event and timeline entry, synced to the disk as the run goes.
+    captures: HashMap<NodeId, (String, DroneCapture, Receiver<TapRecord>)>, //The tapped drones, with the file of their capture.
+    pub(crate) ack_sinks: AckSinks, //The endpoints that ack every fragment, played by me.
+    pub(crate) route_cache: RouteCache, //The routes of the clients for send_to.
+    pub(crate) timeline: Timeline, //Crashes, spawns, floods, faults, alerts and scenario steps.
+    pub(crate) sla: SlaMonitor, //The client/server pairs probed at every period.
+    pub(crate) groups: Vec<GroupDefinition>, //The named groups, in the order they were defined.
+    pub(crate) annotations: Annotations, //The tags and notes of the nodes and links, see tags.rs.
+    pub(crate) audit: AuditLog, //Every command sent to every node, with who asked for it.
+    pub(crate) routing: RoutingConfig, //How send_to routes the messages.
+    pub(crate) repro: ReproRecorder, //What was done to the network, for the repro of a failed scenario.
+    pub(crate) segmented: bool, //Run by a Coordinator, with the other segments past the foreign links.
+    pub(crate) foreign_shortcuts: Vec<Packet>, //For the nodes of the other segments, the Coordinator takes them.
+}
+
+//What the web dashboard receives at every refresh.
+#[derive(Serialize)]
+struct DashboardState {
+    snapshot: SimulationSnapshot,
+    log: Vec<String>,
 }
 
 impl SimulationControl{
-    pub fn new(node_send: HashMap<NodeId, Sender<DroneCommand>>, node_recv: Receiver<DroneEvent>, channel_for_drone :Sender<DroneEvent> , all_sender_packets: HashMap<NodeId, Sender<Packet>>, network_graph: HashMap<NodeId, Vec<NodeId>>)->Self{
+    pub fn new(node_send: HashMap<NodeId, Sender<DroneCommand>>, node_recv: Receiver<DroneEvent>, channel_for_drone :Sender<DroneEvent> , all_sender_packets: HashMap<NodeId, Sender<Packet>>, network_graph: HashMap<NodeId, Vec<NodeId>>, node_types: HashMap<NodeId, NodeType>, node_pdr: HashMap<NodeId, f32>)->Self{
         SimulationControl{
             node_send,
+            crashed_send: HashMap::new(),
             node_recv,
+            event_batch_recv: never(),
             channel_for_drone,
             all_sender_packets,
             network_graph,
+            node_types,
             log: Vec::new(),
+            node_pdr,
+            link_pdrs: HashMap::new(),
+            crashed: HashSet::new(),
+            stats: HashMap::new(),
+            paused: false,
+            stats_series: StatsSeries::new(Duration::from_secs(1)),
+            ipc_recv: None,
+            shutdown_recv: None,
+            shutdown_requested: false,
+            dashboard: None,
+            dashboard_updated: None,
+            sessions: SessionTable::default(),
+            otlp_endpoint: None,
+            channel_capacity: None,
+            send_timeout: Duration::from_millis(50),
+            counters: HashMap::new(),
+            memory_limits: MemoryLimits::default(),
+            memory: MemoryReport::default(),
+            memory_checked: None,
+            memory_warned: HashSet::new(),
+            link_activity: HashMap::new(),
+            logged_events: 0,
+            coalesced_events: 0,
+            skylink_send: HashMap::new(),
+            regions: Vec::new(),
+            positions: HashMap::new(),
+            link_impairments: HashMap::new(),
+            stats_file: "stats.csv".to_string(),
+            link_capacity: LinkCapacityConfig::default(),
+            link_bytes: HashMap::new(),
+            bandwidth: BandwidthUsage::default(),
+            link_utilization: HashMap::new(),
+            utilization_updated: Instant::now(),
+            discovery: DiscoveryTracker::default(),
+            faults: FaultSchedule::default(),
+            filters: HashMap::new(),
+            processing_delays: Vec::new(),
+            link_rates: Vec::new(),
+            packet_faults: HashMap::new(),
+            reorder: HashMap::new(),
+            burst_loss: HashMap::new(),
+            energy: HashMap::new(),
+            telemetry: HashMap::new(),
+            traceroute: None,
+            alerts: AlertMonitor::default(),
+            inbox: ServerInboxes::default(),
+            transfers: Vec::new(),
+            hop_counts: HopCountTable::default(),
+            flow_control: FlowControlConfig::default(),
+            next_transfer_session: TRANSFER_SESSION_BASE,
+            next_probe_session: PROBE_SESSION_BASE,
+            handshake: None,
+            max_events_per_s: None,
+            max_hops: None,
+            flood_cache: FloodCacheLimits::default(),
+            dedup: None,
+            tick: None,
+            event_batching: None,
+            drone_gossip: None,
+            drone_hooks: None,
+            drone_seed: None,
+            handshake_recv: never(),
+            heartbeat: None,
+            heartbeat_recv: never(),
+            eviction: None,
+            link_down_recv: never(),
+            evicted_links: HashSet::new(),
+            health: None,
+            asymmetric_links: HashSet::new(),
+            profiles: profiles::builtin_profiles(),
+            node_latency: HashMap::new(),
+            dead_letters: DeadLetterQueue::default(),
+            crash_history: Vec::new(),
+            pending_reboots: HashMap::new(),
+            faults_injected: 0,
+            node_logs: None,
+            journal: None,
+            captures: HashMap::new(),
+            ack_sinks: AckSinks::default(),
+            route_cache: RouteCache::default(),
+            timeline: Timeline::default(),
+            sla: SlaMonitor::default(),
+            groups: Vec::new(),
+            annotations: Annotations::default(),
+            audit: AuditLog::default(),
+            routing: RoutingConfig::default(),
+            repro: ReproRecorder::default(),
+            segmented: false,
+            foreign_shortcuts: Vec::new(),
+        }
+    }
+
+    pub fn run(&mut self){
+        loop{
+            select! {
+            recv(self.node_recv) -> e =>{
+                    if let Ok(event) = e {
+                        self.logged_events = 0; //Every event is logged when running on its own thread.
+                        self.add_to_log(event);
+                    }
+                }
+            }
+        }
+    }
+
+    // Non blocking version of run, to be called periodically by the frontends (GUI/TUI)
+    // that own the controller in their own thread.
+    pub fn process_events(&mut self){
+        //The ipc requests are served even when paused, otherwise nobody could resume from outside.
+        if let Some(ipc_recv) = self.ipc_recv.clone() {
+            while let Ok(call) = ipc_recv.try_recv() {
+                let origin = self.audit.set_origin(CommandOrigin::Api);
+                let response = ipc::handle_request(self, call.request);
+                self.audit.set_origin(origin);
+                let _ = call.reply.send(response);
+            }
+        }
+        if let Some(shutdown_recv) = &self.shutdown_recv {
+            if shutdown_recv.try_recv().is_ok() && !self.shutdown_requested {
+                self.shutdown_requested = true;
+                self.log.push("shutdown requested.".to_string());
+            }
+        }
+        //The reboots too, a paused network can still be fixed.
+        self.finish_reboots();
+        if self.paused {
+            return;
+        }
+        self.run_due_faults();
+        self.receive_events(Some(EVENT_BUDGET));
+        if let Some(node_logs) = self.node_logs.as_mut() {
+            node_logs.flush();
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.append_timeline(&self.timeline);
+            journal.sync_if_due();
+        }
+        self.receive_handshakes();
+        self.receive_links_down();
+        self.receive_heartbeats();
+        self.pump_transfers();
+        self.send_due_acks();
+        self.run_sla_probes();
+        self.trim_log();
+        self.read_counters();
+        self.update_queue_stats();
+        self.check_memory();
+        self.check_traceroute();
+        self.update_link_utilization();
+        self.update_discovery();
+        self.check_alerts();
+        let mut ids = self.network_graph.keys().copied().collect::<Vec<NodeId>>();
+        ids.sort();
+        self.stats_series.sample_if_due(&ids, &self.stats);
+        self.publish_dashboard();
+    }
+
+    //The dashboard state is shared with the http thread, so it only needs a JSON string
+    //and never touches the Sim Contr itself.
+    pub fn attach_dashboard(&mut self, state: Arc<Mutex<String>>) {
+        self.dashboard = Some(state);
+        self.publish_dashboard();
+    }
+
+    fn publish_dashboard(&mut self) {
+        let Some(state) = &self.dashboard else {
+            return;
+        };
+        if let Some(updated) = self.dashboard_updated {
+            if updated.elapsed() < Duration::from_millis(250) {
+                return;
+            }
+        }
+        let log_start = self.log.len().saturating_sub(100);
+        let dashboard_state = DashboardState {
+            snapshot: self.snapshot(),
+            log: self.log[log_start..].to_vec(),
+        };
+        if let Ok(json) = serde_json::to_string(&dashboard_state) {
+            *state.lock().unwrap() = json;
+        }
+        self.dashboard_updated = Some(Instant::now());
+    }
+
+    //The receiving end of the batches sent by drones configured with with_event_batching, and how
+    //they were configured, for the drones started later.
+    pub fn attach_event_batches(&mut self, event_batch_send: Sender<Vec<TimedEvent>>, max_len: usize, max_delay: Duration, event_batch_recv: Receiver<Vec<TimedEvent>>) {
+        self.event_batching = Some((event_batch_send, max_len, max_delay));
+        self.event_batch_recv = event_batch_recv;
+    }
+
+    //Every message on this channel (e.g. sent by the Ctrl-C handler) asks the frontends to close.
+    pub fn attach_shutdown_signal(&mut self, shutdown_recv: Receiver<()>) {
+        self.shutdown_recv = Some(shutdown_recv);
+    }
+
+    //Stops every drone cleanly, so their threads can be joined.
+    pub fn shutdown(&mut self) {
+        //The captures end before the drones are stopped, they'd only get the RemoveSenders of the shutdown.
+        for id in self.captures.keys().copied().collect::<Vec<NodeId>>() {
+            self.stop_capture(id);
+        }
+        let ids = self.network_graph.keys().copied().collect::<Vec<NodeId>>();
+        //First every drone (crashed ones too) forgets all its neighbours, while everyone can still
+        //receive commands, then the running ones crash. Once I drop my packet senders too, nobody
+        //can send to a drone anymore, its packet channel disconnects and its thread ends.
+        for (node, sender) in self.node_send.iter().chain(self.crashed_send.iter()) {
+            for id in ids.iter() {
+                let _ = send_command(&mut self.audit, *node, sender, RemoveSender(*id));
+            }
+        }
+        for (id, sender) in self.node_send.iter() {
+            if send_command(&mut self.audit, *id, sender, DroneCommand::Crash).is_ok() {
+                self.crashed.insert(*id);
+            }
+        }
+        self.all_sender_packets.clear();
+
+        //I read the last events before writing the exports.
+        self.paused = false;
+        self.receive_events(None);
+        self.process_events();
+        let stats_file = self.stats_file.clone();
+        self.export_stats(&stats_file);
+        self.export_sessions(&format!("{}_sessions.csv", stats_file.trim_end_matches(".csv")));
+        self.export_hop_counts(&format!("{}_hops.csv", stats_file.trim_end_matches(".csv")));
+        self.export_report(&format!("{}_report.html", stats_file.trim_end_matches(".csv")));
+        self.export_summary(&format!("{}_summary.txt", stats_file.trim_end_matches(".csv")));
+        if let Some(endpoint) = self.otlp_endpoint.clone() {
+            self.export_traces(&endpoint);
+        }
+        if let Some(node_logs) = self.node_logs.as_mut() {
+            node_logs.flush();
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.append_timeline(&self.timeline);
+            journal.sync();
+            self.log.push(format!("{} records in the journal.", journal.records));
+        }
+        self.log.push("simulation shut down.".to_string());
+    }
+
+    //Like shutdown, but first the running SkyLink drones are asked to stop (SkyLinkCommand::Shutdown):
+    //they handle what's in their queue, tell me they're done and their run() returns. The crashed
+    //ones, the others and the ones that don't answer in time are stopped by shutdown() as before.
+    //Then all the threads are joined.
+    pub fn shutdown_all(&mut self, handles: Vec<JoinHandle<()>>) {
+        //Before the drones stop, or the rest of their traffic wouldn't get to the captures.
+        for id in self.captures.keys().copied().collect::<Vec<NodeId>>() {
+            self.stop_capture(id);
+        }
+        let (exit_send, exit_recv) = unbounded::<DroneExit>();
+        let mut waiting = HashSet::new();
+        for (id, sender) in self.skylink_send.iter() {
+            if !self.crashed.contains(id) && send_skylink_command(&mut self.audit, *id, sender, SkyLinkCommand::Shutdown(exit_send.clone())).is_ok() {
+                waiting.insert(*id);
+            }
+        }
+        drop(exit_send);
+        let asked = waiting.len();
+        let mut drained = 0;
+        let deadline = Instant::now() + SHUTDOWN_WAIT;
+        while !waiting.is_empty() {
+            let Ok(exit) = exit_recv.recv_timeout(deadline.saturating_duration_since(Instant::now())) else {
+                break;
+            };
+            waiting.remove(&exit.id);
+            drained += exit.drained;
+        }
+        self.log.push(format!("{} drones exited, {} packets handled from their queues.", asked - waiting.len(), drained));
+        if !waiting.is_empty() {
+            let mut late = waiting.into_iter().collect::<Vec<NodeId>>();
+            late.sort();
+            self.log.push(format!("drones {:?} didn't exit in time, they're crashed.", late));
+        }
+        self.shutdown();
+        join_drones(handles);
+    }
+
+    //Shuts the network down and builds it again from its snapshot, in place: whoever holds the
+    //controller (the GUI, the ipc, the dashboard) keeps holding it. The crashes and the stats are
+    //put back, the packets in flight and the messages being sent are lost. Returns the threads
+    //of the new drones, the old ones end by themselves.
+    pub fn restart(&mut self) -> Vec<JoinHandle<()>> {
+        let snapshot = self.snapshot();
+        self.shutdown();
+        let (mut restarted, handles) = initialize_from_state(&snapshot);
+        restarted.ipc_recv = self.ipc_recv.take();
+        restarted.shutdown_recv = self.shutdown_recv.take();
+        restarted.dashboard = self.dashboard.take();
+        restarted.node_logs = self.node_logs.take();
+        if let Some(journal) = self.journal.take() {
+            restarted.set_journal(journal);
+        }
+        restarted.otlp_endpoint = self.otlp_endpoint.take();
+        restarted.stats_file = self.stats_file.clone();
+        for config in self.telemetry.values() {
+            restarted.set_telemetry(config.clone());
+        }
+        let origin = self.audit.set_origin(CommandOrigin::SimContr);
+        restarted.audit.set_origin(origin);
+        restarted.log.push("simulation restarted.".to_string());
+        *self = restarted;
+        handles
+    }
+
+    pub fn set_stats_file(&mut self, stats_file: String) {
+        self.stats_file = stats_file;
+    }
+
+    //Every event of a node is written to its own file in dir too, only for the nodes in only if it's given.
+    pub fn set_node_logs(&mut self, node_logs: NodeLogs) {
+        self.node_logs = Some(node_logs);
+    }
+
+    //Every event and timeline entry is written to the journal too, see journal.rs.
+    pub fn set_journal(&mut self, mut journal: EventJournal) {
+        journal.reset_timeline();
+        self.journal = Some(journal);
+    }
+
+    fn node_log(&mut self, id: NodeId, line: &str) {
+        if let Some(node_logs) = self.node_logs.as_mut() {
+            node_logs.write(id, self.node_types.get(&id), SystemTime::now(), line);
+        }
+    }
+
+    //The sessions are exported as traces to this OTLP collector when the simulation shuts down.
+    pub fn set_otlp_endpoint(&mut self, endpoint: String) {
+        self.otlp_endpoint = Some(endpoint);
+    }
+
+    pub fn export_traces(&mut self, endpoint: &str) {
+        match otlp::export_traces(&self.sessions, endpoint) {
+            Ok(n_spans) => self.log.push(format!("{} spans exported to {}.", n_spans, endpoint)),
+            Err(e) => println!("error in exporting the traces to {}: {}", endpoint, e),
+        }
+    }
+
+    pub fn attach_ipc(&mut self, ipc_recv: Receiver<IpcCall>) {
+        self.ipc_recv = Some(ipc_recv);
+    }
+
+    pub fn export_stats(&mut self, file: &str) {
+        match self.stats_series.export_csv(file) {
+            Ok(_) => self.log.push(format!("stats exported to {}.", file)),
+            Err(e) => println!("error in exporting the stats to {}: {}", file, e),
+        }
+    }
+
+    //Topology, stats, latency, alerts and the end of the log in a single page, to keep with the experiment.
+    pub fn export_report(&mut self, file: &str) {
+        let data = ReportData {
+            network_graph: &self.network_graph,
+            node_types: &self.node_types,
+            crashed: &self.crashed,
+            positions: &self.positions,
+            stats: &self.stats,
+            stats_series: &self.stats_series,
+            sessions: &self.sessions,
+            hop_counts: &self.hop_counts,
+            alerts: &self.alerts.history,
+            bandwidth: &self.bandwidth,
+            annotations: &self.annotations,
+            log: &self.log,
+        };
+        match report::write_report(&data, file) {
+            Ok(_) => self.log.push(format!("report written to {}.", file)),
+            Err(e) => println!("error in writing the report to {}: {}", file, e),
+        }
+    }
+
+    //Per node and per message, with the latency, the crashes and the faults: printed and written to the file.
+    pub fn export_summary(&mut self, file: &str) {
+        let summary = RunSummary::new(&self.node_types, &self.stats, &self.sessions, &self.crash_history, self.faults_injected);
+        println!("{}", summary.describe(false));
+        match summary.save(file) {
+            Ok(_) => self.log.push(format!("summary written to {}.", file)),
+            Err(e) => println!("error in writing the summary to {}: {}", file, e),
+        }
+    }
+
+    //Latency of every session and of its hops.
+    pub fn export_sessions(&mut self, file: &str) {
+        match self.sessions.export_csv(file) {
+            Ok(_) => self.log.push(format!("sessions exported to {}.", file)),
+            Err(e) => println!("error in exporting the sessions to {}: {}", file, e),
+        }
+    }
+
+    //The route lengths of the delivered messages, by source and destination.
+    pub fn export_hop_counts(&mut self, file: &str) {
+        match self.hop_counts.export_csv(file) {
+            Ok(_) => self.log.push(format!("hop counts exported to {}.", file)),
+            Err(e) => println!("error in exporting the hop counts to {}: {}", file, e),
+        }
+    }
+
+    //The timeline as JSON, for the runs without the GUI.
+    pub fn export_timeline(&mut self, file: &str) {
+        match self.timeline.save(file) {
+            Ok(_) => self.log.push(format!("timeline exported to {}.", file)),
+            Err(e) => println!("error in exporting the timeline to {}: {}", file, e),
+        }
+    }
+
+    //Messages attempted, delivered and failed, with their latency, for every source and destination.
+    pub fn traffic_matrix(&self) -> TrafficMatrix {
+        TrafficMatrix::new(&self.sessions)
+    }
+
+    //The drones still running, sorted by id.
+    pub fn drone_ids(&self) -> Vec<NodeId> {
+        let mut ids = self.node_types
+            .iter()
+            .filter(|(id, node_type)| matches!(node_type, NodeType::Drone) && self.node_send.contains_key(id))
+            .map(|(id, _)| *id)
+            .collect::<Vec<NodeId>>();
+        ids.sort();
+        ids
+    }
+
+    pub fn toggle_pause(&mut self){
+        self.paused = !self.paused;
+        if self.paused {
+            self.log.push("simulation controller paused.".to_string());
+        } else {
+            self.log.push("simulation controller resumed.".to_string());
+        }
+    }
+
+    //Reads the events waiting in the channels, for at most budget (None to read all of them).
+    fn receive_events(&mut self, budget: Option<Duration>){
+        let deadline = budget.map(|budget| Instant::now() + budget);
+        let in_time = |deadline: Option<Instant>| deadline.map_or(true, |deadline| Instant::now() < deadline);
+        self.link_activity.clear();
+        self.logged_events = 0;
+        self.coalesced_events = 0;
+
+        while in_time(deadline) {
+            let Ok(event) = self.node_recv.try_recv() else { break };
+            self.add_to_log(event);
+        }
+        while in_time(deadline) {
+            let Ok(batch) = self.event_batch_recv.try_recv() else { break };
+            for timed_event in batch {
+                self.add_to_log_at(&timed_event.event, timed_event.time);
+            }
+        }
+
+        if self.coalesced_events > 0 {
+            let mut links = self.link_activity.iter().collect::<Vec<(&(NodeId, NodeId), &u64)>>();
+            links.sort_by(|a, b| b.1.cmp(a.1));
+            let busiest = links
+                .iter()
+                .take(3)
+                .map(|((from, to), count)| format!("{}->{} x{}", from, to, count))
+                .collect::<Vec<String>>();
+            self.log.push(format!("... {} more events not logged (busiest links: {})", self.coalesced_events, busiest.join(", ")));
+        }
+    }
+
+    //I trim a bit more than needed, so the front of the Vec isn't moved at every call.
+    fn trim_log(&mut self){
+        if self.log.len() > MAX_LOG_ENTRIES + MAX_LOG_ENTRIES / 10 {
+            let excess = self.log.len() - MAX_LOG_ENTRIES;
+            self.log.drain(..excess);
+        }
+    }
+
+    fn add_to_log(&mut self, e: DroneEvent){
+        self.add_to_log_at(&e, SystemTime::now());
+    }
+
+    //The event is borrowed, the ones of a batch are shared with the drone that made them.
+    fn add_to_log_at(&mut self, e: &DroneEvent, time: SystemTime){
+        self.update_stats(e);
+        self.alerts.record(e);
+        //The probes are real packets for the stats and the links, but they aren't sessions.
+        let probe = self.traceroute.as_mut().map_or(false, |traceroute| traceroute.record(e, time));
+        let probe = probe || matches!(e, DroneEvent::PacketSent(packet) if handshake::is_hello(packet));
+        //The SLA probes aren't sessions either, but their nacks still tell the clients about the broken links.
+        let sla_probe = self.sla.record(e, time);
+        if let (true, DroneEvent::PacketSent(packet)) = (sla_probe, e) {
+            self.route_cache.record(packet);
+        }
+        let probe = probe || sla_probe;
+        if let Some(client) = self.discovery.record(e) {
+            if let Some(progress) = self.discovery.flood_progress(client) {
+                self.log.push(format!("flood of client {}: {}", client, discovery::describe(&progress)));
+            }
+        }
+        if let DroneEvent::PacketSent(packet) = e {
+            let hop_latency = if probe { None } else { self.sessions.record_at(packet, time) };
+            if !probe {
+                self.inbox.record(packet, &self.node_types, time);
+                self.ack_sinks.record(packet);
+                let invalidated = self.route_cache.record(packet);
+                if invalidated > 0 {
+                    self.log.push(format!("{} cached routes dropped after an ErrorInRouting nack.", invalidated));
+                }
+                let (graph, node_types, crashed) = (&self.network_graph, &self.node_types, &self.crashed);
+                self.hop_counts.record(packet, |from, to| {
+                    traceroute::shortest_route(graph, node_types, crashed, from, to).map(|route| route.len() - 1)
+                });
+            }
+            self.record_transfers(packet);
+            if let Some((node_id, latency)) = hop_latency {
+                let stats = self.stats.entry(node_id).or_default();
+                stats.timed_hops += 1;
+                stats.mean_hop_latency_ms += (latency.as_secs_f64() * 1000.0 - stats.mean_hop_latency_ms) / stats.timed_hops as f64;
+            }
+            if let Some(link) = packet_link(packet) {
+                *self.link_activity.entry(link).or_default() += 1;
+                *self.link_bytes.entry(link).or_default() += packet_size(packet);
+            }
+            self.record_bandwidth(packet);
+        }
+        if let DroneEvent::ControllerShortcut(packet) = e {
+            self.deliver_shortcut(packet.clone());
+        }
+        let source = packet_source(event_packet(e));
+        let verbosity = source.map_or(LogVerbosity::Normal, |node_id| self.verbosity(node_id));
+        if let (Some(node_logs), Some(node_id), false) = (self.node_logs.as_mut(), source, verbosity == LogVerbosity::Quiet) {
+            node_logs.write_event(node_id, self.node_types.get(&node_id), time, e);
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.append_event(e, time);
+        }
+        match verbosity {
+            LogVerbosity::Quiet => return,
+            //Not counted in the lines of the call, they'd leave the other nodes out.
+            LogVerbosity::Verbose => {
+                let (action, packet) = match e {
+                    DroneEvent::PacketSent(packet) => ("sent", packet),
+                    DroneEvent::PacketDropped(packet) => ("dropped", packet),
+                    DroneEvent::ControllerShortcut(packet) => ("shortcut", packet),
+                };
+                self.log.push(format!(
+                    "node {} {} session {} {:?} route {:?} hop {}",
+                    source.unwrap_or_default(), action, packet.session_id, packet.pack_type, packet.routing_header.hops, packet.routing_header.hop_index
+                ));
+                return;
+            }
+            LogVerbosity::Normal => {}
+        }
+        //Stats and sessions get every event, the log only the first ones of every call.
+        if self.logged_events >= MAX_LOG_LINES_PER_CALL {
+            self.coalesced_events += 1;
+            return;
+        }
+        self.logged_events += 1;
+        match e {
+            DroneEvent::PacketSent(packet) => {
+                let id_drone = packet.routing_header.hops.get(packet.routing_header.hops.len() -1).unwrap();
+                self.log.push( format!("Drone {} sent fragment {:?} of type: {:?}",id_drone ,packet.session_id, packet.pack_type))}
+            DroneEvent::PacketDropped(packet) => {
+                let id_drone = packet.routing_header.hops.get(packet.routing_header.hops.len() -1).unwrap();
+                self.log.push( format!("Drone {} dropped fragment {:?} of type: {:?}",id_drone ,packet.session_id, packet.pack_type))}
+            DroneEvent::ControllerShortcut(packet) => {
+                let id_drone = packet.routing_header.hops.get(packet.routing_header.hops.len() -1).unwrap();
+                self.log.push( format!("Received {:?} from drone {:?}", packet.pack_type, id_drone));
+            }
+        }
+    }
+
+    //A packet the drones couldn't route goes straight to its destination. The clients and the
+    //servers are played by me, so for them it's already arrived. What can't go anywhere ends
+    //in the dead letters, with the reason.
+    fn deliver_shortcut(&mut self, mut packet: Packet){
+        let Some(destination) = packet.routing_header.hops.last().copied() else {
+            self.dead_letters.push(packet, DeadLetterReason::UnknownDestination);
+            return;
+        };
+        let reason = if !self.network_graph.contains_key(&destination) {
+            //In a segment, it may be a node of another one.
+            if self.segmented {
+                self.foreign_shortcuts.push(packet);
+                return;
+            }
+            DeadLetterReason::UnknownDestination
+        } else if self.crashed.contains(&destination) {
+            DeadLetterReason::DestinationCrashed
+        } else {
+            packet.routing_header.hop_index = packet.routing_header.hops.len() - 1;
+            if matches!(self.node_types.get(&destination), Some(NodeType::Client) | Some(NodeType::Server)) {
+                self.record_transfers(&packet);
+                return;
+            }
+            match self.all_sender_packets.get(&destination) {
+                //A drone only takes a fragment as the last hop (and answers with a nack), anything
+                //else would come back here as another shortcut.
+                _ if !matches!(packet.pack_type, PacketType::MsgFragment(_)) => DeadLetterReason::DestinationIsDrone,
+                Some(sender) => match sender.try_send(packet.clone()) {
+                    Ok(()) => return,
+                    Err(TrySendError::Full(_)) => DeadLetterReason::ChannelFull,
+                    Err(TrySendError::Disconnected(_)) => DeadLetterReason::ChannelClosed,
+                },
+                None => DeadLetterReason::ChannelClosed,
+            }
+        };
+        self.log.push(format!("dead letter: {:?} for session {} to {}", reason, packet.session_id, destination));
+        self.dead_letters.push(packet, reason);
+    }
+
+    fn update_stats(&mut self, e: &DroneEvent){
+        if let Some(node_id) = packet_source(event_packet(e)) {
+            //The drones with their own counters are already counted, the events only
+            //count for the other nodes (e.g. bridges).
+            if self.counters.contains_key(&node_id) {
+                return;
+            }
+            let stats = self.stats.entry(node_id).or_default();
+            match e {
+                DroneEvent::PacketSent(_) => stats.packets_sent += 1,
+                DroneEvent::PacketDropped(_) => stats.packets_dropped += 1,
+                DroneEvent::ControllerShortcut(_) => stats.shortcuts += 1,
+            }
+        }
+    }
+
+    //No channel involved: the drones update the counters, I only copy them into the stats.
+    fn read_counters(&mut self){
+        let mut depleted = Vec::new();
+        for (id, counters) in self.counters.iter() {
+            let (packets_sent, packets_dropped, shortcuts, flood_cache) = counters.load();
+            let stats = self.stats.entry(*id).or_default();
+            stats.packets_sent = packets_sent;
+            stats.packets_dropped = packets_dropped;
+            stats.shortcuts = shortcuts;
+            stats.flood_cache = flood_cache;
+            //Said once, when the drone first goes over the limit, the counter tells the rest.
+            let events_suppressed = counters.events_suppressed.load(Ordering::Relaxed);
+            if stats.events_suppressed == 0 && events_suppressed > 0 {
+                self.log.push(format!("drone {} is over the event rate limit, its events are being suppressed.", id));
+            }
+            stats.events_suppressed = events_suppressed;
+            (stats.fragments_forwarded, stats.fragments_dropped, stats.nacks_generated, stats.floods_handled) = counters.load_forwarding();
+            //Like the rate limit, said once when the drone first drops on a full queue.
+            let congestion_drops = counters.congestion_drops.load(Ordering::Relaxed);
+            if stats.congestion_drops == 0 && congestion_drops > 0 {
+                self.log.push(format!("drone {} is dropping fragments, the queue of its next hop is full.", id));
+            }
+            stats.congestion_drops = congestion_drops;
+            let rate_limited = counters.rate_limited.load(Ordering::Relaxed);
+            if stats.rate_limited == 0 && rate_limited > 0 {
+                self.log.push(format!("drone {} is nacking fragments over the packet rate of its links.", id));
+            }
+            stats.rate_limited = rate_limited;
+            stats.fragments_duplicated = counters.fragments_duplicated.load(Ordering::Relaxed);
+            stats.fragments_corrupted = counters.fragments_corrupted.load(Ordering::Relaxed);
+            stats.fragments_reordered = counters.fragments_reordered.load(Ordering::Relaxed);
+            stats.duplicates_suppressed = counters.duplicates_suppressed.load(Ordering::Relaxed);
+            //The last thing a drone tells, said once.
+            let terminated = counters.terminated.load(Ordering::Relaxed);
+            if !stats.terminated && terminated {
+                self.log.push(format!("drone {} terminated, its channels are closed.", id));
+            }
+            stats.terminated = terminated;
+            if self.energy.contains_key(id) {
+                stats.energy_left = Some(counters.energy_left.load(Ordering::Relaxed));
+                if counters.energy_depleted.load(Ordering::Relaxed) && !self.crashed.contains(id) {
+                    depleted.push(*id);
+                }
+            }
+        }
+        //The drone is already crashing, what's left is the part of the crash that's up to me.
+        for id in depleted {
+            self.log.push(format!("drone {} ran out of energy.", id));
+            self.node_log(id, "battery empty, crashed on its own.");
+            self.crash_drone(id);
+        }
+    }
+
+    //The occupancy of every packet channel, the Sim Contr has a sender to all of them.
+    fn update_queue_stats(&mut self){
+        for (id, sender) in self.all_sender_packets.iter() {
+            let stats = self.stats.entry(*id).or_default();
+            stats.queue_len = sender.len() as u64;
+            stats.queue_peak = stats.queue_peak.max(stats.queue_len);
+        }
+    }
+
+    //The capacity used by the initializer, so spawned drones get the same kind of channel.
+    pub fn set_channel_capacity(&mut self, channel_capacity: Option<usize>, send_timeout: Duration){
+        self.channel_capacity = channel_capacity;
+        self.send_timeout = send_timeout;
+    }
+
+    pub fn set_event_rate_limit(&mut self, max_events_per_s: Option<u64>){
+        self.max_events_per_s = max_events_per_s;
+    }
+
+    pub fn set_max_hops(&mut self, max_hops: Option<usize>){
+        self.max_hops = max_hops;
+    }
+
+    pub fn set_flood_cache(&mut self, limits: FloodCacheLimits){
+        self.flood_cache = limits;
+    }
+
+    pub fn set_dedup(&mut self, window: Option<DedupWindow>){
+        self.dedup = window;
+    }
+
+    pub fn set_tick(&mut self, interval: Option<Duration>){
+        self.tick = interval;
+    }
+
+    pub fn set_drone_seed(&mut self, seed: Option<u64>){
+        self.drone_seed = seed;
+    }
+
+    pub fn set_counters(&mut self, counters: HashMap<NodeId, Arc<DroneCounters>>){
+        self.counters = counters;
+    }
+
+    pub fn set_skylink_commands(&mut self, skylink_send: HashMap<NodeId, Sender<SkyLinkCommand>>){
+        self.skylink_send = skylink_send;
+    }
+
+    pub fn set_regions(&mut self, regions: Vec<Region>){
+        self.regions = regions;
+        self.update_link_impairments();
+    }
+
+    //Replaces the filter rules of a drone, returns false if it can't receive them (e.g. not a SkyLink drone).
+    pub fn set_filters(&mut self, id: NodeId, filters: FilterConfig) -> bool {
+        let Some(sender) = self.skylink_send.get(&id) else {
+            return false;
+        };
+        if let Err(e) = send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::SetFilters(filters.rules())) {
+            println!("error in sending the filters to drone {}: {:?}", id, e);
+            return false;
+        }
+        self.log.push(format!("drone {} filters: {:?}", id, filters.rules()));
+        if filters.is_empty() {
+            self.filters.remove(&id);
+        } else {
+            self.filters.insert(id, filters);
+        }
+        true
+    }
+
+    //Makes a drone duplicate and corrupt the fragments it forwards, with these probabilities (0.0 - 1.0).
+    //Both 0 removes the faults. Returns false if the drone can't receive them (e.g. not a SkyLink drone).
+    pub fn set_packet_faults(&mut self, id: NodeId, duplicate: f32, corrupt: f32) -> bool {
+        let Some(sender) = self.skylink_send.get(&id) else {
+            return false;
+        };
+        let faults = PacketFaults { duplicate: duplicate.clamp(0.0, 1.0), corrupt: corrupt.clamp(0.0, 1.0) };
+        if let Err(e) = send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::SetPacketFaults(faults)) {
+            println!("error in sending the packet faults to drone {}: {:?}", id, e);
+            return false;
+        }
+        self.log.push(format!("drone {} duplicates {} and corrupts {} of the fragments", id, faults.duplicate, faults.corrupt));
+        if faults == PacketFaults::default() {
+            self.packet_faults.remove(&id);
+        } else {
+            self.packet_faults.insert(id, faults);
+        }
+        true
+    }
+
+    //How much a node tells, see telemetry.rs. The verbosity works for every node, the interval and
+    //the muted events only for SkyLink drones: for the others it returns false and changes nothing.
+    pub fn set_telemetry(&mut self, config: TelemetryConfig) -> bool {
+        let id = config.node;
+        if !self.network_graph.contains_key(&id) {
+            return false;
+        }
+        let telemetry = config.telemetry();
+        match self.skylink_send.get(&id) {
+            Some(sender) => {
+                if let Err(e) = send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::SetTelemetry(telemetry)) {
+                    println!("error in sending the telemetry to drone {}: {:?}", id, e);
+                    return false;
+                }
+            }
+            None if telemetry != Default::default() => return false,
+            None => {}
+        }
+        self.log.push(format!("node {} logs at {:?}, events every {:?} ms, muted {:?}", id, config.verbosity, config.interval_ms, config.mute));
+        if config.is_default() {
+            self.telemetry.remove(&id);
+        } else {
+            self.telemetry.insert(id, config);
+        }
+        true
+    }
+
+    pub fn verbosity(&self, id: NodeId) -> LogVerbosity {
+        self.telemetry.get(&id).map_or(LogVerbosity::Normal, |config| config.verbosity)
+    }
+
+    //Puts a drone in jitter mode, or takes it out of it with None: what it held leaves at once.
+    //Returns false if the drone can't receive it (e.g. not a SkyLink drone).
+    pub fn set_reorder(&mut self, id: NodeId, reorder: Option<Reorder>) -> bool {
+        let Some(sender) = self.skylink_send.get(&id) else {
+            return false;
+        };
+        if let Err(e) = send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::SetReorder(reorder)) {
+            println!("error in sending the jitter mode to drone {}: {:?}", id, e);
+            return false;
+        }
+        match reorder {
+            Some(reorder) => {
+                self.log.push(format!("drone {} holds up to {} fragments for {:?}", id, reorder.buffer, reorder.delay));
+                self.reorder.insert(id, reorder);
+            }
+            None => {
+                self.log.push(format!("drone {} forwards the fragments in order again", id));
+                self.reorder.remove(&id);
+            }
+        }
+        true
+    }
+
+    //Makes a drone drop in bursts, or goes back to its pdr with None.
+    //Returns false if the drone can't receive it (e.g. not a SkyLink drone).
+    pub fn set_burst_loss(&mut self, id: NodeId, burst_loss: Option<BurstLoss>) -> bool {
+        let Some(sender) = self.skylink_send.get(&id) else {
+            return false;
+        };
+        if let Err(e) = send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::SetBurstLoss(burst_loss)) {
+            println!("error in sending the burst loss to drone {}: {:?}", id, e);
+            return false;
+        }
+        match burst_loss {
+            Some(burst_loss) => {
+                self.log.push(format!("drone {} drops in bursts, {:.1}% of the fragments on average", id, burst_loss.mean_loss() * 100.0));
+                self.burst_loss.insert(id, burst_loss);
+            }
+            None => {
+                self.log.push(format!("drone {} drops with its pdr again", id));
+                self.burst_loss.remove(&id);
+            }
+        }
+        true
+    }
+
+    //Sets the time a drone takes on the packets it forwards, to the node or to everyone with to = None.
+    //A zero delay removes it. Returns false if the drone can't receive it (e.g. not a SkyLink drone).
+    pub fn set_processing_delay(&mut self, id: NodeId, to: Option<NodeId>, delay_ms: u64, jitter_ms: u64) -> bool {
+        let Some(sender) = self.skylink_send.get(&id) else {
+            return false;
+        };
+        let config = ProcessingDelayConfig { drone: id, to, delay_ms, jitter_ms };
+        if let Err(e) = send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::SetProcessingDelay(to, config.delay())) {
+            println!("error in sending the processing delay to drone {}: {:?}", id, e);
+            return false;
+        }
+        match to {
+            Some(to) => self.log.push(format!("drone {} processing delay to {}: {} ms (+{} ms jitter)", id, to, delay_ms, jitter_ms)),
+            None => self.log.push(format!("drone {} processing delay: {} ms (+{} ms jitter)", id, delay_ms, jitter_ms)),
+        }
+        self.processing_delays.retain(|delay| delay.drone != id || delay.to != to);
+        if delay_ms > 0 || jitter_ms > 0 {
+            self.processing_delays.push(config);
+        }
+        true
+    }
+
+    //Sets the packets per second a drone lets out to the node, or to each of its neighbours with
+    //to = None. A rate of 0 removes it. Returns false if the drone can't receive it.
+    pub fn set_link_rate(&mut self, config: LinkRateConfig) -> bool {
+        let (id, to) = (config.drone, config.to);
+        let Some(sender) = self.skylink_send.get(&id) else {
+            return false;
+        };
+        let rate = (config.packets_per_sec > 0).then(|| config.rate());
+        if let Err(e) = send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::SetLinkRate(to, rate)) {
+            println!("error in sending the link rate to drone {}: {:?}", id, e);
+            return false;
+        }
+        match (to, rate) {
+            (Some(to), Some(_)) => self.log.push(format!("drone {} sends at most {} packets/s to {}", id, config.packets_per_sec, to)),
+            (None, Some(_)) => self.log.push(format!("drone {} sends at most {} packets/s on each link", id, config.packets_per_sec)),
+            (Some(to), None) => self.log.push(format!("drone {} has no packet rate to {} anymore", id, to)),
+            (None, None) => self.log.push(format!("drone {} has no packet rate anymore", id)),
+        }
+        self.link_rates.retain(|rate| rate.drone != id || rate.to != to);
+        if rate.is_some() {
+            self.link_rates.push(config);
+        }
+        true
+    }
+
+    //Changes the effect of a region at runtime, returns false if there's no such region.
+    pub fn set_region(&mut self, index: usize, extra_drop: f32, latency_ms: u64) -> bool {
+        let Some(region) = self.regions.get_mut(index) else {
+            return false;
+        };
+        region.extra_drop = extra_drop;
+        region.latency_ms = latency_ms;
+        self.log.push(format!("region {} now has extra drop {} and latency {}ms", index, extra_drop, latency_ms));
+        self.update_link_impairments();
+        true
+    }
+
+    //Called by the GUI with the position of every node, the links are recomputed only if something moved.
+    pub fn set_positions(&mut self, positions: HashMap<NodeId, (f32, f32)>){
+        if positions != self.positions {
+            self.positions = positions;
+            self.update_link_impairments();
+        }
+    }
+
+    //Every drone is told the impairment of its links, only when it changes. Without a position
+    //a node isn't affected by the regions.
+    fn update_link_impairments(&mut self){
+        for (from, neighbours) in self.network_graph.iter() {
+            let Some(sender) = self.skylink_send.get(from) else {
+                continue;
+            };
+            for to in neighbours.iter() {
+                let mut impairment = match (self.positions.get(from), self.positions.get(to)) {
+                    (Some(from_position), Some(to_position)) => regions::link_impairment(&self.regions, *from_position, *to_position),
+                    _ => LinkImpairment::default(),
+                };
+                impairment.latency += self.node_latency.get(from).copied().unwrap_or_default();
+                let previous = self.link_impairments.get(&(*from, *to)).copied().unwrap_or_default();
+                if impairment != previous && send_skylink_command(&mut self.audit, *from, sender, SkyLinkCommand::SetLinkImpairment(*to, impairment)).is_ok() {
+                    self.link_impairments.insert((*from, *to), impairment);
+                }
+            }
+        }
+    }
+
+    //A profile of the input file replaces the built-in one with the same name.
+    pub fn add_profile(&mut self, profile: Profile){
+        self.profiles.retain(|known| known.name != profile.name);
+        self.profiles.push(profile);
+    }
+
+    //Sets the pdr and the latency of the profile on every drone of the group (the one of the
+    //profile if None). Returns false if there's no profile with that name.
+    pub fn apply_profile(&mut self, name: &str, group: Option<NodeGroup>) -> bool {
+        let Some(profile) = self.profiles.iter().find(|profile| profile.name == name).cloned() else {
+            println!("profile {} not found", name);
+            return false;
+        };
+        let group = group.unwrap_or(profile.group.clone());
+        let members = self.group_members(&group);
+        for id in members.iter() {
+            if let Some(pdr) = profile.pdr {
+                self.set_pdr(*id, pdr);
+            }
+            if let Some(latency) = profile.latency() {
+                self.node_latency.insert(*id, latency);
+            }
+        }
+        self.update_link_impairments();
+        self.log.push(format!("profile {} applied to {} drones: {:?}", name, members.len(), members));
+        true
+    }
+
+    //The drones of the group that are still up, sorted.
+    pub fn group_members(&self, group: &NodeGroup) -> Vec<NodeId> {
+        group.members(&self.network_graph, &self.node_types, &self.crashed, &self.known_groups())
+    }
+
+    //The groups defined, and one for every tag of the nodes (tag:<tag>).
+    pub fn known_groups(&self) -> Vec<GroupDefinition> {
+        let mut groups = self.groups.clone();
+        groups.extend(self.annotations.groups());
+        groups
+    }
+
+    //Replaces all the tags and notes, with the [[tag]] tables of the input file or of a snapshot.
+    pub fn set_annotations(&mut self, tags: &[TagConfig]) {
+        let (annotations, errors) = Annotations::from_configs(tags);
+        self.log.extend(errors);
+        self.annotations = annotations;
+        self.alerts.set_groups(&self.known_groups());
+    }
+
+    //The tags of the links are only labels, a link isn't a member of a group.
+    pub fn tag_node(&mut self, id: NodeId, tag: &str) -> bool {
+        let tag = tag.trim();
+        if tag.is_empty() || !self.network_graph.contains_key(&id) || !self.annotations.tag_node(id, tag) {
+            return false;
+        }
+        self.log.push(format!("node {} tagged {}", id, tag));
+        self.alerts.set_groups(&self.known_groups());
+        true
+    }
+
+    pub fn untag_node(&mut self, id: NodeId, tag: &str) -> bool {
+        if !self.annotations.untag_node(id, tag.trim()) {
+            return false;
+        }
+        self.log.push(format!("node {} untagged {}", id, tag.trim()));
+        self.alerts.set_groups(&self.known_groups());
+        true
+    }
+
+    pub fn tag_link(&mut self, a: NodeId, b: NodeId, tag: &str) -> bool {
+        let tag = tag.trim();
+        let linked = self.network_graph.get(&a).map_or(false, |neighbours| neighbours.contains(&b));
+        if tag.is_empty() || !linked || !self.annotations.tag_link(a, b, tag) {
+            return false;
+        }
+        self.log.push(format!("link {} - {} tagged {}", a, b, tag));
+        true
+    }
+
+    pub fn untag_link(&mut self, a: NodeId, b: NodeId, tag: &str) -> bool {
+        if !self.annotations.untag_link(a, b, tag.trim()) {
+            return false;
+        }
+        self.log.push(format!("link {} - {} untagged {}", a, b, tag.trim()));
+        true
+    }
+
+    //With b the note goes on the link a - b, an empty note removes it.
+    pub fn set_note(&mut self, a: NodeId, b: Option<NodeId>, note: &str) {
+        match b {
+            Some(b) => self.annotations.set_link_note(a, b, note),
+            None => self.annotations.set_node_note(a, note),
+        }
+    }
+
+    //A group with the same name is replaced.
+    pub fn define_group(&mut self, group: GroupDefinition){
+        self.log.push(format!("group {}: {:?}", group.name, group.nodes));
+        match self.groups.iter_mut().find(|defined| defined.name == group.name) {
+            Some(defined) => *defined = group,
+            None => self.groups.push(group),
+        }
+        self.alerts.set_groups(&self.known_groups());
+    }
+
+    pub fn remove_group(&mut self, name: &str) -> bool {
+        let before = self.groups.len();
+        self.groups.retain(|group| group.name != name);
+        self.alerts.set_groups(&self.known_groups());
+        before != self.groups.len()
+    }
+
+    pub fn save_groups(&mut self, file: &str) {
+        match groups::save_groups(file, &self.groups) {
+            Ok(_) => self.log.push(format!("{} groups saved to {}.", self.groups.len(), file)),
+            Err(e) => println!("error in saving the groups to {}: {}", file, e),
+        }
+    }
+
+    //The groups of the file are added to the ones already defined, replacing the ones with the same name.
+    pub fn load_groups(&mut self, file: &str) -> bool {
+        match groups::load_groups(file) {
+            Ok(loaded) => {
+                for group in loaded {
+                    self.define_group(group);
+                }
+                true
+            }
+            Err(e) => {
+                self.log.push(format!("error in loading the groups from {}: {}", file, e));
+                false
+            }
+        }
+    }
+
+    pub fn crash_group(&mut self, name: &str){
+        for id in self.group_members(&NodeGroup::Defined(name.to_string())) {
+            self.crash_drone(id);
+        }
+    }
+
+    pub fn reboot_group(&mut self, name: &str){
+        //The crashed members too, that's what a reboot is for.
+        let members = self.known_groups()
+            .iter()
+            .find(|group| group.name == name)
+            .map(|group| group.nodes.iter().filter(|id| matches!(self.node_types.get(id), Some(NodeType::Drone))).copied().collect::<Vec<NodeId>>())
+            .unwrap_or_default();
+        for id in members {
+            self.reboot_drone(id);
+        }
+    }
+
+    pub fn set_group_pdr(&mut self, name: &str, pdr: f32){
+        for id in self.group_members(&NodeGroup::Defined(name.to_string())) {
+            self.set_pdr(id, pdr);
+        }
+    }
+
+    //The frontends and the scenarios say who they are, see CommandOrigin.
+    pub fn set_command_origin(&mut self, origin: CommandOrigin){
+        self.audit.set_origin(origin);
+    }
+
+    pub fn export_audit(&mut self, file: &str) {
+        match self.audit.save(file) {
+            Ok(_) => self.log.push(format!("command audit exported to {}.", file)),
+            Err(e) => println!("error in exporting the command audit to {}: {}", file, e),
+        }
+    }
+
+    pub fn set_link_capacity(&mut self, link_capacity: LinkCapacityConfig){
+        self.link_capacity = link_capacity;
+    }
+
+    //The bytes sent on every link with a capacity over the last period, as a fraction of what it can carry.
+    fn update_link_utilization(&mut self){
+        let elapsed = self.utilization_updated.elapsed();
+        if elapsed < UTILIZATION_PERIOD {
+            return;
+        }
+        self.utilization_updated = Instant::now();
+        self.link_utilization.clear();
+        for ((from, to), bytes) in self.link_bytes.drain() {
+            if let Some(capacity) = self.link_capacity.capacity(from, to) {
+                let utilization = bytes as f64 / elapsed.as_secs_f64() / capacity.bytes_per_sec.max(1) as f64;
+                self.link_utilization.insert((from, to), utilization as f32);
+            }
+        }
+        for (id, stats) in self.stats.iter_mut() {
+            stats.link_utilization = self.link_utilization
+                .iter()
+                .filter(|((from, _), _)| from == id)
+                .map(|(_, utilization)| *utilization)
+                .fold(0.0, f32::max);
+        }
+    }
+
+    pub fn schedule_fault(&mut self, fault: &FaultConfig){
+        self.faults.add(fault);
+    }
+
+    pub fn add_alert(&mut self, alert: AlertConfig){
+        self.alerts.add_rule(alert);
+    }
+
+    fn check_alerts(&mut self){
+        for alert in self.alerts.check_if_due(&self.stats) {
+            println!("{}", alert.describe());
+            self.log.push(alert.describe());
+            let state = if alert.resolved { "resolved" } else { "raised" };
+            self.timeline.add(TimelineKind::Alert, alert.node, format!("{} {}: {}", alert.name, state, alert.message));
+        }
+    }
+
+    fn run_due_faults(&mut self){
+        let origin = self.audit.set_origin(CommandOrigin::Chaos);
+        for (time, action) in self.faults.due() {
+            self.log.push(format!("fault at {:.1}s: {:?}", time.as_secs_f64(), action));
+            self.faults_injected += 1;
+            //A crash is already on the timeline as a crash.
+            match action {
+                FaultAction::Crash(id) => self.crash_drone(id),
+                FaultAction::SetPdr(id, pdr) => {
+                    self.timeline.add(TimelineKind::Fault, Some(id), format!("pdr of drone {} set to {}", id, pdr));
+                    self.set_pdr(id, pdr);
+                }
+                FaultAction::LinkDown(a, b) => {
+                    self.timeline.add(TimelineKind::Fault, Some(a), format!("link {} - {} down", a, b));
+                    self.remove_link(a, b);
+                }
+                FaultAction::LinkUp(a, b) => {
+                    self.timeline.add(TimelineKind::Fault, Some(a), format!("link {} - {} up", a, b));
+                    self.add_link(a, b);
+                }
+                FaultAction::CrashGroup(group, count) => {
+                    let mut members = self.group_members(&NodeGroup::Defined(group));
+                    if let Some(count) = count {
+                        fastrand::shuffle(&mut members);
+                        members.truncate(count);
+                    }
+                    for id in members {
+                        self.crash_drone(id);
+                    }
+                }
+                FaultAction::SetGroupPdr(group, pdr) => {
+                    self.timeline.add(TimelineKind::Fault, None, format!("pdr of group {} set to {}", group, pdr));
+                    self.set_group_pdr(&group, pdr);
+                }
+            }
         }
+        self.audit.set_origin(origin);
     }
 
-    pub fn run(&mut self){
-        loop{
-            select! {
-            recv(self.node_recv) -> e =>{
-                    if let Ok(event) = e {
-                        self.add_to_log(event);
-                    }
+    //The drones check every new neighbour with a hello, and tell how it went on the channel of handshake_send.
+    pub fn attach_handshakes(&mut self, timeout: Duration, handshake_send: Sender<HandshakeReport>, handshake_recv: Receiver<HandshakeReport>){
+        self.handshake = Some((timeout, handshake_send));
+        self.handshake_recv = handshake_recv;
+    }
+
+    fn receive_handshakes(&mut self){
+        while let Ok(report) = self.handshake_recv.try_recv() {
+            //Clients and servers are played by the Sim Contr, they never send a hello back.
+            if !matches!(self.node_types.get(&report.neighbour), Some(NodeType::Drone)) || self.crashed.contains(&report.neighbour) {
+                continue;
+            }
+            match report.rtt {
+                Some(rtt) => {
+                    self.asymmetric_links.remove(&(report.drone, report.neighbour));
+                    self.log.push(format!("link {} -> {} confirmed by the handshake in {} ms", report.drone, report.neighbour, rtt.as_millis()));
+                }
+                None => {
+                    self.asymmetric_links.insert((report.drone, report.neighbour));
+                    println!("asymmetric link: drone {} reaches {}, but {} has no channel back to {}", report.drone, report.neighbour, report.neighbour, report.drone);
+                    self.log.push(format!("asymmetric link: drone {} reaches {}, but {} has no channel back to {}", report.drone, report.neighbour, report.neighbour, report.drone));
                 }
             }
         }
     }
 
-    fn add_to_log(&mut self, e: DroneEvent){
-        match e {
-            DroneEvent::PacketSent(packet) => {
-                let id_drone = packet.routing_header.hops.get(packet.routing_header.hops.len() -1).unwrap();
-                self.log.push( format!("Drone {} sent fragment {:?} of type: {:?}",id_drone ,packet.session_id, packet.pack_type))}
-            DroneEvent::PacketDropped(packet) => {
-                let id_drone = packet.routing_header.hops.get(packet.routing_header.hops.len() -1).unwrap();
-                self.log.push( format!("Drone {} dropped fragment {:?} of type: {:?}",id_drone ,packet.session_id, packet.pack_type))}
-            DroneEvent::ControllerShortcut(packet) => {
-                let id_drone = packet.routing_header.hops.get(packet.routing_header.hops.len() -1).unwrap();
-                self.log.push( format!("Received {:?} from drone {:?}", packet.pack_type, id_drone));
+    //The drones remove the neighbours whose channel stays closed, and tell it on the channel of link_down_send.
+    pub fn attach_links_down(&mut self, after: u32, link_down_send: Sender<LinkDown>, link_down_recv: Receiver<LinkDown>){
+        self.eviction = Some((after, link_down_send));
+        self.link_down_recv = link_down_recv;
+    }
+
+    //The drone can't reach the neighbour anymore, so no route should go that way: the link is out
+    //of my graph in that direction, the other one is up to the neighbour.
+    fn receive_links_down(&mut self){
+        while let Ok(link_down) = self.link_down_recv.try_recv() {
+            println!("link down: drone {} removed {} after {} failed sends", link_down.drone, link_down.neighbour, link_down.failures);
+            self.log.push(format!("link down: drone {} removed {} after {} failed sends", link_down.drone, link_down.neighbour, link_down.failures));
+            self.evicted_links.insert((link_down.drone, link_down.neighbour));
+            self.route_cache.invalidate_link(link_down.drone, link_down.neighbour);
+            if let Some(neighbours) = self.network_graph.get_mut(&link_down.drone) {
+                neighbours.retain(|id| *id != link_down.neighbour);
+            }
+        }
+    }
+
+    //The drones tell they're alive every interval on the channel of beat_send.
+    pub fn attach_heartbeats(&mut self, interval: Duration, beat_send: Sender<Heartbeat>, beat_recv: Receiver<Heartbeat>){
+        self.heartbeat = Some(beat_send);
+        self.heartbeat_recv = beat_recv;
+        self.health = Some(HealthMonitor::new(interval));
+    }
+
+    fn receive_heartbeats(&mut self){
+        let Some(health) = self.health.as_mut() else {
+            return;
+        };
+        while let Ok(beat) = self.heartbeat_recv.try_recv() {
+            if health.record(&beat) {
+                self.log.push(format!("drone {} is beating again.", beat.drone));
+            }
+        }
+        //Said once, the GUI keeps showing it until the drone beats again.
+        for id in health.newly_stalled(&self.crashed) {
+            println!("drone {} stalled: no heartbeat for {:?}", id, health.stall_time());
+            self.log.push(format!("drone {} stalled: no heartbeat for {:?}, but it wasn't crashed.", id, health.stall_time()));
+        }
+    }
+
+    //The clients' mailboxes of the gossip, if the drones gossip.
+    pub fn attach_gossip(&mut self, mailboxes: HashMap<NodeId, Receiver<GossipMessage>>){
+        self.discovery = DiscoveryTracker::with_mailboxes(mailboxes);
+    }
+
+    //How the drones gossip, so the ones started later gossip too.
+    pub fn set_drone_gossip(&mut self, gossip: DroneGossip){
+        self.drone_gossip = Some(gossip);
+    }
+
+    //The hooks can't come from the input file: whoever gives hooks to the drones before the start
+    //sets here the same factory, so the drones started later get them too.
+    pub fn set_drone_hooks(&mut self, hooks: HooksFactory){
+        self.drone_hooks = Some(hooks);
+    }
+
+    pub fn client_ids(&self) -> Vec<NodeId> {
+        let mut clients = self.node_types
+            .iter()
+            .filter(|(_, node_type)| matches!(node_type, NodeType::Client))
+            .map(|(id, _)| *id)
+            .collect::<Vec<NodeId>>();
+        clients.sort();
+        clients
+    }
+
+    fn reachable_from(&self, client: NodeId) -> HashSet<NodeId> {
+        discovery::reachable(&self.network_graph, &self.node_types, &self.crashed, client)
+    }
+
+    fn update_discovery(&mut self){
+        if !self.discovery.gossip_enabled() {
+            return;
+        }
+        //The mailboxes are always emptied, the targets are only needed until every client converged.
+        let targets = if self.discovery.gossip_waiting() {
+            self.client_ids().into_iter().map(|client| (client, self.reachable_from(client))).collect()
+        } else {
+            HashMap::new()
+        };
+        for client in self.discovery.receive_gossip(&targets) {
+            let progress = self.discovery.gossip_progress(client, &targets[&client], self.gossip_sent());
+            self.log.push(format!("gossip to client {}: {}", client, discovery::describe(&progress)));
+        }
+    }
+
+    fn gossip_sent(&self) -> u64 {
+        self.counters.values().map(|counters| counters.gossip_sent.load(Ordering::Relaxed)).sum()
+    }
+
+    //Starts a flood as if the client sent it, the responses reaching the client are followed by the DiscoveryTracker.
+    //The flood id can be chosen, e.g. the same for many clients, to check that their floods don't mix.
+    pub fn start_flood(&mut self, client: NodeId, flood_id: Option<u64>) -> bool {
+        let Some(neighbours) = self.network_graph.get(&client).cloned() else {
+            println!("node {} not found in the network.", client);
+            return false;
+        };
+        //Only the drones answer a flood here (there are no client or server threads), so the
+        //flood can't find the other endpoints, and it's complete once it found every drone.
+        let target = self.reachable_from(client)
+            .into_iter()
+            .filter(|id| *id == client || matches!(self.node_types.get(id), Some(NodeType::Drone)))
+            .collect();
+        let flood_id = self.discovery.start_flood(client, target, flood_id);
+        let packet = Packet {
+            pack_type: PacketType::FloodRequest(FloodRequest {
+                flood_id,
+                initiator_id: client,
+                path_trace: vec![(client, self.node_types.get(&client).copied().unwrap_or(NodeType::Client))],
+            }),
+            routing_header: SourceRoutingHeader { hop_index: 0, hops: Vec::new() },
+            session_id: flood_id,
+        };
+        for neighbour in neighbours.iter().filter(|id| !self.crashed.contains(id)) {
+            if let Some(sender) = self.all_sender_packets.get(neighbour) {
+                let _ = sender.send(packet.clone());
+            }
+        }
+        self.log.push(format!("flood {} started from client {}.", flood_id, client));
+        self.timeline.add(TimelineKind::Flood, Some(client), format!("flood {} from client {}", flood_id, client));
+        true
+    }
+
+    //Whether the last flood of the client found every node it can reach.
+    pub fn flood_converged(&self, client: NodeId) -> bool {
+        self.discovery.flood_progress(client).map_or(false, |progress| progress.converged.is_some())
+    }
+
+    //Where every client stands with the gossip and with its last flood.
+    pub fn discovery_report(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for client in self.client_ids() {
+            let target = self.reachable_from(client);
+            if self.discovery.gossip_enabled() {
+                let progress = self.discovery.gossip_progress(client, &target, self.gossip_sent());
+                lines.push(format!("client {} gossip: {}", client, discovery::describe(&progress)));
+            }
+            if let Some(progress) = self.discovery.flood_progress(client) {
+                lines.push(format!("client {} flood: {}", client, discovery::describe(&progress)));
+            }
+        }
+        lines
+    }
+
+    pub fn set_memory_limits(&mut self, memory_limits: MemoryLimits){
+        self.memory_limits = memory_limits;
+    }
+
+    //Every few seconds I estimate how much memory the growing structures use, and warn
+    //(once per structure) about the ones over their limit.
+    fn check_memory(&mut self){
+        if let Some(checked) = self.memory_checked {
+            if checked.elapsed() < Duration::from_secs(5) {
+                return;
+            }
+        }
+        self.memory_checked = Some(Instant::now());
+
+        let flood_caches = self.counters
+            .keys()
+            .map(|id| (*id, self.stats.get(id).map_or(0, |stats| stats.flood_cache)))
+            .collect::<HashMap<NodeId, u64>>();
+        self.memory = MemoryReport::new(&self.log, &self.sessions, &self.stats_series, &flood_caches);
+
+        for (name, message) in self.memory.exceeded(&self.memory_limits) {
+            if self.memory_warned.insert(name) {
+                println!("warning: {}", message);
+                self.log.push(format!("warning: {}", message));
+            }
+        }
+    }
+
+    //The changes proposed in review mode, all in the same call: no event is read in between.
+    //Returns the ids of the spawned drones, None for the other changes.
+    pub fn apply_mutations(&mut self, mutations: &[Mutation]) -> Vec<Option<NodeId>> {
+        let mut spawned = Vec::new();
+        for mutation in mutations {
+            let mut new_id = None;
+            match mutation {
+                Mutation::AddLink(a, b) => self.add_link(*a, *b),
+                Mutation::RemoveLink(a, b) => self.remove_link(*a, *b),
+                Mutation::Crash(id) => self.crash_drone(*id),
+                Mutation::Reboot(id) => {
+                    self.reboot_drone(*id);
+                }
+                Mutation::Spawn { pdr, connections } => {
+                    //The thread isn't joined: shutdown() stops it like the others, through its channels.
+                    let (id, _) = self.spawn_drone(*pdr, connections.clone());
+                    for neighbour in connections {
+                        if let Some(neighbours) = self.network_graph.get_mut(neighbour) {
+                            neighbours.push(id);
+                        }
+                    }
+                    self.log.push(format!("drone {} spawned.", id));
+                    self.timeline.add(TimelineKind::Spawn, Some(id), format!("drone {} spawned, linked to {:?}", id, connections));
+                    new_id = Some(id);
+                }
             }
+            spawned.push(new_id);
         }
+        self.log.push(format!("{} proposed changes applied.", mutations.len()));
+        spawned
     }
 
-    fn spawn_drone (&mut self, pdr: f32, connections: Vec<NodeId>) -> JoinHandle<()>{
+    fn spawn_drone (&mut self, pdr: f32, connections: Vec<NodeId>) -> (NodeId, JoinHandle<()>){
         let new_id = self.generate_id();
+        (new_id, self.start_drone(new_id, pdr, connections))
+    }
+
+    fn start_drone (&mut self, new_id: NodeId, pdr: f32, connections: Vec<NodeId>) -> JoinHandle<()>{
         //aggiorna network graph
         self.network_graph.insert(new_id, connections.clone());
+        self.node_types.insert(new_id, NodeType::Drone);
+        self.node_pdr.insert(new_id, pdr);
 
         let (control_sender, control_receiver) = unbounded();  //canale per il Sim che manda drone command al drone
         self.node_send.insert(new_id.clone(), control_sender.clone());                                      // do al sim il sender per questo drone
 
 
-        let (packet_send, packet_recv) = unbounded();                       //canale per il drone, il recv gli va dentro, il send va dato in copia a tutti i droni che vogliono comunicare con lui
+        let (packet_send, packet_recv) = packet_channel(self.channel_capacity);                       //canale per il drone, il recv gli va dentro, il send va dato in copia a tutti i droni che vogliono comunicare con lui
         for (id, sender) in self.node_send.iter() {                        // per dare a tutti i droni in node_in il sender al new drone
+            //A crashed neighbour keeps its place in the graph, but it doesn't get new links.
+            if self.crashed.contains(id) {
+                continue;
+            }
             for i in connections.clone() {
-                if i == *id {
-                    sender.send(AddSender(new_id, packet_send.clone())).unwrap();
+                //A neighbour whose thread already exited can't take the link, the others still get it.
+                if i == *id && send_command(&mut self.audit, *id, sender, AddSender(new_id, packet_send.clone())).is_err() {
+                    self.log.push(format!("drone {} didn't get the link to {}, its channel is closed.", id, new_id));
                 }
             }
         }
 
+        self.all_sender_packets.insert(new_id, packet_send.clone());
+
         let mut packet_send = HashMap::new();
         //riempi la hashmap
         for (id, sender) in &self.all_sender_packets {
+            //Nor do I give the new drone a link to it, it would route and flood into a dead drone.
+            if self.crashed.contains(id) {
+                continue;
+            }
             for i in connections.clone() {
                 if i == *id{
                     packet_send.insert(*id, sender.clone());
@@ -86,10 +1703,84 @@ impl SimulationControl{
         }
 
         let channel_clone = self.channel_for_drone.clone();
+        let send_timeout = self.send_timeout;
+        let counters = Arc::new(DroneCounters::default());
+        self.counters.insert(new_id, counters.clone());
+        let (skylink_send, skylink_recv) = unbounded();
+        self.skylink_send.insert(new_id, skylink_send);
+        let filters = self.filters.get(&new_id).map(|filter| filter.rules()).unwrap_or_default();
+        let link_capacities = self.link_capacity.capacities(new_id, &connections);
+        let processing_delays = delays_of(&self.processing_delays, new_id);
+        let link_rates = rates_of(&self.link_rates, new_id);
+        let link_pdrs = pdrs_of(&self.link_pdrs, new_id);
+        let packet_faults = self.packet_faults.get(&new_id).copied().unwrap_or_default();
+        let energy = self.energy.get(&new_id).copied();
+        let reorder = self.reorder.get(&new_id).copied();
+        let burst_loss = self.burst_loss.get(&new_id).copied();
+        let telemetry = self.telemetry.get(&new_id).map(|config| config.telemetry()).unwrap_or_default();
+        let handshake = self.handshake.clone();
+        let heartbeat = self.heartbeat.clone().zip(self.health.as_ref().map(|health| health.interval));
+        let eviction = self.eviction.clone();
+        let max_events_per_s = self.max_events_per_s;
+        let max_hops = self.max_hops;
+        let flood_cache = self.flood_cache;
+        let dedup = self.dedup;
+        let tick = self.tick;
+        let event_batching = self.event_batching.clone();
+        //A rebooted drone reads the mailbox of its id, a new id has none and only talks.
+        let gossip = self.drone_gossip.as_ref().map(|gossip| {
+            let mailbox = gossip.drone_mailboxes.get(&new_id).cloned().unwrap_or(never());
+            (Gossip::new(gossip.mailboxes.clone(), gossip.period, gossip.fanout), mailbox)
+        });
+        let hooks = self.drone_hooks.as_ref().map(|factory| factory(new_id));
+        let seed = self.drone_seed.map(|seed| drone_seed(seed, new_id));
 
         //crea thread
         let handle = thread::spawn(move || {
-            let mut new_drone = SkyLinkDrone::new(new_id, channel_clone, control_receiver, packet_recv, packet_send, pdr);
+            let mut new_drone = SkyLinkDrone::new(new_id, channel_clone, control_receiver, packet_recv, packet_send, pdr)
+                .with_send_timeout(send_timeout)
+                .with_counters(counters)
+                .with_skylink_commands(skylink_recv)
+                .with_filters(filters)
+                .with_link_capacities(link_capacities)
+                .with_processing_delays(processing_delays)
+                .with_link_rates(link_rates)
+                .with_link_pdrs(link_pdrs)
+                .with_packet_faults(packet_faults)
+                .with_reorder(reorder)
+                .with_burst_loss(burst_loss)
+                .with_max_hops(max_hops)
+                .with_flood_cache(flood_cache)
+                .with_dedup(dedup)
+                .with_tick(tick)
+                .with_telemetry(telemetry);
+            if let Some((timeout, handshake_send)) = handshake {
+                new_drone = new_drone.with_handshake(timeout, handshake_send);
+            }
+            if let Some((beat_send, interval)) = heartbeat {
+                new_drone = new_drone.with_heartbeat(interval, beat_send);
+            }
+            if let Some((after, link_down_send)) = eviction {
+                new_drone = new_drone.with_eviction(after, link_down_send);
+            }
+            if let Some(budget) = energy {
+                new_drone = new_drone.with_energy(budget);
+            }
+            if let Some(max_per_s) = max_events_per_s {
+                new_drone = new_drone.with_event_rate_limit(max_per_s);
+            }
+            if let Some(seed) = seed {
+                new_drone = new_drone.with_seed(seed);
+            }
+            if let Some((batch_send, max_len, max_delay)) = event_batching {
+                new_drone = new_drone.with_event_batching(batch_send, max_len, max_delay);
+            }
+            if let Some((gossip, gossip_recv)) = gossip {
+                new_drone = new_drone.with_gossip(gossip, gossip_recv);
+            }
+            if let Some(hooks) = hooks {
+                new_drone = new_drone.with_hooks(hooks);
+            }
             new_drone.run();
         });
         handle
@@ -98,7 +1789,7 @@ impl SimulationControl{
     fn generate_id (&mut self) -> NodeId {//just a function to generate an id that is empty in our hashmap, if is 1-3-4, it should give 2, if it's 1-2-3, should give 4.
         for k in 0..=u8::MAX {
             //If k is not a key in the map, I return it.
-            if !self.node_send.contains_key(&k) {
+            if !self.node_send.contains_key(&k) && !self.network_graph.contains_key(&k) {
                 return k;
             }
         }
@@ -108,7 +1799,7 @@ impl SimulationControl{
 
     pub fn crash_drone(&mut self, id: NodeId){
         if let Some(sender) = self.node_send.get(&id) {
-            if let Err(e) = sender.send(DroneCommand::Crash) {
+            if let Err(e) = send_command(&mut self.audit, id, sender, DroneCommand::Crash) {
                 println!("error in crashing drone {}: {:?}", id, e);
             } else {
                 println!("crash command sent do the drone {}", id);
@@ -117,23 +1808,558 @@ impl SimulationControl{
                 // remove the drone from the neighbour's sends
                 if let Some(vec) = self.network_graph.get(&id) {
                     for (neighbor_id, neighbor_sender) in &self.node_send {
-                        if vec.contains(neighbor_id) {
-                            neighbor_sender.send(RemoveSender(id)).unwrap()
+                        //Skipped if the neighbour's thread already exited, there's nothing left to remove.
+                        if vec.contains(neighbor_id) && send_command(&mut self.audit, *neighbor_id, neighbor_sender, RemoveSender(id)).is_err() {
+                            self.log.push(format!("drone {} didn't get the removal of {}, its channel is closed.", neighbor_id, id));
                         }
                     }
                 }
-                if let Some(to_be_dropped) = self.node_send.remove(&id){
-                    drop(to_be_dropped);
+                //I keep the sender of the crashed drone apart instead of dropping it: the drone still
+                //listens for RemoveSender while crashing, and shutdown() needs to reach it.
+                if let Some(crashed_sender) = self.node_send.remove(&id){
+                    self.crashed_send.insert(id, crashed_sender);
                 }
+                self.crashed.insert(id);
+                self.crash_history.push(id);
+                self.route_cache.invalidate_node(id);
                 self.log.push(format!("drone {} crashed.", id));
+                self.timeline.add(TimelineKind::Crash, Some(id), format!("drone {} crashed", id));
+                self.repro.record(ReproAction::Crash(id));
+                self.node_log(id, "crashed by the Sim Contr.");
             }
         } else {
             println!("drone {} not found in the network.", id);
         }
     }
+    //Everything the drone receives and sends from now on is copied, and written to file by
+    //stop_capture (or the shutdown). It can be replayed alone with 'replay drone <file>'.
+    pub fn start_capture(&mut self, id: NodeId, file: &str) -> bool {
+        let Some(sender) = self.skylink_send.get(&id) else {
+            self.log.push(format!("drone {} can't be captured, it's not a running SkyLink drone.", id));
+            return false;
+        };
+        let (tap_send, tap_recv) = unbounded();
+        if send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::SetTap(Some(tap_send))).is_err() {
+            return false;
+        }
+        let mut neighbours = self.network_graph
+            .get(&id)
+            .map(|neighbours| neighbours.iter().filter(|neighbour| !self.crashed.contains(neighbour)).copied().collect::<Vec<NodeId>>())
+            .unwrap_or_default();
+        neighbours.sort();
+        let pdr = self.node_pdr.get(&id).copied().unwrap_or(0.0);
+        self.captures.insert(id, (file.to_string(), DroneCapture::new(id, pdr, neighbours), tap_recv));
+        self.log.push(format!("capturing the traffic of drone {} to {}.", id, file));
+        true
+    }
+
+    pub fn stop_capture(&mut self, id: NodeId) -> bool {
+        let Some((file, mut capture, tap_recv)) = self.captures.remove(&id) else {
+            return false;
+        };
+        if let Some(sender) = self.skylink_send.get(&id) {
+            let _ = send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::SetTap(None));
+        }
+        //The drone lets go of the tap when it reads the command, a crashed one never does.
+        while let Ok(record) = tap_recv.recv_timeout(Duration::from_millis(200)) {
+            capture.record(record);
+        }
+        match capture.save(&file) {
+            Ok(_) => self.log.push(format!("{} inputs of drone {} captured to {}.", capture.inputs.len(), id, file)),
+            Err(e) => println!("error in writing the capture of drone {} to {}: {}", id, file, e),
+        }
+        true
+    }
+
+    //Crashes the drone (if it's still up) and starts a new SkyLinkDrone with the same id, pdr, links
+    //and filters: the whole crash and recovery in one go. The new drone is only started once the
+    //thread of the old one let go of it (see finish_reboots), so this returns right away: true if
+    //the reboot is on its way.
+    pub fn reboot_drone(&mut self, id: NodeId) -> bool {
+        if !matches!(self.node_types.get(&id), Some(NodeType::Drone)) {
+            self.log.push(format!("drone {} not found in the network.", id));
+            return false;
+        }
+        if self.pending_reboots.contains_key(&id) {
+            self.log.push(format!("drone {} is already rebooting.", id));
+            return false;
+        }
+        if !self.crashed.contains(&id) {
+            self.crash_drone(id);
+            if !self.crashed.contains(&id) {
+                return false;
+            }
+        }
+
+        //The old drone stops when nobody can reach it anymore, and then it drops its counters.
+        self.all_sender_packets.remove(&id);
+        self.crashed_send.remove(&id);
+        self.skylink_send.remove(&id);
+        let old_counters = self.counters.remove(&id);
+        self.pending_reboots.insert(id, (old_counters, Instant::now() + REBOOT_TIMEOUT));
+        self.finish_reboots();
+        true
+    }
+
+    //Whether the drone is waiting for its old thread to stop before starting again.
+    pub fn rebooting(&self, id: NodeId) -> bool {
+        self.pending_reboots.contains_key(&id)
+    }
+
+    //Starts the rebooted drones whose old thread is over. One still going after REBOOT_TIMEOUT stays
+    //crashed: two drones with the same id would both read from the same links.
+    fn finish_reboots(&mut self) {
+        let now = Instant::now();
+        let ready = self.pending_reboots
+            .iter()
+            .filter(|(_, (counters, deadline))| counters.as_ref().map_or(true, |counters| Arc::strong_count(counters) == 1) || now >= *deadline)
+            .map(|(id, _)| *id)
+            .collect::<Vec<NodeId>>();
+        for id in ready {
+            let Some((old_counters, _)) = self.pending_reboots.remove(&id) else {
+                continue;
+            };
+            if let Some(counters) = old_counters.as_ref().filter(|counters| Arc::strong_count(counters) > 1) {
+                //Put back, so another reboot waits for the same thread.
+                self.counters.insert(id, counters.clone());
+                self.log.push(format!("drone {} didn't stop in time, it stays crashed.", id));
+                continue;
+            }
+            self.restart_drone(id, old_counters);
+        }
+    }
+
+    fn restart_drone(&mut self, id: NodeId, old_counters: Option<Arc<DroneCounters>>) {
+        let pdr = self.node_pdr.get(&id).copied().unwrap_or(0.0);
+        let connections = self.network_graph.get(&id).cloned().unwrap_or_default();
+        //The thread isn't joined: shutdown() stops it like the others, through its channels.
+        let _ = self.start_drone(id, pdr, connections);
+        //The stats go on from where the old drone left them.
+        if let (Some(old), Some(new)) = (&old_counters, self.counters.get(&id)) {
+            let (packets_sent, packets_dropped, shortcuts, _) = old.load();
+            new.store(packets_sent, packets_dropped, shortcuts);
+            new.store_forwarding(old.load_forwarding());
+            new.congestion_drops.store(old.congestion_drops.load(Ordering::Relaxed), Ordering::Relaxed);
+            new.rate_limited.store(old.rate_limited.load(Ordering::Relaxed), Ordering::Relaxed);
+            new.fragments_duplicated.store(old.fragments_duplicated.load(Ordering::Relaxed), Ordering::Relaxed);
+            new.fragments_corrupted.store(old.fragments_corrupted.load(Ordering::Relaxed), Ordering::Relaxed);
+            new.fragments_reordered.store(old.fragments_reordered.load(Ordering::Relaxed), Ordering::Relaxed);
+            new.duplicates_suppressed.store(old.duplicates_suppressed.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.crashed.remove(&id);
+        if let Some(health) = self.health.as_mut() {
+            health.forget(id);
+        }
+        //The new drone doesn't know the regions yet.
+        self.link_impairments.retain(|(from, _), _| *from != id);
+        self.update_link_impairments();
+        self.log.push(format!("drone {} rebooted.", id));
+        self.timeline.add(TimelineKind::Reboot, Some(id), format!("drone {} rebooted", id));
+        self.repro.record(ReproAction::Reboot(id));
+        self.node_log(id, "rebooted by the Sim Contr.");
+    }
+
+    pub fn snapshot(&self) -> SimulationSnapshot {
+        let mut ids = self.network_graph.keys().copied().collect::<Vec<NodeId>>();
+        ids.sort();
+
+        let mut snapshot = SimulationSnapshot {
+            drone: Vec::new(),
+            client: Vec::new(),
+            server: Vec::new(),
+            tag: self.annotations.to_configs(),
+            link: link_pdr::to_configs(&self.link_pdrs),
+        };
+        for id in ids {
+            let connected = self.network_graph[&id].clone();
+            let stats = self.stats.get(&id).cloned().unwrap_or_default();
+            match self.node_types.get(&id) {
+                Some(NodeType::Drone) => snapshot.drone.push(DroneSnapshot {
+                    id,
+                    connected_node_ids: connected,
+                    pdr: self.node_pdr.get(&id).copied().unwrap_or(0.0),
+                    crashed: self.crashed.contains(&id),
+                    stats,
+                }),
+                Some(NodeType::Client) => snapshot.client.push(EndpointSnapshot {
+                    id,
+                    connected_drone_ids: connected,
+                    stats,
+                }),
+                Some(NodeType::Server) => snapshot.server.push(EndpointSnapshot {
+                    id,
+                    connected_drone_ids: connected,
+                    stats,
+                }),
+                None => {},
+            }
+        }
+        snapshot
+    }
+
+    pub fn save_snapshot(&mut self, file: &str) {
+        match self.snapshot().save(file) {
+            Ok(_) => self.log.push(format!("snapshot saved to {}.", file)),
+            Err(e) => println!("error in saving the snapshot to {}: {}", file, e),
+        }
+    }
+
+    //The state of a SkyLink drone, told by the drone itself (see DroneState). None if it can't
+    //take the command or doesn't answer in time, crashing drones still answer.
+    pub fn drone_state(&mut self, id: NodeId) -> Option<DroneState> {
+        let sender = self.skylink_send.get(&id)?;
+        let (reply_send, reply_recv) = unbounded();
+        send_skylink_command(&mut self.audit, id, sender, SkyLinkCommand::Snapshot(reply_send)).ok()?;
+        reply_recv.recv_timeout(PROBE_TIMEOUT).ok()
+    }
+
+    //Of every SkyLink drone that answers, by id. With a file they're saved there too, as json.
+    pub fn drone_states(&mut self, file: Option<&str>) -> Vec<DroneState> {
+        let mut ids = self.skylink_send.keys().copied().collect::<Vec<NodeId>>();
+        ids.sort();
+        let states = ids.into_iter().filter_map(|id| self.drone_state(id)).collect::<Vec<DroneState>>();
+        if let Some(file) = file {
+            let saved = serde_json::to_string_pretty(&states).map_err(|e| e.to_string()).and_then(|json| std::fs::write(file, json).map_err(|e| e.to_string()));
+            match saved {
+                Ok(_) => self.log.push(format!("state of {} drones saved to {}.", states.len(), file)),
+                Err(e) => println!("error in saving the drone states to {}: {}", file, e),
+            }
+        }
+        states
+    }
+
+    //What changed in the live network since the snapshot was saved, the changes go in the log too.
+    pub fn diff_with_snapshot(&mut self, file: &str) -> Option<SnapshotDiff> {
+        let saved = match SimulationSnapshot::load(file) {
+            Ok(saved) => saved,
+            Err(e) => {
+                println!("error in loading the snapshot {}: {}", file, e);
+                return None;
+            }
+        };
+        let diff = saved.diff(&self.snapshot());
+        if diff.is_empty() {
+            self.log.push(format!("no changes since {}.", file));
+        }
+        for line in diff.describe() {
+            self.log.push(format!("since {}: {}", file, line));
+        }
+        Some(diff)
+    }
+
+    //Brings a freshly initialized network to the state saved in the snapshot.
+    pub(crate) fn restore_snapshot(&mut self, snapshot: &SimulationSnapshot) {
+        for drone in snapshot.drone.iter() {
+            self.stats.insert(drone.id, drone.stats.clone());
+            if let Some(counters) = self.counters.get(&drone.id) {
+                counters.store(drone.stats.packets_sent, drone.stats.packets_dropped, drone.stats.shortcuts);
+                let stats = &drone.stats;
+                counters.store_forwarding((stats.fragments_forwarded, stats.fragments_dropped, stats.nacks_generated, stats.floods_handled));
+                counters.congestion_drops.store(stats.congestion_drops, Ordering::Relaxed);
+                counters.rate_limited.store(stats.rate_limited, Ordering::Relaxed);
+                counters.fragments_duplicated.store(stats.fragments_duplicated, Ordering::Relaxed);
+                counters.fragments_corrupted.store(stats.fragments_corrupted, Ordering::Relaxed);
+                counters.fragments_reordered.store(stats.fragments_reordered, Ordering::Relaxed);
+                counters.duplicates_suppressed.store(stats.duplicates_suppressed, Ordering::Relaxed);
+            }
+        }
+        for endpoint in snapshot.client.iter().chain(snapshot.server.iter()) {
+            self.stats.insert(endpoint.id, endpoint.stats.clone());
+        }
+        for drone in snapshot.drone.iter().filter(|drone| drone.crashed) {
+            self.crash_drone(drone.id);
+        }
+        self.set_annotations(&snapshot.tag);
+        for link in snapshot.link.iter() {
+            self.set_link_pdr(link.a, link.b, Some(link.pdr));
+        }
+        self.log.push("snapshot restored.".to_string());
+    }
+
+    //Probes the shortest route from one node to another hop by hop, the answers are collected by
+    //process_events and the result ends up in the log (and in self.traceroute for the frontends).
+    pub fn start_traceroute(&mut self, from: NodeId, to: NodeId) -> bool {
+        let Some(route) = traceroute::shortest_route(&self.network_graph, &self.node_types, &self.crashed, from, to) else {
+            self.log.push(format!("traceroute: no route from {} to {}.", from, to));
+            return false;
+        };
+        if route.len() < 2 {
+            self.log.push("traceroute: the source is the destination.".to_string());
+            return false;
+        }
+        let traceroute = Traceroute::new(route, &self.node_types, self.next_probe_session);
+        self.next_probe_session += traceroute.hops.len() as u64;
+        let probes = traceroute.probes();
+        self.traceroute = Some(traceroute);
+        for probe in probes {
+            self.inject_packet(probe);
+        }
+        true
+    }
+
+    //Blocks until every probe got an answer or timed out, for the CLI and the scripts.
+    pub fn wait_traceroute(&mut self) -> Vec<String> {
+        while self.traceroute.as_ref().map_or(false, |traceroute| !traceroute.finished()) {
+            self.process_events();
+            self.check_traceroute(); //Even when paused, so the probes time out.
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.traceroute.as_ref().map(|traceroute| traceroute.report()).unwrap_or_default()
+    }
+
+    //Pings the drone twice: through its command channel, and with a packet along the shortest
+    //route from a client (or from a neighbour, if no client can reach it). The packet part is a
+    //traceroute to the drone, so when it's lost the report also tells where.
+    pub fn ping(&mut self, node_id: NodeId) -> PingReport {
+        let mut report = PingReport { node: node_id, ..PingReport::default() };
+        if let Some(sender) = self.skylink_send.get(&node_id) {
+            let (reply_send, reply_recv) = unbounded();
+            let sent = Instant::now();
+            if send_skylink_command(&mut self.audit, node_id, sender, SkyLinkCommand::Ping(reply_send)).is_ok() && reply_recv.recv_timeout(PROBE_TIMEOUT).is_ok() {
+                report.command_rtt = Some(sent.elapsed());
+            }
+        }
+
+        let clients = self.client_ids();
+        let neighbour = self.network_graph
+            .get(&node_id)
+            .and_then(|neighbours| neighbours.iter().find(|id| !self.crashed.contains(id)).copied());
+        let source = clients
+            .into_iter()
+            .find(|client| traceroute::shortest_route(&self.network_graph, &self.node_types, &self.crashed, *client, node_id).is_some())
+            .or(neighbour);
+        if let Some(source) = source {
+            if self.start_traceroute(source, node_id) {
+                self.wait_traceroute();
+            }
+            if let Some(traceroute) = self.traceroute.as_ref().filter(|traceroute| traceroute.route.last() == Some(&node_id)) {
+                report.route = traceroute.route.clone();
+                report.packet_rtt = traceroute.hops.last().filter(|_| traceroute.dead_end().is_none()).and_then(|hop| hop.rtt);
+                report.dead_end = traceroute.dead_end();
+            }
+        }
+        self.log.push(report.summary());
+        report
+    }
+
+    fn check_traceroute(&mut self){
+        let Some(traceroute) = self.traceroute.as_mut() else {
+            return;
+        };
+        traceroute.expire(SystemTime::now());
+        if traceroute.finished() && !traceroute.reported {
+            traceroute.reported = true;
+            let report = traceroute.report();
+            self.log.extend(report);
+        }
+    }
+
+    //Sends a packet directly into the channel of the node at the current hop_index, as if
+    //it was sent by the previous hop.
+    pub fn inject_packet(&mut self, packet: Packet) {
+        let Some(&target) = packet.routing_header.hops.get(packet.routing_header.hop_index) else {
+            println!("packet not injected, the hop index is outside the route.");
+            return;
+        };
+        if let Some(sender) = self.all_sender_packets.get(&target) {
+            //The session starts now, the latency of its hops is measured from here.
+            let probe = self.traceroute.as_ref().map_or(false, |traceroute| traceroute.is_probe(packet.session_id));
+            let probe = probe || self.sla.is_probe(packet.session_id);
+            if let (PacketType::MsgFragment(_), false) = (&packet.pack_type, probe) {
+                self.sessions.originate(packet.session_id, SystemTime::now());
+            }
+            self.bandwidth.record(packet_source(&packet), Some(target), &packet, &self.node_types);
+            if let Err(e) = sender.send(packet) {
+                println!("error in injecting a packet to node {}: {:?}", target, e);
+            } else {
+                self.log.push(format!("packet injected to node {}.", target));
+            }
+        } else {
+            println!("node {} not found in the network.", target);
+        }
+    }
+
+    //Only a client or a server can be a sink, the drones answer for themselves.
+    pub fn add_ack_sink(&mut self, config: AckSinkConfig) -> bool {
+        if !matches!(self.node_types.get(&config.node), Some(NodeType::Client) | Some(NodeType::Server)) {
+            self.log.push(format!("node {} can't be an ack sink, it's not a client or a server.", config.node));
+            return false;
+        }
+        self.log.push(format!("node {} acks every fragment after {} ms, losing {:.0}% of the acks.", config.node, config.delay_ms, config.loss * 100.0));
+        self.ack_sinks.add(config);
+        true
+    }
+
+    fn send_due_acks(&mut self){
+        for ack in self.ack_sinks.due() {
+            let next_hop = ack.routing_header.hops[1];
+            self.record_bandwidth(&ack);
+            match self.all_sender_packets.get(&next_hop) {
+                Some(sender) if sender.send(ack).is_ok() => {}
+                _ => self.log.push(format!("ack not sent, node {} can't be reached.", next_hop)),
+            }
+        }
+    }
+
+    pub fn add_sla_probe(&mut self, config: SlaProbeConfig) -> bool {
+        let endpoint = |id: &NodeId| matches!(self.node_types.get(id), Some(NodeType::Client) | Some(NodeType::Server));
+        if !endpoint(&config.client) || !endpoint(&config.server) {
+            self.log.push(format!("no SLA probe from {} to {}, they must be clients or servers.", config.client, config.server));
+            return false;
+        }
+        self.log.push(format!("SLA probe from {} to {} every {} ms.", config.client, config.server, config.period_ms));
+        self.sla.add(config);
+        true
+    }
+
+    //The probes go on the routes cached by the clients, so a crash hurts them like the real traffic
+    //until a nack drops the route. The acks of the servers go out at once.
+    fn run_sla_probes(&mut self){
+        let now = SystemTime::now();
+        self.sla.expire(now);
+        for (index, client, server) in self.sla.due(now) {
+            let (graph, node_types, crashed) = (&self.network_graph, &self.node_types, &self.crashed);
+            let route = self.route_cache.route(client, server, || traceroute::shortest_route(graph, node_types, crashed, client, server));
+            if let Some(probe) = self.sla.start(index, route, now) {
+                self.inject_packet(probe);
+            }
+        }
+        for echo in self.sla.take_echoes() {
+            let next_hop = echo.routing_header.hops[1];
+            self.record_bandwidth(&echo);
+            match self.all_sender_packets.get(&next_hop) {
+                Some(sender) if sender.send(echo).is_ok() => {}
+                _ => self.log.push(format!("SLA ack not sent, node {} can't be reached.", next_hop)),
+            }
+        }
+    }
+
+    pub fn set_flow_control(&mut self, flow_control: FlowControlConfig){
+        self.flow_control = flow_control;
+    }
+
+    //Sends a message of n_fragments along the route, as the client at its start would, keeping
+    //at most window fragments unacked (None for the one of the config, 0 for no limit).
+    //Returns the session of the message.
+    pub fn send_message(&mut self, route: Vec<NodeId>, n_fragments: u64, window: Option<usize>) -> u64 {
+        self.start_transfer(vec![route], RoutingStrategy::SinglePath, n_fragments, window)
+    }
+
+    fn start_transfer(&mut self, routes: Vec<Vec<NodeId>>, strategy: RoutingStrategy, n_fragments: u64, window: Option<usize>) -> u64 {
+        let session_id = self.next_transfer_session;
+        self.next_transfer_session += 1;
+        let window = window.unwrap_or(self.flow_control.window);
+        self.repro.record(ReproAction::Message { session_id, routes: routes.clone(), fragments: n_fragments, window });
+        self.transfers.push(Transfer::new(session_id, routes, strategy, n_fragments, window, &self.flow_control));
+        self.pump_transfers();
+        session_id
+    }
+
+    //Like send_message, but the client finds the route itself, with the strategy of the config.
+    pub fn send_to(&mut self, client: NodeId, destination: NodeId, n_fragments: u64, window: Option<usize>) -> Option<u64> {
+        self.send_to_with(client, destination, n_fragments, window, self.routing.strategy)
+    }
+
+    //With a single path the client takes the shortest route when it has none cached for the
+    //destination, the cached one otherwise. With multipath it looks for the disjoint routes
+    //every time. None if there's no route.
+    pub fn send_to_with(&mut self, client: NodeId, destination: NodeId, n_fragments: u64, window: Option<usize>, strategy: RoutingStrategy) -> Option<u64> {
+        let (graph, node_types, crashed) = (&self.network_graph, &self.node_types, &self.crashed);
+        let routes = match strategy {
+            RoutingStrategy::SinglePath => self.route_cache
+                .route(client, destination, || traceroute::shortest_route(graph, node_types, crashed, client, destination))
+                .into_iter()
+                .collect::<Vec<Vec<NodeId>>>(),
+            RoutingStrategy::Multipath => routing::disjoint_routes(graph, node_types, crashed, client, destination, self.routing.max_routes),
+        };
+        if routes.is_empty() {
+            self.log.push(format!("no route from {} to {}.", client, destination));
+            return None;
+        }
+        Some(self.start_transfer(routes, strategy, n_fragments, window))
+    }
+
+    //Everything going out counts, the drones' packets and the ones I send for the endpoints.
+    fn record_bandwidth(&mut self, packet: &Packet) {
+        let receiver = packet_link(packet).map(|(_, to)| to);
+        self.bandwidth.record(packet_source(packet), receiver, packet, &self.node_types);
+    }
+
+    //The client gives up on a message still on its way: no more retransmissions, and the server
+    //drops the fragments it got so far. False if there's no such message, or it's over already.
+    pub fn cancel_transfer(&mut self, session_id: u64) -> bool {
+        let Some(transfer) = self.transfers.iter_mut().find(|transfer| transfer.session_id == session_id && transfer.finished.is_none() && !transfer.cancelled) else {
+            self.log.push(format!("message {} can't be cancelled, it's not on its way.", session_id));
+            return false;
+        };
+        transfer.cancel();
+        let (delivered, total, destination) = (transfer.delivered(), transfer.total, transfer.destination());
+        let discarded = destination.map_or(false, |server| self.inbox.cancel(server, session_id));
+        let discarded = if discarded { ", the server discarded its fragments" } else { "" };
+        self.log.push(format!("message {} cancelled with {}/{} fragments delivered{}.", session_id, delivered, total, discarded));
+        self.timeline.add(TimelineKind::Cancel, destination, format!("message {} cancelled", session_id));
+        true
+    }
+
+    //A link from my drone to a node of another segment: the drone gets the channel of the other
+    //node, and the node is in my graph only as a neighbour, it's the other segment that runs it.
+    pub fn add_foreign_link(&mut self, local: NodeId, foreign: NodeId, foreign_send: Sender<Packet>){
+        let Some(sender) = self.node_send.get(&local) else {
+            return;
+        };
+        if send_command(&mut self.audit, local, sender, DroneCommand::AddSender(foreign, foreign_send)).is_ok() {
+            if let Some(neighbours) = self.network_graph.get_mut(&local) {
+                if !neighbours.contains(&foreign) {
+                    neighbours.push(foreign);
+                }
+            }
+            self.log.push(format!("drone {} linked to {} of another segment", local, foreign));
+        }
+    }
+
+    //A shortcut that another segment couldn't deliver, since the destination is mine.
+    pub fn relay_shortcut(&mut self, packet: Packet){
+        self.deliver_shortcut(packet);
+    }
+
+    pub fn set_routing(&mut self, routing: RoutingConfig){
+        self.routing = routing;
+    }
+
+    //The messages of send_to by strategy, to compare them.
+    pub fn strategy_stats(&self) -> BTreeMap<RoutingStrategy, StrategyStats> {
+        routing::strategy_stats(&self.transfers)
+    }
+
+    //The packets reaching the ends of the messages. An ack can come after the message is over, so
+    //its session is updated here too and not only while sending.
+    fn record_transfers(&mut self, packet: &Packet) {
+        for transfer in self.transfers.iter_mut() {
+            if transfer.record(packet) {
+                self.log.push(transfer.describe());
+            }
+            if transfer.session_id == packet.session_id && matches!(packet.pack_type, PacketType::Ack(_)) {
+                self.sessions.set_flow(transfer.session_id, transfer.window, transfer.retransmissions, transfer.duplicate_acks);
+            }
+        }
+    }
+
+    //Sends what fits in the windows of the unfinished messages.
+
+    fn pump_transfers(&mut self){
+        let mut packets = Vec::new();
+        for transfer in self.transfers.iter_mut().filter(|transfer| transfer.finished.is_none() && !transfer.cancelled) {
+            packets.extend(transfer.next_packets());
+            self.sessions.set_flow(transfer.session_id, transfer.window, transfer.retransmissions, transfer.duplicate_acks);
+        }
+        for packet in packets {
+            self.inject_packet(packet);
+        }
+    }
+
     fn remove_senders(&mut self, id: NodeId, id_to_remove: NodeId){
         if let Some(sender) = self.node_send.get(&id) {
-            if let Err(_e) = sender.send(RemoveSender(id_to_remove)) {
+            if let Err(_e) = send_command(&mut self.audit, id, sender, RemoveSender(id_to_remove)) {
                 println!("error in removing drone {} from drone {} senders", id_to_remove, id);
             } else {
                 println!("drone {} removed from drone {} senders", id_to_remove, id);
@@ -144,8 +2370,9 @@ impl SimulationControl{
 
     fn add_sender(&mut self, id: NodeId, id_to_add: NodeId, ){
         if let Some(sender) = self.node_send.get(&id) {
-            if let Some(senderpacket) = self.all_sender_packets.get(&id) {
-                if let Err(_e) = sender.send(AddSender(id_to_add, senderpacket.clone())) {
+            //The drone needs the channel of the node it's connecting to, not its own.
+            if let Some(senderpacket) = self.all_sender_packets.get(&id_to_add) {
+                if let Err(_e) = send_command(&mut self.audit, id, sender, AddSender(id_to_add, senderpacket.clone())) {
                     println!("error adding drone {} to drone {} senders", id_to_add, id);
                 } else {
                     println!("drone {} added to drone {} senders", id_to_add, id);
@@ -155,16 +2382,130 @@ impl SimulationControl{
         }
     }
 
-    fn set_pdr(&mut self, id: NodeId, pdr: f32 ){
+    //Tears the link down in both directions, e.g. when two drones go out of radio range.
+    pub fn remove_link(&mut self, a: NodeId, b: NodeId){
+        self.remove_senders(a, b);
+        self.remove_senders(b, a);
+        self.route_cache.invalidate_link(a, b);
+        self.asymmetric_links.remove(&(a, b));
+        self.asymmetric_links.remove(&(b, a));
+        self.evicted_links.remove(&(a, b));
+        self.evicted_links.remove(&(b, a));
+        if let Some(neighbours) = self.network_graph.get_mut(&a) {
+            neighbours.retain(|id| *id != b);
+        }
+        if let Some(neighbours) = self.network_graph.get_mut(&b) {
+            neighbours.retain(|id| *id != a);
+        }
+        self.repro.record(ReproAction::LinkDown(a, b));
+    }
+
+    pub fn add_link(&mut self, a: NodeId, b: NodeId){
+        if self.crashed.contains(&a) || self.crashed.contains(&b) {
+            return;
+        }
+        self.add_sender(a, b);
+        self.add_sender(b, a);
+        self.evicted_links.remove(&(a, b));
+        self.evicted_links.remove(&(b, a));
+        self.repro.record(ReproAction::LinkUp(a, b));
+        for (from, to) in [(a, b), (b, a)] {
+            if let Some(neighbours) = self.network_graph.get_mut(&from) {
+                if !neighbours.contains(&to) {
+                    neighbours.push(to);
+                }
+            }
+        }
+        for (from, to) in [(a, b), (b, a)] {
+            if let (Some(sender), Some(capacity)) = (self.skylink_send.get(&from), self.link_capacity.capacity(from, to)) {
+                let _ = send_skylink_command(&mut self.audit, from, sender, SkyLinkCommand::SetLinkCapacity(to, Some(capacity)));
+            }
+        }
+        //A new link may cross some region.
+        self.update_link_impairments();
+    }
+
+    //The pdr of the link between a and b, used by both ends instead of their own. None goes back to
+    //the pdr of the drones. Returns false if neither end is a SkyLink drone.
+    pub fn set_link_pdr(&mut self, a: NodeId, b: NodeId, pdr: Option<f32>) -> bool {
+        let pdr = pdr.map(|pdr| pdr.clamp(0.0, 1.0));
+        let mut taken = false;
+        for (from, to) in [(a, b), (b, a)] {
+            if let Some(sender) = self.skylink_send.get(&from) {
+                match send_skylink_command(&mut self.audit, from, sender, SkyLinkCommand::SetLinkPdr(to, pdr)) {
+                    Ok(_) => taken = true,
+                    Err(e) => println!("error in sending the link pdr to drone {}: {:?}", from, e),
+                }
+            }
+        }
+        if !taken {
+            return false;
+        }
+        match pdr {
+            Some(pdr) => {
+                self.link_pdrs.insert(link_key(a, b), pdr);
+                self.log.push(format!("link {}-{} now has pdr set to {}", a, b, pdr));
+            }
+            None => {
+                self.link_pdrs.remove(&link_key(a, b));
+                self.log.push(format!("link {}-{} is back to the pdr of its drones", a, b));
+            }
+        }
+        true
+    }
+
+    pub fn set_pdr(&mut self, id: NodeId, pdr: f32 ){
         if let Some(sender) = self.node_send.get(&id) {
-            if let Err(_e) = sender.send(DroneCommand::SetPacketDropRate(pdr)) {
+            if let Err(_e) = send_command(&mut self.audit, id, sender, DroneCommand::SetPacketDropRate(pdr)) {
                 println!("error in setting drone {} pdr to {}", id, pdr);
             } else {
                 println!("setting drone {} pdr to {}", id, pdr);
+                let was = self.node_pdr.insert(id, pdr).unwrap_or(0.0);
+                self.repro.record(ReproAction::SetPdr { drone: id, pdr, was });
                 self.log.push(format!("drone {} now has pdr set to {}", id, pdr));
+                self.node_log(id, &format!("pdr set to {} by the Sim Contr.", pdr));
             }
         }
     }
+}
+
+//Every command goes through these two, so the audit trail has all of them.
+fn send_command(audit: &mut AuditLog, node: NodeId, sender: &Sender<DroneCommand>, command: DroneCommand) -> Result<(), SendError<DroneCommand>> {
+    audit.record(node, &command);
+    sender.send(command)
+}
+
+fn send_skylink_command(audit: &mut AuditLog, node: NodeId, sender: &Sender<SkyLinkCommand>, command: SkyLinkCommand) -> Result<(), SendError<SkyLinkCommand>> {
+    audit.record_skylink(node, &command);
+    sender.send(command)
+}
+
+fn event_packet(e: &DroneEvent) -> &Packet {
+    match e {
+        DroneEvent::PacketSent(packet)
+        | DroneEvent::PacketDropped(packet)
+        | DroneEvent::ControllerShortcut(packet) => packet,
+    }
+}
 
+//The link a routed packet has just been sent on (floods don't say where they go).
+fn packet_link(packet: &Packet) -> Option<(NodeId, NodeId)> {
+    let hop_index = packet.routing_header.hop_index;
+    if hop_index == 0 || hop_index >= packet.routing_header.hops.len() {
+        return None;
+    }
+    Some((packet.routing_header.hops[hop_index - 1], packet.routing_header.hops[hop_index]))
 }
 
+//Returns the node that sent the packet, the one before the hop_index for routed packets,
+//and the last one in the path trace for flood requests.
+pub(crate) fn packet_source(packet: &Packet) -> Option<NodeId> {
+    if let PacketType::FloodRequest(flood_request) = &packet.pack_type {
+        return flood_request.path_trace.last().map(|(id, _)| *id);
+    }
+    if packet.routing_header.hop_index > 0 {
+        packet.routing_header.hops.get(packet.routing_header.hop_index - 1).copied()
+    } else {
+        packet.routing_header.hops.first().copied()
+    }
+}
@@ -0,0 +1,270 @@
+use std::cell::RefCell;
+use std::io::{self, Stdout};
+use std::rc::Rc;
+use std::time::Duration;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table, TableState};
+use ratatui::{Frame, Terminal};
+use wg_2024::network::NodeId;
+use crate::sim_control::SimulationControl;
+use crate::audit::CommandOrigin;
+
+// What the keyboard is currently typing into.
+enum InputMode {
+    Normal,
+    Pdr,
+    Filter,
+}
+
+pub struct SimulationTui {
+    sim_contr: Rc<RefCell<SimulationControl>>,
+    table_state: TableState,
+    input_mode: InputMode,
+    input: String,
+    log_filter: String,
+    quit: bool,
+}
+
+impl SimulationTui {
+    fn new(sim_contr: Rc<RefCell<SimulationControl>>) -> Self {
+        sim_contr.borrow_mut().set_command_origin(CommandOrigin::Tui);
+        let mut table_state = TableState::default();
+        table_state.select(Some(0));
+        Self {
+            sim_contr,
+            table_state,
+            input_mode: InputMode::Normal,
+            input: String::new(),
+            log_filter: String::new(),
+            quit: false,
+        }
+    }
+
+    //The ids of the nodes in the table, sorted so the rows don't jump around between frames.
+    fn node_ids(&self) -> Vec<NodeId> {
+        let mut ids = self.sim_contr.borrow().network_graph.keys().copied().collect::<Vec<NodeId>>();
+        ids.sort();
+        ids
+    }
+
+    fn selected_node(&self) -> Option<NodeId> {
+        let ids = self.node_ids();
+        self.table_state.selected().and_then(|i| ids.get(i).copied())
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(45),
+                Constraint::Min(5),
+                Constraint::Length(3),
+            ])
+            .split(frame.size());
+
+        self.render_nodes(frame, chunks[0]);
+        self.render_log(frame, chunks[1]);
+        self.render_status(frame, chunks[2]);
+    }
+
+    fn render_nodes(&mut self, frame: &mut Frame, area: Rect) {
+        let sim_contr = self.sim_contr.borrow();
+        let rows = self.node_ids().into_iter().map(|id| {
+            let neighbours = sim_contr.network_graph[&id]
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+            let pdr = sim_contr.node_pdr.get(&id).map(|pdr| format!("{:.2}", pdr)).unwrap_or("-".to_string());
+            let stats = sim_contr.stats.get(&id).cloned().unwrap_or_default();
+            let crashed = sim_contr.crashed.contains(&id);
+            let style = if crashed {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            };
+            Row::new(vec![
+                id.to_string(),
+                if crashed { "crashed".to_string() } else { "up".to_string() },
+                pdr,
+                stats.packets_sent.to_string(),
+                stats.packets_dropped.to_string(),
+                stats.shortcuts.to_string(),
+                format!("{}/{}", stats.queue_len, stats.queue_peak),
+                neighbours,
+            ]).style(style)
+        }).collect::<Vec<Row>>();
+
+        let widths = [
+            Constraint::Length(5),
+            Constraint::Length(8),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Min(10),
+        ];
+        let table = Table::new(rows, widths)
+            .header(
+                Row::new(vec!["Id", "State", "PDR", "Sent", "Dropped", "Shortcuts", "Queue/Peak", "Neighbours"])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(Block::default().borders(Borders::ALL).title("Nodes"))
+            .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(table, area, &mut self.table_state);
+    }
+
+    fn render_log(&self, frame: &mut Frame, area: Rect) {
+        let sim_contr = self.sim_contr.borrow();
+        //I only show the last lines that fit in the panel, after applying the filter.
+        let visible = area.height.saturating_sub(2) as usize;
+        let mut entries = sim_contr.log
+            .iter()
+            .rev()
+            .filter(|entry| self.log_filter.is_empty() || entry.contains(&self.log_filter))
+            .take(visible)
+            .map(|entry| ListItem::new(entry.clone()))
+            .collect::<Vec<ListItem>>();
+        entries.reverse();
+
+        let title = if self.log_filter.is_empty() {
+            "Simulation controller log".to_string()
+        } else {
+            format!("Simulation controller log (filter: {})", self.log_filter)
+        };
+        let list = List::new(entries).block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(list, area);
+    }
+
+    fn render_status(&self, frame: &mut Frame, area: Rect) {
+        let text = match self.input_mode {
+            InputMode::Normal => {
+                let paused = if self.sim_contr.borrow().paused { " [PAUSED]" } else { "" };
+                format!("q: quit  up/down: select  c: crash  p: set pdr  space: pause  s: snapshot  e: export stats  /: filter{}", paused)
+            },
+            InputMode::Pdr => format!("New pdr for the selected drone (enter to confirm, esc to cancel): {}", self.input),
+            InputMode::Filter => format!("Log filter (enter to confirm, esc to clear): {}", self.input),
+        };
+        let status = Paragraph::new(text).block(Block::default().borders(Borders::ALL));
+        frame.render_widget(status, area);
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        match self.input_mode {
+            InputMode::Normal => self.handle_normal_key(code),
+            InputMode::Pdr | InputMode::Filter => self.handle_input_key(code),
+        }
+    }
+
+    fn handle_normal_key(&mut self, code: KeyCode) {
+        let n_nodes = self.node_ids().len();
+        match code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Down => {
+                if n_nodes > 0 {
+                    let next = self.table_state.selected().map(|i| (i + 1) % n_nodes).unwrap_or(0);
+                    self.table_state.select(Some(next));
+                }
+            },
+            KeyCode::Up => {
+                if n_nodes > 0 {
+                    let prev = self.table_state.selected().map(|i| (i + n_nodes - 1) % n_nodes).unwrap_or(0);
+                    self.table_state.select(Some(prev));
+                }
+            },
+            KeyCode::Char('c') => {
+                if let Some(id) = self.selected_node() {
+                    self.sim_contr.borrow_mut().crash_drone(id);
+                }
+            },
+            KeyCode::Char('p') => {
+                self.input.clear();
+                self.input_mode = InputMode::Pdr;
+            },
+            KeyCode::Char('/') => {
+                self.input = self.log_filter.clone();
+                self.input_mode = InputMode::Filter;
+            },
+            KeyCode::Char(' ') => self.sim_contr.borrow_mut().toggle_pause(),
+            KeyCode::Char('s') => self.sim_contr.borrow_mut().save_snapshot("snapshot.toml"),
+            KeyCode::Char('e') => self.sim_contr.borrow_mut().export_stats("stats.csv"),
+            _ => {},
+        }
+    }
+
+    fn handle_input_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            },
+            KeyCode::Esc => {
+                if let InputMode::Filter = self.input_mode {
+                    self.log_filter.clear();
+                }
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            },
+            KeyCode::Enter => {
+                match self.input_mode {
+                    InputMode::Pdr => {
+                        if let (Some(id), Ok(pdr)) = (self.selected_node(), self.input.trim().parse::<f32>()) {
+                            self.sim_contr.borrow_mut().set_pdr(id, pdr);
+                        }
+                    },
+                    InputMode::Filter => self.log_filter = self.input.clone(),
+                    InputMode::Normal => {},
+                }
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            },
+            _ => {},
+        }
+    }
+
+    fn run(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+        while !self.quit {
+            self.sim_contr.borrow_mut().process_events();
+            if self.sim_contr.borrow().shutdown_requested {
+                break;
+            }
+            terminal.draw(|frame| self.render(frame))?;
+
+            //I wait a bit for a key, so the table is refreshed even when nobody is typing.
+            if event::poll(Duration::from_millis(100))? {
+                if let Event::Key(key) = event::read()? {
+                    //In raw mode Ctrl-C arrives as a key instead of a signal.
+                    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                        self.quit = true;
+                    } else if key.kind == KeyEventKind::Press {
+                        self.handle_key(key.code);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn run_simulation_tui(sim_contr: Rc<RefCell<SimulationControl>>) -> io::Result<()> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    //The stats are exported by the shutdown of the Sim Contr once the monitor is closed.
+    let result = SimulationTui::new(sim_contr).run(&mut terminal);
+
+    //I restore the terminal even if the loop failed, otherwise the shell is left in raw mode.
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
@@ -1,55 +1,106 @@
 use wg_2024::packet::{NackType, Packet, PacketType};
 use crate::skylink_drone::error::create_error;
 use crate::skylink_drone::drone::SkyLinkDrone;
+use crate::skylink_drone::commands::FilterRule;
 
-pub fn id_hop_match_check(drone: &SkyLinkDrone, packet: Packet) -> Result<(), Packet> {
+//The checks only borrow the packet: a copy is made only when the check fails and an error has to be built.
+pub fn id_hop_match_check(drone: &SkyLinkDrone, packet: &Packet) -> Result<(), Packet> {
     if packet.routing_header.hops[packet.routing_header.hop_index] == drone.get_id() {
         Ok(())
     } else {
-        match packet.pack_type.clone() {
-            PacketType::MsgFragment(_fragment) => {
+        match packet.pack_type {
+            PacketType::MsgFragment(_) => {
                 Err(create_error(packet.routing_header.hops[packet.routing_header.hop_index-1], packet, NackType::UnexpectedRecipient(drone.get_id())))
             },
             _ => {
-                Err(packet)
+                Err(packet.clone())
             }
         }}
 }
-pub fn final_destination_check(drone: &SkyLinkDrone, packet: Packet) -> Result<(), Packet> {
+pub fn final_destination_check(drone: &SkyLinkDrone, packet: &Packet) -> Result<(), Packet> {
     if packet.routing_header.hop_index < packet.routing_header.hops.len() {
         Ok(())
     } else {
-        match packet.pack_type.clone() {
-            PacketType::MsgFragment(_fragment) => {
+        match packet.pack_type {
+            PacketType::MsgFragment(_) => {
                 Err(create_error(drone.get_id(), packet, NackType::DestinationIsDrone))
             },
             _ => {
-                Err(packet)
+                Err(packet.clone())
             }
         }
     }
 }
-pub fn is_next_hop_check(drone: &SkyLinkDrone, packet: Packet) -> Result<(), Packet> {
+pub fn is_next_hop_check(drone: &SkyLinkDrone, packet: &Packet) -> Result<(), Packet> {
     let next_hop = &packet.routing_header.hops[packet.routing_header.hop_index];
     if drone.get_packet_send().contains_key(next_hop) {
         Ok(())
     } else {
-        match packet.pack_type.clone() {
-            PacketType::MsgFragment(_fragment) => {
+        match packet.pack_type {
+            PacketType::MsgFragment(_) => {
                 Err(create_error(drone.get_id(), packet, NackType::ErrorInRouting(drone.get_id())))
             },
             _ => {
-                Err(packet)
+                Err(packet.clone())
             }
         }
     }
 }
-pub fn pdr_check(drone: &SkyLinkDrone, packet: Packet) -> Result<(), Packet> {
-    if let PacketType::MsgFragment(_) = packet.pack_type.clone() {
-        let random_number: u32 = fastrand::u32(0..101);
-        if random_number < drone.get_pdr() {
+//With a max_hops a route of more hops than that (one less than its nodes), or one that already went
+//through me (a loop), isn't followed: the fragment is nacked as if I couldn't route it, so the
+//client picks another route instead of sending it around forever. Called before the hop_index is
+//moved past me.
+pub fn ttl_check(drone: &SkyLinkDrone, packet: &Packet) -> Result<(), Packet> {
+    let Some(max_hops) = drone.get_max_hops() else {
+        return Ok(());
+    };
+    let header = &packet.routing_header;
+    let looped = header.hops[..header.hop_index].contains(&drone.get_id());
+    if header.hops.len().saturating_sub(1) <= max_hops && !looped {
+        return Ok(());
+    }
+    match packet.pack_type {
+        PacketType::MsgFragment(_) => {
+            Err(create_error(drone.get_id(), packet, NackType::ErrorInRouting(drone.get_id())))
+        },
+        _ => {
+            Err(packet.clone())
+        }
+    }
+}
+pub fn pdr_check(drone: &SkyLinkDrone, packet: &Packet) -> Result<(), Packet> {
+    if let PacketType::MsgFragment(_) = packet.pack_type {
+        //The pdr of the link to the next hop, if it has one, wins over mine.
+        let link_pdr = packet.routing_header.hops.get(packet.routing_header.hop_index).and_then(|next_hop| drone.get_link_pdr(*next_hop));
+        //In [0, 1): a pdr of 0 never drops, a pdr of 1 always does. Without a link pdr the burst
+        //loss, if I have one, takes the place of mine.
+        let lost = match (link_pdr, drone.has_burst_loss()) {
+            (Some(pdr), _) => drone.roll() < pdr,
+            (None, true) => drone.burst_lost(),
+            (None, false) => drone.roll() < drone.get_pdr(),
+        };
+        if lost {
             return Err(create_error(drone.get_id(), packet, NackType::Dropped))
         }
     }
     Ok(())
 }
+pub fn filter_check(drone: &SkyLinkDrone, packet: &Packet) -> Result<(), Packet> {
+    let source = packet.routing_header.hops[0];
+    let next_hop = packet.routing_header.hops[packet.routing_header.hop_index];
+    let Some(rule) = drone.get_filters().iter().find(|rule| !rule.allows(source, next_hop, packet.session_id)) else {
+        return Ok(());
+    };
+    match (&packet.pack_type, rule) {
+        //A next hop that isn't allowed is like a next hop that doesn't exist.
+        (PacketType::MsgFragment(_), FilterRule::OnlyForwardTo(_)) => {
+            Err(create_error(drone.get_id(), packet, NackType::ErrorInRouting(next_hop)))
+        },
+        (PacketType::MsgFragment(_), _) => {
+            Err(create_error(drone.get_id(), packet, NackType::Dropped))
+        },
+        _ => {
+            Err(packet.clone())
+        }
+    }
+}
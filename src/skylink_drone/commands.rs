@@ -0,0 +1,125 @@
+use std::time::Duration;
+use crossbeam_channel::Sender;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::PacketType;
+use crate::skylink_drone::links::{LinkCapacity, PacketRate};
+use crate::skylink_drone::tap::TapRecord;
+use crate::skylink_drone::reorder::Reorder;
+use crate::skylink_drone::state::DroneState;
+use crate::skylink_drone::loss::BurstLoss;
+
+//What happens to the packets I send on a link, on top of my own pdr.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkImpairment {
+    pub extra_drop: f32, //Probability (0.0 - 1.0) of dropping a fragment sent on the link.
+    pub latency: Duration, //Time the packet takes to go through the link.
+}
+
+//The time I take on every packet before I forward it, to play a slow drone.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProcessingDelay {
+    pub delay: Duration,
+    pub jitter: Duration, //Up to this much more, rolled for every packet.
+}
+
+//Faults I inject on purpose in the fragments I forward, to test the reassembly and the acks.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PacketFaults {
+    pub duplicate: f32, //Probability (0.0 - 1.0) of sending a fragment twice to the next hop.
+    pub corrupt: f32, //Probability (0.0 - 1.0) of flipping a byte of the payload of a fragment.
+}
+
+//My battery: every packet I forward costs forward_cost, every copy of a flood I send to a
+//neighbour flood_cost. When nothing is left I crash on my own.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EnergyBudget {
+    pub capacity: u64,
+    pub forward_cost: u64,
+    pub flood_cost: u64,
+}
+
+//The events I keep from the Sim Contr, to keep a busy drone quiet. The counters and the tap still
+//see all of them, and the shortcuts and the packets at the last hop of their route always go: the
+//Sim Contr plays the clients and the servers, it has to see what reaches them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventMute {
+    pub fragments: bool,
+    pub acks: bool,
+    pub nacks: bool,
+    pub floods: bool, //Flood requests and responses.
+    pub drops: bool,
+}
+
+impl EventMute {
+    pub fn mutes(&self, event: &DroneEvent) -> bool {
+        let packet = match event {
+            DroneEvent::ControllerShortcut(_) => return false,
+            DroneEvent::PacketDropped(_) => return self.drops,
+            DroneEvent::PacketSent(packet) => packet,
+        };
+        let last_hop = packet.routing_header.hop_index + 1 >= packet.routing_header.hops.len();
+        match packet.pack_type {
+            PacketType::FloodRequest(_) => self.floods,
+            _ if last_hop => false,
+            PacketType::MsgFragment(_) => self.fragments,
+            PacketType::Ack(_) => self.acks,
+            PacketType::Nack(_) => self.nacks,
+            PacketType::FloodResponse(_) => self.floods,
+        }
+    }
+}
+
+//How much I tell the Sim Contr about myself.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Telemetry {
+    pub interval: Option<Duration>, //The max delay of my event batches, None keeps the one I have. Only if I send them in batches.
+    pub mute: EventMute,
+}
+
+//What I tell the Sim Contr once I stopped for a Shutdown, right before my thread ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DroneExit {
+    pub id: NodeId,
+    pub drained: usize, //The packets that were still in my queue, handled before stopping.
+}
+
+//Policy rules checked on every packet I route, to play a node enforcing a policy (or a misconfigured one).
+//A refused fragment gets a nack back, the other packets go through the Sim Contr like with the other checks.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterRule {
+    DenyFrom(NodeId), //Packets whose route starts at the node are refused.
+    OnlyForwardTo(Vec<NodeId>), //Packets for the other next hops are refused.
+    BlockSession(u64),
+}
+
+impl FilterRule {
+    //Whether the packet is let through, given its source and its next hop.
+    pub fn allows(&self, source: NodeId, next_hop: NodeId, session_id: u64) -> bool {
+        match self {
+            FilterRule::DenyFrom(node_id) => source != *node_id,
+            FilterRule::OnlyForwardTo(next_hops) => next_hops.contains(&next_hop),
+            FilterRule::BlockSession(blocked) => session_id != *blocked,
+        }
+    }
+}
+
+//Commands that only SkyLink drones understand, so they can't travel with the wg_2024
+//DroneCommands: they have their own channel (see SkyLinkDrone::with_skylink_commands).
+#[derive(Debug, Clone)]
+pub enum SkyLinkCommand {
+    SetLinkImpairment(NodeId, LinkImpairment),
+    SetLinkCapacity(NodeId, Option<LinkCapacity>), //None makes the link to the node unlimited again.
+    SetLinkRate(Option<NodeId>, Option<PacketRate>), //For the link to the node, or to every neighbour with None. None removes it.
+    SetLinkPdr(NodeId, Option<f32>), //Instead of my pdr for the fragments sent to the node, None goes back to mine.
+    SetFilters(Vec<FilterRule>), //Replaces all the rules, an empty Vec removes them.
+    Ping(Sender<()>), //Answered right away, to see if my thread is still reading its channels.
+    Snapshot(Sender<DroneState>), //Answered right away with my state, even while crashing.
+    SetTap(Option<Sender<TapRecord>>), //Everything I receive and send is copied there, None stops it.
+    SetProcessingDelay(Option<NodeId>, ProcessingDelay), //For the packets to the node, or to everyone with None. A zero delay removes it.
+    SetPacketFaults(PacketFaults), //The default removes them.
+    SetBurstLoss(Option<BurstLoss>), //Drops in bursts instead of with the pdr, None goes back to the pdr.
+    SetReorder(Option<Reorder>), //The jitter mode, None turns it off and lets out what was held.
+    SetTelemetry(Telemetry),
+    Shutdown(Sender<DroneExit>), //I handle what's already in my queue, answer there and my run() returns.
+}
@@ -0,0 +1,122 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+use wg_2024::controller::DroneEvent;
+
+//Counters written by the drone and read by whoever holds a copy of the Arc (the Sim Contr,
+//the GUI), without channels or locks: reading them doesn't slow the drone down at all.
+#[derive(Debug, Default)]
+pub struct DroneCounters {
+    pub packets_sent: AtomicU64,
+    pub packets_dropped: AtomicU64,
+    pub shortcuts: AtomicU64,
+    pub flood_cache: AtomicU64, //Not a counter, the current size of the flood cache.
+    pub gossip_sent: AtomicU64, //Gossip messages, they aren't packets so they have no event.
+    pub events_suppressed: AtomicU64, //Events over the rate limit, never sent to the Sim Contr.
+    //What I did with the packets, counted where it happens instead of from the events.
+    pub fragments_forwarded: AtomicU64,
+    pub fragments_dropped: AtomicU64, //By the pdr, a lossy link or a congested next hop: the Dropped nacks I made.
+    pub nacks_generated: AtomicU64, //Of any kind.
+    pub floods_handled: AtomicU64, //Flood requests received, the ones already seen too.
+    pub congestion_drops: AtomicU64, //Fragments dropped because the queue of the next hop stayed full, part of fragments_dropped.
+    pub rate_limited: AtomicU64, //Fragments nacked for going over the packet rate of the link, part of fragments_dropped.
+    pub fragments_duplicated: AtomicU64, //Sent twice on purpose, see PacketFaults.
+    pub fragments_corrupted: AtomicU64,
+    pub fragments_reordered: AtomicU64, //Held fragments that left before one held earlier, in jitter mode.
+    pub duplicates_suppressed: AtomicU64, //Copies of fragments I had just forwarded, dropped without a nack.
+    pub energy_left: AtomicU64, //Not a counter, what's left of the battery (only with an EnergyBudget).
+    pub energy_depleted: AtomicBool, //Set when the battery runs out and I crash on my own, for the Sim Contr to see.
+    pub terminated: AtomicBool, //Set when my thread is done, all my channels closed.
+}
+
+//The counters read all at once, as plain numbers (see DroneState).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CounterValues {
+    pub packets_sent: u64,
+    pub packets_dropped: u64,
+    pub shortcuts: u64,
+    pub gossip_sent: u64,
+    pub events_suppressed: u64,
+    pub fragments_forwarded: u64,
+    pub fragments_dropped: u64,
+    pub nacks_generated: u64,
+    pub floods_handled: u64,
+    pub congestion_drops: u64,
+    pub rate_limited: u64,
+    pub fragments_duplicated: u64,
+    pub fragments_corrupted: u64,
+    pub fragments_reordered: u64,
+    pub duplicates_suppressed: u64,
+    pub energy_left: u64,
+    pub energy_depleted: bool,
+    pub terminated: bool,
+}
+
+impl DroneCounters {
+    pub fn values(&self) -> CounterValues {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        CounterValues {
+            packets_sent: load(&self.packets_sent),
+            packets_dropped: load(&self.packets_dropped),
+            shortcuts: load(&self.shortcuts),
+            gossip_sent: load(&self.gossip_sent),
+            events_suppressed: load(&self.events_suppressed),
+            fragments_forwarded: load(&self.fragments_forwarded),
+            fragments_dropped: load(&self.fragments_dropped),
+            nacks_generated: load(&self.nacks_generated),
+            floods_handled: load(&self.floods_handled),
+            congestion_drops: load(&self.congestion_drops),
+            rate_limited: load(&self.rate_limited),
+            fragments_duplicated: load(&self.fragments_duplicated),
+            fragments_corrupted: load(&self.fragments_corrupted),
+            fragments_reordered: load(&self.fragments_reordered),
+            duplicates_suppressed: load(&self.duplicates_suppressed),
+            energy_left: load(&self.energy_left),
+            energy_depleted: self.energy_depleted.load(Ordering::Relaxed),
+            terminated: self.terminated.load(Ordering::Relaxed),
+        }
+    }
+
+    //Every event the drone sends to the Sim Contr is counted here too.
+    pub fn count(&self, event: &DroneEvent) {
+        let counter = match event {
+            DroneEvent::PacketSent(_) => &self.packets_sent,
+            DroneEvent::PacketDropped(_) => &self.packets_dropped,
+            DroneEvent::ControllerShortcut(_) => &self.shortcuts,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    //Returns (packets_sent, packets_dropped, shortcuts, flood_cache).
+    pub fn load(&self) -> (u64, u64, u64, u64) {
+        (
+            self.packets_sent.load(Ordering::Relaxed),
+            self.packets_dropped.load(Ordering::Relaxed),
+            self.shortcuts.load(Ordering::Relaxed),
+            self.flood_cache.load(Ordering::Relaxed),
+        )
+    }
+
+    //Used when a run is restored from a snapshot, the drone goes on counting from there.
+    pub fn store(&self, packets_sent: u64, packets_dropped: u64, shortcuts: u64) {
+        self.packets_sent.store(packets_sent, Ordering::Relaxed);
+        self.packets_dropped.store(packets_dropped, Ordering::Relaxed);
+        self.shortcuts.store(shortcuts, Ordering::Relaxed);
+    }
+
+    //Returns (fragments_forwarded, fragments_dropped, nacks_generated, floods_handled).
+    pub fn load_forwarding(&self) -> (u64, u64, u64, u64) {
+        (
+            self.fragments_forwarded.load(Ordering::Relaxed),
+            self.fragments_dropped.load(Ordering::Relaxed),
+            self.nacks_generated.load(Ordering::Relaxed),
+            self.floods_handled.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn store_forwarding(&self, (fragments_forwarded, fragments_dropped, nacks_generated, floods_handled): (u64, u64, u64, u64)) {
+        self.fragments_forwarded.store(fragments_forwarded, Ordering::Relaxed);
+        self.fragments_dropped.store(fragments_dropped, Ordering::Relaxed);
+        self.nacks_generated.store(nacks_generated, Ordering::Relaxed);
+        self.floods_handled.store(floods_handled, Ordering::Relaxed);
+    }
+}
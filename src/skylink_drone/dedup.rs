@@ -0,0 +1,58 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+//How many of the fragments I forwarded I remember, and for how long, to drop the copies of them
+//that come again (see FragmentDedup). A short ttl lets the retransmission of a fragment lost
+//after me go through, a long one also stops the slow retransmissions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DedupWindow {
+    pub capacity: usize,
+    pub ttl: Duration,
+}
+
+//The fragments I forwarded recently, by (session_id, fragment_index). When a client or a drone
+//before me sends the same fragment again while I still remember it, I drop it without a nack.
+#[derive(Debug)]
+pub struct FragmentDedup {
+    window: DedupWindow,
+    seen: HashMap<(u64, u64), Instant>,
+    order: VecDeque<(u64, u64)>, //Oldest first.
+}
+
+impl FragmentDedup {
+    pub fn new(window: DedupWindow) -> Self {
+        FragmentDedup { window, seen: HashMap::new(), order: VecDeque::new() }
+    }
+
+    pub fn is_duplicate(&mut self, key: (u64, u64), now: Instant) -> bool {
+        self.expire(now);
+        self.seen.contains_key(&key)
+    }
+
+    //After the fragment was forwarded: a full window forgets the oldest one.
+    pub fn remember(&mut self, key: (u64, u64), now: Instant) {
+        self.expire(now);
+        if self.seen.insert(key, now).is_some() {
+            return;
+        }
+        self.order.push_back(key);
+        while self.order.len() > self.window.capacity.max(1) {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(oldest) = self.order.front() {
+            match self.seen.get(oldest) {
+                Some(seen) if now.saturating_duration_since(*seen) < self.window.ttl => break,
+                _ => {
+                    if let Some(oldest) = self.order.pop_front() {
+                        self.seen.remove(&oldest);
+                    }
+                }
+            }
+        }
+    }
+}
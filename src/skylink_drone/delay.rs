@@ -0,0 +1,85 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+//What a packet goes on with once its wait on the link is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelayedKind {
+    Forward, //Through the jitter mode (if on) and send_forward, as if it had never stopped.
+    FloodCopy, //Straight to the neighbour, one of the copies of a flood.
+}
+
+pub struct DelayedPacket {
+    pub release: Instant,
+    seq: u64, //Two packets due at the same time leave in the order they came.
+    pub next_hop: NodeId,
+    pub packet: Packet,
+    pub kind: DelayedKind,
+}
+
+impl PartialEq for DelayedPacket {
+    fn eq(&self, other: &Self) -> bool {
+        (self.release, self.seq) == (other.release, other.seq)
+    }
+}
+
+impl Eq for DelayedPacket {}
+
+impl PartialOrd for DelayedPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+//Reversed, so the heap gives the one due first.
+impl Ord for DelayedPacket {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.release, other.seq).cmp(&(self.release, self.seq))
+    }
+}
+
+//The packets waiting for the processing delay, the latency or the queue of their link. The drone
+//keeps reading its channels meanwhile (a crash isn't stuck behind a slow link) and sends them when
+//they're due, like the held fragments of the jitter mode.
+#[derive(Default)]
+pub struct DelayQueue {
+    waiting: BinaryHeap<DelayedPacket>,
+    seq: u64,
+}
+
+impl DelayQueue {
+    pub fn push(&mut self, release: Instant, next_hop: NodeId, packet: Packet, kind: DelayedKind) {
+        self.waiting.push(DelayedPacket { release, seq: self.seq, next_hop, packet, kind });
+        self.seq += 1;
+    }
+
+    //The packets whose time has come, the one due first first.
+    pub fn due(&mut self, now: Instant) -> Vec<DelayedPacket> {
+        let mut due = Vec::new();
+        while self.waiting.peek().is_some_and(|delayed| delayed.release <= now) {
+            if let Some(delayed) = self.waiting.pop() {
+                due.push(delayed);
+            }
+        }
+        due
+    }
+
+    //Everything, in release order, when I shut down.
+    pub fn drain(&mut self) -> Vec<DelayedPacket> {
+        let mut all = Vec::new();
+        while let Some(delayed) = self.waiting.pop() {
+            all.push(delayed);
+        }
+        all
+    }
+
+    pub fn until_next(&self, now: Instant) -> Option<Duration> {
+        self.waiting.peek().map(|delayed| delayed.release.saturating_duration_since(now))
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+}
@@ -1,12 +1,33 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
 use wg_2024::network::{NodeId, SourceRoutingHeader};
-use crossbeam_channel::{select_biased, Receiver, Sender};
+use crossbeam_channel::{never, select_biased, tick, Receiver, SendTimeoutError, Sender, TryRecvError};
 use wg_2024::controller::{DroneCommand, DroneEvent};
 use wg_2024::controller::DroneEvent::ControllerShortcut;
 use wg_2024::drone::Drone;
-use wg_2024::packet::{Packet, PacketType, FloodResponse, NodeType, FloodRequest, NackType};
+use wg_2024::packet::{Packet, PacketType, FloodResponse, NodeType, FloodRequest, Nack, NackType};
 use crate::skylink_drone::error::create_error;
-use crate::skylink_drone::checks::{id_hop_match_check, final_destination_check, pdr_check, is_next_hop_check};
+use crate::skylink_drone::commands::{DroneExit, EnergyBudget, EventMute, FilterRule, LinkImpairment, PacketFaults, ProcessingDelay, SkyLinkCommand, Telemetry};
+use crate::skylink_drone::counters::DroneCounters;
+use crate::skylink_drone::links::{packet_size, LinkCapacity, PacketRate, TokenBucket};
+use crate::skylink_drone::gossip::{Gossip, GossipMessage};
+use crate::skylink_drone::hooks::DroneHooks;
+use crate::skylink_drone::handshake::{hello, Handshake, HandshakeReport};
+use crate::skylink_drone::heartbeat::{Heartbeat, HeartbeatTimer};
+use crate::skylink_drone::priority::PriorityQueues;
+use crate::skylink_drone::flood_cache::{FloodCache, FloodCacheLimits};
+use crate::skylink_drone::state::DroneState;
+use crate::skylink_drone::loss::BurstLoss;
+use crate::skylink_drone::dedup::{DedupWindow, FragmentDedup};
+use crate::skylink_drone::eviction::{LinkDown, NeighbourEviction};
+use crate::skylink_drone::delay::{DelayQueue, DelayedKind, DelayedPacket};
+use crate::skylink_drone::reorder::{Reorder, ReorderBuffer};
+use crate::skylink_drone::events::{EventBatcher, EventRateLimit, TimedEvent};
+use crate::skylink_drone::tap::{TapRecord, TappedCommand};
+use crate::skylink_drone::checks::{id_hop_match_check, final_destination_check, filter_check, pdr_check, is_next_hop_check, ttl_check};
 
 
 pub struct SkyLinkDrone {
@@ -15,11 +36,54 @@ pub struct SkyLinkDrone {
     controller_recv: Receiver<DroneCommand>,
     packet_recv: Receiver<Packet>,
     packet_send: HashMap<NodeId, Sender<Packet>>,
-    pdr: u32,
-    flood_ids: HashSet<(u64, NodeId)>, //Tuple with the flood_id and the id of the initiator, to distinguish uniquely every flooding.
+    neighbours: Vec<(NodeId, Sender<Packet>)>, //Copy of packet_send for the flood fan-out, rebuilt when packet_send changes.
+    pdr: f32,
+    link_pdrs: HashMap<NodeId, f32>, //Instead of pdr on the links to these neighbours.
+    flood_ids: FloodCache, //By the flood_id and the id of the initiator, to distinguish uniquely every flooding.
     crashing: bool,
+    send_timeout: Duration, //How long I wait on a full (bounded) channel before giving up on the packet.
+    event_batcher: Option<EventBatcher>, //If set, the events go to the Sim Contr in batches instead of one by one.
+    event_limit: Option<EventRateLimit>, //If set, the events over it in a second don't reach the Sim Contr.
+    event_mute: EventMute, //The kinds of events that don't reach the Sim Contr, see SkyLinkCommand::SetTelemetry.
+    counters: Arc<DroneCounters>, //Shared with the Sim Contr, which reads them whenever it wants.
+    skylink_recv: Receiver<SkyLinkCommand>, //Commands outside of wg_2024, by default nobody sends any.
+    link_impairments: HashMap<NodeId, LinkImpairment>,
+    filters: Vec<FilterRule>,
+    link_buckets: RefCell<HashMap<NodeId, TokenBucket>>, //Only for the links with a capacity.
+    link_rate: Option<PacketRate>, //For every neighbour, unless it has its own in link_rates.
+    link_rates: HashMap<NodeId, PacketRate>,
+    rate_buckets: RefCell<HashMap<NodeId, TokenBucket>>, //Made at the first packet sent on the link.
+    gossip: Option<Gossip>, //Only if the topology is also spread with the gossip (see gossip.rs).
+    gossip_recv: Receiver<GossipMessage>,
+    hooks: Vec<Box<dyn DroneHooks>>, //Behaviour added from outside, see hooks.rs.
+    handshake: Option<Handshake>, //Only if the new neighbours have to be checked (see handshake.rs).
+    heartbeat: Option<HeartbeatTimer>, //Only if someone wants to know I'm alive (see heartbeat.rs).
+    tap: Option<Sender<TapRecord>>, //Set with SkyLinkCommand::SetTap, to capture my traffic (see tap.rs).
+    rng: RefCell<fastrand::Rng>, //For the drops, my own so a seed gives the same drops whatever the other threads do.
+    processing_delay: ProcessingDelay, //On every packet I forward, unless the next hop has its own.
+    link_delays: HashMap<NodeId, ProcessingDelay>,
+    packet_faults: PacketFaults,
+    burst_loss: Option<BurstLoss>, //Instead of the pdr, see loss.rs.
+    burst_bad: Cell<bool>, //The state of the burst loss, the link starts good.
+    max_hops: Option<usize>, //The longest route I follow, see ttl_check.
+    eviction: Option<NeighbourEviction>, //Only if the neighbours whose channel closed are removed, see eviction.rs.
+    dedup: Option<FragmentDedup>, //Only if the copies of the fragments I forwarded are dropped, see dedup.rs.
+    reorder: Option<ReorderBuffer>, //Only in jitter mode, see SkyLinkCommand::SetReorder.
+    energy: Option<EnergyBudget>, //Without it my battery never runs out.
+    energy_left: Cell<u64>,
+    exited: bool, //Set by SkyLinkCommand::Shutdown, unlike a crash I don't handle anything after it.
+    //A closed channel is always ready in a select, so once closed it's swapped for never() and remembered here.
+    controller_closed: bool,
+    packets_closed: bool,
+    queued: PriorityQueues, //Read from my channel but not handled yet, the control packets first.
+    delayed: DelayQueue, //The packets waiting on their link, see cross_link.
+    busy_until: Cell<Instant>, //I process one packet at a time, so the processing delays queue up behind this.
+    tick: Receiver<Instant>, //Wakes me up regularly for on_tick, by default it never does (see with_tick).
 }
 
+//Even with nothing to do, the drone wakes up this often to check its event batch.
+const EVENT_WAKE_UP: Duration = Duration::from_secs(1);
+
 impl Drone for SkyLinkDrone {
     fn new(id: NodeId,
            controller_send: Sender<DroneEvent>,
@@ -27,88 +91,275 @@ impl Drone for SkyLinkDrone {
            packet_recv: Receiver<Packet>,
            packet_send: HashMap<NodeId, Sender<Packet>>,
            pdr: f32) -> Self {
-        let mut pdr = pdr;
-        if pdr > 1.00 {
-            pdr = 1.00;
-        }
-        if pdr < 0.00 {
-            pdr = 0.00;
-        }
+        let pdr = pdr.clamp(0.0, 1.0);
+        let neighbours = neighbour_list(&packet_send);
         SkyLinkDrone {
             id,
             controller_send,
             controller_recv,
             packet_recv,
             packet_send,
-            pdr: (pdr*100.0) as u32,
-            flood_ids: HashSet::new(),
+            neighbours,
+            pdr,
+            link_pdrs: HashMap::new(),
+            flood_ids: FloodCache::default(),
             crashing: false,
+            send_timeout: Duration::from_millis(50),
+            event_batcher: None,
+            event_limit: None,
+            event_mute: EventMute::default(),
+            counters: Arc::new(DroneCounters::default()),
+            skylink_recv: never(),
+            link_impairments: HashMap::new(),
+            filters: Vec::new(),
+            link_buckets: RefCell::new(HashMap::new()),
+            link_rate: None,
+            link_rates: HashMap::new(),
+            rate_buckets: RefCell::new(HashMap::new()),
+            gossip: None,
+            gossip_recv: never(),
+            hooks: Vec::new(),
+            handshake: None,
+            heartbeat: None,
+            tap: None,
+            rng: RefCell::new(fastrand::Rng::new()),
+            processing_delay: ProcessingDelay::default(),
+            link_delays: HashMap::new(),
+            packet_faults: PacketFaults::default(),
+            burst_loss: None,
+            burst_bad: Cell::new(false),
+            max_hops: None,
+            dedup: None,
+            eviction: None,
+            reorder: None,
+            energy: None,
+            energy_left: Cell::new(0),
+            exited: false,
+            controller_closed: false,
+            packets_closed: false,
+            queued: PriorityQueues::default(),
+            delayed: DelayQueue::default(),
+            busy_until: Cell::new(Instant::now()),
+            tick: never(),
         }
     }
 
     fn run(&mut self) {
-        loop {
+        while !self.exited {
+            let mut wake_up = self.on_tick();
+            //With packets already read I only look for what's more urgent, I don't wait.
+            if !self.queued.is_empty() {
+                wake_up = Duration::ZERO;
+            }
             if !self.crashing {
                 select_biased! {
                     recv(self.controller_recv) -> cmd => {
-                        if let Ok(command) = cmd {
-                            self.handle_command(command);
+                        match cmd {
+                            Ok(command) => self.handle_command(command),
+                            Err(_) => self.close_controller(),
+                        }
+                    }
+                    recv(self.skylink_recv) -> cmd => {
+                        match cmd {
+                            Ok(command) => self.handle_skylink_command(command),
+                            Err(_) => self.skylink_recv = never(),
                         }
                     }
                     recv(self.packet_recv) -> pkt => {
-                        if let Ok(packet) = pkt {
-                            self.handle_packet(packet);
+                        match pkt {
+                            Ok(packet) => self.queue_packet(packet),
+                            Err(_) => self.close_packets(),
                         }
                     }
+                    recv(self.gossip_recv) -> msg => {
+                        match (msg, self.gossip.as_mut()) {
+                            (Ok(message), Some(gossip)) => gossip.receive(&message),
+                            (Ok(_), None) => {}
+                            (Err(_), _) => self.gossip_recv = never(),
+                        }
+                    }
+                    recv(self.tick) -> _ => {
+                        self.run_tick_hooks();
+                    }
+                    default(wake_up) => {}
                 }
             } else {
                 select_biased! {
                     recv(self.controller_recv) -> cmd => {
-                        // If I'm in crushing behavior, I still listen for RemoveSender command,
-                        // to avoid neighbour drones not crushing because of each other existence.
-                        if let Ok(command) = cmd {
-                            if let DroneCommand::RemoveSender(node_id) = command {
-                                if self.packet_send.contains_key(&node_id) {
-                                    if let Some(to_be_dropped) = self.packet_send.remove(&node_id) {
-                                        drop(to_be_dropped);
-                                    }
-                                }
-                            }
+                        match cmd {
+                            Ok(command) => self.crashing_handle_command(command),
+                            Err(_) => self.close_controller(),
+                        }
+                    }
+                    recv(self.skylink_recv) -> cmd => {
+                        match cmd {
+                            Ok(command) => self.crashing_handle_skylink_command(command),
+                            Err(_) => self.skylink_recv = never(),
                         }
                     }
                     recv(self.packet_recv) -> pkt => {
                         match pkt {
                             Ok(packet) => {
-                                self.crashing_handle_packet(packet);
+                                self.queue_packet(packet);
                             },
                             Err(_error) => {
+                                //What I had already read still gets its nacks.
+                                while let Some(packet) = self.queued.pop() {
+                                    self.receive_packet(packet);
+                                }
                                 break;
                             }
                         }
                     }
+                    recv(self.tick) -> _ => {
+                        self.run_tick_hooks();
+                    }
+                    default(wake_up) => {}
                 }
             }
+            if let Some(packet) = self.queued.pop() {
+                self.receive_packet(packet);
+            }
+            //Nobody can reach me anymore, with commands or packets: nothing left to wait for.
+            if self.controller_closed && self.packets_closed && self.queued.is_empty() {
+                break;
+            }
+        }
+        if let Some(batcher) = &self.event_batcher {
+            batcher.flush();
+        }
+        if !self.exited {
+            self.terminated();
         }
     }
 }
 
+//What a single non blocking step of the drone did, see SkyLinkDrone::step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DroneStep {
+    Worked,
+    Idle,
+    Finished, //The drone crashed and nobody can send it packets anymore, all its channels closed, or it was shut down, like when run() ends.
+}
+
 impl SkyLinkDrone {
+    //Same behaviour as an iteration of run(), but it never blocks: it's meant for executors
+    //that move many drones with a few threads, instead of a thread for every drone.
+    pub fn step(&mut self) -> DroneStep {
+        if self.exited {
+            return DroneStep::Finished;
+        }
+        //Like run() does before every wait: whatever is due goes first, busy or not, or a drone
+        //that always has packets would never flush its events or let its delayed packets out.
+        self.on_tick();
+        match self.controller_recv.try_recv() {
+            Ok(command) => {
+                if !self.crashing {
+                    self.handle_command(command);
+                } else {
+                    self.crashing_handle_command(command);
+                }
+                return DroneStep::Worked;
+            }
+            Err(TryRecvError::Disconnected) => self.close_controller(),
+            Err(TryRecvError::Empty) => {}
+        }
+        if self.controller_closed && self.packets_closed && self.queued.is_empty() {
+            if let Some(batcher) = &self.event_batcher {
+                batcher.flush();
+            }
+            self.terminated();
+            return DroneStep::Finished;
+        }
+        if let Ok(command) = self.skylink_recv.try_recv() {
+            match self.crashing {
+                false => self.handle_skylink_command(command),
+                true => self.crashing_handle_skylink_command(command),
+            }
+            return DroneStep::Worked;
+        }
+        if !self.crashing {
+            if let Ok(message) = self.gossip_recv.try_recv() {
+                if let Some(gossip) = self.gossip.as_mut() {
+                    gossip.receive(&message);
+                }
+                return DroneStep::Worked;
+            }
+        }
+        self.queued.fill(&self.packet_recv);
+        if let Some(packet) = self.queued.pop() {
+            self.receive_packet(packet);
+            return DroneStep::Worked;
+        }
+        match self.packet_recv.try_recv() {
+            Ok(packet) => {
+                self.receive_packet(packet);
+                DroneStep::Worked
+            },
+            Err(TryRecvError::Disconnected) if self.crashing || self.controller_closed => {
+                if let Some(batcher) = &self.event_batcher {
+                    batcher.flush();
+                }
+                self.terminated();
+                DroneStep::Finished
+            },
+            Err(TryRecvError::Disconnected) => {
+                self.close_packets();
+                DroneStep::Idle
+            },
+            Err(_) => DroneStep::Idle,
+        }
+    }
+
+    //A packet just read, with the ones behind it in the channel: they're sorted before handling
+    //any of them, see priority.rs.
+    fn queue_packet(&mut self, packet: Packet) {
+        self.queued.push(packet);
+        self.queued.fill(&self.packet_recv);
+    }
+
+    //Every packet read from my channel starts here, so the hooks see it before anything else.
+    fn receive_packet(&mut self, mut packet: Packet) {
+        if let Some(heartbeat) = self.heartbeat.as_mut() {
+            heartbeat.count_packet();
+        }
+        if let Some(tap) = &self.tap {
+            let _ = tap.send(TapRecord::Packet(packet.clone()));
+        }
+        for hooks in self.hooks.iter_mut() {
+            if !hooks.on_packet_received(self.id, &mut packet) {
+                return;
+            }
+        }
+        if !self.crashing {
+            self.handle_packet(packet);
+            self.evict_failed_neighbours();
+            self.crash_if_depleted();
+        } else {
+            self.crashing_handle_packet(packet);
+        }
+    }
+
     fn handle_command(&mut self, command: DroneCommand) {
+        self.tap_command(&command);
+        for hooks in self.hooks.iter_mut() {
+            hooks.on_command(self.id, &command);
+        }
         match command {
             DroneCommand::AddSender(node_id, sender) => {
                 self.packet_send.insert(node_id, sender);
+                self.neighbours = neighbour_list(&self.packet_send);
+                if let Some(handshake) = self.handshake.as_mut() {
+                    handshake.start(node_id);
+                }
+                if let Some(eviction) = &self.eviction {
+                    eviction.forget(node_id);
+                }
                 //println!("Drone {} added a channel to {}!", self.id, node_id);
             },
             DroneCommand::SetPacketDropRate(pdr) => {
-                let mut pdr = pdr;
-                if pdr > 1.00 {
-                    pdr = 1.00;
-                }
-                if pdr < 0.00 {
-                    pdr = 0.00;
-                }
-                self.pdr = (pdr * 100.0) as u32;
-                //println!("Drone {} new pdr: {}%!", self.id, self.pdr);
+                self.pdr = pdr.clamp(0.0, 1.0);
+                //println!("Drone {} new pdr: {}!", self.id, self.pdr);
             },
             DroneCommand::Crash => {
                 self.crashing = true;
@@ -118,6 +369,13 @@ impl SkyLinkDrone {
                 if self.packet_send.contains_key(&node_id) {
                     if let Some(to_be_dropped) = self.packet_send.remove(&node_id) {
                         drop(to_be_dropped);
+                        self.neighbours = neighbour_list(&self.packet_send);
+                        if let Some(handshake) = self.handshake.as_mut() {
+                            handshake.cancel(node_id);
+                        }
+                        if let Some(eviction) = &self.eviction {
+                            eviction.forget(node_id);
+                        }
                         //println!("Drone {} no more has a connection to {}!", self.id, node_id);
                     }
                 }
@@ -126,88 +384,563 @@ impl SkyLinkDrone {
     }
 
     fn handle_packet(&mut self, mut packet: Packet) {
-        if let PacketType::FloodRequest(mut flood_request) = packet.pack_type.clone() {
+        if let PacketType::FloodRequest(flood_request) = &mut packet.pack_type {
             //First check if we're dealing with a flood request, since we ignore its SRH.
             flood_request.path_trace.push((self.id, NodeType::Drone));
-            //I add myself to the path trace.
+            self.counters.floods_handled.fetch_add(1, Ordering::Relaxed);
+            //I add myself to the path trace (directly inside the packet, so it doesn't need to be rebuilt).
 
-            //If I can insert the flooding inside the HashSet, then I never met this flooding.
-            if self.flood_ids.insert((flood_request.flood_id.clone(), flood_request.initiator_id.clone())) {
+            //If I can insert the flooding inside the cache, then I never met this flooding (or I forgot it).
+            if self.flood_ids.insert((flood_request.flood_id, flood_request.initiator_id), Instant::now()) {
+                self.counters.flood_cache.store(self.flood_ids.len() as u64, Ordering::Relaxed);
                 if self.packet_send.len() == 1 {
+                    let flood_request = flood_request.clone();
                     self.send_flood_response(flood_request);
                 } else {
-                    let mut prev = flood_request.initiator_id.clone();
-                    if flood_request.path_trace.clone().len() > 1 {
-                        prev = flood_request.path_trace.get(flood_request.path_trace.len() - 2).unwrap().0;
-                    }
-                    //I update the path_trace in the packet.
-                    packet.pack_type = PacketType::FloodRequest(flood_request);
-                    for (key, _) in self.packet_send.iter() {
-                        //println!("Previous: {}", prev);
-                        //println!("Key: {}", key);
-                        if *key != prev {
-                            //I send the flooding to everyone except the node I received it from.
-                            if let Ok(_) = self.packet_send.get(key).unwrap().send(packet.clone()) {
-                                self.controller_send.send(DroneEvent::PacketSent(packet.clone())).unwrap();
-                                //If the message was sent, I also notify the sim controller.
-                            } //There's no else, since I don't care of nodes which can't be reached.
-                        }
+                    //A client may leave the path_trace empty and only set initiator_id, then I'm the
+                    //only one in it and the flood came from the initiator.
+                    let mut prev = flood_request.initiator_id;
+                    if flood_request.path_trace.len() > 1 {
+                        prev = flood_request.path_trace[flood_request.path_trace.len() - 2].0;
                     }
+                    self.forward_flood(packet, prev);
                 }
             } else {
+                let flood_request = flood_request.clone();
                 self.send_flood_response(flood_request);
             }
         } else {
             //If the packet is not a flood response.
-            match self.apply_checks(packet.clone()) {
+            if let Some(handshake) = self.handshake.as_mut() {
+                if handshake.receive(self.id, &packet) {
+                    //The hello came back from the neighbour, the link works both ways.
+                    return;
+                }
+            }
+            //The checks borrow the packet, so the fragment data is never copied on the way through.
+            let is_fragment = matches!(packet.pack_type, PacketType::MsgFragment(_));
+            if self.suppress_if_duplicate(&packet) {
+                return;
+            }
+            match self.apply_checks(packet) {
                 //If every check is passed
                 Ok(packet) => {
                     let next_hop = packet.routing_header.hops[packet.routing_header.hop_index];
-                    if let Some(sender) = self.packet_send.get(&next_hop) {
-                        if let Ok(_) = sender.send(packet.clone()) {
-                            self.controller_send.send(DroneEvent::PacketSent(packet)).unwrap();
-                            //If the message was sent, I also notify the sim controller.
-                            return;
-                        }
+                    let start = self.busy_until.get().max(Instant::now());
+                    let Some(release) = self.cross_link(next_hop, &packet, start) else {
+                        //Lost because of the interference on the link, the sender is told like with the pdr.
+                        let err = create_error(self.id, &packet, NackType::Dropped);
+                        self.run_drop_hooks(&err);
+                        self.send_nack(&err.routing_header.hops[1].clone(), err);
+                        return;
+                    };
+                    if release > Instant::now() {
+                        self.delayed.push(release, next_hop, packet, DelayedKind::Forward);
+                        return;
                     }
-                    let err = create_error(self.id, packet, NackType::ErrorInRouting(next_hop));
-                    self.send_nack(&err.routing_header.hops[1].clone(), err);
-                    //If the message wasn't sent, despite all the checks, I still send an error back.
+                    self.forward_packet(next_hop, packet);
                 },
                 //Otherwise the error is already the right one to send.
                 Err(err) => {
+                    if !is_fragment {
+                        //If I had got an error from the checks of the routing of an Ack, Nack or
+                        //FloodResponse, err is the packet itself: I just forward it through the Simulation Controller.
+                        self.send_event(ControllerShortcut(err));
+                        return;
+                    }
                     if let PacketType::Nack(nack) = err.pack_type.clone() {
                         if let NackType::UnexpectedRecipient(_) = nack.nack_type {
                             //If my drone isn't the one that should have received the message, I've to
                             //route the message differently, since I'm not the first id in the routing header.
+                            self.run_drop_hooks(&err);
                             self.send_nack(&err.routing_header.hops[0].clone(), err);
                         } else {
-                            match packet.pack_type {
-                                PacketType::FloodRequest(_) => { unreachable!() },
-                                PacketType::MsgFragment(_) => {
-                                    self.handle_packet(err);
-                                },
-                                _ => {
-                                    self.controller_send.send(ControllerShortcut(err)).unwrap();
-                                    //If I had got an error from the checks of the routing of an
-                                    //Ack, Nack or FloodResponse, I just forward it through the Simulation Controller.
-                                }
-                            }
+                            self.run_drop_hooks(&err);
+                            self.handle_packet(err);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    //A packet that passed the checks and crossed the link: the hooks and the faults get it, then it
+    //goes out (or in the reorder buffer, in jitter mode).
+    fn forward_packet(&mut self, next_hop: NodeId, mut packet: Packet) {
+        for hooks in self.hooks.iter_mut() {
+            hooks.on_forward(self.id, next_hop, &mut packet);
+        }
+        self.corrupt_if_due(&mut packet);
+        if matches!(packet.pack_type, PacketType::MsgFragment(_)) {
+            if let Some(delay) = self.reorder.as_ref().map(|buffer| buffer.reorder.delay) {
+                //Jitter mode: the fragment waits its turn, a full buffer lets out the one due first.
+                let release = Instant::now() + delay.delay(self.roll());
+                if let Some(evicted) = self.reorder.as_mut().and_then(|buffer| buffer.hold(next_hop, packet, release)) {
+                    self.release_held(vec![evicted]);
+                }
+                return;
+            }
+        }
+        self.send_forward(next_hop, packet);
+    }
+
+    //Sends a packet that passed the checks to its next hop, telling the Sim Contr, or what's left
+    //to do when it can't: a nack for a fragment, the Sim Contr for the rest.
+    fn send_forward(&mut self, next_hop: NodeId, packet: Packet) {
+        let is_fragment = matches!(packet.pack_type, PacketType::MsgFragment(_));
+        if let Some(sender) = self.packet_send.get(&next_hop) {
+            let sent = sender.send_timeout(packet.clone(), self.send_timeout);
+            self.note_send(next_hop, &sent);
+            match sent {
+                Ok(_) => {
+                    if let PacketType::MsgFragment(fragment) = &packet.pack_type {
+                        self.counters.fragments_forwarded.fetch_add(1, Ordering::Relaxed);
+                        if let Some(dedup) = self.dedup.as_mut() {
+                            dedup.remember((packet.session_id, fragment.fragment_index), Instant::now());
                         }
                     }
+                    //The next hop gets it twice, if the faults say so.
+                    if is_fragment && self.fault_due(self.packet_faults.duplicate) && sender.send_timeout(packet.clone(), self.send_timeout).is_ok() {
+                        self.counters.fragments_duplicated.fetch_add(1, Ordering::Relaxed);
+                        self.send_event(DroneEvent::PacketSent(packet.clone()));
+                    }
+                    self.send_event(DroneEvent::PacketSent(packet));
+                    //If the message was sent, I also notify the sim controller.
+                    if let Some(energy) = self.energy {
+                        self.spend_energy(energy.forward_cost);
+                    }
+                    return;
+                },
+                Err(SendTimeoutError::Timeout(_)) => {
+                    //The next hop is congested: a fragment is dropped and its sender is told,
+                    //while Acks, Nacks and FloodResponses go through the Sim Contr.
+                    if let PacketType::MsgFragment(_) = packet.pack_type {
+                        //The Sim Contr is told too, it's the only drop it hears about from me.
+                        self.counters.congestion_drops.fetch_add(1, Ordering::Relaxed);
+                        self.send_event(DroneEvent::PacketDropped(packet.clone()));
+                        let err = create_error(self.id, &packet, NackType::Dropped);
+                        self.run_drop_hooks(&err);
+                        self.send_nack(&err.routing_header.hops[1].clone(), err);
+                    } else {
+                        self.send_event(ControllerShortcut(packet));
+                    }
+                    return;
+                },
+                Err(SendTimeoutError::Disconnected(_)) => {},
+            }
+        }
+        if !is_fragment {
+            //Only a fragment gets a nack, the rest can't be lost: the Sim Contr delivers it.
+            self.send_event(ControllerShortcut(packet));
+            return;
+        }
+        let err = create_error(self.id, &packet, NackType::ErrorInRouting(next_hop));
+        self.run_drop_hooks(&err);
+        self.send_nack(&err.routing_header.hops[1].clone(), err);
+        //If the message wasn't sent, despite all the checks, I still send an error back.
+    }
+
+    //The fragments out of the reorder buffer go on as if they had never stopped.
+    fn release_held(&mut self, released: Vec<(NodeId, Packet, bool)>) {
+        for (next_hop, packet, overtook) in released {
+            if overtook {
+                self.counters.fragments_reordered.fetch_add(1, Ordering::Relaxed);
+            }
+            self.send_forward(next_hop, packet);
+        }
+        self.evict_failed_neighbours();
+        //Sent from on_tick, maybe with no packet coming after them to notice the empty battery.
+        self.crash_if_depleted();
+    }
+
+    //Sends the held fragments that are due, returns how long until the next one.
+    fn release_held_if_due(&mut self) -> Duration {
+        let Some(buffer) = self.reorder.as_mut() else {
+            return EVENT_WAKE_UP;
+        };
+        let now = Instant::now();
+        let due = buffer.due(now);
+        let wake_up = buffer.until_next(now).unwrap_or(EVENT_WAKE_UP);
+        self.release_held(due);
+        wake_up
+    }
+
+    //The packets that waited enough on their link go on, returns how long until the next one.
+    fn release_delayed_if_due(&mut self) -> Duration {
+        let now = Instant::now();
+        let due = self.delayed.due(now);
+        let wake_up = self.delayed.until_next(now).unwrap_or(EVENT_WAKE_UP);
+        self.release_delayed(due);
+        wake_up
+    }
+
+    fn release_delayed(&mut self, released: Vec<DelayedPacket>) {
+        if released.is_empty() {
+            return;
+        }
+        for delayed in released {
+            match delayed.kind {
+                DelayedKind::Forward => self.forward_packet(delayed.next_hop, delayed.packet),
+                DelayedKind::FloodCopy => self.send_flood_copy(delayed.next_hop, delayed.packet),
+            }
+        }
+        self.evict_failed_neighbours();
+        self.crash_if_depleted();
+    }
+
+    //A copy of a flood that had to wait for its link.
+    fn send_flood_copy(&mut self, neighbour: NodeId, mut packet: Packet) {
+        let Some(sender) = self.packet_send.get(&neighbour) else {
+            return;
+        };
+        for hooks in self.hooks.iter_mut() {
+            hooks.on_forward(self.id, neighbour, &mut packet);
+        }
+        let sent = sender.send_timeout(packet.clone(), self.send_timeout);
+        self.note_send(neighbour, &sent);
+        if sent.is_ok() {
+            self.send_event(DroneEvent::PacketSent(packet));
+            if let Some(energy) = self.energy {
+                self.spend_energy(energy.flood_cost);
+            }
+        }
+    }
+
+    //I send the flooding to everyone except the node I received it from. The packet is the same for
+    //every neighbour, so is its event: it's made once and shared, and every neighbour only costs the
+    //copy that goes in its channel. The hooks can change the packet of a neighbour, then it gets its own.
+    fn forward_flood(&mut self, packet: Packet, prev: NodeId) {
+        let event = Arc::new(DroneEvent::PacketSent(packet));
+        let DroneEvent::PacketSent(packet) = event.as_ref() else {
+            return;
+        };
+        let mut copies = 0;
+        //The flood is processed once, then every copy goes on its own link.
+        let start = self.busy_until.get().max(Instant::now());
+        for (key, sender) in self.neighbours.iter() {
+            if *key == prev {
+                continue;
+            }
+            let Some(release) = self.cross_link(*key, packet, start) else {
+                continue;
+            };
+            if release > Instant::now() {
+                self.delayed.push(release, *key, packet.clone(), DelayedKind::FloodCopy);
+                continue;
+            }
+            if self.hooks.is_empty() {
+                let sent = sender.send_timeout(packet.clone(), self.send_timeout);
+                self.note_send(*key, &sent);
+                if sent.is_ok() {
+                    self.send_shared_event(event.clone());
+                    copies += 1;
+                }
+                continue;
+            }
+            let mut packet = packet.clone();
+            for hooks in self.hooks.iter_mut() {
+                hooks.on_forward(self.id, *key, &mut packet);
+            }
+            let sent = sender.send_timeout(packet.clone(), self.send_timeout);
+            self.note_send(*key, &sent);
+            if let Ok(_) = sent {
+                self.send_event(DroneEvent::PacketSent(packet));
+                copies += 1;
+                //If the message was sent, I also notify the sim controller.
+            } //There's no else, since I don't care of nodes which can't be reached.
+        }
+        if let Some(energy) = self.energy {
+            self.spend_energy(copies * energy.flood_cost);
+        }
+    }
+
+    fn handle_skylink_command(&mut self, command: SkyLinkCommand) {
+        match command {
+            SkyLinkCommand::SetLinkImpairment(node_id, impairment) => {
+                if impairment == LinkImpairment::default() {
+                    self.link_impairments.remove(&node_id);
+                } else {
+                    self.link_impairments.insert(node_id, impairment);
+                }
+            }
+            SkyLinkCommand::SetLinkCapacity(node_id, capacity) => {
+                match capacity {
+                    Some(capacity) => self.link_buckets.get_mut().insert(node_id, TokenBucket::new(capacity)),
+                    None => self.link_buckets.get_mut().remove(&node_id),
+                };
+            }
+            SkyLinkCommand::SetLinkRate(to, rate) => {
+                self.set_link_rate(to, rate);
+            }
+            SkyLinkCommand::SetLinkPdr(node_id, pdr) => {
+                match pdr {
+                    Some(pdr) => self.link_pdrs.insert(node_id, pdr.clamp(0.0, 1.0)),
+                    None => self.link_pdrs.remove(&node_id),
+                };
+            }
+            SkyLinkCommand::SetFilters(filters) => {
+                self.filters = filters;
+            }
+            SkyLinkCommand::Ping(reply) => {
+                let _ = reply.send(());
+            }
+            SkyLinkCommand::Snapshot(reply) => {
+                let _ = reply.send(self.snapshot());
+            }
+            SkyLinkCommand::SetBurstLoss(burst_loss) => {
+                self.burst_loss = burst_loss;
+                self.burst_bad.set(false);
+            }
+            SkyLinkCommand::SetTap(tap) => {
+                self.tap = tap;
+            }
+            SkyLinkCommand::SetProcessingDelay(to, delay) => {
+                self.set_processing_delay(to, delay);
+            }
+            SkyLinkCommand::SetPacketFaults(faults) => {
+                self.packet_faults = faults;
+            }
+            SkyLinkCommand::SetReorder(reorder) => self.set_reorder(reorder),
+            SkyLinkCommand::SetTelemetry(telemetry) => {
+                self.set_telemetry(telemetry);
+            }
+            SkyLinkCommand::Shutdown(reply) => {
+                self.shut_down(reply);
+            }
+        }
+    }
+
+    fn set_telemetry(&mut self, telemetry: Telemetry) {
+        self.event_mute = telemetry.mute;
+        if let (Some(interval), Some(batcher)) = (telemetry.interval, self.event_batcher.as_mut()) {
+            batcher.set_max_delay(interval);
+        }
+    }
+
+    //Unlike a crash, the packets already in my queue are handled as usual (the ones arriving
+    //later aren't, or I'd never stop under traffic), then my events are sent and I say I'm done.
+    fn shut_down(&mut self, reply: Sender<DroneExit>) {
+        let mut drained = 0;
+        while let Some(packet) = self.queued.pop() {
+            self.receive_packet(packet);
+            drained += 1;
+        }
+        let queued = drained + self.packet_recv.len();
+        while drained < queued {
+            let Ok(packet) = self.packet_recv.try_recv() else {
+                break;
+            };
+            self.receive_packet(packet);
+            drained += 1;
+        }
+        //The delayed packets and the held fragments don't wait for their delay anymore.
+        let delayed = self.delayed.drain();
+        self.release_delayed(delayed);
+        if let Some(held) = self.reorder.as_mut().map(|buffer| buffer.drain()) {
+            self.release_held(held);
+        }
+        if let Some(batcher) = &self.event_batcher {
+            batcher.flush();
+        }
+        self.exited = true;
+        let _ = reply.send(DroneExit { id: self.id, drained });
+    }
+
+    fn spend_energy(&self, units: u64) {
+        let left = self.energy_left.get().saturating_sub(units);
+        self.energy_left.set(left);
+        self.counters.energy_left.store(left, Ordering::Relaxed);
+    }
+
+    //An empty battery is a crash like the one of the Sim Contr, except that I'm the one who knows:
+    //the flag in the counters tells it, so it can remove me from my neighbours.
+    fn crash_if_depleted(&mut self) {
+        if self.energy.is_some() && self.energy_left.get() == 0 && !self.crashing {
+            self.crashing = true;
+            self.counters.energy_depleted.store(true, Ordering::Relaxed);
+        }
+    }
+
+    //Without the Sim Contr the simulation is over. I let my neighbours go too: in a ring we'd all
+    //hold each other's channel open and nobody would ever see its own close.
+    fn close_controller(&mut self) {
+        self.controller_recv = never();
+        self.controller_closed = true;
+        self.packet_send.clear();
+        self.neighbours.clear();
+    }
+
+    fn close_packets(&mut self) {
+        self.packet_recv = never();
+        self.packets_closed = true;
+    }
+
+    //My channels are all closed (or I crashed and the packets are over): the last word goes to the
+    //hooks, and to the counters in case the Sim Contr is still reading them.
+    fn terminated(&mut self) {
+        //The delayed packets were already on their way when I was told to stop, so they still go out.
+        let delayed = self.delayed.drain();
+        self.release_delayed(delayed);
+        self.exited = true;
+        for hooks in self.hooks.iter_mut() {
+            hooks.on_terminated(self.id);
+        }
+        self.counters.terminated.store(true, Ordering::Relaxed);
+    }
+
+    //Turning the jitter mode off (or changing it) lets out what was held, in release order.
+    fn set_reorder(&mut self, reorder: Option<Reorder>) {
+        if let Some(mut buffer) = self.reorder.take() {
+            let held = buffer.drain();
+            self.release_held(held);
+        }
+        self.reorder = reorder.map(ReorderBuffer::new);
+    }
+
+    //Only rolled when the fault is on, so the drops of a seeded run don't change without faults.
+    fn fault_due(&self, probability: f32) -> bool {
+        probability > 0.0 && self.roll() < probability
+    }
+
+    //A byte of the payload of a fragment flipped, as if the link had damaged it.
+    fn corrupt_if_due(&self, packet: &mut Packet) {
+        let PacketType::MsgFragment(fragment) = &mut packet.pack_type else {
+            return;
+        };
+        if !self.fault_due(self.packet_faults.corrupt) {
+            return;
+        }
+        let length = (fragment.length as usize).clamp(1, fragment.data.len());
+        let index = self.rng.borrow_mut().usize(..length);
+        fragment.data[index] ^= 0xFF;
+        self.counters.fragments_corrupted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn set_processing_delay(&mut self, to: Option<NodeId>, delay: ProcessingDelay) {
+        match to {
+            None => self.processing_delay = delay,
+            Some(node_id) if delay == ProcessingDelay::default() => {
+                self.link_delays.remove(&node_id);
+            }
+            Some(node_id) => {
+                self.link_delays.insert(node_id, delay);
+            }
+        }
+    }
+
+    //A new rate starts with a full bucket, what was queued under the old one is forgotten.
+    fn set_link_rate(&mut self, to: Option<NodeId>, rate: Option<PacketRate>) {
+        match (to, rate) {
+            (None, rate) => {
+                self.link_rate = rate;
+                self.rate_buckets.get_mut().clear();
+            }
+            (Some(node_id), Some(rate)) => {
+                self.link_rates.insert(node_id, rate);
+                self.rate_buckets.get_mut().remove(&node_id);
+            }
+            (Some(node_id), None) => {
+                self.link_rates.remove(&node_id);
+                self.rate_buckets.get_mut().remove(&node_id);
+            }
+        }
+    }
+
+    //The delay for a packet to the next hop, with its jitter rolled.
+    fn processing_wait(&self, next_hop: NodeId) -> Duration {
+        let delay = self.link_delays.get(&next_hop).unwrap_or(&self.processing_delay);
+        if delay.jitter.is_zero() {
+            delay.delay
+        } else {
+            delay.delay + delay.jitter.mul_f32(self.roll())
+        }
+    }
+
+    fn tap_command(&self, command: &DroneCommand) {
+        if let Some(tap) = &self.tap {
+            let _ = tap.send(TapRecord::Command(TappedCommand::from_command(command)));
+        }
+    }
+
+    //Applies the processing delay (starting from start) and the impairment, the capacity and the packet
+    //rate of the link to next_hop: returns when the packet can go out, or None if it's lost on the way
+    //(only fragments can be lost, like with the pdr). The wait is up to the caller, see DelayQueue.
+    fn cross_link(&self, next_hop: NodeId, packet: &Packet, start: Instant) -> Option<Instant> {
+        let is_fragment = matches!(packet.pack_type, PacketType::MsgFragment(_));
+        let processed = start + self.processing_wait(next_hop);
+        if processed > self.busy_until.get() {
+            self.busy_until.set(processed);
+        }
+        let mut wait = Duration::ZERO;
+        if let Some(impairment) = self.link_impairments.get(&next_hop) {
+            if is_fragment && self.roll() < impairment.extra_drop {
+                return None;
+            }
+            wait += impairment.latency;
+        }
+        //A full queue drops the fragments, the other packets can't be lost so they go out after the whole queue.
+        if let Some(bucket) = self.link_buckets.borrow_mut().get_mut(&next_hop) {
+            match bucket.admit(packet_size(packet), Instant::now()) {
+                Some(queued) => wait += queued,
+                None if is_fragment => return None,
+                None => wait += bucket.drain_time(),
+            }
+        }
+        //The packet rate comes on top of the capacity: a link can be limited in bytes, in packets or both.
+        if let Some(rate) = self.link_rates.get(&next_hop).or(self.link_rate.as_ref()) {
+            let mut rate_buckets = self.rate_buckets.borrow_mut();
+            let bucket = rate_buckets.entry(next_hop).or_insert_with(|| rate.bucket());
+            match bucket.admit(1, Instant::now()) {
+                Some(queued) => wait += queued,
+                None if is_fragment => {
+                    self.counters.rate_limited.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                None => wait += bucket.drain_time(),
+            }
+        }
+        Some(processed + wait)
+    }
+
+    fn crashing_handle_command(&mut self, command: DroneCommand) {
+        self.tap_command(&command);
+        for hooks in self.hooks.iter_mut() {
+            hooks.on_command(self.id, &command);
+        }
+        // If I'm in crushing behavior, I still listen for RemoveSender command,
+        // to avoid neighbour drones not crushing because of each other existence.
+        if let DroneCommand::RemoveSender(node_id) = command {
+            if self.packet_send.contains_key(&node_id) {
+                if let Some(to_be_dropped) = self.packet_send.remove(&node_id) {
+                    drop(to_be_dropped);
+                    //The cached list holds a copy of the sender too, it has to go as well.
+                    self.neighbours = neighbour_list(&self.packet_send);
                 }
             }
         }
     }
 
+    //While crashing I only tell my state, the rest is about a drone that is going away.
+    fn crashing_handle_skylink_command(&mut self, command: SkyLinkCommand) {
+        if let SkyLinkCommand::Snapshot(reply) = command {
+            let _ = reply.send(self.snapshot());
+        }
+    }
+
     fn crashing_handle_packet(&mut self, packet: Packet) {
-        match packet.clone().pack_type {
-            PacketType::MsgFragment(_fragment) => {
-                //If the message is a fragment, I send back a Nack
-                let err = create_error(self.id, packet, NackType::ErrorInRouting(self.id));
+        match packet.pack_type {
+            PacketType::MsgFragment(_) => {
+                //If the message is a fragment, I send back a Nack, as long as there's someone
+                //before me on its route to send it to (a crafted packet may not even have me in it).
+                let behind_me = packet.routing_header.hops.iter().position(|id| *id == self.id).map_or(false, |position| position > 0);
+                if !behind_me {
+                    return;
+                }
+                let err = create_error(self.id, &packet, NackType::ErrorInRouting(self.id));
+                self.run_drop_hooks(&err);
                 self.send_nack(&err.routing_header.hops[1].clone(), err);
             }
-            PacketType::FloodRequest(_flood_request) => {}, //I discard them.
+            PacketType::FloodRequest(_) => {}, //I discard them.
             _ => {
                 self.handle_packet(packet);
                 //If the message is an Ack, Nack or FloodResponse, I route it normally.
@@ -215,27 +948,93 @@ impl SkyLinkDrone {
         }
     }
 
+    //A fragment I forwarded not long ago, sent to me again: it's dropped without a nack (the
+    //sender already has the first copy on its way) and the Sim Contr sees it in the counters.
+    fn suppress_if_duplicate(&mut self, packet: &Packet) -> bool {
+        let (Some(dedup), PacketType::MsgFragment(fragment)) = (self.dedup.as_mut(), &packet.pack_type) else {
+            return false;
+        };
+        if !dedup.is_duplicate((packet.session_id, fragment.fragment_index), Instant::now()) {
+            return false;
+        }
+        self.counters.duplicates_suppressed.fetch_add(1, Ordering::Relaxed);
+        for hooks in self.hooks.iter_mut() {
+            hooks.on_duplicate_suppressed(self.id, packet);
+        }
+        true
+    }
+
+    //A send that found the channel of the neighbour closed counts towards removing it, one that went
+    //through starts the count over. A full channel is only congestion, it counts for nothing.
+    fn note_send<T>(&self, neighbour: NodeId, sent: &Result<(), SendTimeoutError<T>>) {
+        let Some(eviction) = &self.eviction else {
+            return;
+        };
+        match sent {
+            Ok(_) => eviction.succeeded(neighbour),
+            Err(SendTimeoutError::Disconnected(_)) => eviction.failed(neighbour),
+            Err(SendTimeoutError::Timeout(_)) => {}
+        }
+    }
+
+    //Removes the neighbours that failed too many sends in a row, as a RemoveSender would: the
+    //fragments routed through them are nacked with ErrorInRouting from now on, by is_next_hop_check.
+    fn evict_failed_neighbours(&mut self) {
+        let Some(eviction) = &self.eviction else {
+            return;
+        };
+        let evicted = eviction.evict(self.id);
+        if evicted.is_empty() {
+            return;
+        }
+        for neighbour in evicted {
+            self.packet_send.remove(&neighbour);
+            if let Some(handshake) = self.handshake.as_mut() {
+                handshake.cancel(neighbour);
+            }
+        }
+        self.neighbours = neighbour_list(&self.packet_send);
+    }
+
+    //Every nack I make goes through here before it's sent, so it's counted here too.
+    fn run_drop_hooks(&mut self, nack: &Packet) {
+        self.counters.nacks_generated.fetch_add(1, Ordering::Relaxed);
+        if let PacketType::Nack(Nack { nack_type: NackType::Dropped, .. }) = nack.pack_type {
+            self.counters.fragments_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        for hooks in self.hooks.iter_mut() {
+            hooks.on_drop(self.id, nack);
+        }
+    }
+
     fn send_nack(&self, index: &NodeId, err: Packet) {
         if let Some(sender) = self.packet_send.get(index) {
-            sender.send(err.clone()).unwrap();
-            self.controller_send.send(DroneEvent::PacketSent(err)).unwrap();
-        } else {
-            self.controller_send.send(ControllerShortcut(err)).unwrap();
-            //If the routing of the nack gives an error, I pass through the Sim Contr.
+            let sent = sender.send_timeout(err.clone(), self.send_timeout);
+            self.note_send(*index, &sent);
+            if let Ok(_) = sent {
+                self.send_event(DroneEvent::PacketSent(err));
+                return;
+            }
         }
+        self.send_event(ControllerShortcut(err));
+        //If the routing of the nack gives an error (or the neighbour is congested), I pass through the Sim Contr.
     }
 
     fn apply_checks(&self, mut packet: Packet) -> Result<Packet, Packet> {
         //Check if we're on the right hop.
-        id_hop_match_check(&self, packet.clone())?;
+        id_hop_match_check(self, &packet)?;
+        //Check if the route is too long or goes around in a loop (only with a max_hops).
+        ttl_check(self, &packet)?;
         //Increase the index.
         packet.routing_header.hop_index += 1;
         //Check if we're a final destination.
-        final_destination_check(&self, packet.clone())?;
+        final_destination_check(self, &packet)?;
+        //Check if the rules of the drone let the packet through.
+        filter_check(self, &packet)?;
         //Check if the packet is dropped (only when msg_fragment).
-        pdr_check(&self, packet.clone())?;
+        pdr_check(self, &packet)?;
         //Check if the next_hop exists.
-        is_next_hop_check(&self, packet.clone())?;
+        is_next_hop_check(self, &packet)?;
 
         //If no check gave an error, we return the starting packet.
         Ok(packet)
@@ -270,13 +1069,320 @@ impl SkyLinkDrone {
         //self.controller_send.send(DroneEvent::PacketSent(resp)).unwrap(); //Should be set by handle_packet.
     }
 
+    fn send_event(&self, event: DroneEvent) {
+        self.send_shared_event(Arc::new(event));
+    }
+
+    //The packet is only copied if the event goes out one by one while someone else still holds
+    //it (a flood), in a batch it stays shared until the Sim Contr reads it.
+    fn send_shared_event(&self, event: Arc<DroneEvent>) {
+        self.counters.count(&event);
+        if let Some(tap) = &self.tap {
+            let _ = tap.send(TapRecord::Event(event.as_ref().clone()));
+        }
+        //The counters and the tap still see everything, only the channel is spared.
+        if self.event_mute.mutes(&event) {
+            return;
+        }
+        if let Some(limit) = &self.event_limit {
+            if !limit.allow() {
+                self.counters.events_suppressed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        match &self.event_batcher {
+            Some(batcher) => batcher.push(event),
+            None => {
+                //Like a batch: the Sim Contr may have dropped the receiver before I saw my command
+                //channel close, then the event has nobody to go to and my loop finds out on its own.
+                let _ = self.controller_send.send(Arc::try_unwrap(event).unwrap_or_else(|event| event.as_ref().clone()));
+            }
+        }
+    }
+
+    //Everything I do because time passed instead of because a packet came: run before every wait of
+    //run() (the tick only makes sure the wait ends) and at every step(). Returns how long I
+    //can wait before something is due, a new timed feature only needs a line here.
+    fn on_tick(&mut self) -> Duration {
+        let now = Instant::now();
+        if self.flood_ids.expire(now) {
+            self.counters.flood_cache.store(self.flood_ids.len() as u64, Ordering::Relaxed);
+        }
+        self.flush_due_events()
+            .min(self.gossip_if_due())
+            .min(self.handshake_if_due())
+            .min(self.heartbeat_if_due())
+            .min(self.release_held_if_due())
+            .min(self.release_delayed_if_due())
+    }
+
+    //Only on the real ticks, so the hooks get a steady beat whatever the traffic.
+    fn run_tick_hooks(&mut self) {
+        for hooks in self.hooks.iter_mut() {
+            hooks.on_tick(self.id);
+        }
+    }
+
+    //Runs my gossip round when it's due, returns how long until the next one.
+    fn gossip_if_due(&mut self) -> Duration {
+        let Some(gossip) = self.gossip.as_mut().filter(|_| !self.crashing) else {
+            return EVENT_WAKE_UP;
+        };
+        let neighbours = self.neighbours.iter().map(|(node_id, _)| *node_id).collect::<Vec<NodeId>>();
+        let sent = gossip.round_if_due(self.id, &neighbours);
+        self.counters.gossip_sent.fetch_add(sent, Ordering::Relaxed);
+        gossip.until_next_round()
+    }
+
+    //Sends the hellos that are due, returns how long until the next one.
+    fn handshake_if_due(&mut self) -> Duration {
+        let Some(handshake) = self.handshake.as_mut().filter(|_| !self.crashing) else {
+            return EVENT_WAKE_UP;
+        };
+        for neighbour in handshake.due(self.id) {
+            if let Some(sender) = self.packet_send.get(&neighbour) {
+                let packet = hello(self.id, neighbour);
+                if sender.send_timeout(packet.clone(), self.send_timeout).is_ok() {
+                    self.send_event(DroneEvent::PacketSent(packet));
+                }
+            }
+        }
+        self.handshake.as_ref().and_then(|handshake| handshake.until_next()).unwrap_or(EVENT_WAKE_UP)
+    }
+
+    //Sends the heartbeat if it's due, returns how long until the next one.
+    fn heartbeat_if_due(&mut self) -> Duration {
+        let (queue_depth, pdr, crashing) = (self.packet_recv.len() + self.queued.len() + self.delayed.len(), self.pdr, self.crashing);
+        match self.heartbeat.as_mut().filter(|_| !crashing) {
+            Some(heartbeat) => heartbeat.beat_if_due(self.id, queue_depth, pdr),
+            None => EVENT_WAKE_UP,
+        }
+    }
+
+    //Returns how long the drone can wait for packets before the batch has to be flushed.
+    fn flush_due_events(&self) -> Duration {
+        match &self.event_batcher {
+            Some(batcher) => batcher.flush_if_due().unwrap_or(EVENT_WAKE_UP),
+            None => EVENT_WAKE_UP,
+        }
+    }
+
+    //The events are sent on batch_send in groups of max_len, or after max_delay, each with its own timestamp.
+    pub fn with_event_batching(mut self, batch_send: Sender<Vec<TimedEvent>>, max_len: usize, max_delay: Duration) -> Self {
+        self.event_batcher = Some(EventBatcher::new(batch_send, max_len, max_delay));
+        self
+    }
+
+    //The drops are rolled with this seed instead of a seed taken from the thread, so the run can
+    //be repeated drop by drop.
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.rng.replace(fastrand::Rng::with_seed(seed));
+        self
+    }
+
+    //A number in [0, 1) for the drops.
+    pub fn roll(&self) -> f32 {
+        self.rng.borrow_mut().f32()
+    }
+
+    //After with_event_batching, or the interval is lost.
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.set_telemetry(telemetry);
+        self
+    }
+
+    //At most max_per_s events a second go to the Sim Contr, batched or not.
+    pub fn with_event_rate_limit(mut self, max_per_s: u64) -> Self {
+        self.event_limit = Some(EventRateLimit::new(max_per_s));
+        self
+    }
+
+    //Without it the floods I've seen are remembered forever.
+    pub fn with_flood_cache(mut self, limits: FloodCacheLimits) -> Self {
+        self.flood_ids = FloodCache::new(limits);
+        self
+    }
+
+    //The counters are given from outside, so the Sim Contr can keep a copy of the Arc.
+    pub fn with_counters(mut self, counters: Arc<DroneCounters>) -> Self {
+        counters.flood_cache.store(self.flood_ids.len() as u64, Ordering::Relaxed);
+        self.counters = counters;
+        self
+    }
+
+    //The Sim Contr keeps the sending end, to reach me with the commands outside of wg_2024.
+    pub fn with_skylink_commands(mut self, skylink_recv: Receiver<SkyLinkCommand>) -> Self {
+        self.skylink_recv = skylink_recv;
+        self
+    }
+
+    pub fn with_filters(mut self, filters: Vec<FilterRule>) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    pub fn with_packet_faults(mut self, faults: PacketFaults) -> Self {
+        self.packet_faults = faults;
+        self
+    }
+
+    pub fn with_burst_loss(mut self, burst_loss: Option<BurstLoss>) -> Self {
+        self.burst_loss = burst_loss;
+        self
+    }
+
+    //Wakes me up every interval even without packets, for on_tick and DroneHooks::on_tick.
+    pub fn with_tick(mut self, interval: Option<Duration>) -> Self {
+        self.tick = match interval {
+            Some(interval) if !interval.is_zero() => tick(interval),
+            _ => never(),
+        };
+        self
+    }
+
+    pub fn with_eviction(mut self, after: u32, link_down_send: Sender<LinkDown>) -> Self {
+        self.eviction = Some(NeighbourEviction::new(after, link_down_send));
+        self
+    }
+
+    pub fn with_dedup(mut self, window: Option<DedupWindow>) -> Self {
+        self.dedup = window.map(FragmentDedup::new);
+        self
+    }
+
+    pub fn with_max_hops(mut self, max_hops: Option<usize>) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    pub fn with_reorder(mut self, reorder: Option<Reorder>) -> Self {
+        self.reorder = reorder.map(ReorderBuffer::new);
+        self
+    }
+
+    //A full battery. After with_counters, so the Sim Contr sees it from the start.
+    pub fn with_energy(mut self, energy: EnergyBudget) -> Self {
+        self.energy = Some(energy);
+        self.energy_left.set(energy.capacity);
+        self.counters.energy_left.store(energy.capacity, Ordering::Relaxed);
+        self
+    }
+
+    //The delays are for a neighbour, or for everyone with None (see SkyLinkCommand::SetProcessingDelay).
+    pub fn with_processing_delays(mut self, delays: Vec<(Option<NodeId>, ProcessingDelay)>) -> Self {
+        for (to, delay) in delays {
+            self.set_processing_delay(to, delay);
+        }
+        self
+    }
+
+    //gossip_recv is my own mailbox, the one the others find in the mailboxes of the Gossip.
+    pub fn with_gossip(mut self, gossip: Gossip, gossip_recv: Receiver<GossipMessage>) -> Self {
+        self.gossip = Some(gossip);
+        self.gossip_recv = gossip_recv;
+        self
+    }
+
+    //Every neighbour added with AddSender gets a hello, and how it went is sent on report_send:
+    //a hello not back after timeout means the neighbour has no channel to me.
+    pub fn with_handshake(mut self, timeout: Duration, report_send: Sender<HandshakeReport>) -> Self {
+        self.handshake = Some(Handshake::new(timeout, report_send));
+        self
+    }
+
+    //Every interval a Heartbeat goes on beat_send, so a drone that stops beating can be told from a quiet one.
+    pub fn with_heartbeat(mut self, interval: Duration, beat_send: Sender<Heartbeat>) -> Self {
+        self.heartbeat = Some(HeartbeatTimer::new(interval, beat_send));
+        self
+    }
+
+    //Can be called more than once, the hooks are called in the order they were added.
+    pub fn with_hooks(mut self, hooks: Box<dyn DroneHooks>) -> Self {
+        self.hooks.push(hooks);
+        self
+    }
+
+    //The links without a capacity carry everything at once, as before.
+    pub fn with_link_capacities(mut self, capacities: HashMap<NodeId, LinkCapacity>) -> Self {
+        self.link_buckets = RefCell::new(capacities
+            .into_iter()
+            .map(|(node_id, capacity)| (node_id, TokenBucket::new(capacity)))
+            .collect());
+        self
+    }
+
+    //The links without a pdr of their own keep the one of the drone.
+    pub fn with_link_pdrs(mut self, link_pdrs: HashMap<NodeId, f32>) -> Self {
+        self.link_pdrs = link_pdrs.into_iter().map(|(node_id, pdr)| (node_id, pdr.clamp(0.0, 1.0))).collect();
+        self
+    }
+
+    //The rates are for a neighbour, or for every one of them with None (see SkyLinkCommand::SetLinkRate).
+    pub fn with_link_rates(mut self, rates: Vec<(Option<NodeId>, PacketRate)>) -> Self {
+        for (to, rate) in rates {
+            self.set_link_rate(to, Some(rate));
+        }
+        self
+    }
+
+    //With bounded channels this is the time a send may block before the packet counts as lost.
+    pub fn with_send_timeout(mut self, send_timeout: Duration) -> Self {
+        self.send_timeout = send_timeout;
+        self
+    }
+
     pub fn get_id(&self) -> NodeId {
         self.id
     }
-    pub fn get_pdr(&self) -> u32 {
+    pub fn get_pdr(&self) -> f32 {
         self.pdr
     }
+    //The pdr of the link to next_hop, if it has its own.
+    pub fn get_link_pdr(&self, next_hop: NodeId) -> Option<f32> {
+        self.link_pdrs.get(&next_hop).copied()
+    }
+    pub fn snapshot(&self) -> DroneState {
+        let mut neighbours = self.packet_send.keys().copied().collect::<Vec<NodeId>>();
+        neighbours.sort();
+        DroneState {
+            id: self.id,
+            pdr: self.pdr,
+            neighbours,
+            flood_cache: self.flood_ids.len(),
+            queued: self.packet_recv.len() + self.queued.len() + self.delayed.len(),
+            crashing: self.crashing,
+            counters: self.counters.values(),
+        }
+    }
+    pub fn has_burst_loss(&self) -> bool {
+        self.burst_loss.is_some()
+    }
+    //Moves the burst loss to the state of this fragment and rolls its loss there.
+    pub fn burst_lost(&self) -> bool {
+        let Some(burst_loss) = self.burst_loss else {
+            return false;
+        };
+        let bad = burst_loss.next_state(self.burst_bad.get(), self.roll());
+        self.burst_bad.set(bad);
+        self.roll() < burst_loss.loss(bad)
+    }
+    pub fn get_max_hops(&self) -> Option<usize> {
+        self.max_hops
+    }
+    pub fn get_filters(&self) -> &[FilterRule] {
+        &self.filters
+    }
     pub fn get_packet_send(&self) -> &HashMap<NodeId, Sender<Packet>>{
         &self.packet_send
     }
 }
+
+//Sorted by id, so the flood goes out to the neighbours always in the same order.
+fn neighbour_list(packet_send: &HashMap<NodeId, Sender<Packet>>) -> Vec<(NodeId, Sender<Packet>)> {
+    let mut neighbours = packet_send
+        .iter()
+        .map(|(id, sender)| (*id, sender.clone()))
+        .collect::<Vec<(NodeId, Sender<Packet>)>>();
+    neighbours.sort_by_key(|(id, _)| *id);
+    neighbours
+}
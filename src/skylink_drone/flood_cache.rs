@@ -0,0 +1,67 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use wg_2024::network::NodeId;
+
+//How long the floods I've seen are remembered: at most capacity of them (the oldest goes first)
+//and each for at most ttl. Without either I remember them forever, as before.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FloodCacheLimits {
+    pub capacity: Option<usize>,
+    pub ttl: Option<Duration>,
+}
+
+//The floods I've seen, by (flood_id, initiator). A forgotten flood is a new one: a client that
+//floods again with an id I've seen before gets a fresh round instead of only my old answer.
+#[derive(Debug, Default)]
+pub struct FloodCache {
+    limits: FloodCacheLimits,
+    seen: HashMap<(u64, NodeId), Instant>,
+    order: VecDeque<(u64, NodeId)>, //Oldest first, only kept with some limit.
+}
+
+impl FloodCache {
+    pub fn new(limits: FloodCacheLimits) -> Self {
+        FloodCache { limits, seen: HashMap::new(), order: VecDeque::new() }
+    }
+
+    //Returns true if the flood is new (or was forgotten), and remembers it.
+    pub fn insert(&mut self, key: (u64, NodeId), now: Instant) -> bool {
+        self.expire(now);
+        if self.seen.contains_key(&key) {
+            return false;
+        }
+        if self.limits.capacity.is_some_and(|capacity| self.seen.len() >= capacity.max(1)) {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.seen.insert(key, now);
+        if self.limits != FloodCacheLimits::default() {
+            self.order.push_back(key);
+        }
+        true
+    }
+
+    //Forgets the floods older than the ttl, returns true if there were some.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let Some(ttl) = self.limits.ttl else {
+            return false;
+        };
+        let len = self.seen.len();
+        while let Some(oldest) = self.order.front() {
+            match self.seen.get(oldest) {
+                Some(seen) if now.saturating_duration_since(*seen) < ttl => break,
+                _ => {
+                    if let Some(oldest) = self.order.pop_front() {
+                        self.seen.remove(&oldest);
+                    }
+                }
+            }
+        }
+        self.seen.len() != len
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crossbeam_channel::Sender;
+use wg_2024::network::NodeId;
+
+//What a node knows of the network: the neighbours of every node it heard of, each list with
+//the version its owner gave it, so a newer list always replaces an older one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopologyView {
+    pub neighbours: HashMap<NodeId, (u64, Vec<NodeId>)>,
+}
+
+impl TopologyView {
+    //Keeps the newest list of every node, returns true if something changed.
+    pub fn merge(&mut self, other: &TopologyView) -> bool {
+        let mut changed = false;
+        for (node_id, (version, neighbours)) in other.neighbours.iter() {
+            let newer = self.neighbours.get(node_id).map_or(true, |(known, _)| version > known);
+            if newer {
+                self.neighbours.insert(*node_id, (*version, neighbours.clone()));
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    //The nodes with a list, and the ones that are only in the list of some other node.
+    pub fn known_nodes(&self) -> HashSet<NodeId> {
+        self.neighbours
+            .iter()
+            .flat_map(|(node_id, (_, neighbours))| std::iter::once(*node_id).chain(neighbours.iter().copied()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GossipMessage {
+    pub view: TopologyView,
+}
+
+//Every node taking part in the gossip (drones and clients) has a mailbox, they're all here.
+//The gossip has its own channels: its messages aren't wg_2024 packets.
+pub type GossipMailboxes = Arc<HashMap<NodeId, Sender<GossipMessage>>>;
+
+//My side of the gossip: every period I refresh my own list of neighbours, and I tell my view
+//to fanout random neighbours, or to all of them when fanout is 0. With fanout 0 I only talk
+//when my view changed since the last round, so a quiet network costs nothing.
+pub struct Gossip {
+    mailboxes: GossipMailboxes,
+    period: Duration,
+    fanout: usize,
+    next_round: Instant,
+    version: u64,
+    view: TopologyView,
+    changed: bool,
+}
+
+impl Gossip {
+    pub fn new(mailboxes: GossipMailboxes, period: Duration, fanout: usize) -> Self {
+        Gossip {
+            mailboxes,
+            period,
+            fanout,
+            //The first rounds are spread over a period, so the drones don't all talk at once.
+            next_round: Instant::now() + period.mul_f32(fastrand::f32()),
+            version: 0,
+            view: TopologyView::default(),
+            changed: false,
+        }
+    }
+
+    pub fn receive(&mut self, message: &GossipMessage) {
+        if self.view.merge(&message.view) {
+            self.changed = true;
+        }
+    }
+
+    pub fn until_next_round(&self) -> Duration {
+        self.next_round.saturating_duration_since(Instant::now())
+    }
+
+    //Runs the round if it's due, returns the messages sent.
+    pub fn round_if_due(&mut self, id: NodeId, neighbours: &[NodeId]) -> u64 {
+        if Instant::now() < self.next_round {
+            return 0;
+        }
+        self.next_round = Instant::now() + self.period;
+
+        let mut neighbours = neighbours.to_vec();
+        neighbours.sort();
+        if self.view.neighbours.get(&id).map(|(_, known)| known) != Some(&neighbours) {
+            self.version += 1;
+            self.view.neighbours.insert(id, (self.version, neighbours.clone()));
+            self.changed = true;
+        }
+
+        let mut peers = neighbours
+            .into_iter()
+            .filter(|node_id| self.mailboxes.contains_key(node_id))
+            .collect::<Vec<NodeId>>();
+        if self.fanout == 0 {
+            if !self.changed {
+                return 0;
+            }
+        } else {
+            fastrand::shuffle(&mut peers);
+            peers.truncate(self.fanout);
+        }
+        self.changed = false;
+
+        let mut sent = 0;
+        for peer in peers {
+            let message = GossipMessage { view: self.view.clone() };
+            if self.mailboxes[&peer].try_send(message).is_ok() {
+                sent += 1;
+            }
+        }
+        sent
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use crossbeam_channel::Sender;
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+use wg_2024::packet::{Ack, Packet, PacketType};
+
+//The hellos are sent again this often until the answer comes back or the handshake times out:
+//when a link is added the two AddSender can reach the two drones in any order.
+pub const HELLO_RETRY: Duration = Duration::from_millis(100);
+//A session of its own, so the hellos can't be taken for the acks of a real message.
+pub const HELLO_SESSION: u64 = u64::MAX;
+
+//How a handshake ended, sent to whoever gave the drone the channel (the Sim Contr).
+#[derive(Debug, Clone)]
+pub struct HandshakeReport {
+    pub drone: NodeId,
+    pub neighbour: NodeId,
+    pub rtt: Option<Duration>, //None if the hello never came back: the neighbour can't reach me.
+}
+
+//The hello is an Ack with the route me -> neighbour -> me: any drone forwards it back to me if
+//it has a channel to me, and drops it without a word if it hasn't. So the same packet is the
+//hello on the way there and the hello-ack on the way back, and the neighbour needs nothing new.
+pub fn hello(me: NodeId, neighbour: NodeId) -> Packet {
+    Packet {
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        routing_header: SourceRoutingHeader { hop_index: 1, hops: vec![me, neighbour, me] },
+        session_id: HELLO_SESSION,
+    }
+}
+
+pub fn is_hello(packet: &Packet) -> bool {
+    packet.session_id == HELLO_SESSION && matches!(packet.pack_type, PacketType::Ack(_)) && packet.routing_header.hops.len() == 3
+}
+
+//My side of the handshakes, one for every neighbour added since the last answer.
+pub struct Handshake {
+    timeout: Duration,
+    report_send: Sender<HandshakeReport>,
+    pending: HashMap<NodeId, (Instant, Instant)>, //(started, next hello)
+}
+
+impl Handshake {
+    pub fn new(timeout: Duration, report_send: Sender<HandshakeReport>) -> Self {
+        Handshake { timeout, report_send, pending: HashMap::new() }
+    }
+
+    //A new channel to the neighbour, the first hello goes out at the next round.
+    pub fn start(&mut self, neighbour: NodeId) {
+        let now = Instant::now();
+        self.pending.insert(neighbour, (now, now));
+    }
+
+    pub fn cancel(&mut self, neighbour: NodeId) {
+        self.pending.remove(&neighbour);
+    }
+
+    //Returns true if the packet was the answer to one of my hellos, so it goes no further.
+    pub fn receive(&mut self, me: NodeId, packet: &Packet) -> bool {
+        let hops = &packet.routing_header.hops;
+        if !is_hello(packet) || hops[0] != me || packet.routing_header.hop_index != 2 {
+            return false;
+        }
+        if let Some((started, _)) = self.pending.remove(&hops[1]) {
+            let _ = self.report_send.send(HandshakeReport { drone: me, neighbour: hops[1], rtt: Some(started.elapsed()) });
+        }
+        true
+    }
+
+    //The neighbours to send a hello to now. The ones that didn't answer in time are reported and forgotten.
+    pub fn due(&mut self, me: NodeId) -> Vec<NodeId> {
+        let now = Instant::now();
+        let expired = self.pending
+            .iter()
+            .filter(|(_, (started, _))| now.duration_since(*started) > self.timeout)
+            .map(|(neighbour, _)| *neighbour)
+            .collect::<Vec<NodeId>>();
+        for neighbour in expired {
+            self.pending.remove(&neighbour);
+            let _ = self.report_send.send(HandshakeReport { drone: me, neighbour, rtt: None });
+        }
+        let mut due = Vec::new();
+        for (neighbour, (_, next_hello)) in self.pending.iter_mut() {
+            if *next_hello <= now {
+                *next_hello = now + HELLO_RETRY;
+                due.push(*neighbour);
+            }
+        }
+        due
+    }
+
+    pub fn until_next(&self) -> Option<Duration> {
+        self.pending.values().map(|(_, next_hello)| next_hello.saturating_duration_since(Instant::now())).min()
+    }
+}
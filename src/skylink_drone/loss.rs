@@ -0,0 +1,38 @@
+//The Gilbert-Elliott loss model: the link is either good or bad, and before every fragment it
+//moves from good to bad with p_good_to_bad and back with p_bad_to_good. In each state the fragment
+//is lost with its own probability, so the drops come in bursts like on a real radio link instead
+//of one every so often like with the pdr.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BurstLoss {
+    pub p_good_to_bad: f32,
+    pub p_bad_to_good: f32,
+    pub loss_good: f32,
+    pub loss_bad: f32,
+}
+
+impl BurstLoss {
+    //Whether the next fragment finds the link bad, from the state of the last one and a roll.
+    pub fn next_state(&self, bad: bool, roll: f32) -> bool {
+        match bad {
+            false => roll < self.p_good_to_bad,
+            true => roll >= self.p_bad_to_good,
+        }
+    }
+
+    pub fn loss(&self, bad: bool) -> f32 {
+        match bad {
+            false => self.loss_good,
+            true => self.loss_bad,
+        }
+    }
+
+    //The share of fragments lost in the long run, to compare it with a pdr.
+    pub fn mean_loss(&self) -> f32 {
+        let switches = self.p_good_to_bad + self.p_bad_to_good;
+        if switches <= 0.0 {
+            return self.loss_good;
+        }
+        let bad = self.p_good_to_bad / switches;
+        (1.0 - bad) * self.loss_good + bad * self.loss_bad
+    }
+}
@@ -1,3 +1,20 @@
 pub mod drone;
+pub mod commands;
+pub mod counters;
+pub mod events;
+pub mod links;
+pub mod gossip;
+pub mod hooks;
+pub mod handshake;
+pub mod heartbeat;
+pub mod priority;
+pub mod reorder;
+pub mod flood_cache;
+pub mod state;
+pub mod loss;
+pub mod dedup;
+pub mod eviction;
+pub mod delay;
+pub mod tap;
 mod error;
 mod checks;
\ No newline at end of file
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+//How long a held fragment waits, rolled for every fragment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DelayDistribution {
+    Uniform { min: Duration, max: Duration },
+    Exponential { mean: Duration, max: Duration }, //Most wait little, a few a lot; never more than max.
+}
+
+impl DelayDistribution {
+    //From a roll in 0.0 - 1.0.
+    pub fn delay(&self, roll: f32) -> Duration {
+        match *self {
+            DelayDistribution::Uniform { min, max } => min + max.saturating_sub(min).mul_f32(roll),
+            DelayDistribution::Exponential { mean, max } => mean.mul_f64((1.0 - roll as f64).max(f64::MIN_POSITIVE).ln().abs()).min(max),
+        }
+    }
+}
+
+//The jitter mode: the fragments I forward wait in a buffer of up to buffer fragments, each for its
+//own delay, so they leave out of order (see SkyLinkCommand::SetReorder).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reorder {
+    pub buffer: usize,
+    pub delay: DelayDistribution,
+}
+
+struct HeldFragment {
+    release: Instant,
+    seq: u64, //In the order they were held, to tell the ones that overtook another.
+    next_hop: NodeId,
+    packet: Packet,
+}
+
+pub struct ReorderBuffer {
+    pub reorder: Reorder,
+    held: Vec<HeldFragment>,
+    seq: u64,
+}
+
+impl ReorderBuffer {
+    pub fn new(reorder: Reorder) -> Self {
+        ReorderBuffer { reorder, held: Vec::new(), seq: 0 }
+    }
+
+    //Holds the fragment until release. With a full buffer the one due first leaves right away to
+    //make room, and it's returned.
+    pub fn hold(&mut self, next_hop: NodeId, packet: Packet, release: Instant) -> Option<(NodeId, Packet, bool)> {
+        let evicted = match self.held.len() >= self.reorder.buffer.max(1) {
+            true => self.take_first(),
+            false => None,
+        };
+        self.held.push(HeldFragment { release, seq: self.seq, next_hop, packet });
+        self.seq += 1;
+        evicted
+    }
+
+    //The fragments whose time has come, by release time: each with whether it overtook one held before it.
+    pub fn due(&mut self, now: Instant) -> Vec<(NodeId, Packet, bool)> {
+        let mut due = Vec::new();
+        while self.held.iter().any(|held| held.release <= now) {
+            if let Some(released) = self.take_first() {
+                due.push(released);
+            }
+        }
+        due
+    }
+
+    //Everything, in release order, when the mode is turned off or I shut down.
+    pub fn drain(&mut self) -> Vec<(NodeId, Packet, bool)> {
+        let mut all = Vec::new();
+        while let Some(released) = self.take_first() {
+            all.push(released);
+        }
+        all
+    }
+
+    //How long until the next fragment is due.
+    pub fn until_next(&self, now: Instant) -> Option<Duration> {
+        self.held.iter().map(|held| held.release.saturating_duration_since(now)).min()
+    }
+
+    fn take_first(&mut self) -> Option<(NodeId, Packet, bool)> {
+        let (index, _) = self.held.iter().enumerate().min_by_key(|(_, held)| held.release)?;
+        let held = self.held.swap_remove(index);
+        let overtook = self.held.iter().any(|other| other.seq < held.seq);
+        Some((held.next_hop, held.packet, overtook))
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, SystemTime};
+use serde::Deserialize;
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+use wg_2024::packet::{Ack, Fragment, Packet, PacketType};
+use crate::traceroute::PROBE_TIMEOUT;
+
+//The SLA probes use their own session ids, between the ones of the transfers and of the traceroutes.
+pub const SLA_SESSION_BASE: u64 = 1 << 44;
+//The rtts kept for every pair, for the chart and the mean.
+const RTT_HISTORY: usize = 100;
+
+//A flow watched with a small probe every period_ms, as written in the input file:
+//    [[sla_probe]]
+//    client = 0
+//    server = 3
+//    period_ms = 1000
+//    target_rtt_ms = 50
+//The probe is a one fragment message on the route the client has cached, and the server
+//(played by the Sim Contr) acks it at once: the rtt is the time until the ack is back at the client.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlaProbeConfig {
+    pub client: NodeId,
+    pub server: NodeId,
+    #[serde(default = "default_period_ms")]
+    pub period_ms: u64,
+    pub target_rtt_ms: Option<f64>, //Without it a probe meets the SLA when it's acked at all.
+}
+
+fn default_period_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Clone)]
+pub struct SlaPair {
+    pub config: SlaProbeConfig,
+    pub sent: u64,
+    pub acked: u64,
+    pub failed: u64, //Nacked, never acked in PROBE_TIMEOUT, or with no route to send it on.
+    pub late: u64,   //Acked, but slower than the target.
+    pub rtts: VecDeque<Duration>,
+    last_sent: Option<SystemTime>,
+}
+
+impl SlaPair {
+    pub fn success_ratio(&self) -> f64 {
+        (self.acked - self.late) as f64 / (self.acked + self.failed).max(1) as f64
+    }
+
+    pub fn mean_rtt(&self) -> Option<Duration> {
+        if self.rtts.is_empty() {
+            return None;
+        }
+        Some(self.rtts.iter().sum::<Duration>() / self.rtts.len() as u32)
+    }
+
+    pub fn describe(&self) -> String {
+        let rtt = self.mean_rtt().map_or("-".to_string(), |rtt| format!("{:.2} ms", rtt.as_secs_f64() * 1000.0));
+        format!(
+            "{} -> {}: {} sent, {} acked, {} failed, {} late, {:.1}% within the SLA, mean rtt {}",
+            self.config.client,
+            self.config.server,
+            self.sent,
+            self.acked,
+            self.failed,
+            self.late,
+            self.success_ratio() * 100.0,
+            rtt
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SlaMonitor {
+    pub(crate) pairs: Vec<SlaPair>,
+    outstanding: HashMap<u64, (usize, SystemTime)>, //The probes without an answer, with their pair.
+    next_session: u64,
+    echoes: Vec<Packet>, //The acks of the servers, to be sent by the Sim Contr.
+}
+
+impl SlaMonitor {
+    pub fn add(&mut self, config: SlaProbeConfig) {
+        self.pairs.push(SlaPair { config, sent: 0, acked: 0, failed: 0, late: 0, rtts: VecDeque::new(), last_sent: None });
+    }
+
+    //The pairs whose period is over, to be given a route and sent with start.
+    pub fn due(&self, now: SystemTime) -> Vec<(usize, NodeId, NodeId)> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, pair)| pair.last_sent.map_or(true, |last| now.duration_since(last).unwrap_or_default() >= Duration::from_millis(pair.config.period_ms)))
+            .map(|(index, pair)| (index, pair.config.client, pair.config.server))
+            .collect()
+    }
+
+    //The probe of the pair on this route, or None (and a failure) if there's no route.
+    pub fn start(&mut self, index: usize, route: Option<Vec<NodeId>>, now: SystemTime) -> Option<Packet> {
+        let pair = &mut self.pairs[index];
+        pair.last_sent = Some(now);
+        pair.sent += 1;
+        let Some(route) = route.filter(|route| route.len() > 1) else {
+            pair.failed += 1;
+            return None;
+        };
+        let session_id = SLA_SESSION_BASE + self.next_session;
+        self.next_session += 1;
+        self.outstanding.insert(session_id, (index, now));
+        Some(Packet {
+            pack_type: PacketType::MsgFragment(Fragment { fragment_index: 0, total_n_fragments: 1, length: 0, data: [0; 128] }),
+            routing_header: SourceRoutingHeader { hop_index: 1, hops: route },
+            session_id,
+        })
+    }
+
+    pub fn is_probe(&self, session_id: u64) -> bool {
+        (SLA_SESSION_BASE..SLA_SESSION_BASE + self.next_session).contains(&session_id)
+    }
+
+    //Looks for the probes in the event, returns false if the event has nothing to do with them.
+    pub fn record(&mut self, event: &DroneEvent, time: SystemTime) -> bool {
+        let (packet, shortcut) = match event {
+            DroneEvent::PacketSent(packet) => (packet, false),
+            DroneEvent::ControllerShortcut(packet) => (packet, true),
+            DroneEvent::PacketDropped(packet) => (packet, false),
+        };
+        if !self.is_probe(packet.session_id) {
+            return false;
+        }
+        let header = &packet.routing_header;
+        let arrived = shortcut || header.hop_index + 1 == header.hops.len();
+        if !arrived || matches!(event, DroneEvent::PacketDropped(_)) {
+            return true;
+        }
+        let Some(&(index, sent)) = self.outstanding.get(&packet.session_id) else {
+            return true; //Answered already, or given up.
+        };
+        let pair = &mut self.pairs[index];
+        match &packet.pack_type {
+            PacketType::MsgFragment(fragment) if header.hops.last() == Some(&pair.config.server) => {
+                self.echoes.push(Packet {
+                    pack_type: PacketType::Ack(Ack { fragment_index: fragment.fragment_index }),
+                    routing_header: SourceRoutingHeader { hop_index: 1, hops: header.hops.iter().rev().copied().collect() },
+                    session_id: packet.session_id,
+                });
+            }
+            PacketType::Ack(_) if header.hops.last() == Some(&pair.config.client) => {
+                let rtt = time.duration_since(sent).unwrap_or_default();
+                pair.acked += 1;
+                if pair.config.target_rtt_ms.map_or(false, |target| rtt.as_secs_f64() * 1000.0 > target) {
+                    pair.late += 1;
+                }
+                pair.rtts.push_back(rtt);
+                if pair.rtts.len() > RTT_HISTORY {
+                    pair.rtts.pop_front();
+                }
+                self.outstanding.remove(&packet.session_id);
+            }
+            PacketType::Nack(_) if header.hops.last() == Some(&pair.config.client) => {
+                pair.failed += 1;
+                self.outstanding.remove(&packet.session_id);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    //The probes without an answer after PROBE_TIMEOUT are failed.
+    pub fn expire(&mut self, now: SystemTime) {
+        let pairs = &mut self.pairs;
+        self.outstanding.retain(|_, (index, sent)| {
+            let expired = now.duration_since(*sent).unwrap_or_default() >= PROBE_TIMEOUT;
+            if expired {
+                pairs[*index].failed += 1;
+            }
+            !expired
+        });
+    }
+
+    pub fn take_echoes(&mut self) -> Vec<Packet> {
+        std::mem::take(&mut self.echoes)
+    }
+}
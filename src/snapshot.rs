@@ -0,0 +1,170 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::{fs, io};
+use serde::{Deserialize, Serialize};
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::network::NodeId;
+use crate::sim_control::NodeStats;
+use crate::tags::TagConfig;
+use crate::link_pdr::LinkPdrConfig;
+
+//The snapshot is written in the same shape of the input config ([[drone]], [[client]], [[server]]),
+//with the runtime state added to every node, so it can also be read by a human.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationSnapshot {
+    pub drone: Vec<DroneSnapshot>,
+    pub client: Vec<EndpointSnapshot>,
+    pub server: Vec<EndpointSnapshot>,
+    #[serde(default)]
+    pub tag: Vec<TagConfig>, //The tags and notes, see tags.rs.
+    #[serde(default)]
+    pub link: Vec<LinkPdrConfig>, //The pdr of single links, see link_pdr.rs.
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DroneSnapshot {
+    pub id: NodeId,
+    pub connected_node_ids: Vec<NodeId>,
+    pub pdr: f32,
+    pub crashed: bool,
+    pub stats: NodeStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointSnapshot {
+    pub id: NodeId,
+    pub connected_drone_ids: Vec<NodeId>,
+    pub stats: NodeStats,
+}
+
+impl SimulationSnapshot {
+    pub fn save(&self, file: &str) -> io::Result<()> {
+        let file_str = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(file, file_str)
+    }
+
+    pub fn load(file: &str) -> io::Result<Self> {
+        let file_str = fs::read_to_string(file)?;
+        toml::from_str(&file_str).map_err(io::Error::other)
+    }
+
+    //The config used to build the network again, before the runtime state is restored.
+    pub fn to_config(&self) -> Config {
+        Config {
+            drone: self.drone
+                .iter()
+                .map(|drone| Drone {
+                    id: drone.id,
+                    connected_node_ids: drone.connected_node_ids.clone(),
+                    pdr: drone.pdr,
+                })
+                .collect(),
+            client: self.client
+                .iter()
+                .map(|client| Client {
+                    id: client.id,
+                    connected_drone_ids: client.connected_drone_ids.clone(),
+                })
+                .collect(),
+            server: self.server
+                .iter()
+                .map(|server| Server {
+                    id: server.id,
+                    connected_drone_ids: server.connected_drone_ids.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+//What changed from an older snapshot to a newer one (or to the live network). The links are
+//written with the smaller id first, since a link is in the lists of both its ends.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<NodeId>,
+    pub removed: Vec<NodeId>,
+    pub crashed: Vec<NodeId>,
+    pub recovered: Vec<NodeId>,
+    pub links_added: Vec<(NodeId, NodeId)>,
+    pub links_removed: Vec<(NodeId, NodeId)>,
+    pub pdr_changed: Vec<(NodeId, f32, f32)>, //(drone, before, after)
+}
+
+impl SimulationSnapshot {
+    fn nodes(&self) -> BTreeMap<NodeId, &Vec<NodeId>> {
+        self.drone
+            .iter()
+            .map(|drone| (drone.id, &drone.connected_node_ids))
+            .chain(self.client.iter().chain(self.server.iter()).map(|endpoint| (endpoint.id, &endpoint.connected_drone_ids)))
+            .collect()
+    }
+
+    fn links(&self) -> BTreeSet<(NodeId, NodeId)> {
+        self.nodes()
+            .into_iter()
+            .flat_map(|(id, connected)| connected.iter().map(move |other| (id.min(*other), id.max(*other))))
+            .collect()
+    }
+
+    pub fn diff(&self, newer: &SimulationSnapshot) -> SnapshotDiff {
+        let (before, after) = (self.nodes(), newer.nodes());
+        let (links_before, links_after) = (self.links(), newer.links());
+        let crashed = |snapshot: &SimulationSnapshot| -> BTreeSet<NodeId> {
+            snapshot.drone.iter().filter(|drone| drone.crashed).map(|drone| drone.id).collect()
+        };
+        let (crashed_before, crashed_after) = (crashed(self), crashed(newer));
+
+        let mut pdr_changed = Vec::new();
+        for drone in self.drone.iter() {
+            if let Some(newer_drone) = newer.drone.iter().find(|newer_drone| newer_drone.id == drone.id) {
+                if newer_drone.pdr != drone.pdr {
+                    pdr_changed.push((drone.id, drone.pdr, newer_drone.pdr));
+                }
+            }
+        }
+
+        SnapshotDiff {
+            added: after.keys().filter(|id| !before.contains_key(id)).copied().collect(),
+            removed: before.keys().filter(|id| !after.contains_key(id)).copied().collect(),
+            crashed: crashed_after.difference(&crashed_before).copied().collect(),
+            recovered: crashed_before.difference(&crashed_after).filter(|id| after.contains_key(id)).copied().collect(),
+            links_added: links_after.difference(&links_before).copied().collect(),
+            links_removed: links_before.difference(&links_after).copied().collect(),
+            pdr_changed,
+        }
+    }
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.describe().is_empty()
+    }
+
+    //One line for every kind of change, nothing when the two are the same.
+    pub fn describe(&self) -> Vec<String> {
+        let ids = |ids: &[NodeId]| ids.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(", ");
+        let links = |links: &[(NodeId, NodeId)]| links.iter().map(|(a, b)| format!("{}-{}", a, b)).collect::<Vec<String>>().join(", ");
+        let mut lines = Vec::new();
+        if !self.added.is_empty() {
+            lines.push(format!("nodes added: {}", ids(&self.added)));
+        }
+        if !self.removed.is_empty() {
+            lines.push(format!("nodes removed: {}", ids(&self.removed)));
+        }
+        if !self.crashed.is_empty() {
+            lines.push(format!("drones crashed: {}", ids(&self.crashed)));
+        }
+        if !self.recovered.is_empty() {
+            lines.push(format!("drones no longer crashed: {}", ids(&self.recovered)));
+        }
+        if !self.links_added.is_empty() {
+            lines.push(format!("links added: {}", links(&self.links_added)));
+        }
+        if !self.links_removed.is_empty() {
+            lines.push(format!("links removed: {}", links(&self.links_removed)));
+        }
+        for (id, before, after) in self.pdr_changed.iter() {
+            lines.push(format!("pdr of drone {}: {:.2} -> {:.2}", id, before, after));
+        }
+        lines
+    }
+}
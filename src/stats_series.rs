@@ -0,0 +1,197 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+use wg_2024::network::NodeId;
+use crate::sim_control::NodeStats;
+
+pub struct StatsSample {
+    pub time_ms: u128,
+    pub node_id: NodeId,
+    pub stats: NodeStats,
+    pub resolution_ms: u128, //The interval for a fresh sample, the width of the bucket for a merged one.
+}
+
+//The older a sample is, the coarser I keep it: a tier is (interval in sampling intervals, rows
+//per node). What falls out of a tier is merged into a bucket of the next one, what falls out
+//of the last tier is gone. With a 1 s interval it's 5 minutes at 1 s, an hour at 10 s and a
+//day at 1 min, so a node never costs more than ~2100 rows however long the run is.
+const TIERS: [(u32, usize); 3] = [(1, 300), (10, 360), (60, 1440)];
+
+//A point of the charts and of the CSV, the counters of the drones become rates.
+#[derive(Debug, Clone, Copy)]
+pub struct StatsRate {
+    pub time_ms: u128,
+    pub throughput: f64, //Packets sent per second since the previous sample.
+    pub drops: f64,      //Same, for the packets dropped.
+    pub queue_len: u64,
+}
+
+struct NodeSeries {
+    tiers: Vec<VecDeque<StatsSample>>, //The first one is the newest.
+    buckets: Vec<Option<StatsSample>>, //buckets[i] collects what falls out of tiers[i], until it goes into tiers[i + 1].
+}
+
+impl NodeSeries {
+    fn new() -> Self {
+        NodeSeries {
+            tiers: TIERS.iter().map(|_| VecDeque::new()).collect(),
+            buckets: TIERS.iter().skip(1).map(|_| None).collect(),
+        }
+    }
+
+    fn push(&mut self, tier: usize, sample: StatsSample, interval_ms: u128) {
+        self.tiers[tier].push_back(sample);
+        if self.tiers[tier].len() <= TIERS[tier].1 {
+            return;
+        }
+        let oldest = self.tiers[tier].pop_front().unwrap();
+        if tier + 1 == TIERS.len() {
+            return;
+        }
+
+        let width_ms = interval_ms * TIERS[tier + 1].0 as u128;
+        match self.buckets[tier].take() {
+            Some(bucket) if bucket.time_ms / width_ms == oldest.time_ms / width_ms => {
+                self.buckets[tier] = Some(merge(bucket, oldest, width_ms));
+            }
+            Some(bucket) => {
+                self.buckets[tier] = Some(StatsSample { resolution_ms: width_ms, ..oldest });
+                self.push(tier + 1, bucket, interval_ms);
+            }
+            None => self.buckets[tier] = Some(StatsSample { resolution_ms: width_ms, ..oldest }),
+        }
+    }
+
+    //From the oldest sample to the newest.
+    fn samples(&self) -> impl Iterator<Item = &StatsSample> {
+        let last = self.tiers.len() - 1;
+        (0..=last).rev().flat_map(move |tier| {
+            let bucket = if tier < last { self.buckets[tier].as_ref() } else { None };
+            bucket.into_iter().chain(self.tiers[tier].iter())
+        })
+    }
+
+    fn bytes(&self) -> usize {
+        let rows = self.tiers.iter().map(|tier| tier.capacity()).sum::<usize>() + self.buckets.len();
+        rows * size_of::<StatsSample>()
+    }
+}
+
+//The counters only grow, so the last sample of the bucket has them all. For the queue and the
+//links the worst moment is more interesting than the last one.
+fn merge(bucket: StatsSample, newer: StatsSample, width_ms: u128) -> StatsSample {
+    let queue_len = bucket.stats.queue_len.max(newer.stats.queue_len);
+    let link_utilization = bucket.stats.link_utilization.max(newer.stats.link_utilization);
+    StatsSample {
+        time_ms: newer.time_ms,
+        node_id: newer.node_id,
+        stats: NodeStats { queue_len, link_utilization, ..newer.stats },
+        resolution_ms: width_ms,
+    }
+}
+
+//Time series of the Sim Contr stats: every interval I save a row for every node.
+pub struct StatsSeries {
+    interval: Duration,
+    start: Instant,
+    last_sample: Option<Instant>,
+    nodes: HashMap<NodeId, NodeSeries>,
+}
+
+impl StatsSeries {
+    pub fn new(interval: Duration) -> Self {
+        StatsSeries {
+            interval,
+            start: Instant::now(),
+            last_sample: None,
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn sample_if_due(&mut self, node_ids: &[NodeId], stats: &HashMap<NodeId, NodeStats>) {
+        let now = Instant::now();
+        if let Some(last) = self.last_sample {
+            if now.duration_since(last) < self.interval {
+                return;
+            }
+        }
+        self.last_sample = Some(now);
+
+        let time_ms = now.duration_since(self.start).as_millis();
+        let interval_ms = self.interval.as_millis().max(1);
+        for id in node_ids {
+            let sample = StatsSample {
+                time_ms,
+                node_id: *id,
+                stats: stats.get(id).cloned().unwrap_or_default(),
+                resolution_ms: interval_ms,
+            };
+            self.nodes.entry(*id).or_insert_with(NodeSeries::new).push(0, sample, interval_ms);
+        }
+    }
+
+    //The rates of a node from its first sample, at the resolution each part is kept at.
+    pub fn rates(&self, node_id: NodeId) -> Vec<StatsRate> {
+        let Some(series) = self.nodes.get(&node_id) else {
+            return Vec::new();
+        };
+        let mut rates = Vec::new();
+        let mut previous: Option<&StatsSample> = None;
+        for sample in series.samples() {
+            let (throughput, drops) = match previous {
+                Some(previous) if sample.time_ms > previous.time_ms => {
+                    let seconds = (sample.time_ms - previous.time_ms) as f64 / 1000.0;
+                    (
+                        sample.stats.packets_sent.saturating_sub(previous.stats.packets_sent) as f64 / seconds,
+                        sample.stats.packets_dropped.saturating_sub(previous.stats.packets_dropped) as f64 / seconds,
+                    )
+                }
+                _ => (0.0, 0.0),
+            };
+            rates.push(StatsRate { time_ms: sample.time_ms, throughput, drops, queue_len: sample.stats.queue_len });
+            previous = Some(sample);
+        }
+        rates
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.nodes.values().map(|series| series.bytes()).sum::<usize>() as u64
+    }
+
+    //One row per node per interval (or per bucket, for the older part), ready to be loaded with
+    //pandas.read_csv or R's read.csv.
+    pub fn export_csv(&self, file: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(file)?);
+        writeln!(writer, "time_ms,node_id,packets_sent,packets_dropped,shortcuts,queue_len,flood_cache,mean_hop_latency_ms,link_utilization,resolution_ms,throughput_per_s,drops_per_s")?;
+        let mut node_ids = self.nodes.keys().copied().collect::<Vec<NodeId>>();
+        node_ids.sort();
+        let mut rows = Vec::new();
+        for node_id in node_ids {
+            let samples = self.nodes[&node_id].samples();
+            rows.extend(samples.zip(self.rates(node_id)));
+        }
+        //Sorted by time like before, a stable sort keeps the nodes in order inside an instant.
+        rows.sort_by_key(|(sample, _)| sample.time_ms);
+        for (sample, rate) in rows {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{:.3},{:.3},{},{:.3},{:.3}",
+                sample.time_ms,
+                sample.node_id,
+                sample.stats.packets_sent,
+                sample.stats.packets_dropped,
+                sample.stats.shortcuts,
+                sample.stats.queue_len,
+                sample.stats.flood_cache,
+                sample.stats.mean_hop_latency_ms,
+                sample.stats.link_utilization,
+                sample.resolution_ms,
+                rate.throughput,
+                rate.drops
+            )?;
+        }
+        writer.flush()
+    }
+}
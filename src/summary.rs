@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::time::Duration;
+use wg_2024::network::NodeId;
+use wg_2024::packet::NodeType;
+use crate::sessions::{SessionRecord, SessionTable};
+use crate::sim_control::NodeStats;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionOutcome {
+    Delivered,
+    DeliveredWithDrops, //It got there, but some fragment was nacked on the way.
+    Dropped,
+    Undelivered, //Still going, or lost without a nack (e.g. in a crashed drone).
+}
+
+impl SessionOutcome {
+    fn of(session: &SessionRecord) -> Self {
+        match (session.delivered.is_some(), session.dropped) {
+            (true, false) => SessionOutcome::Delivered,
+            (true, true) => SessionOutcome::DeliveredWithDrops,
+            (false, true) => SessionOutcome::Dropped,
+            (false, false) => SessionOutcome::Undelivered,
+        }
+    }
+}
+
+//What a run did, in a few lines: printed when the simulation shuts down, and written next to
+//the stats with the outcome of every session.
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    pub nodes: Vec<(NodeId, NodeType, u64, u64)>, //(id, type, forwarded, dropped), sorted by id.
+    pub sessions: Vec<(u64, Option<NodeId>, SessionOutcome, Option<Duration>)>, //(session, destination, outcome, latency)
+    pub mean_latency: Option<Duration>,
+    pub p95_latency: Option<Duration>,
+    pub crashes: Vec<NodeId>, //In the order they happened, the shutdown isn't counted.
+    pub faults_injected: u64,
+}
+
+impl RunSummary {
+    pub fn new(node_types: &HashMap<NodeId, NodeType>, stats: &HashMap<NodeId, NodeStats>, sessions: &SessionTable, crashes: &[NodeId], faults_injected: u64) -> Self {
+        let mut nodes = node_types
+            .iter()
+            .map(|(id, node_type)| {
+                let stats = stats.get(id).cloned().unwrap_or_default();
+                (*id, *node_type, stats.packets_sent, stats.packets_dropped)
+            })
+            .collect::<Vec<(NodeId, NodeType, u64, u64)>>();
+        nodes.sort_by_key(|(id, ..)| *id);
+
+        //Only the messages: the sessions of the acks and nacks alone have no destination.
+        let mut messages = sessions.sessions
+            .values()
+            .filter(|session| session.destination.is_some())
+            .map(|session| (session.session_id, session.destination, SessionOutcome::of(session), session.end_to_end_latency()))
+            .collect::<Vec<(u64, Option<NodeId>, SessionOutcome, Option<Duration>)>>();
+        messages.sort_by_key(|(session_id, ..)| *session_id);
+
+        let mut latencies = messages.iter().filter_map(|(.., latency)| *latency).collect::<Vec<Duration>>();
+        latencies.sort();
+        let mean_latency = (!latencies.is_empty()).then(|| latencies.iter().sum::<Duration>() / latencies.len() as u32);
+        let p95_latency = (!latencies.is_empty()).then(|| latencies[(latencies.len() * 95).div_ceil(100) - 1]);
+
+        RunSummary {
+            nodes,
+            sessions: messages,
+            mean_latency,
+            p95_latency,
+            crashes: crashes.to_vec(),
+            faults_injected,
+        }
+    }
+
+    pub fn delivery_ratio(&self) -> Option<f64> {
+        let delivered = self.sessions
+            .iter()
+            .filter(|(_, _, outcome, _)| matches!(outcome, SessionOutcome::Delivered | SessionOutcome::DeliveredWithDrops))
+            .count();
+        (!self.sessions.is_empty()).then(|| delivered as f64 / self.sessions.len() as f64)
+    }
+
+    //With every_session the outcome of every message is listed too, for the file: on the
+    //terminal a run with thousands of messages would bury the rest.
+    pub fn describe(&self, every_session: bool) -> String {
+        let ms = |latency: Option<Duration>| latency.map_or("-".to_string(), |latency| format!("{:.3}ms", latency.as_secs_f64() * 1000.0));
+        let mut text = String::from("=== run summary ===\n");
+
+        text.push_str("node  type    forwarded  dropped\n");
+        for (id, node_type, forwarded, dropped) in self.nodes.iter() {
+            let _ = writeln!(text, "{:<5} {:<7} {:>9} {:>8}", id, format!("{:?}", node_type).to_lowercase(), forwarded, dropped);
+        }
+
+        let count = |outcome: SessionOutcome| self.sessions.iter().filter(|(_, _, o, _)| *o == outcome).count();
+        let _ = writeln!(
+            text,
+            "messages: {} ({} delivered, {} delivered with drops, {} dropped, {} undelivered)",
+            self.sessions.len(),
+            count(SessionOutcome::Delivered),
+            count(SessionOutcome::DeliveredWithDrops),
+            count(SessionOutcome::Dropped),
+            count(SessionOutcome::Undelivered)
+        );
+        let _ = writeln!(
+            text,
+            "delivery ratio: {}",
+            self.delivery_ratio().map_or("-".to_string(), |ratio| format!("{:.1}%", ratio * 100.0))
+        );
+        let _ = writeln!(text, "latency: mean {}, 95p {}", ms(self.mean_latency), ms(self.p95_latency));
+        let crashed = self.crashes.iter().map(|id| id.to_string()).collect::<Vec<String>>();
+        let _ = writeln!(text, "crashes: {} [{}]", self.crashes.len(), crashed.join(", "));
+        let _ = writeln!(text, "faults injected: {}", self.faults_injected);
+
+        if every_session {
+            text.push_str("session  destination  outcome               latency\n");
+            for (session_id, destination, outcome, latency) in self.sessions.iter() {
+                let destination = destination.map_or("?".to_string(), |id| id.to_string());
+                let _ = writeln!(text, "{:<8} {:<12} {:<21} {}", session_id, destination, format!("{:?}", outcome), ms(*latency));
+            }
+        }
+        text
+    }
+
+    pub fn save(&self, file: &str) -> io::Result<()> {
+        fs::write(file, self.describe(true))
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::{BTreeMap, BTreeSet};
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+use crate::groups::GroupDefinition;
+
+//Tags and a note on a node or on a link, as written in the input file (and in the snapshots):
+//    [[tag]]
+//    node = 3
+//    tags = ["backbone", "north"]
+//    note = "the one on the roof"
+//    [[tag]]
+//    link = [3, 4]
+//    tags = ["fiber"]
+//Every tag of the nodes is also a group named "tag:<tag>", so it can be used wherever a group
+//goes: the profiles, the faults, the alerts, crash_group and the others (e.g. "tag:backbone").
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagConfig {
+    pub node: Option<NodeId>,
+    pub link: Option<[NodeId; 2]>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotation {
+    pub tags: BTreeSet<String>,
+    pub note: String,
+}
+
+impl Annotation {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.note.is_empty()
+    }
+
+    //"backbone, north - the one on the roof", empty with nothing on it.
+    pub fn describe(&self) -> String {
+        let tags = self.tags.iter().cloned().collect::<Vec<String>>().join(", ");
+        match (tags.is_empty(), self.note.is_empty()) {
+            (_, true) => tags,
+            (true, false) => self.note.clone(),
+            (false, false) => format!("{} - {}", tags, self.note),
+        }
+    }
+}
+
+//The prefix of the groups made from the tags.
+pub const TAG_GROUP_PREFIX: &str = "tag:";
+
+//A link is kept with the smaller id first, so a - b and b - a are the same link.
+fn link_key(a: NodeId, b: NodeId) -> (NodeId, NodeId) {
+    (a.min(b), a.max(b))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    pub(crate) nodes: BTreeMap<NodeId, Annotation>,
+    pub(crate) links: BTreeMap<(NodeId, NodeId), Annotation>,
+}
+
+impl Annotations {
+    //The tables without a node or a link are left out, with a line in the log of the caller.
+    pub fn from_configs(configs: &[TagConfig]) -> (Self, Vec<String>) {
+        let mut annotations = Annotations::default();
+        let mut errors = Vec::new();
+        for config in configs {
+            let annotation = match (config.node, config.link) {
+                (Some(node), None) => annotations.nodes.entry(node).or_default(),
+                (None, Some([a, b])) => annotations.links.entry(link_key(a, b)).or_default(),
+                _ => {
+                    errors.push(format!("[[tag]] {:?} needs either a node or a link, it's left out.", config.tags));
+                    continue;
+                }
+            };
+            annotation.tags.extend(config.tags.iter().cloned());
+            if !config.note.is_empty() {
+                annotation.note = config.note.clone();
+            }
+        }
+        (annotations, errors)
+    }
+
+    //Back to [[tag]] tables, for the snapshots.
+    pub fn to_configs(&self) -> Vec<TagConfig> {
+        let table = |node: Option<NodeId>, link: Option<[NodeId; 2]>, annotation: &Annotation| TagConfig {
+            node,
+            link,
+            tags: annotation.tags.iter().cloned().collect(),
+            note: annotation.note.clone(),
+        };
+        self.nodes
+            .iter()
+            .map(|(node, annotation)| table(Some(*node), None, annotation))
+            .chain(self.links.iter().map(|((a, b), annotation)| table(None, Some([*a, *b]), annotation)))
+            .collect()
+    }
+
+    pub fn node(&self, node: NodeId) -> Option<&Annotation> {
+        self.nodes.get(&node)
+    }
+
+    pub fn link(&self, a: NodeId, b: NodeId) -> Option<&Annotation> {
+        self.links.get(&link_key(a, b))
+    }
+
+    //Returns false if the node already had the tag.
+    pub fn tag_node(&mut self, node: NodeId, tag: &str) -> bool {
+        self.nodes.entry(node).or_default().tags.insert(tag.to_string())
+    }
+
+    pub fn untag_node(&mut self, node: NodeId, tag: &str) -> bool {
+        let removed = self.nodes.get_mut(&node).map_or(false, |annotation| annotation.tags.remove(tag));
+        self.nodes.retain(|_, annotation| !annotation.is_empty());
+        removed
+    }
+
+    pub fn tag_link(&mut self, a: NodeId, b: NodeId, tag: &str) -> bool {
+        self.links.entry(link_key(a, b)).or_default().tags.insert(tag.to_string())
+    }
+
+    pub fn untag_link(&mut self, a: NodeId, b: NodeId, tag: &str) -> bool {
+        let removed = self.links.get_mut(&link_key(a, b)).map_or(false, |annotation| annotation.tags.remove(tag));
+        self.links.retain(|_, annotation| !annotation.is_empty());
+        removed
+    }
+
+    //An empty note removes it.
+    pub fn set_node_note(&mut self, node: NodeId, note: &str) {
+        self.nodes.entry(node).or_default().note = note.trim().to_string();
+        self.nodes.retain(|_, annotation| !annotation.is_empty());
+    }
+
+    pub fn set_link_note(&mut self, a: NodeId, b: NodeId, note: &str) {
+        self.links.entry(link_key(a, b)).or_default().note = note.trim().to_string();
+        self.links.retain(|_, annotation| !annotation.is_empty());
+    }
+
+    //Every tag of the nodes as a group named tag:<tag>, see TAG_GROUP_PREFIX.
+    pub fn groups(&self) -> Vec<GroupDefinition> {
+        let mut members: BTreeMap<&String, Vec<NodeId>> = BTreeMap::new();
+        for (node, annotation) in self.nodes.iter() {
+            for tag in annotation.tags.iter() {
+                members.entry(tag).or_default().push(*node);
+            }
+        }
+        members
+            .into_iter()
+            .map(|(tag, nodes)| GroupDefinition { name: format!("{}{}", TAG_GROUP_PREFIX, tag), nodes, color: None })
+            .collect()
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+use crate::skylink_drone::commands::{EventMute, Telemetry};
+
+//How much a node tells, as written in the input file:
+//    [[telemetry]]
+//    node = 3
+//    verbosity = "verbose"
+//    interval_ms = 5
+//    [[telemetry]]
+//    node = 7
+//    verbosity = "quiet"
+//    mute = ["floods", "acks"]
+//verbosity is about the log of the Sim Contr (and the node logs), it works for every node.
+//interval_ms (the max delay of the event batches, only with event_batch_size) and mute are sent
+//to the drone, only SkyLink drones take them. All of them can be changed while running.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    pub node: NodeId,
+    #[serde(default)]
+    pub verbosity: LogVerbosity,
+    pub interval_ms: Option<u64>,
+    #[serde(default)]
+    pub mute: Vec<EventCategory>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogVerbosity {
+    Quiet, //Nothing in the log, the stats and the sessions still get every event.
+    #[default]
+    Normal,
+    Verbose, //The whole route of every packet, and never left out under a storm.
+}
+
+impl LogVerbosity {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim() {
+            "quiet" => Some(LogVerbosity::Quiet),
+            "normal" => Some(LogVerbosity::Normal),
+            "verbose" => Some(LogVerbosity::Verbose),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventCategory {
+    Fragments,
+    Acks,
+    Nacks,
+    Floods,
+    Drops,
+}
+
+impl EventCategory {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim() {
+            "fragments" => Some(EventCategory::Fragments),
+            "acks" => Some(EventCategory::Acks),
+            "nacks" => Some(EventCategory::Nacks),
+            "floods" => Some(EventCategory::Floods),
+            "drops" => Some(EventCategory::Drops),
+            _ => None,
+        }
+    }
+}
+
+impl TelemetryConfig {
+    //The part that goes to the drone.
+    pub fn telemetry(&self) -> Telemetry {
+        let mut mute = EventMute::default();
+        for category in self.mute.iter() {
+            match category {
+                EventCategory::Fragments => mute.fragments = true,
+                EventCategory::Acks => mute.acks = true,
+                EventCategory::Nacks => mute.nacks = true,
+                EventCategory::Floods => mute.floods = true,
+                EventCategory::Drops => mute.drops = true,
+            }
+        }
+        Telemetry { interval: self.interval_ms.map(Duration::from_millis), mute }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == TelemetryConfig { node: self.node, ..TelemetryConfig::default() }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod test_bench;
-mod test_initializer;
\ No newline at end of file
+mod test_initializer;
+mod topologies;
\ No newline at end of file
@@ -1,14 +1,43 @@
-use std::collections::{HashMap};
-use std::{thread, vec};
+use std::collections::{HashMap, HashSet};
+use std::{fs, thread, vec};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use crossbeam_channel::{select, select_biased, unbounded, Receiver, Sender};
 use wg_2024::controller::{DroneCommand, DroneEvent};
 use wg_2024::controller::DroneCommand::{SetPacketDropRate};
 use wg_2024::drone::Drone;
 use wg_2024::network::{NodeId, SourceRoutingHeader};
-use wg_2024::packet::{Fragment, Nack, NackType, Packet, PacketType};
-use crate::skylink_drone::drone::SkyLinkDrone;
-use crate::test::test_initializer::test_initialize;
+use wg_2024::packet::{Fragment, Nack, NackType, NodeType, Packet, PacketType};
+use crate::skylink_drone::drone::{DroneStep, SkyLinkDrone};
+use crate::skylink_drone::hooks::DroneHooks;
+use crate::skylink_drone::counters::DroneCounters;
+use crate::skylink_drone::links::PacketRate;
+use crate::skylink_drone::commands::{EnergyBudget, EventMute, PacketFaults, ProcessingDelay, SkyLinkCommand, Telemetry};
+use crate::test::test_initializer::{test_initialize, test_initialize_config};
+use crate::test::topologies::{self, GeneratedTopology, RECEIVER, SENDER};
+use crate::replay::{self, ReplayInput, ReplayTrace};
+use crate::drone_capture::DroneCapture;
+use crate::initializer::{initialize, initialize_segmented};
+use crate::sla::SlaProbeConfig;
+use crate::profiles::NodeGroup;
+use crate::audit::CommandOrigin;
+use crate::faults::{Fault, FaultConfig};
+use crate::journal::{self, EventJournal, JournalRecord, RecoveredJournal};
+use crate::timeline::{Timeline, TimelineEntry, TimelineKind};
+use crate::postmortem;
+use crate::flow::{FlowControlConfig, Transfer};
+use crate::routing::RoutingStrategy;
+use crate::tags::{Annotations, TagConfig};
+use crate::link_pdr::{self, LinkPdrConfig};
+use crate::health::HealthMonitor;
+use crate::skylink_drone::reorder::{DelayDistribution, Reorder};
+use crate::skylink_drone::flood_cache::FloodCacheLimits;
+use crate::skylink_drone::state::DroneState;
+use crate::skylink_drone::loss::BurstLoss;
+use crate::skylink_drone::dedup::DedupWindow;
+use crate::skylink_drone::eviction::LinkDown;
 
 fn packet_printer(packet: Packet) {
     match packet.pack_type.clone() {
@@ -134,6 +163,34 @@ fn client_only_listen_handle(client_receiver: Receiver<Packet>) -> JoinHandle<()
     })
 }
 
+//The channels around drone 1 when a test builds it alone: the senders the drone reads from stay
+//here (so the drone isn't closed while the test runs), and there's a receiver for every neighbour.
+struct DroneFixture<const N: usize> {
+    packet_send: Sender<Packet>,
+    command_send: Sender<DroneCommand>,
+    skylink_send: Sender<SkyLinkCommand>,
+    event_recv: Receiver<DroneEvent>,
+    neighbour_recv: [Receiver<Packet>; N],
+}
+
+//Drone 1 with a channel to each of neighbours (the receivers in the same order), ready for the
+//with_* of the test.
+fn drone_fixture<const N: usize>(neighbours: [NodeId; N], pdr: f32) -> (SkyLinkDrone, DroneFixture<N>) {
+    let (packet_send, packet_recv) = unbounded::<Packet>();
+    let (event_send, event_recv) = unbounded();
+    let (command_send, command_recv) = unbounded::<DroneCommand>();
+    let (skylink_send, skylink_recv) = unbounded::<SkyLinkCommand>();
+    let mut neighbour_send = HashMap::new();
+    let neighbour_recv = neighbours.map(|id| {
+        let (send, recv) = unbounded::<Packet>();
+        neighbour_send.insert(id, send);
+        recv
+    });
+    let drone = SkyLinkDrone::new(1, event_send, command_recv, packet_recv, neighbour_send, pdr)
+        .with_skylink_commands(skylink_recv);
+    (drone, DroneFixture { packet_send, command_send, skylink_send, event_recv, neighbour_recv })
+}
+
 /// This function is used to test the packet forward functionality of a drone.
 pub fn test_generic_fragment_forward() {
     let (sim_contr, clients, mut handles) = test_initialize("inputs/input_generic_fragment_forward.toml");
@@ -353,6 +410,1345 @@ pub fn test_tree_flood(){
 }
 
 
+//Every client of the grid floods at the same moment with the same flood id. The drones keep
+//the floods apart by (flood_id, initiator), so every client must still find all the drones.
+pub fn test_concurrent_floods(){
+    let (_sim_contr, clients, _handles) = test_initialize("inputs/input_multi_client.toml");
+    let drones = (1..=9).collect::<HashSet<NodeId>>();
+
+    for round in 1..=5 {
+        for client in clients.iter() {
+            let packet = Packet{
+                pack_type: PacketType::FloodRequest(wg_2024::packet::FloodRequest{
+                    flood_id: round,
+                    initiator_id: client.id,
+                    path_trace: vec![(client.id, wg_2024::packet::NodeType::Client)],
+                }),
+                routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+                session_id: round,
+            };
+            for sender in client.client_send.values() {
+                sender.send(packet.clone()).unwrap();
+            }
+        }
+
+        //The clients also get the requests of the others, only their own responses count.
+        for client in clients.iter() {
+            let mut found = HashSet::new();
+            while let Ok(packet) = client.client_recv.recv_timeout(Duration::from_millis(300)) {
+                if let PacketType::FloodResponse(response) = packet.pack_type {
+                    assert_eq!(response.flood_id, round, "client {} got a response of an old flood", client.id);
+                    assert_eq!(response.path_trace[0].0, client.id, "client {} got the response of another client", client.id);
+                    found.extend(response.path_trace.iter().map(|(id, _)| *id).filter(|id| drones.contains(id)));
+                }
+            }
+            assert_eq!(found, drones, "round {}: client {} didn't find every drone", round, client.id);
+        }
+        println!("round {}: every client found the {} drones", round, drones.len());
+    }
+}
+
+//Two clients start a flood with the same flood_id at the same time, on a drone between them and a
+//third node: each request must be forwarded, not taken for the other one and answered at once.
+pub fn test_flood_id_collision(){
+    let (mut drone1, fixture) = drone_fixture([10, 11, 12], 0.0);
+    let [c10_packet_receiver, c11_packet_receiver, n12_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    thread::spawn(move || drone1.run());
+
+    for initiator in [10, 11] {
+        d1_packet_sender.send(Packet{
+            pack_type: PacketType::FloodRequest(wg_2024::packet::FloodRequest{
+                flood_id: 7,
+                initiator_id: initiator,
+                path_trace: vec![(initiator, wg_2024::packet::NodeType::Client)],
+            }),
+            routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+            session_id: 7,
+        }).unwrap();
+    }
+
+    let mut initiators = HashSet::new();
+    while let Ok(packet) = n12_packet_receiver.recv_timeout(Duration::from_millis(300)) {
+        if let PacketType::FloodRequest(request) = packet.pack_type {
+            initiators.insert(request.initiator_id);
+        }
+    }
+    assert_eq!(initiators, HashSet::from([10, 11]), "the flood of one client was taken for the other one");
+    for (client, receiver) in [(10, &c10_packet_receiver), (11, &c11_packet_receiver)] {
+        while let Ok(packet) = receiver.recv_timeout(Duration::from_millis(100)) {
+            if let PacketType::FloodResponse(response) = packet.pack_type {
+                panic!("client {} got an early response to its flood: {:?}", client, response.path_trace);
+            }
+        }
+    }
+    println!("both floods with id 7 were forwarded");
+}
+
+//A drone that forgets the floods after 50 ms, and keeps at most 2: the same flood seen again right
+//away is answered, after the ttl or pushed out by two newer ones it's forwarded again as a new one.
+pub fn test_flood_cache_expiry(){
+    let (drone1, fixture) = drone_fixture([10, 12], 0.0);
+    let [c10_packet_receiver, n12_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let counters = Arc::new(DroneCounters::default());
+    let mut drone1 = drone1
+        .with_counters(counters.clone())
+        .with_flood_cache(FloodCacheLimits { capacity: Some(2), ttl: Some(Duration::from_millis(50)) });
+    thread::spawn(move || drone1.run());
+
+    let flood = |flood_id: u64| Packet {
+        pack_type: PacketType::FloodRequest(wg_2024::packet::FloodRequest {
+            flood_id,
+            initiator_id: 10,
+            path_trace: vec![(10, NodeType::Client)],
+        }),
+        routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+        session_id: flood_id,
+    };
+    //Forwarded to 12 or answered back to 10.
+    let outcome = |flood_id: u64| -> &str {
+        d1_packet_sender.send(flood(flood_id)).unwrap();
+        if n12_packet_receiver.recv_timeout(Duration::from_millis(30)).is_ok() {
+            return "forwarded";
+        }
+        match c10_packet_receiver.recv_timeout(Duration::from_millis(30)).map(|packet| packet.pack_type) {
+            Ok(PacketType::FloodResponse(_)) => "answered",
+            _ => "lost",
+        }
+    };
+    assert_eq!(outcome(1), "forwarded");
+    assert_eq!(outcome(1), "answered");
+    thread::sleep(Duration::from_millis(60));
+    assert_eq!(outcome(1), "forwarded", "the flood wasn't forgotten after the ttl");
+
+    assert_eq!(outcome(2), "forwarded");
+    assert_eq!(outcome(3), "forwarded");
+    assert_eq!(counters.flood_cache.load(Ordering::Relaxed), 2, "the cache went over its capacity");
+    assert_eq!(outcome(1), "forwarded", "the oldest flood wasn't pushed out");
+    println!("floods forgotten after the ttl and over the capacity");
+}
+
+//Two discovery rounds of client 0 back to back with the same flood id, on a chain whose drones
+//forget the floods after 50 ms: both find every drone. Without the expiry the second one only
+//got the answer of drone 1.
+pub fn test_rediscovery(){
+    let (mut sim_contr, handles) = initialize("inputs/input_flood_cache.toml");
+    for round in 1..=2 {
+        sim_contr.start_flood(0, Some(1));
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !sim_contr.flood_converged(0) && Instant::now() < deadline {
+            sim_contr.process_events();
+            thread::sleep(Duration::from_millis(10));
+        }
+        let progress = sim_contr.discovery.flood_progress(0).unwrap_or_default();
+        assert!(sim_contr.flood_converged(0), "round {}: {}/{} drones found", round, progress.known, progress.reachable);
+        println!("round {}: {}/{} drones found", round, progress.known, progress.reachable);
+        thread::sleep(Duration::from_millis(60));
+    }
+    sim_contr.shutdown();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+//A client that only sets initiator_id and leaves the path_trace empty: the drone must take the
+//initiator as the node the flood came from, so it's forwarded to everyone else and not back to it,
+//and the response to the same flood seen again still finds its way back to the client.
+pub fn test_flood_empty_path_trace(){
+    let (mut drone1, fixture) = drone_fixture([10, 12], 0.0);
+    let [c10_packet_receiver, n12_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let handle = thread::spawn(move || drone1.run());
+
+    let flood = Packet{
+        pack_type: PacketType::FloodRequest(wg_2024::packet::FloodRequest{
+            flood_id: 3,
+            initiator_id: 10,
+            path_trace: vec![],
+        }),
+        routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+        session_id: 3,
+    };
+    d1_packet_sender.send(flood.clone()).unwrap();
+    match n12_packet_receiver.recv_timeout(Duration::from_millis(300)).map(|packet| packet.pack_type) {
+        Ok(PacketType::FloodRequest(request)) => assert_eq!(request.path_trace, vec![(1, NodeType::Drone)]),
+        other => panic!("the flood wasn't forwarded to 12: {:?}", other),
+    }
+    assert!(c10_packet_receiver.recv_timeout(Duration::from_millis(100)).is_err(), "the flood went back to its initiator");
+
+    //The second time it's answered, on the route back to the initiator.
+    d1_packet_sender.send(flood).unwrap();
+    let response = c10_packet_receiver.recv_timeout(Duration::from_millis(300)).expect("no response to the flood seen again");
+    assert!(matches!(response.pack_type, PacketType::FloodResponse(_)));
+    assert_eq!(response.routing_header.hops, vec![1, 10]);
+    assert!(!handle.is_finished(), "the drone stopped on the empty path_trace");
+    println!("flood with an empty path_trace forwarded and answered");
+}
+
+//An Ack, a Nack and a FloodResponse whose next hop isn't a neighbour of the drone: none of them
+//can be nacked, so all three must reach the Sim Contr as a ControllerShortcut.
+pub fn test_shortcut_fallback(){
+    let (mut drone1, fixture) = drone_fixture([0], 0.0);
+    let (d1_packet_sender, sc_receiver) = (&fixture.packet_send, &fixture.event_recv);
+    thread::spawn(move || drone1.run());
+
+    let packets = vec![
+        PacketType::Ack(wg_2024::packet::Ack { fragment_index: 0 }),
+        PacketType::Nack(Nack { fragment_index: 0, nack_type: NackType::Dropped }),
+        PacketType::FloodResponse(wg_2024::packet::FloodResponse { flood_id: 1, path_trace: vec![] }),
+    ];
+    for pack_type in packets {
+        d1_packet_sender.send(Packet {
+            pack_type,
+            routing_header: SourceRoutingHeader { hop_index: 1, hops: vec![0, 1, 3] },
+            session_id: 1,
+        }).unwrap();
+    }
+
+    let mut shortcuts = 0;
+    while let Ok(event) = sc_receiver.recv_timeout(Duration::from_millis(300)) {
+        match event {
+            DroneEvent::ControllerShortcut(packet) => {
+                assert_eq!(packet.routing_header.hops.last(), Some(&3));
+                shortcuts += 1;
+            },
+            other => panic!("expected only shortcuts, got {:?}", other),
+        }
+    }
+    assert_eq!(shortcuts, 3, "some packets were lost instead of going through the Sim Contr");
+    println!("the {} packets went through the Sim Contr", shortcuts);
+}
+
+//The counters of a drone must tell what it did: 10 fragments forwarded, then with a pdr of 1.0
+//5 dropped (each with its nack), and a flood request handled.
+pub fn test_forwarding_counters(){
+    let (drone1, fixture) = drone_fixture([0, 2], 0.0);
+    let (d1_packet_sender, d1_command_sender) = (&fixture.packet_send, &fixture.command_send);
+    let counters = Arc::new(DroneCounters::default());
+    let mut drone1 = drone1.with_counters(counters.clone());
+
+    for _ in 0..10 {
+        d1_packet_sender.send(create_packet(vec![0, 1, 2])).unwrap();
+    }
+    while !matches!(drone1.step(), DroneStep::Idle) {}
+    d1_command_sender.send(SetPacketDropRate(1.0)).unwrap();
+    for _ in 0..5 {
+        d1_packet_sender.send(create_packet(vec![0, 1, 2])).unwrap();
+    }
+    d1_packet_sender.send(Packet {
+        pack_type: PacketType::FloodRequest(wg_2024::packet::FloodRequest { flood_id: 1, initiator_id: 0, path_trace: vec![(0, wg_2024::packet::NodeType::Client)] }),
+        routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+        session_id: 2,
+    }).unwrap();
+    while !matches!(drone1.step(), DroneStep::Idle) {}
+
+    assert_eq!(counters.load_forwarding(), (10, 5, 5, 1), "(forwarded, dropped, nacks, floods)");
+    println!("counters of drone 1: {:?}", counters.load_forwarding());
+}
+
+//Two drones with the same seed must drop the same fragments, one with another seed must not.
+pub fn test_drone_seed(){
+    let drops = |seed: u64| -> Vec<bool> {
+        let (drone1, fixture) = drone_fixture([0, 2], 0.3);
+        let [c0_packet_receiver, _] = &fixture.neighbour_recv;
+        let d1_packet_sender = &fixture.packet_send;
+        let mut drone1 = drone1.with_seed(seed);
+        //Another thread rolling in between doesn't change anything.
+        thread::spawn(|| (0..1000).map(|_| fastrand::f32()).sum::<f32>()).join().unwrap();
+        (0..200)
+            .map(|_| {
+                d1_packet_sender.send(create_packet(vec![0, 1, 2])).unwrap();
+                while !matches!(drone1.step(), DroneStep::Idle) {}
+                c0_packet_receiver.try_recv().is_ok()
+            })
+            .collect()
+    };
+    assert_eq!(drops(7), drops(7), "the same seed dropped different fragments");
+    assert_ne!(drops(7), drops(8), "another seed dropped the same fragments");
+    println!("{} of 200 fragments dropped with seed 7", drops(7).iter().filter(|dropped| **dropped).count());
+}
+
+//A drone duplicating every fragment sends the 4 fragments of a message twice each, and the server
+//delivers them once; a drone corrupting every fragment gets none of them delivered.
+pub fn test_packet_faults(){
+    for (faults, expected_sent) in [(PacketFaults { duplicate: 1.0, corrupt: 0.0 }, 8), (PacketFaults { duplicate: 0.0, corrupt: 1.0 }, 4)] {
+        let (drone1, fixture) = drone_fixture([0, 9], 0.0);
+        let [_, s9_packet_receiver] = &fixture.neighbour_recv;
+        let d1_packet_sender = &fixture.packet_send;
+        let counters = Arc::new(DroneCounters::default());
+        let mut drone1 = drone1
+            .with_counters(counters.clone())
+            .with_packet_faults(faults);
+
+        let mut transfer = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 4, 0, &FlowControlConfig::default());
+        for packet in transfer.next_packets() {
+            d1_packet_sender.send(packet).unwrap();
+        }
+        while !matches!(drone1.step(), DroneStep::Idle) {}
+        let received = s9_packet_receiver.try_iter().collect::<Vec<Packet>>();
+        for packet in received.iter() {
+            transfer.record(packet);
+        }
+
+        assert_eq!(received.len(), expected_sent, "{:?}", faults);
+        if faults.duplicate > 0.0 {
+            assert!(transfer.finished.is_some(), "the duplicates broke the reassembly");
+            assert_eq!(counters.fragments_duplicated.load(Ordering::Relaxed), 4);
+        } else {
+            assert_eq!(transfer.delivered(), 0, "a corrupted fragment was delivered");
+            assert_eq!(transfer.corrupted, 4);
+            assert_eq!(counters.fragments_corrupted.load(Ordering::Relaxed), 4);
+        }
+        println!("{:?}: {}", faults, transfer.describe());
+    }
+}
+
+//Drone 1 is told to mute the fragments: the one going on to drone 10 is counted but doesn't reach
+//the Sim Contr, the one for server 9 (the last hop) still does. Unmuted, both do again.
+pub fn test_telemetry(){
+    let (drone1, fixture) = drone_fixture([0, 9], 0.0);
+    let (d1_packet_sender, d1_skylink_sender, sc_receiver) = (&fixture.packet_send, &fixture.skylink_send, &fixture.event_recv);
+    let counters = Arc::new(DroneCounters::default());
+    let mut drone1 = drone1.with_counters(counters.clone());
+    let fragment = |hops: Vec<NodeId>| Packet {
+        pack_type: PacketType::MsgFragment(Fragment { fragment_index: 0, total_n_fragments: 1, length: 1, data: [0; 128] }),
+        routing_header: SourceRoutingHeader { hop_index: 1, hops },
+        session_id: 3,
+    };
+
+    for (mute, expected_events) in [(true, 1), (false, 2)] {
+        let telemetry = Telemetry { interval: None, mute: EventMute { fragments: mute, ..EventMute::default() } };
+        d1_skylink_sender.send(SkyLinkCommand::SetTelemetry(telemetry)).unwrap();
+        d1_packet_sender.send(fragment(vec![0, 1, 9, 10])).unwrap();
+        d1_packet_sender.send(fragment(vec![0, 1, 9])).unwrap();
+        while !matches!(drone1.step(), DroneStep::Idle) {}
+        let events = sc_receiver.try_iter().collect::<Vec<DroneEvent>>();
+        assert_eq!(events.len(), expected_events, "mute {}: {:?}", mute, events);
+        println!("mute {}: {} events", mute, events.len());
+    }
+    assert_eq!(counters.load().0, 4, "the muted events weren't counted");
+}
+
+//Three fragments wait in the queue of drone 1 when it's told to shut down: it forwards them
+//to the server, says it drained three packets and its run() returns, with all its channels still open.
+pub fn test_drone_shutdown(){
+    let (mut drone1, fixture) = drone_fixture([0, 9], 0.0);
+    let [_, s9_packet_receiver] = &fixture.neighbour_recv;
+    let (d1_packet_sender, d1_skylink_sender) = (&fixture.packet_send, &fixture.skylink_send);
+
+    let mut transfer = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 3, 0, &FlowControlConfig::default());
+    let packets = transfer.next_packets();
+    for packet in packets.iter() {
+        d1_packet_sender.send(packet.clone()).unwrap();
+    }
+    let (exit_sender, exit_receiver) = unbounded();
+    d1_skylink_sender.send(SkyLinkCommand::Shutdown(exit_sender)).unwrap();
+    let handle = thread::spawn(move || drone1.run());
+
+    let exit = exit_receiver.recv_timeout(Duration::from_secs(1)).expect("the drone didn't say it stopped");
+    assert_eq!(exit.id, 1);
+    assert_eq!(exit.drained, 3);
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while !handle.is_finished() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(handle.is_finished(), "run() didn't return after the shutdown");
+    handle.join().unwrap();
+    assert_eq!(s9_packet_receiver.try_iter().count(), 3, "the queued fragments weren't forwarded");
+    //The drone is gone with its thread, nothing can be sent to it anymore.
+    assert!(d1_packet_sender.send(packets[0].clone()).is_err());
+    println!("{:?}", exit);
+}
+
+//A drone with a heartbeat tells how many packets it read between two beats, and once it crashes
+//it goes silent: the monitor calls it stalled after three missed beats, unless it knows of the crash.
+pub fn test_heartbeat(){
+    let (drone1, fixture) = drone_fixture([9], 0.25);
+    let (d1_packet_sender, d1_command_sender) = (&fixture.packet_send, &fixture.command_send);
+    let (beat_sender, beat_receiver) = unbounded();
+    let interval = Duration::from_millis(50);
+    let mut drone1 = drone1.with_heartbeat(interval, beat_sender);
+    thread::spawn(move || drone1.run());
+
+    let mut transfer = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 6, 0, &FlowControlConfig::default());
+    for packet in transfer.next_packets() {
+        d1_packet_sender.send(packet).unwrap();
+    }
+    let mut monitor = HealthMonitor::new(interval);
+    let mut handled = 0;
+    for _i in 0..4 {
+        let beat = beat_receiver.recv_timeout(interval * 4).expect("no heartbeat from the drone");
+        assert_eq!(beat.drone, 1);
+        assert_eq!(beat.pdr, 0.25);
+        handled += beat.handled;
+        monitor.record(&beat);
+    }
+    assert_eq!(handled, 6, "the packets between the beats don't add up");
+    assert!(monitor.newly_stalled(&HashSet::new()).is_empty());
+
+    d1_command_sender.send(DroneCommand::Crash).unwrap();
+    thread::sleep(interval * 5);
+    while let Ok(beat) = beat_receiver.try_recv() {
+        monitor.record(&beat);
+    }
+    assert!(beat_receiver.recv_timeout(interval * 2).is_err(), "the drone beats while crashing");
+    assert_eq!(monitor.newly_stalled(&HashSet::new()), vec![1]);
+    assert!(monitor.newly_stalled(&HashSet::new()).is_empty(), "the stall was said twice");
+    assert!(monitor.newly_stalled(&HashSet::from([1])).is_empty());
+    assert!(monitor.nodes.is_empty(), "a crashed drone is still watched");
+    println!("{} packets counted by the heartbeats, the crashed drone went silent", handled);
+}
+
+//A drone with a battery for 3 fragments gets 6: the first 3 go to the server, then it crashes on its own
+//and nacks the others like any crashing drone.
+pub fn test_energy(){
+    let (drone1, fixture) = drone_fixture([0, 9], 0.0);
+    let [c0_packet_receiver, s9_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let counters = Arc::new(DroneCounters::default());
+    let mut drone1 = drone1
+        .with_counters(counters.clone())
+        .with_energy(EnergyBudget { capacity: 3, forward_cost: 1, flood_cost: 1 });
+    thread::spawn(move || drone1.run());
+
+    let mut transfer = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 6, 0, &FlowControlConfig::default());
+    for packet in transfer.next_packets() {
+        d1_packet_sender.send(packet).unwrap();
+    }
+    thread::sleep(Duration::from_millis(200));
+
+    let delivered = s9_packet_receiver.try_iter().count();
+    let nacked = c0_packet_receiver
+        .try_iter()
+        .filter(|packet| matches!(&packet.pack_type, PacketType::Nack(nack) if nack.nack_type == NackType::ErrorInRouting(1)))
+        .count();
+    assert_eq!(delivered, 3, "the battery didn't stop the drone");
+    assert_eq!(nacked, 3, "the fragments after the crash weren't nacked");
+    assert_eq!(counters.energy_left.load(Ordering::Relaxed), 0);
+    assert!(counters.energy_depleted.load(Ordering::Relaxed), "the Sim Contr isn't told about the empty battery");
+    println!("{} fragments delivered before the battery ran out, {} nacked after", delivered, nacked);
+}
+
+//A message of 20 fragments is waiting in the channel of drone 1, which takes 20 ms for every one
+//it sends to the server, and a nack for the client comes right after it: the nack gets out first
+//instead of waiting the 400 ms of the fragments.
+pub fn test_priority(){
+    let (drone1, fixture) = drone_fixture([0, 9], 0.0);
+    let [c0_packet_receiver, s9_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let mut drone1 = drone1
+        .with_processing_delays(vec![(Some(9), ProcessingDelay { delay: Duration::from_millis(20), jitter: Duration::ZERO })]);
+
+    let mut transfer = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 20, 0, &FlowControlConfig::default());
+    for packet in transfer.next_packets() {
+        d1_packet_sender.send(packet).unwrap();
+    }
+    d1_packet_sender.send(Packet {
+        pack_type: PacketType::Nack(Nack { fragment_index: 3, nack_type: NackType::Dropped }),
+        routing_header: SourceRoutingHeader { hop_index: 1, hops: vec![9, 1, 0] },
+        session_id: 4,
+    }).unwrap();
+    let start = Instant::now();
+    thread::spawn(move || drone1.run());
+
+    let nack = c0_packet_receiver.recv_timeout(Duration::from_millis(200)).expect("the nack waited behind the fragments");
+    let waited = start.elapsed();
+    assert!(matches!(nack.pack_type, PacketType::Nack(_)), "{:?}", nack.pack_type);
+    assert!(s9_packet_receiver.len() <= 1, "{} fragments went out before the nack", s9_packet_receiver.len());
+
+    //The fragments still go out, in their order.
+    let mut indexes = Vec::new();
+    while let Ok(packet) = s9_packet_receiver.recv_timeout(Duration::from_millis(200)) {
+        if let PacketType::MsgFragment(fragment) = packet.pack_type {
+            indexes.push(fragment.fragment_index);
+        }
+    }
+    assert_eq!(indexes, (0..20).collect::<Vec<u64>>());
+    println!("nack out after {:?}, the 20 fragments after it in order", waited);
+}
+
+//Drone 1 in jitter mode holds up to 8 fragments for 0 - 30 ms each: the 20 fragments of a message
+//all reach the server, not in the order they were sent, and the drone counts the ones that overtook.
+pub fn test_reorder(){
+    let (drone1, fixture) = drone_fixture([9], 0.0);
+    let [s9_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let counters = Arc::new(DroneCounters::default());
+    let delay = DelayDistribution::Uniform { min: Duration::ZERO, max: Duration::from_millis(30) };
+    let mut drone1 = drone1
+        .with_counters(counters.clone())
+        .with_reorder(Some(Reorder { buffer: 8, delay }));
+    thread::spawn(move || drone1.run());
+
+    let mut transfer = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 20, 0, &FlowControlConfig::default());
+    for packet in transfer.next_packets() {
+        d1_packet_sender.send(packet).unwrap();
+    }
+    let mut indexes = Vec::new();
+    while let Ok(packet) = s9_packet_receiver.recv_timeout(Duration::from_millis(200)) {
+        if let PacketType::MsgFragment(fragment) = packet.pack_type {
+            indexes.push(fragment.fragment_index);
+        }
+    }
+    let mut sorted = indexes.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..20).collect::<Vec<u64>>(), "a held fragment never left");
+    assert_ne!(indexes, sorted, "the fragments came out in order");
+    let reordered = counters.fragments_reordered.load(Ordering::Relaxed);
+    assert!(reordered > 0);
+
+    //The exponential delays stay under their max.
+    let exponential = DelayDistribution::Exponential { mean: Duration::from_millis(10), max: Duration::from_millis(100) };
+    assert_eq!(exponential.delay(0.0), Duration::ZERO);
+    assert_eq!(exponential.delay(1.0), Duration::from_millis(100));
+    println!("fragments at the server: {:?}, {} reordered", indexes, reordered);
+}
+
+//Drone 1 follows routes of at most 4 hops: a longer one and one that already went through it are
+//nacked back as ErrorInRouting(1), a short one and one of exactly 4 hops go on.
+pub fn test_ttl(){
+    let (drone1, fixture) = drone_fixture([0, 2], 0.0);
+    let [c0_packet_receiver, d2_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let mut drone1 = drone1.with_max_hops(Some(4));
+    thread::spawn(move || drone1.run());
+
+    let fragment = |session_id: u64, hop_index: usize, hops: Vec<NodeId>| Packet {
+        pack_type: PacketType::MsgFragment(Fragment { fragment_index: 0, total_n_fragments: 1, length: 1, data: [1; 128] }),
+        routing_header: SourceRoutingHeader { hop_index, hops },
+        session_id,
+    };
+    d1_packet_sender.send(fragment(1, 1, vec![0, 1, 2, 9])).unwrap();
+    d1_packet_sender.send(fragment(4, 1, vec![0, 1, 2, 3, 9])).unwrap();
+    d1_packet_sender.send(fragment(2, 1, vec![0, 1, 2, 3, 4, 9])).unwrap();
+    d1_packet_sender.send(fragment(3, 3, vec![0, 1, 2, 1, 9])).unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    let forwarded = d2_packet_receiver.try_iter().map(|packet| packet.session_id).collect::<Vec<u64>>();
+    assert_eq!(forwarded, vec![1, 4], "only the routes of at most 4 hops without loops go on");
+    let mut nacked = Vec::new();
+    for packet in c0_packet_receiver.try_iter() {
+        if let PacketType::Nack(nack) = &packet.pack_type {
+            assert_eq!(nack.nack_type, NackType::ErrorInRouting(1));
+            nacked.push(packet.session_id);
+        }
+    }
+    //The nack of the loop starts from my first place in the route, so it goes straight back to 0 too.
+    assert_eq!(nacked, vec![2, 3], "the long route and the loop weren't nacked back to the client");
+    println!("forwarded {:?}, nacked {:?}", forwarded, nacked);
+}
+
+//The state drone 1 tells with SkyLinkCommand::Snapshot, after forwarding a message and a flood:
+//what it did is there, it's still there after a json round trip, and a crashing drone answers too.
+pub fn test_drone_state(){
+    let (mut drone1, fixture) = drone_fixture([9, 0], 0.2);
+    let (d1_packet_sender, d1_command_sender, d1_skylink_sender) = (&fixture.packet_send, &fixture.command_send, &fixture.skylink_send);
+    thread::spawn(move || drone1.run());
+
+    let mut transfer = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 4, 0, &FlowControlConfig::default());
+    for packet in transfer.next_packets() {
+        d1_packet_sender.send(packet).unwrap();
+    }
+    d1_packet_sender.send(Packet {
+        pack_type: PacketType::FloodRequest(wg_2024::packet::FloodRequest { flood_id: 2, initiator_id: 0, path_trace: vec![(0, NodeType::Client)] }),
+        routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+        session_id: 2,
+    }).unwrap();
+    let state = |sender: &Sender<SkyLinkCommand>| -> DroneState {
+        let (reply_send, reply_recv) = unbounded();
+        sender.send(SkyLinkCommand::Snapshot(reply_send)).unwrap();
+        reply_recv.recv_timeout(Duration::from_millis(300)).expect("the drone didn't tell its state")
+    };
+    thread::sleep(Duration::from_millis(100));
+
+    let before = state(d1_skylink_sender);
+    assert_eq!((before.id, before.pdr, before.neighbours.clone()), (1, 0.2, vec![0, 9]));
+    assert_eq!(before.flood_cache, 1);
+    assert_eq!(before.counters.floods_handled, 1);
+    assert_eq!(before.counters.fragments_forwarded + before.counters.fragments_dropped, 4);
+    assert!(!before.crashing);
+    let json = serde_json::to_string(&before).unwrap();
+    assert_eq!(serde_json::from_str::<DroneState>(&json).unwrap(), before);
+
+    d1_command_sender.send(DroneCommand::Crash).unwrap();
+    let after = state(d1_skylink_sender);
+    assert!(after.crashing, "the crash isn't in the state");
+    assert_eq!(after.counters, before.counters);
+    println!("{}", json);
+}
+
+//Drone 1 drops in bursts (bad with 0.05, good again with 0.2, everything lost while bad): the
+//fragments lost to the server come in runs of about 5, while a pdr with the same mean loss
+//(1 in 5) gives runs of about 1. Back to its pdr of 0 at runtime, nothing is lost anymore.
+pub fn test_burst_loss(){
+    let (drone1, fixture) = drone_fixture([9, 0], 0.0);
+    let [s9_packet_receiver, _] = &fixture.neighbour_recv;
+    let (d1_packet_sender, d1_skylink_sender) = (&fixture.packet_send, &fixture.skylink_send);
+    let burst_loss = BurstLoss { p_good_to_bad: 0.05, p_bad_to_good: 0.2, loss_good: 0.0, loss_bad: 1.0 };
+    assert!((burst_loss.mean_loss() - 0.2).abs() < 0.001);
+    let mut drone1 = drone1
+        .with_burst_loss(Some(burst_loss))
+        .with_seed(7);
+    thread::spawn(move || drone1.run());
+
+    //The lengths of the runs of fragments that never reached the server.
+    let lost_runs = |session: u64, fragments: u64| -> Vec<u64> {
+        let mut transfer = Transfer::new(session, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, fragments, 0, &FlowControlConfig::default());
+        for packet in transfer.next_packets() {
+            d1_packet_sender.send(packet).unwrap();
+        }
+        let mut arrived = HashSet::new();
+        while let Ok(packet) = s9_packet_receiver.recv_timeout(Duration::from_millis(200)) {
+            if let PacketType::MsgFragment(fragment) = packet.pack_type {
+                arrived.insert(fragment.fragment_index);
+            }
+        }
+        let mut runs = Vec::new();
+        let mut run = 0;
+        for index in 0..fragments {
+            match arrived.contains(&index) {
+                true if run > 0 => {
+                    runs.push(run);
+                    run = 0;
+                }
+                true => {}
+                false => run += 1,
+            }
+        }
+        if run > 0 {
+            runs.push(run);
+        }
+        runs
+    };
+
+    let runs = lost_runs(5, 1000);
+    let lost: u64 = runs.iter().sum();
+    let mean_run = lost as f64 / runs.len().max(1) as f64;
+    assert!((100..350).contains(&lost), "{} lost instead of about 200", lost);
+    assert!(mean_run > 2.5, "runs of {:.1} on average aren't bursts", mean_run);
+
+    d1_skylink_sender.send(SkyLinkCommand::SetBurstLoss(None)).unwrap();
+    assert_eq!(lost_runs(6, 200), Vec::<u64>::new());
+    println!("{} fragments lost in {} bursts of {:.1} on average", lost, runs.len(), mean_run);
+}
+
+//Drone 1 remembers the fragments it forwarded for 100 ms: the same 4 fragments sent three times
+//in a row reach the server once, the 8 copies are counted as suppressed and nobody gets a nack.
+//Once the drone forgot them, a retransmission goes through again.
+pub fn test_dedup(){
+    let (drone1, fixture) = drone_fixture([9, 0], 0.0);
+    let [s9_packet_receiver, c0_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let counters = Arc::new(DroneCounters::default());
+    let mut drone1 = drone1
+        .with_counters(counters.clone())
+        .with_dedup(Some(DedupWindow { capacity: 16, ttl: Duration::from_millis(100) }));
+    thread::spawn(move || drone1.run());
+
+    let mut transfer = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 4, 0, &FlowControlConfig::default());
+    let packets = transfer.next_packets();
+    for _ in 0..3 {
+        for packet in packets.iter() {
+            d1_packet_sender.send(packet.clone()).unwrap();
+        }
+    }
+    let arrived = |receiver: &Receiver<Packet>| -> Vec<u64> {
+        let mut indexes = Vec::new();
+        while let Ok(packet) = receiver.recv_timeout(Duration::from_millis(50)) {
+            if let PacketType::MsgFragment(fragment) = packet.pack_type {
+                indexes.push(fragment.fragment_index);
+            }
+        }
+        indexes
+    };
+    assert_eq!(arrived(&s9_packet_receiver), vec![0, 1, 2, 3]);
+    assert_eq!(counters.duplicates_suppressed.load(Ordering::Relaxed), 8);
+    assert!(c0_packet_receiver.try_recv().is_err(), "a suppressed copy was nacked");
+
+    thread::sleep(Duration::from_millis(100));
+    d1_packet_sender.send(packets[2].clone()).unwrap();
+    assert_eq!(arrived(&s9_packet_receiver), vec![2]);
+    assert_eq!(counters.duplicates_suppressed.load(Ordering::Relaxed), 8);
+    println!("12 fragments in, 4 at the server, 8 suppressed; the retransmission after the ttl went through");
+}
+
+//Drone 1 removes a neighbour after 3 sends in a row found its channel closed: the 3 fragments
+//for drone 2 (already gone) are nacked with ErrorInRouting(2), then the Sim Contr hears the link
+//is down and the next fragment is nacked by the routing check, without trying 2 again.
+pub fn test_neighbour_eviction(){
+    let (drone1, fixture) = drone_fixture([0, 2], 0.0);
+    let [c0_packet_receiver, d2_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let (link_down_send, link_down_recv) = unbounded::<LinkDown>();
+    let mut drone1 = drone1.with_eviction(3, link_down_send);
+    thread::spawn(move || drone1.run());
+    drop(d2_packet_receiver);
+
+    let fragment = |session_id: u64| Packet {
+        pack_type: PacketType::MsgFragment(Fragment { fragment_index: 0, total_n_fragments: 1, length: 1, data: [1; 128] }),
+        routing_header: SourceRoutingHeader { hop_index: 1, hops: vec![0, 1, 2, 9] },
+        session_id,
+    };
+    let nack = |session_id: u64| -> NackType {
+        let packet = c0_packet_receiver.recv_timeout(Duration::from_millis(200)).expect("no nack came back");
+        assert_eq!(packet.session_id, session_id);
+        match packet.pack_type {
+            PacketType::Nack(nack) => nack.nack_type,
+            other => panic!("{:?} instead of a nack", other),
+        }
+    };
+    for session_id in 1..=3 {
+        d1_packet_sender.send(fragment(session_id)).unwrap();
+        assert_eq!(nack(session_id), NackType::ErrorInRouting(2));
+        if session_id < 3 {
+            assert!(link_down_recv.try_recv().is_err(), "evicted after {} failures", session_id);
+        }
+    }
+    let link_down = link_down_recv.recv_timeout(Duration::from_millis(200)).expect("the link down never came");
+    assert_eq!((link_down.drone, link_down.neighbour, link_down.failures), (1, 2, 3));
+
+    d1_packet_sender.send(fragment(4)).unwrap();
+    assert_eq!(nack(4), NackType::ErrorInRouting(1));
+    assert!(link_down_recv.recv_timeout(Duration::from_millis(100)).is_err(), "the link went down twice");
+    println!("drone 1 removed {} after {} failed sends", link_down.neighbour, link_down.failures);
+}
+
+struct TickHooks {
+    ticks: Arc<AtomicU64>,
+}
+
+impl DroneHooks for TickHooks {
+    fn on_tick(&mut self, _drone_id: NodeId) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+//Drone 1 ticks every 20 ms: with no traffic at all its hooks still get the ticks, and the flood
+//it remembered for 50 ms leaves the cache on its own (the stat goes back to 0). A drone without
+//a tick only does it when a packet wakes it up, so its stat stays at 1.
+pub fn test_tick(){
+    let flood = Packet {
+        pack_type: PacketType::FloodRequest(wg_2024::packet::FloodRequest { flood_id: 1, initiator_id: 10, path_trace: vec![(10, NodeType::Client)] }),
+        routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+        session_id: 1,
+    };
+    let mut cache_sizes = Vec::new();
+    let ticks = Arc::new(AtomicU64::new(0));
+    for interval in [Some(Duration::from_millis(20)), None] {
+        let (drone1, fixture) = drone_fixture([10], 0.0);
+        let d1_packet_sender = &fixture.packet_send;
+        let counters = Arc::new(DroneCounters::default());
+        let mut drone1 = drone1
+            .with_counters(counters.clone())
+            .with_flood_cache(FloodCacheLimits { capacity: None, ttl: Some(Duration::from_millis(50)) })
+            .with_hooks(Box::new(TickHooks { ticks: ticks.clone() }))
+            .with_tick(interval);
+        thread::spawn(move || drone1.run());
+
+        d1_packet_sender.send(flood.clone()).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(counters.flood_cache.load(Ordering::Relaxed), 1);
+        thread::sleep(Duration::from_millis(150));
+        cache_sizes.push(counters.flood_cache.load(Ordering::Relaxed));
+    }
+    assert_eq!(cache_sizes, vec![0, 1], "the flood cache didn't expire on the tick");
+    let ticks = ticks.load(Ordering::Relaxed);
+    assert!(ticks >= 5, "only {} ticks in 170 ms", ticks);
+    println!("{} ticks in 170 ms, flood cache {:?} with and without the tick", ticks, cache_sizes);
+}
+
+//Client 0 caches its route to 9 through drone 1, then drone 1 loses its channel to the next drone
+//without the Sim Contr knowing: the next message on the cached route is nacked by drone 1 with
+//ErrorInRouting(1), and that nack alone must drop the route from the cache.
+pub fn test_route_cache_invalidation(){
+    let (mut sim_contr, handles) = initialize("inputs/input_eviction.toml");
+    let settle = |sim_contr: &mut crate::sim_control::SimulationControl| {
+        let deadline = Instant::now() + Duration::from_millis(300);
+        while Instant::now() < deadline {
+            sim_contr.process_events();
+            thread::sleep(Duration::from_millis(10));
+        }
+    };
+    sim_contr.send_to(0, 9, 1, None).expect("no route from 0 to 9");
+    settle(&mut sim_contr);
+    let route = sim_contr.route_cache.cached(0, 9).cloned().expect("the route wasn't cached");
+    assert_eq!(&route[..2], &[0, 1]);
+
+    let cut = route[2];
+    sim_contr.node_send[&1].send(DroneCommand::RemoveSender(cut)).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    let invalidations = sim_contr.route_cache.invalidations;
+    sim_contr.send_to(0, 9, 1, None).expect("the cached route is gone already");
+    settle(&mut sim_contr);
+    assert!(sim_contr.route_cache.invalidations > invalidations, "the ErrorInRouting nack didn't invalidate anything");
+    assert_eq!(sim_contr.route_cache.cached(0, 9), None, "{:?} is still cached after drone 1 lost {}", route, cut);
+    println!("{:?} dropped from the cache after drone 1 lost its link to {}", route, cut);
+
+    sim_contr.shutdown();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+struct TerminationHooks {
+    terminated: Arc<AtomicU64>,
+}
+
+impl DroneHooks for TerminationHooks {
+    fn on_terminated(&mut self, _drone_id: NodeId) {
+        self.terminated.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+//Two drones linked to each other, each holding the channel of the other open: once the Sim Contr
+//drops its senders both run() return (and the same with step()), with the hooks told once and
+//the counters saying so for the Sim Contr.
+pub fn test_drone_terminates(){
+    let terminated = Arc::new(AtomicU64::new(0));
+    let (d1_counters, d2_counters) = (Arc::new(DroneCounters::default()), Arc::new(DroneCounters::default()));
+    //Drone 2 reads the channel the fixture made for it, and holds the one of drone 1.
+    let (drone1, fixture) = drone_fixture([2], 0.0);
+    let [d2_packet_receiver] = fixture.neighbour_recv;
+    let (sc_sender, _sc_receiver) = unbounded();
+    let (d2_command_sender, d2_command_receiver) = unbounded::<DroneCommand>();
+    let mut drone1 = drone1
+        .with_counters(d1_counters.clone())
+        .with_hooks(Box::new(TerminationHooks { terminated: terminated.clone() }));
+    let mut drone2 = SkyLinkDrone::new(2, sc_sender, d2_command_receiver, d2_packet_receiver, HashMap::from([(1, fixture.packet_send.clone())]), 0.0)
+        .with_counters(d2_counters.clone())
+        .with_hooks(Box::new(TerminationHooks { terminated: terminated.clone() }));
+    let handle = thread::spawn(move || drone1.run());
+    let stepped = thread::spawn(move || {
+        while drone2.step() != DroneStep::Finished {
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+
+    thread::sleep(Duration::from_millis(50));
+    assert!(!handle.is_finished(), "the drone stopped with its channels still open");
+    assert!(!d1_counters.terminated.load(Ordering::Relaxed));
+    drop((fixture.packet_send, fixture.command_send, d2_command_sender));
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while !(handle.is_finished() && stepped.is_finished()) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(handle.is_finished(), "run() didn't return with its channels closed");
+    assert!(stepped.is_finished(), "step() never said Finished with its channels closed");
+    handle.join().unwrap();
+    stepped.join().unwrap();
+    assert_eq!(terminated.load(Ordering::Relaxed), 2);
+    assert!(d1_counters.terminated.load(Ordering::Relaxed) && d2_counters.terminated.load(Ordering::Relaxed), "the counters don't say it");
+    println!("both drones terminated once their channels closed");
+}
+
+//The [[tag]] tables of an input file: the node tags become the groups tag:<tag> (a crashed member
+//drops out like in any group), the link is the same either way round, and the snapshot tables
+//give back the same annotations.
+pub fn test_tags(){
+    #[derive(serde::Deserialize)]
+    struct TagFile {
+        tag: Vec<TagConfig>,
+    }
+    let file: TagFile = toml::from_str(r#"
+        [[tag]]
+        node = 1
+        tags = ["backbone", "north"]
+        note = "the one on the roof"
+        [[tag]]
+        node = 2
+        tags = ["backbone"]
+        [[tag]]
+        link = [2, 1]
+        tags = ["fiber"]
+        [[tag]]
+        tags = ["lost"]
+    "#).unwrap();
+    let (mut annotations, errors) = Annotations::from_configs(&file.tag);
+    assert_eq!(errors.len(), 1, "the table without a node or a link wasn't reported");
+    assert_eq!(annotations.node(1).unwrap().describe(), "backbone, north - the one on the roof");
+    assert!(annotations.link(1, 2).unwrap().tags.contains("fiber"));
+
+    let graph = HashMap::from([(1, vec![2]), (2, vec![1, 3]), (3, vec![2])]);
+    let node_types = HashMap::from([(1, NodeType::Drone), (2, NodeType::Drone), (3, NodeType::Drone)]);
+    let backbone = NodeGroup::parse("tag:backbone").unwrap();
+    assert_eq!(backbone.members(&graph, &node_types, &HashSet::new(), &annotations.groups()), vec![1, 2]);
+    assert_eq!(backbone.members(&graph, &node_types, &HashSet::from([2]), &annotations.groups()), vec![1]);
+
+    assert!(annotations.tag_node(3, "backbone"));
+    assert!(!annotations.tag_node(3, "backbone"), "the same tag was added twice");
+    assert!(annotations.untag_node(1, "north"));
+    assert!(annotations.untag_link(2, 1, "fiber"));
+    assert!(annotations.link(1, 2).is_none(), "a link without tags or note was kept");
+    assert_eq!(backbone.members(&graph, &node_types, &HashSet::new(), &annotations.groups()), vec![1, 2, 3]);
+
+    let (restored, errors) = Annotations::from_configs(&annotations.to_configs());
+    assert!(errors.is_empty());
+    assert_eq!(restored.nodes, annotations.nodes);
+    println!("{:?}", restored.groups().iter().map(|group| group.name.clone()).collect::<Vec<String>>());
+}
+
+//Every fragment of a message is acked once, then fragment 1 twice more (a retransmission acked
+//twice): the two extra acks are counted, and they only grow the window when they aren't suppressed.
+pub fn test_duplicate_acks(){
+    let at_end = |pack_type: PacketType, hops: Vec<NodeId>| Packet {
+        pack_type,
+        routing_header: SourceRoutingHeader { hop_index: hops.len() - 1, hops },
+        session_id: 7,
+    };
+    let ack = |index: u64| at_end(PacketType::Ack(wg_2024::packet::Ack { fragment_index: index }), vec![9, 1, 0]);
+    for suppress_duplicate_acks in [true, false] {
+        let config = FlowControlConfig { window: 2, suppress_duplicate_acks, ..FlowControlConfig::default() };
+        let mut transfer = Transfer::new(7, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 4, 2, &config);
+        loop {
+            let packets = transfer.next_packets();
+            if packets.is_empty() {
+                break;
+            }
+            for packet in packets {
+                if let PacketType::MsgFragment(fragment) = packet.pack_type {
+                    transfer.record(&ack(fragment.fragment_index));
+                }
+            }
+        }
+        assert!(transfer.finished.is_some(), "the acks alone didn't complete the message");
+        let window = transfer.window;
+        transfer.record(&ack(1));
+        transfer.record(&ack(1));
+        assert_eq!(transfer.duplicate_acks, 2);
+        if suppress_duplicate_acks {
+            assert_eq!(transfer.window, window, "a suppressed duplicate changed the window");
+        }
+        println!("suppress {}: {}", suppress_duplicate_acks, transfer.describe());
+    }
+}
+
+//The channel of drone 2 holds a single packet and is already full: the fragment for it must come
+//back to client 0 as a Dropped nack, and the Sim Contr must get a PacketDropped for it.
+pub fn test_congestion_nack(){
+    let (drone1, fixture) = drone_fixture([0], 0.0);
+    let [c0_packet_receiver] = fixture.neighbour_recv;
+    let (d1_packet_sender, sc_receiver) = (&fixture.packet_send, &fixture.event_recv);
+    //The bounded channel of drone 2 is given to drone 1 like the Sim Contr would, before the fragment.
+    let (d2_packet_sender, d2_packet_receiver) = crossbeam_channel::bounded::<Packet>(1);
+    d2_packet_sender.send(create_packet(vec![0, 1, 2])).unwrap();
+    fixture.command_send.send(DroneCommand::AddSender(2, d2_packet_sender)).unwrap();
+    let counters = Arc::new(DroneCounters::default());
+    let mut drone1 = drone1
+        .with_send_timeout(Duration::from_millis(5))
+        .with_counters(counters.clone());
+
+    d1_packet_sender.send(create_packet(vec![0, 1, 2])).unwrap();
+    while !matches!(drone1.step(), DroneStep::Idle) {}
+
+    assert_eq!(d2_packet_receiver.len(), 1, "the full queue got another packet");
+    let nack = c0_packet_receiver.try_recv().expect("no nack for the congested fragment");
+    assert!(matches!(nack.pack_type, PacketType::Nack(Nack { nack_type: NackType::Dropped, .. })), "{:?}", nack.pack_type);
+    assert!(sc_receiver.try_iter().any(|event| matches!(event, DroneEvent::PacketDropped(_))), "the Sim Contr wasn't told");
+    assert_eq!(counters.congestion_drops.load(Ordering::Relaxed), 1);
+    println!("congested fragment nacked: {:?}", nack.routing_header.hops);
+}
+
+//The [[link]] tables give each drone the pdr of its own links only, and a link that always
+//drops loses the fragments sent on it, in that direction, while the other link of the drone
+//keeps its pdr of 0. Without the link pdr everything goes through again.
+pub fn test_link_pdr(){
+    #[derive(serde::Deserialize)]
+    struct LinkFile {
+        link: Vec<LinkPdrConfig>,
+    }
+    let file: LinkFile = toml::from_str(r#"
+        [[link]]
+        a = 9
+        b = 1
+        pdr = 1.0
+        [[link]]
+        a = 2
+        b = 3
+        pdr = 0.4
+    "#).unwrap();
+    let pdrs = link_pdr::link_pdrs(&file.link);
+    assert_eq!(link_pdr::pdrs_of(&pdrs, 1), HashMap::from([(9, 1.0)]));
+    assert_eq!(link_pdr::pdrs_of(&pdrs, 3), HashMap::from([(2, 0.4)]));
+
+    let (drone1, fixture) = drone_fixture([0, 9], 0.0);
+    let [c0_packet_receiver, s9_packet_receiver] = fixture.neighbour_recv;
+    let (d1_packet_sender, d1_skylink_sender) = (&fixture.packet_send, &fixture.skylink_send);
+    let mut drone1 = drone1.with_link_pdrs(link_pdr::pdrs_of(&pdrs, 1));
+    thread::spawn(move || drone1.run());
+
+    let mut to_server = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 10, 0, &FlowControlConfig::default());
+    let mut to_client = Transfer::new(6, vec![vec![9, 1, 0]], RoutingStrategy::SinglePath, 10, 0, &FlowControlConfig::default());
+    for packet in to_server.next_packets().into_iter().chain(to_client.next_packets()) {
+        d1_packet_sender.send(packet).unwrap();
+    }
+    let mut client_fragments = 0;
+    let mut nacks = 0;
+    while let Ok(packet) = c0_packet_receiver.recv_timeout(Duration::from_millis(300)) {
+        match packet.pack_type {
+            PacketType::MsgFragment(_) => client_fragments += 1,
+            PacketType::Nack(Nack { nack_type: NackType::Dropped, .. }) => nacks += 1,
+            _ => {}
+        }
+    }
+    assert_eq!(nacks, 10, "the link to 9 didn't drop everything");
+    assert_eq!(client_fragments, 10, "the link to 0 took the pdr of the other one");
+    assert!(s9_packet_receiver.try_recv().is_err());
+
+    d1_skylink_sender.send(SkyLinkCommand::SetLinkPdr(9, None)).unwrap();
+    let mut to_server = Transfer::new(7, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 10, 0, &FlowControlConfig::default());
+    for packet in to_server.next_packets() {
+        d1_packet_sender.send(packet).unwrap();
+    }
+    let mut delivered = 0;
+    while let Ok(_) = s9_packet_receiver.recv_timeout(Duration::from_millis(300)) {
+        delivered += 1;
+    }
+    assert_eq!(delivered, 10, "the link pdr wasn't removed");
+    println!("link 1-9 dropped {} fragments, 1-0 delivered {}, {} after removing the link pdr", nacks, client_fragments, delivered);
+}
+
+//A link with a packet rate and no queue: only the burst goes through at once, the other
+//fragments are nacked as Dropped and counted, and without the rate everything goes through again.
+pub fn test_link_rate(){
+    let (drone1, fixture) = drone_fixture([0, 9], 0.0);
+    let [c0_packet_receiver, s9_packet_receiver] = fixture.neighbour_recv;
+    let (d1_packet_sender, d1_skylink_sender) = (&fixture.packet_send, &fixture.skylink_send);
+    let counters = Arc::new(DroneCounters::default());
+    let rate = PacketRate { packets_per_sec: 1, burst: 3, queue: 0 };
+    let mut drone1 = drone1
+        .with_counters(counters.clone())
+        .with_link_rates(vec![(Some(9), rate)]);
+    thread::spawn(move || drone1.run());
+
+    let mut transfer = Transfer::new(5, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 10, 0, &FlowControlConfig::default());
+    for packet in transfer.next_packets() {
+        d1_packet_sender.send(packet).unwrap();
+    }
+    let forwarded = s9_packet_receiver.iter().take(3).count();
+    let mut nacked = 0;
+    while let Ok(packet) = c0_packet_receiver.recv_timeout(Duration::from_millis(300)) {
+        if let PacketType::Nack(Nack { nack_type: NackType::Dropped, .. }) = packet.pack_type {
+            nacked += 1;
+        }
+    }
+    assert_eq!(forwarded, 3, "the burst didn't go through");
+    assert!(s9_packet_receiver.try_recv().is_err(), "more than the burst went through");
+    assert_eq!(nacked, 7);
+    assert_eq!(counters.rate_limited.load(Ordering::Relaxed), 7);
+
+    d1_skylink_sender.send(SkyLinkCommand::SetLinkRate(Some(9), None)).unwrap();
+    let mut transfer = Transfer::new(6, vec![vec![0, 1, 9]], RoutingStrategy::SinglePath, 10, 0, &FlowControlConfig::default());
+    for packet in transfer.next_packets() {
+        d1_packet_sender.send(packet).unwrap();
+    }
+    let mut delivered = 0;
+    while let Ok(_) = s9_packet_receiver.recv_timeout(Duration::from_millis(300)) {
+        delivered += 1;
+    }
+    assert_eq!(delivered, 10, "the rate wasn't removed");
+    println!("{} fragments forwarded and {} nacked over the rate, {} after removing it", forwarded, nacked, delivered);
+}
+
+//Drone 1 takes 20 ms on every packet, and only 5 ms on the ones for drone 2: 5 fragments
+//towards 2 must go out a lot faster than 5 towards client 0.
+pub fn test_processing_delay(){
+    let (drone1, fixture) = drone_fixture([0, 2], 0.0);
+    let [c0_packet_receiver, d2_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let delay = |ms: u64| ProcessingDelay { delay: Duration::from_millis(ms), jitter: Duration::ZERO };
+    let mut drone1 = drone1
+        .with_processing_delays(vec![(None, delay(20)), (Some(2), delay(5))]);
+
+    //The packets wait in the drone's queue, not in a sleep: no step can take as long as a delay.
+    let mut forward = |hops: Vec<NodeId>, receiver: &Receiver<Packet>| -> Duration {
+        let start = Instant::now();
+        for _ in 0..5 {
+            d1_packet_sender.send(create_packet(hops.clone())).unwrap();
+        }
+        let mut received = 0;
+        while received < 5 && start.elapsed() < Duration::from_secs(1) {
+            let step = Instant::now();
+            drone1.step();
+            assert!(step.elapsed() < Duration::from_millis(5), "a step blocked for {:?}", step.elapsed());
+            received += receiver.try_iter().count();
+        }
+        assert_eq!(received, 5);
+        start.elapsed()
+    };
+    let to_drone2 = forward(vec![0, 1, 2], &d2_packet_receiver);
+    let to_client0 = forward(vec![2, 1, 0], &c0_packet_receiver);
+    assert!(to_drone2 >= Duration::from_millis(25) && to_drone2 < Duration::from_millis(100), "towards drone 2 in {:?}", to_drone2);
+    assert!(to_client0 >= Duration::from_millis(100), "towards client 0 in {:?}", to_client0);
+    println!("5 fragments towards drone 2 in {:?}, towards client 0 in {:?}", to_drone2, to_client0);
+}
+
+//A journal cut in the middle of a line, as a crash would leave it: the whole lines before the cut
+//must come back, and once repaired the file must read back with nothing cut off.
+pub fn test_journal_recovery(){
+    let file = std::env::temp_dir().join("skylink_test_journal.jsonl").to_string_lossy().to_string();
+    let mut timeline = Timeline::default();
+    {
+        let mut journal = EventJournal::create(&file, Duration::from_secs(60)).unwrap();
+        for _ in 0..10 {
+            journal.append_event(&DroneEvent::PacketSent(create_packet(vec![0, 1, 2])), std::time::SystemTime::now());
+        }
+        timeline.add(TimelineKind::Crash, Some(1), "drone 1 crashed".to_string());
+        journal.append_timeline(&timeline);
+        journal.append_timeline(&timeline);
+        journal.sync();
+    }
+    let mut torn = fs::read(&file).unwrap();
+    torn.extend_from_slice(b"{\"record\":\"sent\",\"unix_ms\":17");
+    fs::write(&file, torn).unwrap();
+
+    let recovered = journal::recover(&file).unwrap();
+    for line in recovered.describe() {
+        println!("{}", line);
+    }
+    assert_eq!(recovered.records.len(), 12, "start, 10 events and 1 timeline entry");
+    assert_eq!(recovered.timeline().len(), 1);
+    assert!(recovered.torn_bytes > 0, "the torn line wasn't noticed");
+    journal::repair(&file, &recovered).unwrap();
+    let repaired = journal::recover(&file).unwrap();
+    assert_eq!(repaired.records.len(), 12);
+    assert_eq!(repaired.torn_bytes, 0, "the torn line is still there after the repair");
+    let _ = fs::remove_file(&file);
+}
+
+//Three sessions on the route 0 1 2 9: the first one gets there, fragment 1 of the second is dropped
+//by drone 1 and never sent again, the third can't get past drone 2 because server 9 had crashed.
+pub fn test_postmortem(){
+    let fragment = |session_id: u64, index: u64, hop_index: usize| Packet {
+        pack_type: PacketType::MsgFragment(Fragment { fragment_index: index, total_n_fragments: 2, length: 128, data: [1; 128] }),
+        routing_header: SourceRoutingHeader { hop_index, hops: vec![0, 1, 2, 9] },
+        session_id,
+    };
+    let nack = |session_id: u64, index: u64, hops: Vec<NodeId>, nack_type: NackType| Packet {
+        pack_type: PacketType::Nack(Nack { fragment_index: index, nack_type }),
+        routing_header: SourceRoutingHeader { hop_index: 1, hops },
+        session_id,
+    };
+    let sent = |unix_ms: u64, packet: Packet| JournalRecord::Sent { unix_ms, packet };
+    let mut records = vec![
+        JournalRecord::Start { unix_ms: 1000 },
+        JournalRecord::Timeline { entry: TimelineEntry { at: Duration::from_millis(500), kind: TimelineKind::Crash, node: Some(9), label: "drone 9 crashed".to_string() } },
+    ];
+    for index in 0..2 {
+        records.push(sent(2000, fragment(1, index, 2)));
+        records.push(sent(2001, fragment(1, index, 3)));
+    }
+    records.push(sent(2000, fragment(2, 0, 2)));
+    records.push(sent(2001, fragment(2, 0, 3)));
+    records.push(JournalRecord::Dropped { unix_ms: 2002, packet: fragment(2, 1, 2) });
+    records.push(sent(2002, nack(2, 1, vec![1, 0], NackType::Dropped)));
+    for index in 0..2 {
+        records.push(sent(2000, fragment(3, index, 2)));
+        records.push(sent(2001, nack(3, index, vec![2, 1, 0], NackType::ErrorInRouting(9))));
+    }
+
+    let (stories, crashes) = postmortem::analyze(&RecoveredJournal { records, valid_bytes: 0, torn_bytes: 0 });
+    let failed = stories.iter().filter(|story| story.failed()).map(|story| story.session_id).collect::<Vec<u64>>();
+    assert_eq!(failed, vec![2, 3]);
+    let diagnosis = stories.iter().map(|story| story.describe(&crashes).join("\n")).collect::<Vec<String>>();
+    for lines in diagnosis.iter() {
+        println!("{}", lines);
+    }
+    assert!(diagnosis[1].contains("cause: dropped by drone 1, not sent again (fragments [1])"), "{}", diagnosis[1]);
+    assert!(diagnosis[2].contains("cause: drone 2 couldn't reach 9, which had crashed, not sent again (fragments [0, 1])"), "{}", diagnosis[2]);
+}
+
+//Two segments joined by the link 3 - 4: every node must end up in one of them, and an ack for
+//server 9 (east) that drone 2 (west) can't forward must get there through the coordinator.
+pub fn test_segments(){
+    let (mut coordinator, handles) = initialize_segmented("inputs/input_segments.toml");
+    assert_eq!(coordinator.segment_of(0), coordinator.segment_of(3));
+    assert_eq!(coordinator.segment_of(4), coordinator.segment_of(9));
+    assert_ne!(coordinator.segment_of(3), coordinator.segment_of(4));
+
+    let west = coordinator.segment_of(0).unwrap();
+    coordinator.segments[west].sim_contr.inject_packet(Packet {
+        pack_type: PacketType::Ack(wg_2024::packet::Ack { fragment_index: 0 }),
+        routing_header: SourceRoutingHeader { hop_index: 2, hops: vec![0, 1, 2, 5, 9] },
+        session_id: 1,
+    });
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while coordinator.relayed == 0 && Instant::now() < deadline {
+        coordinator.process_events();
+        thread::sleep(Duration::from_millis(10));
+    }
+    for line in coordinator.describe() {
+        println!("{}", line);
+    }
+    assert_eq!(coordinator.relayed, 1, "the shortcut didn't cross to the other segment");
+    for segment in coordinator.segments.iter() {
+        assert_eq!(segment.sim_contr.dead_letters.total, 0, "segment {} has dead letters", segment.name);
+    }
+
+    coordinator.shutdown();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+//A pdr of 0 never drops, a pdr of 1 always does, and 0.05 drops about one fragment out of 20
+//(the old pdr in percent rounded it, and 1.0 still let one fragment out of 101 through).
+pub fn test_pdr_values(){
+    const FRAGMENTS: usize = 10000;
+    for (pdr, min_drops, max_drops) in [(0.0, 0, 0), (1.0, FRAGMENTS, FRAGMENTS), (0.05, 400, 600)] {
+        let (drone1, fixture) = drone_fixture([0, 2], pdr);
+        let [c0_packet_receiver, d2_packet_receiver] = fixture.neighbour_recv;
+        let mut drone1 = drone1.with_seed(42);
+        for _ in 0..FRAGMENTS {
+            fixture.packet_send.send(create_packet(vec![0, 1, 2])).unwrap();
+        }
+        while !matches!(drone1.step(), DroneStep::Idle) {}
+
+        let drops = c0_packet_receiver.try_iter().filter(|packet| matches!(packet.pack_type, PacketType::Nack(Nack { nack_type: NackType::Dropped, .. }))).count();
+        let forwarded = d2_packet_receiver.try_iter().count();
+        assert_eq!(drops + forwarded, FRAGMENTS, "pdr {}: some fragments were neither dropped nor forwarded", pdr);
+        assert!((min_drops..=max_drops).contains(&drops), "pdr {}: {} fragments dropped, expected {} to {}", pdr, drops, min_drops, max_drops);
+        println!("pdr {}: {} of {} fragments dropped", pdr, drops, FRAGMENTS);
+    }
+}
+
+//The same seed and inputs must give the same events, and another seed (with a pdr to roll)
+//must not: otherwise the verifier wouldn't catch anything.
+pub fn test_replay_determinism(){
+    let config_toml = fs::read_to_string("inputs/input_multi_client.toml").unwrap();
+    let inputs = vec![
+        ReplayInput::SetPdr { drone: 5, pdr: 0.3 },
+        ReplayInput::Flood { client: 20 },
+        ReplayInput::Message { route: vec![20, 1, 2, 5, 8, 9, 24], fragments: 50 },
+        ReplayInput::Crash { drone: 5 },
+        ReplayInput::Message { route: vec![20, 1, 2, 5, 8, 9, 24], fragments: 10 },
+    ];
+    let events = replay::run_deterministic(&config_toml, 42, &inputs).unwrap();
+    let trace = ReplayTrace { config_toml: config_toml.clone(), seed: 42, inputs: inputs.clone(), events };
+    assert!(replay::verify(&trace).unwrap().is_none(), "the same run gave other events");
+
+    let other = ReplayTrace { seed: 43, ..trace.clone() };
+    match replay::verify(&other).unwrap() {
+        Some(divergence) => println!("seed 43 diverges as expected, {}", divergence.describe()),
+        None => panic!("seed 43 gave the same events as seed 42"),
+    }
+    println!("{} events replayed the same way", trace.events.len());
+}
+
+//Drone 1 of the chain is captured while a message goes through it, then the capture is replayed
+//on a drone alone: with pdr 0 it must do exactly what drone 1 did in the simulation.
+pub fn test_drone_capture(){
+    let (mut sim_contr, handles) = initialize("inputs/input_generic_fragment_forward.toml");
+    let file = std::env::temp_dir().join("skylink_capture_drone_1.json");
+    let file = file.to_str().unwrap();
+    assert!(sim_contr.start_capture(1, file));
+    sim_contr.send_message(vec![0, 1, 2, 3], 20, None);
+    let deadline = Instant::now() + Duration::from_millis(500);
+    while Instant::now() < deadline {
+        sim_contr.process_events();
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(sim_contr.stop_capture(1));
+    sim_contr.shutdown();
+    drop(sim_contr);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let capture = DroneCapture::load(file).unwrap();
+    assert!(!capture.inputs.is_empty(), "nothing captured");
+    if let Some(divergence) = replay::first_divergence(&capture.events, &capture.replay(0)) {
+        panic!("the replay of drone 1 diverges, {}", divergence.describe());
+    }
+    println!("{} inputs and {} events of drone 1 replayed the same way", capture.inputs.len(), capture.events.len());
+}
+
+//Server 3 is an ack sink: every fragment reaching it should come back to client 0 as an ack,
+//except the ones lost by the sink and the ones dropped by drone 2 on the way.
+pub fn test_ack_sink(){
+    let (mut sim_contr, handles) = initialize("inputs/input_ack_sink.toml");
+    let session_id = sim_contr.send_message(vec![0, 1, 2, 3], 100, Some(0));
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while Instant::now() < deadline {
+        sim_contr.process_events();
+        thread::sleep(Duration::from_millis(10));
+    }
+    let hops = sim_contr.sessions.sessions.get(&session_id).map(|session| session.hops.clone()).unwrap_or_default();
+    let delivered = hops.iter().filter(|hop| hop.to == 3 && hop.packet_type.starts_with("fragment")).count();
+    let acked = hops.iter().filter(|hop| hop.to == 0 && hop.packet_type.starts_with("ack")).count();
+    println!("{} fragments delivered, {} acks back to the client", delivered, acked);
+    println!("{}", sim_contr.ack_sinks.describe());
+    assert!(acked > 0 && acked <= delivered, "the acks don't match the fragments delivered");
+    sim_contr.shutdown();
+    drop(sim_contr);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+pub fn test_sla_probe(){
+    let (mut sim_contr, handles) = initialize("inputs/input_ack_sink.toml");
+    assert!(sim_contr.add_sla_probe(SlaProbeConfig { client: 0, server: 3, period_ms: 50, target_rtt_ms: None }));
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while Instant::now() < deadline {
+        sim_contr.process_events();
+        thread::sleep(Duration::from_millis(10));
+    }
+    let pair = &sim_contr.sla.pairs[0];
+    println!("{}", pair.describe());
+    assert!(pair.acked > 0, "no probe was acked");
+    assert!(pair.acked + pair.failed <= pair.sent, "more answers than probes");
+    //The probes aren't messages of the clients.
+    assert!(sim_contr.sessions.sessions.keys().all(|session_id| !sim_contr.sla.is_probe(*session_id)));
+    sim_contr.shutdown();
+    drop(sim_contr);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+pub fn test_node_groups(){
+    let (mut sim_contr, handles) = initialize("inputs/input_faults.toml");
+    let detour = NodeGroup::Defined("detour".to_string());
+    assert_eq!(sim_contr.group_members(&detour), vec![4, 5, 6]);
+    sim_contr.save_groups("groups_test.toml");
+    sim_contr.remove_group("detour");
+    assert!(sim_contr.group_members(&detour).is_empty(), "a removed group still has members");
+    assert!(sim_contr.load_groups("groups_test.toml"));
+    assert_eq!(sim_contr.group_members(&detour), vec![4, 5, 6]);
+    sim_contr.set_group_pdr("detour", 0.5);
+    assert!([4, 5, 6].iter().all(|id| sim_contr.node_pdr.get(id) == Some(&0.5)));
+    sim_contr.crash_group("detour");
+    assert!(sim_contr.group_members(&detour).is_empty(), "the crashed members are still up");
+    let _ = fs::remove_file("groups_test.toml");
+    sim_contr.shutdown();
+    drop(sim_contr);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+pub fn test_command_audit(){
+    let (mut sim_contr, handles) = initialize("inputs/input_ack_sink.toml");
+    sim_contr.set_command_origin(CommandOrigin::Gui);
+    sim_contr.set_pdr(1, 0.3);
+    sim_contr.schedule_fault(&FaultConfig { at_s: 0.0, fault: Fault::Crash { drone: 2 } });
+    sim_contr.process_events();
+    for entry in sim_contr.audit.query(1, None).iter().chain(sim_contr.audit.query(2, None).iter()) {
+        println!("drone {}: {}", entry.node, entry.describe());
+    }
+    let from_gui = sim_contr.audit.query(1, Some(CommandOrigin::Gui));
+    assert!(from_gui.iter().any(|entry| entry.command == "SetPacketDropRate(0.3)"), "the pdr isn't in the audit of drone 1");
+    //The crash of the fault, and the RemoveSender it makes drone 1 get.
+    assert!(sim_contr.audit.query(2, Some(CommandOrigin::Chaos)).iter().any(|entry| entry.command == "Crash"));
+    assert!(sim_contr.audit.query(1, Some(CommandOrigin::Chaos)).iter().any(|entry| entry.command == "RemoveSender(2)"));
+    sim_contr.shutdown();
+    drop(sim_contr);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
 pub fn test_drone_commands(){
     let mut handles = Vec::new();
 
@@ -431,6 +1827,79 @@ pub fn test_drone_commands(){
     }
 }
 
+//Counts what the drone does, and scribbles on the fragments it forwards, like a faulty node would.
+struct TamperingHooks {
+    received: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl DroneHooks for TamperingHooks {
+    fn on_packet_received(&mut self, _drone_id: NodeId, _packet: &mut Packet) -> bool {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    fn on_forward(&mut self, _drone_id: NodeId, _next_hop: NodeId, packet: &mut Packet) {
+        if let PacketType::MsgFragment(fragment) = &mut packet.pack_type {
+            fragment.data[0] = 0xFF;
+        }
+    }
+
+    fn on_drop(&mut self, _drone_id: NodeId, _nack: &Packet) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+//Chain 0 -> 1 -> 2 -> 3, drone 1 has the hooks and drone 2 drops half of the fragments:
+//client 3 should only get tampered fragments, and client 0 a nack for every drop seen by the hooks of drone 2.
+pub fn test_drone_hooks(){
+    let (drone1, fixture) = drone_fixture([0, 2], 0.0);
+    let [c0_packet_receiver, d2_packet_receiver] = fixture.neighbour_recv;
+    let d1_packet_sender = &fixture.packet_send;
+    let (c3_packet_sender, c3_packet_receiver) = unbounded::<Packet>();
+    let (sc_sender, _sc_receiver) = unbounded();
+    let (_d2_command_sender, d2_command_receiver) = unbounded::<DroneCommand>();
+
+    let received = Arc::new(AtomicU64::new(0));
+    let dropped = Arc::new(AtomicU64::new(0));
+
+    let mut drone1 = drone1
+        .with_hooks(Box::new(TamperingHooks { received: received.clone(), dropped: Arc::new(AtomicU64::new(0)) }));
+    let mut drone2 = SkyLinkDrone::new(
+        2,
+        sc_sender,
+        d2_command_receiver,
+        d2_packet_receiver,
+        HashMap::from([(1, d1_packet_sender.clone()), (3, c3_packet_sender)]),
+        0.5)
+        .with_hooks(Box::new(TamperingHooks { received: Arc::new(AtomicU64::new(0)), dropped: dropped.clone() }));
+    thread::spawn(move || drone1.run());
+    thread::spawn(move || drone2.run());
+
+    let n_packets = 100;
+    for _i in 0..n_packets {
+        d1_packet_sender.send(create_packet(vec![0,1,2,3])).unwrap();
+    }
+
+    let mut delivered = 0;
+    let mut tampered = 0;
+    while let Ok(packet) = c3_packet_receiver.recv_timeout(Duration::from_millis(500)) {
+        delivered += 1;
+        if let PacketType::MsgFragment(fragment) = packet.pack_type {
+            if fragment.data[0] == 0xFF {
+                tampered += 1;
+            }
+        }
+    }
+    let mut nacks = 0;
+    while let Ok(_) = c0_packet_receiver.recv_timeout(Duration::from_millis(100)) {
+        nacks += 1;
+    }
+    println!("drone 1 received {} packets (the nacks coming back too)", received.load(Ordering::Relaxed));
+    println!("client 3 got {} fragments, {} of them tampered", delivered, tampered);
+    println!("drone 2 dropped {} fragments, client 0 got {} nacks", dropped.load(Ordering::Relaxed), nacks);
+}
+
 //Use star configuration and test busy network with a full route around the configuration, sending u64::max messages.
 pub fn test_busy_network(){
     let (_sim_contr, clients, mut handles) = test_initialize("inputs/input_star.toml");
@@ -456,3 +1925,209 @@ pub fn test_busy_network(){
         i.join().unwrap();
     }
 }
+
+//Benchmarks: they don't print the packets, they only measure how long the drones take to move them.
+//Run them with `cargo run --release`, the debug build is way slower.
+
+/// Measures the throughput of a chain of two drones, sending fragments from client 0 to client 3.
+pub fn bench_fragment_forward(){
+    let (_sim_contr, clients, _handles) = test_initialize("inputs/input_generic_fragment_forward.toml");
+    //The Sim Contr is kept alive, otherwise the drones can't send their events.
+
+    let n_packets = 100_000;
+    let sender = clients.get(0).unwrap().client_send.get(&1).unwrap().clone();
+    let client_receiver = clients.get(1).unwrap().client_recv.clone();
+    let packet = create_packet(vec![0,1,2,3]);
+
+    let start = Instant::now();
+    for _i in 0..n_packets {
+        sender.send(packet.clone()).unwrap();
+    }
+    for _i in 0..n_packets {
+        client_receiver.recv().unwrap();
+    }
+    let elapsed = start.elapsed();
+    println!("{} fragments forwarded in {:?} ({:.0} fragments/s)", n_packets, elapsed, n_packets as f64 / elapsed.as_secs_f64());
+}
+
+/// Measures the flood fan-out on the star configuration, counting the flood responses that get back to client 0.
+pub fn bench_star_flood(){
+    let (_sim_contr, clients, _handles) = test_initialize("inputs/input_star.toml");
+
+    let n_floods = 10_000;
+    let sender = clients.get(0).unwrap().client_send.get(&1).unwrap().clone();
+    let client_receiver = clients.get(0).unwrap().client_recv.clone();
+
+    let start = Instant::now();
+    for flood_id in 0..n_floods {
+        let flood_request = wg_2024::packet::FloodRequest{
+            flood_id,
+            initiator_id: 0,
+            path_trace: vec![],
+        };
+        let packet = Packet{
+            pack_type: PacketType::FloodRequest(flood_request),
+            routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+            session_id: 0,
+        };
+        sender.send(packet).unwrap();
+    }
+    let mut responses = 0;
+    while let Ok(_) = client_receiver.recv_timeout(Duration::from_secs(1)) {
+        responses += 1;
+    }
+    //The last second is spent waiting for responses that don't arrive anymore.
+    let elapsed = start.elapsed() - Duration::from_secs(1);
+    println!("{} floods, {} responses in {:?} ({:.0} floods/s)", n_floods, responses, elapsed, n_floods as f64 / elapsed.as_secs_f64());
+}
+
+/// Same as bench_star_flood on the butterfly configuration, where every flood crosses each drone
+/// from more than one side (and most copies end as duplicates).
+pub fn bench_butterfly_flood(){
+    let (_sim_contr, clients, _handles) = test_initialize("inputs/input_butterfly.toml");
+
+    let n_floods = 10_000;
+    let sender = clients.get(0).unwrap().client_send.get(&1).unwrap().clone();
+    let client_receiver = clients.get(0).unwrap().client_recv.clone();
+
+    let start = Instant::now();
+    for flood_id in 0..n_floods {
+        let flood_request = wg_2024::packet::FloodRequest{
+            flood_id,
+            initiator_id: 0,
+            path_trace: vec![],
+        };
+        let packet = Packet{
+            pack_type: PacketType::FloodRequest(flood_request),
+            routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+            session_id: 0,
+        };
+        sender.send(packet).unwrap();
+    }
+    let mut responses = 0;
+    while let Ok(_) = client_receiver.recv_timeout(Duration::from_secs(1)) {
+        responses += 1;
+    }
+    let elapsed = start.elapsed() - Duration::from_secs(1);
+    println!("{} floods, {} responses in {:?} ({:.0} floods/s)", n_floods, responses, elapsed, n_floods as f64 / elapsed.as_secs_f64());
+}
+
+/// A single drone with a lot of neighbours and batched events: every flood goes out to all of
+/// them but one, so this is the fan-out alone, without the rest of the network in the way.
+pub fn bench_flood_fanout(){
+    const N_NEIGHBOURS: usize = 64;
+    let n_floods = 5_000;
+    let (batch_sender, batch_receiver) = unbounded();
+    let (drone1, fixture) = drone_fixture::<N_NEIGHBOURS>(std::array::from_fn(|i| 10 + i as NodeId), 0.0);
+    let (d1_packet_sender, receivers) = (&fixture.packet_send, &fixture.neighbour_recv);
+    let mut drone1 = drone1
+        .with_event_batching(batch_sender, 256, Duration::from_millis(5));
+    thread::spawn(move || drone1.run());
+    //The Sim Contr would read the batches, here they're only thrown away.
+    thread::spawn(move || while let Ok(_) = batch_receiver.recv() {});
+
+    let start = Instant::now();
+    for flood_id in 0..n_floods {
+        let flood_request = wg_2024::packet::FloodRequest{
+            flood_id,
+            initiator_id: 10,
+            path_trace: vec![(10, NodeType::Client)],
+        };
+        d1_packet_sender.send(Packet{
+            pack_type: PacketType::FloodRequest(flood_request),
+            routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+            session_id: 0,
+        }).unwrap();
+    }
+    //Every neighbour but the one they come from (the first) gets all of them.
+    for receiver in receivers.iter().skip(1) {
+        for _i in 0..n_floods {
+            receiver.recv_timeout(Duration::from_secs(5)).expect("a flood didn't get to a neighbour");
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{} floods to {} neighbours in {:?} ({:.0} floods/s)",
+        n_floods, N_NEIGHBOURS - 1, elapsed, n_floods as f64 / elapsed.as_secs_f64()
+    );
+}
+
+/// Charts how the flood time and the delivery latency grow with the size of the network, on the
+/// generated families (see topologies.rs). One CSV row per network, to paste in a spreadsheet.
+pub fn bench_scaling(){
+    let mut topologies = Vec::new();
+    for side in [2, 4, 6, 8, 10, 12, 15] {
+        topologies.push(topologies::grid(side, side));
+    }
+    for depth in 1..=4 {
+        topologies.push(topologies::ring_of_trees(6, 2, depth));
+    }
+    for n in [16, 32, 64, 128, 250] {
+        topologies.push(topologies::expander(n, 4, 42));
+    }
+
+    println!("topology,drones,diameter,flood_responses,flood_ms,latency_us");
+    for topology in topologies {
+        let (drones, diameter) = (topology.config.drone.len(), topology.diameter());
+        let (responses, flood_time, latency) = measure_scaling(&topology);
+        println!(
+            "{},{},{},{},{:.1},{:.1}",
+            topology.name,
+            drones,
+            diameter,
+            responses,
+            flood_time.as_secs_f64() * 1000.0,
+            latency.as_secs_f64() * 1_000_000.0
+        );
+    }
+}
+
+//Floods from the sender until the responses stop coming, then sends fragments one at a time
+//on the longest route. Returns (responses, time of the last response, mean delivery latency).
+fn measure_scaling(topology: &GeneratedTopology) -> (u64, Duration, Duration) {
+    let (sim_contr, clients, _handles) = test_initialize_config(topology.config.clone());
+    let sender = clients.iter().find(|client| client.id == SENDER).unwrap();
+    let receiver = clients.iter().find(|client| client.id == RECEIVER).unwrap();
+    let first_hop = sender.client_send.values().next().unwrap().clone();
+
+    let flood_request = wg_2024::packet::FloodRequest{
+        flood_id: 1,
+        initiator_id: SENDER,
+        path_trace: vec![(SENDER, wg_2024::packet::NodeType::Client)],
+    };
+    let start = Instant::now();
+    first_hop.send(Packet{
+        pack_type: PacketType::FloodRequest(flood_request),
+        routing_header: SourceRoutingHeader { hop_index: 0, hops: vec![] },
+        session_id: 0,
+    }).unwrap();
+    let mut responses = 0;
+    let mut last_response = Duration::ZERO;
+    while sender.client_recv.recv_timeout(Duration::from_millis(500)).is_ok() {
+        responses += 1;
+        last_response = start.elapsed();
+    }
+    //The receiver got the flood too, its copies don't matter here.
+    while receiver.client_recv.try_recv().is_ok() {}
+
+    let n_packets = 200;
+    let packet = create_packet(topology.route.clone());
+    let mut total = Duration::ZERO;
+    for _i in 0..n_packets {
+        let sent = Instant::now();
+        first_hop.send(packet.clone()).unwrap();
+        receiver.client_recv.recv_timeout(Duration::from_secs(1)).unwrap();
+        total += sent.elapsed();
+    }
+
+    //The drones forget each other and crash, so their threads end once the clients are gone.
+    for (id, command_send) in sim_contr.command_send.iter() {
+        for drone in topology.config.drone.iter().filter(|drone| drone.id == *id) {
+            for neighbour in drone.connected_node_ids.iter() {
+                let _ = command_send.send(DroneCommand::RemoveSender(*neighbour));
+            }
+        }
+        let _ = command_send.send(DroneCommand::Crash);
+    }
+    (responses, last_response, total / n_packets)
+}
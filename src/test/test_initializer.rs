@@ -10,7 +10,11 @@ use wg_2024::packet::{Packet};
 use crate::skylink_drone::drone::SkyLinkDrone;
 
 pub fn test_initialize(file: &str) -> (MySimContr, Vec<MyClient>, Vec<JoinHandle<()>>) {
-    let config = parse_config(file);
+    test_initialize_config(parse_config(file))
+}
+
+//For the networks that aren't in a file, like the generated ones.
+pub fn test_initialize_config(config: Config) -> (MySimContr, Vec<MyClient>, Vec<JoinHandle<()>>) {
     let mut handles = Vec::new();
     //I'll return the handles of the threads, and join them to the main thread.
 
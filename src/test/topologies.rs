@@ -0,0 +1,143 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use wg_2024::config::{Client, Config, Drone};
+use wg_2024::network::NodeId;
+
+//Families of networks whose size can be turned up, to see how the floods and the deliveries
+//scale. The drones are numbered from 1, and two clients are added at the ends of the longest
+//shortest path: the sender (id 0) and the receiver (id 255). Both are clients because the
+//test initializer only gives a channel to the clients.
+pub struct GeneratedTopology {
+    pub name: String,
+    pub config: Config,
+    pub route: Vec<NodeId>, //From the sender to the receiver, both included.
+}
+
+pub const SENDER: NodeId = 0;
+pub const RECEIVER: NodeId = 255;
+
+//rows x cols drones, each linked to the ones on its sides. The diameter is rows + cols - 2.
+pub fn grid(rows: usize, cols: usize) -> GeneratedTopology {
+    let id = |row: usize, col: usize| (row * cols + col + 1) as NodeId;
+    let mut graph = empty_graph(rows * cols);
+    for row in 0..rows {
+        for col in 0..cols {
+            if col + 1 < cols {
+                link(&mut graph, id(row, col), id(row, col + 1));
+            }
+            if row + 1 < rows {
+                link(&mut graph, id(row, col), id(row + 1, col));
+            }
+        }
+    }
+    build(format!("grid {}x{}", rows, cols), graph)
+}
+
+//n_trees roots on a ring, each one the root of a full tree with that branching and depth.
+//The diameter grows with the depth twice and with the ring once.
+pub fn ring_of_trees(n_trees: usize, branching: usize, depth: usize) -> GeneratedTopology {
+    let tree_size = (0..=depth).map(|level| branching.pow(level as u32)).sum::<usize>();
+    let mut graph = empty_graph(n_trees * tree_size);
+    for tree in 0..n_trees {
+        let root = tree * tree_size + 1;
+        let next_root = (tree + 1) % n_trees * tree_size + 1;
+        if n_trees > 1 {
+            link(&mut graph, root as NodeId, next_root as NodeId);
+        }
+        //In a full tree stored by level, the children of i (from 0) are i * branching + 1 and on.
+        for index in 0..tree_size {
+            for child in (index * branching + 1)..=(index * branching + branching) {
+                if child < tree_size {
+                    link(&mut graph, (root + index) as NodeId, (root + child) as NodeId);
+                }
+            }
+        }
+    }
+    build(format!("ring of {} trees ({}^{})", n_trees, branching, depth), graph)
+}
+
+//A ring with degree - 2 random chords per drone: a sparse network with a small diameter
+//(around log n). The same seed gives the same network.
+pub fn expander(n: usize, degree: usize, seed: u64) -> GeneratedTopology {
+    let mut rng = fastrand::Rng::with_seed(seed);
+    let mut graph = empty_graph(n);
+    for i in 0..n {
+        link(&mut graph, (i + 1) as NodeId, ((i + 1) % n + 1) as NodeId);
+    }
+    for i in 1..=n {
+        let mut tries = 0;
+        while graph[&(i as NodeId)].len() < degree && tries < 10 * degree {
+            tries += 1;
+            let other = rng.usize(1..=n) as NodeId;
+            if other != i as NodeId && graph[&other].len() < degree && !graph[&other].contains(&(i as NodeId)) {
+                link(&mut graph, i as NodeId, other);
+            }
+        }
+    }
+    build(format!("expander {} (degree {})", n, degree), graph)
+}
+
+fn empty_graph(n_drones: usize) -> HashMap<NodeId, Vec<NodeId>> {
+    assert!(n_drones <= 254, "only 254 drones fit between the two clients");
+    (1..=n_drones).map(|id| (id as NodeId, Vec::new())).collect()
+}
+
+fn link(graph: &mut HashMap<NodeId, Vec<NodeId>>, a: NodeId, b: NodeId) {
+    if a != b && !graph[&a].contains(&b) {
+        graph.get_mut(&a).unwrap().push(b);
+        graph.get_mut(&b).unwrap().push(a);
+    }
+}
+
+fn build(name: String, mut graph: HashMap<NodeId, Vec<NodeId>>) -> GeneratedTopology {
+    //The ends of the longest shortest path: the farthest drone from drone 1, and the farthest from that one.
+    let (first, _) = farthest(&graph, 1);
+    let (last, path) = farthest(&graph, first);
+    graph.get_mut(&first).unwrap().push(SENDER);
+    graph.get_mut(&last).unwrap().push(RECEIVER);
+
+    let mut ids = graph.keys().copied().collect::<Vec<NodeId>>();
+    ids.sort();
+    let config = Config {
+        drone: ids
+            .iter()
+            .map(|id| Drone { id: *id, connected_node_ids: graph[id].clone(), pdr: 0.0 })
+            .collect(),
+        client: vec![
+            Client { id: SENDER, connected_drone_ids: vec![first] },
+            Client { id: RECEIVER, connected_drone_ids: vec![last] },
+        ],
+        server: Vec::new(),
+    };
+    let route = std::iter::once(SENDER).chain(path).chain(std::iter::once(RECEIVER)).collect();
+    GeneratedTopology { name, config, route }
+}
+
+//The drone farthest from `from` with the path to it (from included).
+fn farthest(graph: &HashMap<NodeId, Vec<NodeId>>, from: NodeId) -> (NodeId, Vec<NodeId>) {
+    let mut previous = HashMap::new();
+    let mut seen = HashSet::from([from]);
+    let mut queue = VecDeque::from([from]);
+    let mut last = from;
+    while let Some(node) = queue.pop_front() {
+        last = node;
+        for next in graph[&node].iter() {
+            if seen.insert(*next) {
+                previous.insert(*next, node);
+                queue.push_back(*next);
+            }
+        }
+    }
+    let mut path = vec![last];
+    while let Some(node) = previous.get(path.last().unwrap()) {
+        path.push(*node);
+    }
+    path.reverse();
+    (last, path)
+}
+
+impl GeneratedTopology {
+    //In hops between the two clients.
+    pub fn diameter(&self) -> usize {
+        self.route.len() - 1
+    }
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+
+//Enough for hours of crashes and alerts, the packets aren't in here.
+const MAX_TIMELINE_ENTRIES: usize = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineKind {
+    Crash,
+    Spawn,
+    Reboot,
+    Flood,
+    Fault,
+    Alert,
+    Step, //A mark("...") of a scenario.
+    Cancel, //A message given up by its client.
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub at: Duration, //From the start of the simulation.
+    pub kind: TimelineKind,
+    pub node: Option<NodeId>,
+    pub label: String,
+}
+
+impl TimelineEntry {
+    pub fn describe(&self) -> String {
+        format!("{:.1}s {:?}: {}", self.at.as_secs_f64(), self.kind, self.label)
+    }
+}
+
+//The notable things of the run, in order, without the packets: what the GUI draws on the
+//timeline, so the crashes, floods and alerts can be seen one after the other at a glance.
+#[derive(Debug)]
+pub struct Timeline {
+    started: Instant,
+    pub(crate) entries: Vec<TimelineEntry>,
+    pub(crate) total: usize, //Entries ever added, the oldest ones aren't in entries anymore.
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline { started: Instant::now(), entries: Vec::new(), total: 0 }
+    }
+}
+
+impl Timeline {
+    pub fn add(&mut self, kind: TimelineKind, node: Option<NodeId>, label: String) {
+        self.entries.push(TimelineEntry { at: self.started.elapsed(), kind, node, label });
+        self.total += 1;
+        if self.entries.len() > MAX_TIMELINE_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn save(&self, file: &str) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.entries).map_err(io::Error::other)?;
+        fs::write(file, json)
+    }
+}
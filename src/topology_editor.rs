@@ -0,0 +1,315 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::rc::Rc;
+use eframe::egui::{self, Color32, Context, Vec2};
+use eframe::{App, Frame, NativeOptions};
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::network::NodeId;
+
+const NODE_RADIUS: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditorNodeType {
+    Drone,
+    Client,
+    Server,
+}
+
+struct EditorNode {
+    id: NodeId,
+    node_type: EditorNodeType,
+    position: Vec2,
+    pdr: f32,
+}
+
+//A network drawn by hand before anything is started: no drone, no channel and no Sim Contr
+//exist until Launch, which closes the editor and gives the config to the initializer.
+pub struct TopologyEditor {
+    nodes: Vec<EditorNode>,
+    links: BTreeSet<(NodeId, NodeId)>, //The smaller id first, a link is always both ways.
+    selected: Option<usize>,
+    dragging: Option<usize>,
+    save_file: String,
+    message: String, //The result of the last action, or why the config can't be launched.
+    launched: Rc<RefCell<Option<Config>>>,
+}
+
+impl TopologyEditor {
+    fn new(config: Option<Config>, save_file: String, launched: Rc<RefCell<Option<Config>>>) -> Self {
+        let mut editor = TopologyEditor {
+            nodes: Vec::new(),
+            links: BTreeSet::new(),
+            selected: None,
+            dragging: None,
+            save_file,
+            message: String::new(),
+            launched,
+        };
+        if let Some(config) = config {
+            let nodes = config.drone.iter().map(|drone| (drone.id, EditorNodeType::Drone, drone.pdr, &drone.connected_node_ids))
+                .chain(config.client.iter().map(|client| (client.id, EditorNodeType::Client, 0.0, &client.connected_drone_ids)))
+                .chain(config.server.iter().map(|server| (server.id, EditorNodeType::Server, 0.0, &server.connected_drone_ids)))
+                .collect::<Vec<_>>();
+            //A loaded network starts on a circle, the nodes can be moved from there.
+            for (index, (id, node_type, pdr, neighbours)) in nodes.iter().enumerate() {
+                let angle = index as f32 / nodes.len() as f32 * std::f32::consts::TAU;
+                editor.nodes.push(EditorNode {
+                    id: *id,
+                    node_type: *node_type,
+                    position: Vec2::new(450.0 + 250.0 * angle.cos(), 350.0 + 250.0 * angle.sin()),
+                    pdr: *pdr,
+                });
+                for neighbour in neighbours.iter() {
+                    editor.links.insert(((*id).min(*neighbour), (*id).max(*neighbour)));
+                }
+            }
+        }
+        editor
+    }
+
+    fn free_id(&self) -> Option<NodeId> {
+        (0..=NodeId::MAX).find(|id| self.nodes.iter().all(|node| node.id != *id))
+    }
+
+    fn add_node(&mut self, node_type: EditorNodeType, position: Vec2) {
+        let Some(id) = self.free_id() else {
+            self.message = "no free id left".to_string();
+            return;
+        };
+        self.nodes.push(EditorNode { id, node_type, position, pdr: 0.0 });
+        self.selected = Some(self.nodes.len() - 1);
+    }
+
+    fn remove_node(&mut self, index: usize) {
+        let id = self.nodes.remove(index).id;
+        self.links.retain(|(a, b)| *a != id && *b != id);
+        self.selected = None;
+        self.dragging = None;
+    }
+
+    fn node_type(&self, id: NodeId) -> Option<EditorNodeType> {
+        self.nodes.iter().find(|node| node.id == id).map(|node| node.node_type)
+    }
+
+    //Adds the link, or removes it if it's already there. Clients and servers only talk to drones.
+    fn toggle_link(&mut self, a: NodeId, b: NodeId) {
+        let link = (a.min(b), a.max(b));
+        if a == b || self.links.remove(&link) {
+            return;
+        }
+        if self.node_type(a) != Some(EditorNodeType::Drone) && self.node_type(b) != Some(EditorNodeType::Drone) {
+            self.message = format!("{} and {} can't be linked: clients and servers only connect to drones", a, b);
+            return;
+        }
+        self.links.insert(link);
+    }
+
+    fn neighbours(&self, id: NodeId) -> Vec<NodeId> {
+        self.links
+            .iter()
+            .filter_map(|(a, b)| if *a == id { Some(*b) } else if *b == id { Some(*a) } else { None })
+            .collect()
+    }
+
+    //The config the initializer would get, or what's wrong with the network.
+    fn to_config(&self) -> Result<Config, String> {
+        let mut config = Config { drone: Vec::new(), client: Vec::new(), server: Vec::new() };
+        //A node can become a client after it was linked, so the links are checked again here.
+        if let Some((a, b)) = self.links.iter().find(|(a, b)| self.node_type(*a) != Some(EditorNodeType::Drone) && self.node_type(*b) != Some(EditorNodeType::Drone)) {
+            return Err(format!("{} and {} are linked, but clients and servers only connect to drones", a, b));
+        }
+        for node in self.nodes.iter() {
+            let neighbours = self.neighbours(node.id);
+            match node.node_type {
+                EditorNodeType::Drone => config.drone.push(Drone { id: node.id, connected_node_ids: neighbours, pdr: node.pdr }),
+                EditorNodeType::Client => {
+                    if neighbours.is_empty() || neighbours.len() > 2 {
+                        return Err(format!("client {} must be linked to one or two drones", node.id));
+                    }
+                    config.client.push(Client { id: node.id, connected_drone_ids: neighbours });
+                }
+                EditorNodeType::Server => {
+                    if neighbours.len() < 2 {
+                        return Err(format!("server {} must be linked to at least two drones", node.id));
+                    }
+                    config.server.push(Server { id: node.id, connected_drone_ids: neighbours });
+                }
+            }
+        }
+        if config.drone.is_empty() {
+            return Err("the network has no drones".to_string());
+        }
+        Ok(config)
+    }
+
+    fn render_canvas(&mut self, ui: &mut egui::Ui) {
+        let canvas = ui.allocate_rect(ui.max_rect(), egui::Sense::click());
+        //A double click on an empty spot puts a drone there.
+        if canvas.double_clicked() {
+            if let Some(position) = canvas.interact_pointer_pos() {
+                self.add_node(EditorNodeType::Drone, position.to_vec2());
+            }
+        }
+
+        let position = |id: NodeId| self.nodes.iter().find(|node| node.id == id).map(|node| node.position.to_pos2());
+        for (a, b) in self.links.iter() {
+            if let (Some(pos1), Some(pos2)) = (position(*a), position(*b)) {
+                ui.painter().line_segment([pos1, pos2], (2.0, Color32::GREEN));
+            }
+        }
+
+        //Click to select, drag to move, right click on another node to link it to the selected one.
+        let mut clicked = None;
+        let mut right_clicked = None;
+        for (index, node) in self.nodes.iter_mut().enumerate() {
+            let rect = egui::Rect::from_center_size(node.position.to_pos2(), Vec2::splat(2.0 * NODE_RADIUS));
+            let response = ui.interact(rect, ui.id().with(("node", node.id)), egui::Sense::click_and_drag());
+            if response.clicked() {
+                clicked = Some(index);
+            }
+            if response.secondary_clicked() {
+                right_clicked = Some(index);
+            }
+            if response.dragged() && self.dragging.map_or(true, |dragging| dragging == index) {
+                self.dragging = Some(index);
+                node.position += response.drag_delta();
+            }
+            if response.drag_released() && self.dragging == Some(index) {
+                self.dragging = None;
+            }
+
+            let color = match node.node_type {
+                EditorNodeType::Drone => Color32::from_rgb(44, 160, 44),
+                EditorNodeType::Client => Color32::from_rgb(31, 119, 180),
+                EditorNodeType::Server => Color32::from_rgb(148, 103, 189),
+            };
+            ui.painter().circle_filled(node.position.to_pos2(), NODE_RADIUS, color);
+            if self.selected == Some(index) {
+                ui.painter().circle_stroke(node.position.to_pos2(), NODE_RADIUS + 3.0, (2.0, Color32::YELLOW));
+            }
+            ui.painter().text(node.position.to_pos2(), egui::Align2::CENTER_CENTER, node.id.to_string(), egui::FontId::default(), Color32::WHITE);
+        }
+        if let Some(index) = clicked {
+            self.selected = Some(index);
+        }
+        if let (Some(index), Some(selected)) = (right_clicked, self.selected) {
+            let (a, b) = (self.nodes[selected].id, self.nodes[index].id);
+            self.toggle_link(a, b);
+        }
+    }
+
+    fn render_controls(&mut self, ui: &mut egui::Ui) {
+        ui.label("Double click to add a drone, right click a node to link it to the selected one.");
+        ui.horizontal(|ui| {
+            let position = Vec2::new(300.0 + fastrand::f32() * 300.0, 200.0 + fastrand::f32() * 300.0);
+            if ui.button("Add Drone").clicked() {
+                self.add_node(EditorNodeType::Drone, position);
+            }
+            if ui.button("Add Client").clicked() {
+                self.add_node(EditorNodeType::Client, position);
+            }
+            if ui.button("Add Server").clicked() {
+                self.add_node(EditorNodeType::Server, position);
+            }
+        });
+        ui.separator();
+
+        if let Some(index) = self.selected {
+            let id = self.nodes[index].id;
+            ui.label(format!("Selected: {}", id));
+            let node = &mut self.nodes[index];
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut node.node_type, EditorNodeType::Drone, "Drone");
+                ui.radio_value(&mut node.node_type, EditorNodeType::Client, "Client");
+                ui.radio_value(&mut node.node_type, EditorNodeType::Server, "Server");
+            });
+            if node.node_type == EditorNodeType::Drone {
+                ui.add(egui::Slider::new(&mut node.pdr, 0.0..=1.0).text("pdr"));
+            }
+            for neighbour in self.neighbours(id) {
+                ui.horizontal(|ui| {
+                    ui.label(format!("linked to {}", neighbour));
+                    if ui.small_button("x").clicked() {
+                        self.toggle_link(id, neighbour);
+                    }
+                });
+            }
+            if ui.button("Remove Node").clicked() {
+                self.remove_node(index);
+            }
+        } else {
+            ui.label("No node selected");
+        }
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.save_file);
+            if ui.button("Save").clicked() {
+                self.message = match self.to_config() {
+                    Ok(config) => match fs::write(&self.save_file, config_to_toml(&config)) {
+                        Ok(_) => format!("saved to {}", self.save_file),
+                        Err(e) => format!("error in saving to {}: {}", self.save_file, e),
+                    },
+                    Err(e) => e,
+                };
+            }
+        });
+        if ui.button("Launch").clicked() {
+            match self.to_config() {
+                Ok(config) => {
+                    *self.launched.borrow_mut() = Some(config);
+                    ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+                Err(e) => self.message = e,
+            }
+        }
+        if !self.message.is_empty() {
+            ui.colored_label(Color32::YELLOW, &self.message);
+        }
+    }
+}
+
+impl App for TopologyEditor {
+    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        egui::TopBottomPanel::top("header").show(ctx, |ui| {
+            ui.heading("SkyLink Topology Editor");
+        });
+        egui::SidePanel::right("controls").show(ctx, |ui| {
+            ui.heading("Controls");
+            self.render_controls(ui);
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.render_canvas(ui);
+        });
+    }
+}
+
+//The input file format, without the sections that aren't part of the wg_2024 Config.
+fn config_to_toml(config: &Config) -> String {
+    let mut toml = String::new();
+    for drone in config.drone.iter() {
+        let _ = writeln!(toml, "[[drone]]\nid = {}\nconnected_node_ids = {:?}\npdr = {}\n", drone.id, drone.connected_node_ids, drone.pdr);
+    }
+    for client in config.client.iter() {
+        let _ = writeln!(toml, "[[client]]\nid = {}\nconnected_drone_ids = {:?}\n", client.id, client.connected_drone_ids);
+    }
+    for server in config.server.iter() {
+        let _ = writeln!(toml, "[[server]]\nid = {}\nconnected_drone_ids = {:?}\n", server.id, server.connected_drone_ids);
+    }
+    toml
+}
+
+//Opens the editor on the network of the file (or on an empty one), returns the config to
+//start when the window is closed with Launch, None when it's just closed.
+pub fn run_topology_editor(config: Option<Config>, save_file: &str) -> Option<Config> {
+    let launched = Rc::new(RefCell::new(None));
+    let editor = TopologyEditor::new(config, save_file.to_string(), launched.clone());
+    eframe::run_native(
+        "SkyLink Topology Editor",
+        NativeOptions::default(),
+        Box::new(|_cc| Box::new(editor)),
+    ).expect("Failed to start the topology editor");
+    launched.take()
+}
@@ -0,0 +1,218 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, SystemTime};
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+use wg_2024::packet::{Fragment, NackType, NodeType, Packet, PacketType};
+
+//Like traceroute: the probe of hop k is a fragment whose route stops at the k-th node, so that
+//node answers with a DestinationIsDrone nack and the time until the nack is back at the source
+//is the rtt of the hop. A probe that gets another nack (or nothing) shows where the path dies.
+
+//A probe without an answer after this long is given up.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+//The probes use their own session ids, far from the ones of the scenarios and of the clients.
+pub const PROBE_SESSION_BASE: u64 = 1 << 48;
+
+#[derive(Debug, Clone)]
+pub enum ProbeReply {
+    Waiting,
+    Reached,                                     //The node of the hop answered.
+    Delivered,                                   //The hop is a client/server and got the probe (one way time).
+    Nack { from: NodeId, nack_type: NackType },  //Another node stopped the probe before.
+    Timeout,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeHop {
+    pub node: NodeId,
+    pub session_id: u64,
+    pub rtt: Option<Duration>,
+    pub reply: ProbeReply,
+    is_drone: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Traceroute {
+    pub route: Vec<NodeId>,
+    pub hops: Vec<ProbeHop>,
+    sent: SystemTime,
+    pub(crate) reported: bool, //The result is written to the log only once.
+}
+
+impl Traceroute {
+    pub fn new(route: Vec<NodeId>, node_types: &HashMap<NodeId, NodeType>, first_session: u64) -> Self {
+        let hops = route
+            .iter()
+            .skip(1)
+            .enumerate()
+            .map(|(i, node)| ProbeHop {
+                node: *node,
+                session_id: first_session + i as u64,
+                rtt: None,
+                reply: ProbeReply::Waiting,
+                is_drone: matches!(node_types.get(node), Some(NodeType::Drone) | None),
+            })
+            .collect();
+        Traceroute { route, hops, sent: SystemTime::now(), reported: false }
+    }
+
+    //The fragments to inject, one for every hop, all delivered to the second node of the route.
+    pub fn probes(&self) -> Vec<Packet> {
+        self.hops
+            .iter()
+            .enumerate()
+            .map(|(i, hop)| Packet {
+                pack_type: PacketType::MsgFragment(Fragment {
+                    fragment_index: 0,
+                    total_n_fragments: 1,
+                    length: 0,
+                    data: [0; 128],
+                }),
+                routing_header: SourceRoutingHeader {
+                    hop_index: 1,
+                    hops: self.route[..i + 2].to_vec(),
+                },
+                session_id: hop.session_id,
+            })
+            .collect()
+    }
+
+    pub fn is_probe(&self, session_id: u64) -> bool {
+        self.hops.iter().any(|hop| hop.session_id == session_id)
+    }
+
+    //Looks for the answer to a probe in the event, returns false if the event has nothing to do with the probes.
+    pub fn record(&mut self, event: &DroneEvent, time: SystemTime) -> bool {
+        let (packet, shortcut) = match event {
+            DroneEvent::PacketSent(packet) => (packet, false),
+            DroneEvent::ControllerShortcut(packet) => (packet, true),
+            DroneEvent::PacketDropped(packet) => (packet, false),
+        };
+        let Some(hop) = self.hops.iter_mut().find(|hop| hop.session_id == packet.session_id) else {
+            return false;
+        };
+        if !matches!(hop.reply, ProbeReply::Waiting) {
+            return true;
+        }
+        let header = &packet.routing_header;
+        //Only the last step counts: the nack reaching the source, or the fragment reaching a client/server.
+        let arrived = shortcut || header.hop_index + 1 == header.hops.len();
+        match &packet.pack_type {
+            PacketType::Nack(nack) if arrived => {
+                let from = header.hops[0];
+                hop.reply = match nack.nack_type {
+                    NackType::DestinationIsDrone if from == hop.node => ProbeReply::Reached,
+                    nack_type => ProbeReply::Nack { from, nack_type },
+                };
+                hop.rtt = time.duration_since(self.sent).ok();
+            }
+            PacketType::MsgFragment(_) if arrived && !hop.is_drone => {
+                hop.reply = ProbeReply::Delivered;
+                hop.rtt = time.duration_since(self.sent).ok();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn expire(&mut self, now: SystemTime) {
+        if now.duration_since(self.sent).unwrap_or_default() < PROBE_TIMEOUT {
+            return;
+        }
+        for hop in self.hops.iter_mut().filter(|hop| matches!(hop.reply, ProbeReply::Waiting)) {
+            hop.reply = ProbeReply::Timeout;
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.hops.iter().all(|hop| !matches!(hop.reply, ProbeReply::Waiting))
+    }
+
+    //The first hop that didn't answer: the path dies between it and the previous one.
+    pub fn dead_end(&self) -> Option<NodeId> {
+        self.hops
+            .iter()
+            .find(|hop| !matches!(hop.reply, ProbeReply::Reached | ProbeReply::Delivered))
+            .map(|hop| hop.node)
+    }
+
+    pub fn report(&self) -> Vec<String> {
+        let mut lines = vec![format!("traceroute {:?}", self.route)];
+        for (i, hop) in self.hops.iter().enumerate() {
+            let rtt = hop.rtt.map_or("*".to_string(), |rtt| format!("{:.2} ms", rtt.as_secs_f64() * 1000.0));
+            let reply = match &hop.reply {
+                ProbeReply::Waiting => "waiting".to_string(),
+                ProbeReply::Reached => "reached".to_string(),
+                ProbeReply::Delivered => "delivered (one way)".to_string(),
+                ProbeReply::Nack { from, nack_type } => format!("{:?} from {}", nack_type, from),
+                ProbeReply::Timeout => "no answer".to_string(),
+            };
+            lines.push(format!("{:>3}  node {:<4} {:>10}  {}", i + 1, hop.node, rtt, reply));
+        }
+        if self.finished() {
+            match self.dead_end() {
+                Some(node) => lines.push(format!("the path dies before node {}.", node)),
+                None => lines.push("the destination is reachable.".to_string()),
+            }
+        }
+        lines
+    }
+}
+
+//A ping measures the two ways of reaching a drone separately: a stuck drone thread doesn't
+//answer the command, while a broken route only loses the packet.
+#[derive(Debug, Clone, Default)]
+pub struct PingReport {
+    pub node: NodeId,
+    pub command_rtt: Option<Duration>, //Through the command channel, straight from the Sim Contr.
+    pub packet_rtt: Option<Duration>, //Through the network, a probe along route.
+    pub route: Vec<NodeId>,
+    pub dead_end: Option<NodeId>,
+}
+
+impl PingReport {
+    pub fn summary(&self) -> String {
+        let rtt = |rtt: Option<Duration>| rtt.map_or("no answer".to_string(), |rtt| format!("{:.2} ms", rtt.as_secs_f64() * 1000.0));
+        let mut summary = format!("ping {}: command {}, packet {} (route {:?})", self.node, rtt(self.command_rtt), rtt(self.packet_rtt), self.route);
+        if let Some(node) = self.dead_end {
+            summary.push_str(&format!(", the path dies before node {}", node));
+        }
+        summary
+    }
+}
+
+//The shortest route through the drones that are up, the one a client with a full view of the
+//network would use. Clients and servers can only be at its ends.
+pub fn shortest_route(
+    graph: &HashMap<NodeId, Vec<NodeId>>,
+    node_types: &HashMap<NodeId, NodeType>,
+    crashed: &HashSet<NodeId>,
+    from: NodeId,
+    to: NodeId,
+) -> Option<Vec<NodeId>> {
+    let mut previous = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    previous.insert(from, from);
+    while let Some(node) = queue.pop_front() {
+        if node == to {
+            let mut route = vec![to];
+            while *route.last().unwrap() != from {
+                route.push(previous[route.last().unwrap()]);
+            }
+            route.reverse();
+            return Some(route);
+        }
+        let is_drone = matches!(node_types.get(&node), Some(NodeType::Drone) | None);
+        if node != from && !is_drone {
+            continue;
+        }
+        for next in graph.get(&node).into_iter().flatten() {
+            if crashed.contains(next) || previous.contains_key(next) {
+                continue;
+            }
+            previous.insert(*next, node);
+            queue.push_back(*next);
+        }
+    }
+    None
+}
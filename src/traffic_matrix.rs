@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+use wg_2024::network::NodeId;
+use crate::sessions::SessionTable;
+
+//The messages from a source to a destination. A message that is neither delivered nor failed
+//is still on its way, or lost without a nack.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PairTraffic {
+    pub source: NodeId,
+    pub destination: NodeId,
+    pub attempted: u64,
+    pub delivered: u64,
+    pub failed: u64, //Some fragment was nacked as dropped, and none got there.
+    pub mean_latency_ms: Option<f64>, //End to end, of the delivered ones.
+}
+
+impl PairTraffic {
+    pub fn delivery_ratio(&self) -> f64 {
+        self.delivered as f64 / self.attempted.max(1) as f64
+    }
+
+    pub fn describe(&self) -> String {
+        let latency = self.mean_latency_ms.map_or("-".to_string(), |latency| format!("{:.3} ms", latency));
+        format!(
+            "{} -> {}: {} attempted, {} delivered ({:.1}%), {} failed, mean latency {}",
+            self.source,
+            self.destination,
+            self.attempted,
+            self.delivered,
+            self.delivery_ratio() * 100.0,
+            self.failed,
+            latency
+        )
+    }
+}
+
+//Sources x destinations, built from the sessions whenever it's asked for: the sessions already
+//have everything, so there's nothing more to record on every event.
+#[derive(Debug, Clone, Default)]
+pub struct TrafficMatrix {
+    pub pairs: BTreeMap<(NodeId, NodeId), PairTraffic>,
+}
+
+impl TrafficMatrix {
+    pub fn new(sessions: &SessionTable) -> Self {
+        let mut pairs: BTreeMap<(NodeId, NodeId), PairTraffic> = BTreeMap::new();
+        let mut latency_sums: BTreeMap<(NodeId, NodeId), f64> = BTreeMap::new();
+        for session in sessions.sessions.values() {
+            let (Some(source), Some(destination)) = (session.source, session.destination) else {
+                continue;
+            };
+            let pair = pairs.entry((source, destination)).or_insert(PairTraffic { source, destination, ..PairTraffic::default() });
+            pair.attempted += 1;
+            if session.delivered.is_some() {
+                pair.delivered += 1;
+                if let Some(latency) = session.end_to_end_latency() {
+                    *latency_sums.entry((source, destination)).or_insert(0.0) += latency.as_secs_f64() * 1000.0;
+                }
+            } else if session.dropped {
+                pair.failed += 1;
+            }
+        }
+        for (key, sum) in latency_sums {
+            if let Some(pair) = pairs.get_mut(&key) {
+                pair.mean_latency_ms = Some(sum / pair.delivered.max(1) as f64);
+            }
+        }
+        TrafficMatrix { pairs }
+    }
+
+    //The rows and the columns of the matrix, sorted.
+    pub fn sources(&self) -> Vec<NodeId> {
+        let mut sources = self.pairs.keys().map(|(source, _)| *source).collect::<Vec<NodeId>>();
+        sources.dedup();
+        sources
+    }
+
+    pub fn destinations(&self) -> Vec<NodeId> {
+        let mut destinations = self.pairs.keys().map(|(_, destination)| *destination).collect::<Vec<NodeId>>();
+        destinations.sort();
+        destinations.dedup();
+        destinations
+    }
+
+    pub fn get(&self, source: NodeId, destination: NodeId) -> Option<&PairTraffic> {
+        self.pairs.get(&(source, destination))
+    }
+}
@@ -0,0 +1,135 @@
+use wg_2024::config::{Client, Config, Drone, Server};
+use wg_2024::network::NodeId;
+use crate::sim_control::SimulationControl;
+
+//The part of the window a step points at, circled while the step is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    Canvas,
+    Drone(NodeId),
+    Controls,
+    Log,
+    Timeline,
+}
+
+pub struct WalkthroughStep {
+    pub title: &'static str,
+    pub text: &'static str,
+    pub code: Option<&'static str>, //Run with the scenario engine when the step starts.
+    pub highlight: Highlight,
+    pub waiting_for: &'static str, //Shown until done says the step happened.
+    pub done: fn(&SimulationControl) -> bool,
+}
+
+//Client 0 and server 9 with drones 1-2-3 between them, and drone 4 on the side.
+pub fn walkthrough_config() -> Config {
+    let drone = |id: NodeId, connected_node_ids: Vec<NodeId>| Drone { id, connected_node_ids, pdr: 0.0 };
+    Config {
+        drone: vec![
+            drone(1, vec![0, 2, 4]),
+            drone(2, vec![1, 3]),
+            drone(3, vec![2, 4, 9]),
+            drone(4, vec![1, 3]),
+        ],
+        client: vec![Client { id: 0, connected_drone_ids: vec![1] }],
+        server: vec![Server { id: 9, connected_drone_ids: vec![3] }],
+    }
+}
+
+//The steps that only show something are done as soon as they're read.
+fn always_done(_: &SimulationControl) -> bool {
+    true
+}
+
+fn message_delivered(sim_contr: &SimulationControl) -> bool {
+    sim_contr.transfers.iter().any(|transfer| transfer.finished.is_some())
+}
+
+fn drone_2_dropped(sim_contr: &SimulationControl) -> bool {
+    sim_contr.stats.get(&2).map_or(false, |stats| stats.fragments_dropped > 0)
+}
+
+fn drone_3_crashed(sim_contr: &SimulationControl) -> bool {
+    sim_contr.crashed.contains(&3)
+}
+
+fn drone_3_back(sim_contr: &SimulationControl) -> bool {
+    !sim_contr.crashed.contains(&3)
+}
+
+pub const WALKTHROUGH: &[WalkthroughStep] = &[
+    WalkthroughStep {
+        title: "The network",
+        text: "Every icon is a node: client 0 and server 9 at the ends, drones 1 to 4 in between. \
+               The lines are the links, they light up when packets go through them. Drag the icons around to tidy them up.",
+        code: None,
+        highlight: Highlight::Canvas,
+        waiting_for: "",
+        done: always_done,
+    },
+    WalkthroughStep {
+        title: "A message",
+        text: "Client 0 sends 10 fragments to server 9 on the route 0-1-2-3-9. Every fragment is acked by the server, \
+               the Sim Contr plays both the client and the server and writes here when the message is complete.",
+        code: Some("send_message([0, 1, 2, 3, 9], 10, 4);"),
+        highlight: Highlight::Log,
+        waiting_for: "the message to be delivered",
+        done: message_delivered,
+    },
+    WalkthroughStep {
+        title: "A drop",
+        text: "Drone 2 now drops every fragment (pdr 1.0): it answers with a Dropped nack instead of forwarding them. \
+               Select a drone and its stats show up in the controls, drone 2 counts the drops.",
+        code: Some("set_pdr(2, 1.0); send_message([0, 1, 2, 3, 9], 5, 4);"),
+        highlight: Highlight::Drone(2),
+        waiting_for: "drone 2 to drop a fragment",
+        done: drone_2_dropped,
+    },
+    WalkthroughStep {
+        title: "A crash",
+        text: "Drone 2 is fixed, and drone 3 crashes: it turns red, its neighbours forget it, and the crash is on the \
+               timeline at the bottom. Hover the dots of the timeline to read them.",
+        code: Some("set_pdr(2, 0.0); crash(3);"),
+        highlight: Highlight::Timeline,
+        waiting_for: "drone 3 to crash",
+        done: drone_3_crashed,
+    },
+    WalkthroughStep {
+        title: "The recovery",
+        text: "The Crash and Reboot buttons of the selected drone do the same by hand. Drone 3 is rebooted: \
+               a new drone with the same id and links takes its place.",
+        code: Some("reboot(3);"),
+        highlight: Highlight::Controls,
+        waiting_for: "drone 3 to be back",
+        done: drone_3_back,
+    },
+    WalkthroughStep {
+        title: "That's all",
+        text: "Everything done here is a line of a rhai scenario (see the inputs/scenario_*.rhai files), \
+               and every button of the controls works on this network too.",
+        code: None,
+        highlight: Highlight::Controls,
+        waiting_for: "",
+        done: always_done,
+    },
+];
+
+//Where the walkthrough is: a step is started when its code has run.
+#[derive(Debug, Default)]
+pub struct Walkthrough {
+    pub step: usize,
+    pub started: bool,
+    pub error: Option<String>, //Of the code of the step, if it failed.
+}
+
+impl Walkthrough {
+    pub fn current(&self) -> Option<&'static WalkthroughStep> {
+        WALKTHROUGH.get(self.step)
+    }
+
+    pub fn next(&mut self) {
+        self.step += 1;
+        self.started = false;
+        self.error = None;
+    }
+}